@@ -15,7 +15,17 @@ use crate::*;
 
 use libc::{ENOENT, fclose, fopen, strerror};
 
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io::{Read as _, Seek as _};
+use std::sync::atomic::Ordering;
+
+use crate::cmd_::cmd_bind_key::cmd_bind_key_entry;
+use crate::cmd_::cmd_define_command::{cmd_define_command_declare, cmd_define_command_entry};
+use crate::cmd_::cmd_find;
 use crate::cmd_::cmd_queue::cmdq_get_callback;
+use crate::cmd_::cmd_set_option::{cmd_set_option_entry, cmd_set_window_option_entry};
+use crate::cmd_::cmd_unbind_key::cmd_unbind_key_entry;
 use crate::compat::{queue::tailq_first, tree::rb_min};
 
 pub static mut cfg_client: *mut client = null_mut();
@@ -54,6 +64,7 @@ unsafe fn cfg_done(_item: *mut cmdq_item, _data: *mut c_void) -> cmd_retval {
         }
 
         status_prompt_load_history();
+        cfg_watch_update();
 
         cmd_retval::CMD_RETURN_NORMAL
     }
@@ -120,6 +131,7 @@ pub unsafe fn load_cfg(
         }
 
         log_debug!("loading {}", path);
+        cfg_watch_add(path, null());
         let mut f = match std::fs::OpenOptions::new().read(true).open(path) {
             Ok(f) => std::io::BufReader::new(f),
             Err(err) => {
@@ -160,6 +172,7 @@ pub unsafe fn load_cfg(
         } else {
             cmdq_new_state(null_mut(), null_mut(), cmdq_state_flags::empty())
         };
+        (*state).flags |= cmdq_state_flags::CMDQ_STATE_CONFIG;
         cmdq_add_format!(
             state,
             c"current_file".as_ptr(),
@@ -226,6 +239,7 @@ pub unsafe fn load_cfg_from_buffer(
         } else {
             cmdq_new_state(null_mut(), null_mut(), cmdq_state_flags::empty())
         };
+        (*state).flags |= cmdq_state_flags::CMDQ_STATE_CONFIG;
         cmdq_add_format!(
             state,
             c"current_file".as_ptr(),
@@ -292,7 +306,15 @@ pub unsafe fn cfg_show_causes(mut s: *mut session) {
 
             if !c.is_null() && (*c).flags.intersects(client_flag::CONTROL) {
                 for i in 0..cfg_ncauses {
-                    control_write!(c, "%config-error {}", _s(*cfg_causes.add(i as usize)),);
+                    if (*c).flags.intersects(client_flag::CONTROL_JSON) {
+                        control_write_json(
+                            c,
+                            "config-error",
+                            &[("message", control_json::cstr(*cfg_causes.add(i as usize)))],
+                        );
+                    } else {
+                        control_write!(c, "%config-error {}", _s(*cfg_causes.add(i as usize)),);
+                    }
                     free_(*cfg_causes.add(i as usize));
                 }
                 break 'out;
@@ -332,3 +354,960 @@ pub unsafe fn cfg_show_causes(mut s: *mut session) {
         cfg_ncauses = 0;
     }
 }
+
+/// A configuration file that has been loaded and what its commands set, so it
+/// can be reloaded when it changes.
+struct cfg_watch_file {
+    path: String,
+    real: String,
+    parent: Option<String>,
+    patterns: Vec<String>,
+    options: Vec<(*mut options, String)>,
+    keys: Vec<(String, key_code)>,
+}
+
+static mut cfg_watch_files: Vec<cfg_watch_file> = Vec::new();
+static mut cfg_watch_dirs: Vec<(i32, String)> = Vec::new();
+static mut cfg_watch_changed: Vec<String> = Vec::new();
+
+static mut cfg_watch_reloads: u32 = 0;
+
+static mut cfg_watch_fd: c_int = -1;
+static mut cfg_watch_event: event = unsafe { zeroed() };
+static mut cfg_watch_timer: event = unsafe { zeroed() };
+
+#[expect(clippy::deref_addrof)]
+unsafe fn cfg_watch_find(path: &str) -> Option<&'static mut cfg_watch_file> {
+    unsafe {
+        (*&raw mut cfg_watch_files)
+            .iter_mut()
+            .find(|cf| cf.path == path)
+    }
+}
+
+/// Get the file that loaded a file, following the chain to the top.
+#[expect(clippy::deref_addrof)]
+unsafe fn cfg_watch_root(path: &str) -> String {
+    unsafe {
+        let mut root = path.to_string();
+        for _ in 0..(*&raw const cfg_watch_files).len() {
+            match cfg_watch_find(&root).and_then(|cf| cf.parent.clone()) {
+                Some(parent) => root = parent,
+                None => break,
+            }
+        }
+        root
+    }
+}
+
+/// Get a file and the files it loaded, directly or not.
+#[expect(clippy::deref_addrof)]
+unsafe fn cfg_watch_family(root: &str) -> Vec<String> {
+    unsafe {
+        (*&raw const cfg_watch_files)
+            .iter()
+            .filter(|cf| cfg_watch_root(&cf.path) == root)
+            .map(|cf| cf.path.clone())
+            .collect()
+    }
+}
+
+/// Watch the directory containing a path, so replacing the file is noticed.
+#[expect(clippy::deref_addrof)]
+unsafe fn cfg_watch_directory(path: &str) {
+    unsafe {
+        if cfg_watch_fd == -1 {
+            return;
+        }
+        let Some(dir) = std::path::Path::new(path).parent() else {
+            return;
+        };
+        let dir = dir.to_string_lossy().into_owned();
+        if (*&raw const cfg_watch_dirs).iter().any(|(_, d)| *d == dir) {
+            return;
+        }
+        let Ok(cdir) = CString::new(dir.as_str()) else {
+            return;
+        };
+        let mask = libc::IN_CLOSE_WRITE
+            | libc::IN_MOVED_TO
+            | libc::IN_MOVED_FROM
+            | libc::IN_CREATE
+            | libc::IN_DELETE;
+        let wd = libc::inotify_add_watch(cfg_watch_fd, cdir.as_ptr(), mask);
+        if wd == -1 {
+            log_debug!("cfg_watch_directory: {}: {}", dir, _s(strerror(errno!())));
+            return;
+        }
+        log_debug!("cfg_watch_directory: {} is {}", dir, wd);
+        (*&raw mut cfg_watch_dirs).push((wd, dir));
+    }
+}
+
+/// Remember a configuration file when it is loaded. The parent is the file
+/// whose source-file command loaded it, if any.
+#[expect(clippy::deref_addrof)]
+pub unsafe fn cfg_watch_add(path: &str, parent: *const c_char) {
+    unsafe {
+        if path == "-" {
+            return;
+        }
+        let parent = (!parent.is_null()).then(|| cstr_to_str(parent).to_string());
+        if let Some(cf) = cfg_watch_find(path) {
+            if parent.is_some() && parent.as_deref() != Some(path) {
+                cf.parent = parent;
+            }
+            return;
+        }
+        let real = std::fs::canonicalize(path)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| path.to_string());
+        cfg_watch_directory(path);
+        cfg_watch_directory(&real);
+        (*&raw mut cfg_watch_files).push(cfg_watch_file {
+            path: path.to_string(),
+            real,
+            parent,
+            patterns: Vec::new(),
+            options: Vec::new(),
+            keys: Vec::new(),
+        });
+    }
+}
+
+/// Remember a pattern given to source-file in a configuration file, so a new
+/// file matching it reloads that file.
+pub unsafe fn cfg_watch_add_pattern(file: *const c_char, pattern: *const c_char) {
+    unsafe {
+        let pattern = cstr_to_str(pattern).to_string();
+        if let Some(cf) = cfg_watch_find(cstr_to_str(file))
+            && !cf.patterns.contains(&pattern)
+        {
+            cfg_watch_directory(&pattern);
+            cf.patterns.push(pattern);
+        }
+    }
+}
+
+/// Get the configuration file a command was loaded from, if it is being run
+/// as part of loading it rather than later from a key binding or hook.
+unsafe fn cfg_watch_source(item: *mut cmdq_item, self_: *mut cmd) -> Option<&'static str> {
+    unsafe {
+        if !(*cmdq_get_state(item))
+            .flags
+            .intersects(cmdq_state_flags::CMDQ_STATE_CONFIG)
+        {
+            return None;
+        }
+        let mut file = null();
+        cmd_get_source(self_, &raw mut file, &AtomicU32::new(0));
+        (!file.is_null()).then(|| cstr_to_str(file))
+    }
+}
+
+/// Whether an error from a command should be kept for the summary shown when
+/// a reload finishes.
+pub unsafe fn cfg_watch_collecting(item: *mut cmdq_item) -> bool {
+    unsafe {
+        cfg_watch_reloads != 0
+            && (*cmdq_get_state(item))
+                .flags
+                .intersects(cmdq_state_flags::CMDQ_STATE_CONFIG)
+    }
+}
+
+/// Get the configuration file a source-file command is in, if any.
+pub unsafe fn cfg_watch_parent(item: *mut cmdq_item, self_: *mut cmd) -> *mut c_char {
+    unsafe {
+        match cfg_watch_source(item, self_) {
+            Some(file) if cfg_watch_find(file).is_some() => format_nul!("{}", file),
+            _ => null_mut(),
+        }
+    }
+}
+
+/// Record that a configuration file set a global option.
+pub unsafe fn cfg_watch_set_option(
+    item: *mut cmdq_item,
+    self_: *mut cmd,
+    oo: *mut options,
+    name: *const c_char,
+) {
+    unsafe {
+        if oo != global_options && oo != global_s_options && oo != global_w_options {
+            return;
+        }
+        if let Some(cf) = cfg_watch_source(item, self_).and_then(|f| cfg_watch_find(f)) {
+            let name = cstr_to_str(name).to_string();
+            if !cf.options.iter().any(|(o, n)| *o == oo && *n == name) {
+                cf.options.push((oo, name));
+            }
+        }
+    }
+}
+
+/// Record that a configuration file bound or unbound a key.
+pub unsafe fn cfg_watch_set_key(
+    item: *mut cmdq_item,
+    self_: *mut cmd,
+    tablename: *const c_char,
+    key: key_code,
+) {
+    unsafe {
+        if let Some(cf) = cfg_watch_source(item, self_).and_then(|f| cfg_watch_find(f)) {
+            let tablename = cstr_to_str(tablename).to_string();
+            let key = key & !KEYC_MASK_FLAGS;
+            if !cf.keys.iter().any(|(t, k)| *t == tablename && *k == key) {
+                cf.keys.push((tablename, key));
+            }
+        }
+    }
+}
+
+/// Put an option back to its default.
+unsafe fn cfg_watch_reset_option(oo: *mut options, name: &str) {
+    unsafe {
+        let Ok(name) = CString::new(name) else {
+            return;
+        };
+        let o = options_get_only(oo, name.as_ptr());
+        if o.is_null() {
+            return;
+        }
+        let mut cause = null_mut();
+        if options_remove_or_default(o, -1, &raw mut cause) != 0 {
+            free_(cause);
+            return;
+        }
+        options_push_changes(name.as_ptr());
+    }
+}
+
+/// Put a key back to its default binding, or remove it if it has none.
+unsafe fn cfg_watch_reset_key(tablename: &str, key: key_code) {
+    unsafe {
+        let Ok(name) = CString::new(tablename) else {
+            return;
+        };
+        let Some(table) = NonNull::new(key_bindings_get_table(name.as_ptr(), 0)) else {
+            return;
+        };
+        if !key_bindings_get(table, key).is_null() {
+            key_bindings_reset(name.as_ptr(), key);
+            return;
+        }
+        let dd = key_bindings_get_default(table.as_ptr(), key);
+        if !dd.is_null() {
+            key_bindings_add(
+                name.as_ptr(),
+                key,
+                (*dd).note,
+                ((*dd).flags & KEY_BINDING_REPEAT != 0) as i32,
+                (*dd).cmdlist,
+            );
+            (*(*dd).cmdlist).references += 1;
+        }
+    }
+}
+
+/// What a file and the files it loaded set before it was reloaded.
+struct cfg_watch_reload {
+    root: String,
+    options: Vec<(*mut options, String)>,
+    keys: Vec<(String, key_code)>,
+}
+
+/// Reload a configuration file and anything it loads.
+#[expect(clippy::deref_addrof)]
+unsafe fn cfg_watch_reload(root: &str) {
+    unsafe {
+        log_debug!("cfg_watch_reload: {}", root);
+
+        let mut reload = Box::new(cfg_watch_reload {
+            root: root.to_string(),
+            options: Vec::new(),
+            keys: Vec::new(),
+        });
+        let family = cfg_watch_family(root);
+        for cf in (*&raw mut cfg_watch_files).iter_mut() {
+            if family.contains(&cf.path) {
+                reload.options.append(&mut cf.options);
+                reload.keys.append(&mut cf.keys);
+                cf.patterns.clear();
+            }
+        }
+        (*&raw mut cfg_watch_files).retain(|cf| cf.path == root || !family.contains(&cf.path));
+
+        let flags = if cfg_quiet != 0 {
+            cmd_parse_input_flags::CMD_PARSE_QUIET
+        } else {
+            cmd_parse_input_flags::empty()
+        };
+        cfg_watch_reloads += 1;
+        load_cfg(root, null_mut(), null_mut(), null_mut(), flags, null_mut());
+        cmdq_append(
+            null_mut(),
+            cmdq_get_callback!(cfg_watch_reload_done, Box::into_raw(reload).cast()).as_ptr(),
+        );
+    }
+}
+
+/// Once a file has been reloaded, put back anything it no longer sets and
+/// show what went wrong.
+#[expect(clippy::deref_addrof)]
+unsafe fn cfg_watch_reload_done(_item: *mut cmdq_item, data: *mut c_void) -> cmd_retval {
+    unsafe {
+        let reload = Box::from_raw(data.cast::<cfg_watch_reload>());
+        cfg_watch_reloads -= 1;
+
+        let family = cfg_watch_family(&reload.root);
+        let files: Vec<&cfg_watch_file> = (*&raw const cfg_watch_files)
+            .iter()
+            .filter(|cf| family.contains(&cf.path))
+            .collect();
+        for (oo, name) in &reload.options {
+            if !files
+                .iter()
+                .any(|cf| cf.options.contains(&(*oo, name.clone())))
+            {
+                cfg_watch_reset_option(*oo, name);
+            }
+        }
+        for (tablename, key) in &reload.keys {
+            if !files
+                .iter()
+                .any(|cf| cf.keys.contains(&(tablename.clone(), *key)))
+            {
+                cfg_watch_reset_key(tablename, *key);
+            }
+        }
+
+        let errors = cfg_ncauses;
+        for c in tailq_foreach(&raw mut clients).map(NonNull::as_ptr) {
+            if (*c).session.is_null() || (*c).flags.intersects(client_flag::CONTROL) {
+                continue;
+            }
+            if errors == 0 {
+                status_message_set!(c, -1, 1, 0, "Reloaded {}", reload.root);
+            } else {
+                status_message_set!(
+                    c,
+                    -1,
+                    1,
+                    0,
+                    "Reloaded {} with {} error{}",
+                    reload.root,
+                    errors,
+                    if errors == 1 { "" } else { "s" },
+                );
+            }
+        }
+        cfg_show_causes(null_mut());
+
+        cmd_retval::CMD_RETURN_NORMAL
+    }
+}
+
+#[expect(clippy::deref_addrof)]
+unsafe extern "C" fn cfg_watch_timer_callback(_fd: i32, _events: i16, _data: *mut c_void) {
+    unsafe {
+        for root in std::mem::take(&mut *&raw mut cfg_watch_changed) {
+            cfg_watch_reload(&root);
+        }
+    }
+}
+
+#[expect(clippy::deref_addrof)]
+unsafe extern "C" fn cfg_watch_callback(fd: i32, _events: i16, _data: *mut c_void) {
+    unsafe {
+        let mut buf = [0u64; 512];
+        let size = size_of_val(&buf);
+        let mut changed = false;
+        loop {
+            let n = libc::read(fd, buf.as_mut_ptr().cast(), size);
+            if n <= 0 {
+                break;
+            }
+            let bytes = std::slice::from_raw_parts(buf.as_ptr().cast::<u8>(), n as usize);
+            let mut off = 0;
+            while off + size_of::<libc::inotify_event>() <= bytes.len() {
+                let ev = bytes
+                    .as_ptr()
+                    .add(off)
+                    .cast::<libc::inotify_event>()
+                    .read_unaligned();
+                let name = &bytes[off + size_of::<libc::inotify_event>()..][..ev.len as usize]
+                    .split(|&ch| ch == 0)
+                    .next()
+                    .unwrap_or_default();
+                off += size_of::<libc::inotify_event>() + ev.len as usize;
+
+                let Some((_, dir)) = (*&raw const cfg_watch_dirs)
+                    .iter()
+                    .find(|(wd, _)| *wd == ev.wd)
+                else {
+                    continue;
+                };
+                let path = format!("{}/{}", dir, String::from_utf8_lossy(name));
+                log_debug!("cfg_watch_callback: {} ({:#x})", path, ev.mask);
+                if cfg_watch_changed_path(&path) {
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            let tv = timeval {
+                tv_sec: 0,
+                tv_usec: 200000,
+            };
+            evtimer_add(&raw mut cfg_watch_timer, &raw const tv);
+        }
+    }
+}
+
+/// Mark the files to reload when a path changes. Returns true if there are
+/// any.
+#[expect(clippy::deref_addrof)]
+unsafe fn cfg_watch_changed_path(path: &str) -> bool {
+    unsafe {
+        let Ok(cpath) = CString::new(path) else {
+            return false;
+        };
+        let mut found = false;
+        for cf in (*&raw const cfg_watch_files).iter() {
+            let matches = cf.path == path
+                || cf.real == path
+                || cf.patterns.iter().any(|pattern| {
+                    CString::new(pattern.as_str()).is_ok_and(|p| {
+                        libc::fnmatch(p.as_ptr(), cpath.as_ptr(), libc::FNM_PATHNAME) == 0
+                    })
+                });
+            if !matches {
+                continue;
+            }
+            let root = cfg_watch_root(&cf.path);
+            if !(*&raw const cfg_watch_changed).contains(&root) {
+                (*&raw mut cfg_watch_changed).push(root);
+            }
+            found = true;
+        }
+        found
+    }
+}
+
+/// Start or stop watching configuration files to match the config-watch
+/// option.
+#[expect(clippy::deref_addrof)]
+pub unsafe fn cfg_watch_update() {
+    unsafe {
+        let on = options_get_number_(global_options, c"config-watch") != 0;
+        if on && cfg_watch_fd == -1 {
+            cfg_watch_fd = libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC);
+            if cfg_watch_fd == -1 {
+                log_debug!("cfg_watch_update: {}", _s(strerror(errno!())));
+                return;
+            }
+            event_set(
+                &raw mut cfg_watch_event,
+                cfg_watch_fd,
+                EV_READ | EV_PERSIST,
+                Some(cfg_watch_callback),
+                null_mut(),
+            );
+            event_add(&raw mut cfg_watch_event, null_mut());
+            evtimer_set(
+                &raw mut cfg_watch_timer,
+                Some(cfg_watch_timer_callback),
+                null_mut(),
+            );
+            for cf in (*&raw const cfg_watch_files).iter() {
+                cfg_watch_directory(&cf.path);
+                cfg_watch_directory(&cf.real);
+                for pattern in &cf.patterns {
+                    cfg_watch_directory(pattern);
+                }
+            }
+        } else if !on && cfg_watch_fd != -1 {
+            event_del(&raw mut cfg_watch_event);
+            evtimer_del(&raw mut cfg_watch_timer);
+            libc::close(cfg_watch_fd);
+            cfg_watch_fd = -1;
+            (*&raw mut cfg_watch_dirs).clear();
+            (*&raw mut cfg_watch_changed).clear();
+        }
+    }
+}
+
+/// State while checking a configuration file.
+struct cfg_check_state<'a> {
+    path: &'a str,
+    lines: Vec<&'a [u8]>,
+    seen: HashMap<u32, usize>,
+    diagnostics: Vec<String>,
+}
+
+/// Check configuration files without a server, printing each problem as
+/// file:line:column. Returns the exit code.
+pub unsafe fn cfg_check(argc: i32, argv: *mut *mut c_char) -> i32 {
+    unsafe {
+        let mut files = Vec::new();
+        let mut i = 1;
+        while i < argc as usize {
+            let arg = CStr::from_ptr(*argv.add(i)).to_string_lossy().into_owned();
+            if arg == "-f" && i + 1 < argc as usize {
+                files.push(
+                    CStr::from_ptr(*argv.add(i + 1))
+                        .to_string_lossy()
+                        .into_owned(),
+                );
+                i += 2;
+            } else if let Some(file) = arg.strip_prefix("-f")
+                && !file.is_empty()
+            {
+                files.push(file.to_string());
+                i += 1;
+            } else {
+                eprintln!("usage: check-config [-f file]");
+                return 1;
+            }
+        }
+
+        let quiet = files.is_empty() && cfg_quiet != 0;
+        if files.is_empty() {
+            for i in 0..cfg_nfiles {
+                files.push(cstr_to_str(*cfg_files.add(i as usize)).to_string());
+            }
+        }
+
+        let mut errors = 0;
+        for path in &files {
+            errors += cfg_check_file(path, quiet);
+        }
+        (errors != 0) as i32
+    }
+}
+
+unsafe fn cfg_check_file(path: &str, quiet: bool) -> u32 {
+    unsafe {
+        match cfg_check_diagnostics(path) {
+            Ok(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    eprintln!("{diagnostic}");
+                }
+                diagnostics.len() as u32
+            }
+            Err(err) => {
+                if quiet && err.kind() == std::io::ErrorKind::NotFound {
+                    return 0;
+                }
+                eprintln!(
+                    "{}: {}",
+                    path,
+                    _s(strerror(err.raw_os_error().unwrap_or(0)))
+                );
+                1
+            }
+        }
+    }
+}
+
+/// Parse and check one file, returning its problems.
+unsafe fn cfg_check_diagnostics(path: &str) -> std::io::Result<Vec<String>> {
+    unsafe {
+        let mut f = std::fs::File::open(path)?;
+        let mut content = Vec::new();
+        f.read_to_end(&mut content)?;
+        f.rewind()?;
+        let mut f = std::io::BufReader::new(f);
+
+        let mut cs = cfg_check_state {
+            path,
+            lines: content.split(|&ch| ch == b'\n').collect(),
+            seen: HashMap::new(),
+            diagnostics: Vec::new(),
+        };
+
+        // Only parse, so nothing is set or declared until the commands are
+        // checked.
+        let mut pi: cmd_parse_input = zeroed();
+        pi.flags = cmd_parse_input_flags::CMD_PARSE_PARSEONLY.into();
+        pi.line = AtomicU32::new(1);
+        match cmd_parse_do_file(&mut f, &pi) {
+            Ok(cmds) => {
+                cfg_check_commands(&mut cs, cmds);
+                cmd_parse_free_commands(cmds);
+            }
+            Err(cause) => {
+                let line = pi.line.load(Ordering::SeqCst);
+                if cause.is_null() {
+                    cfg_check_report(&mut cs, line, &[], |_| false, "syntax error");
+                } else {
+                    cfg_check_report(&mut cs, line, &[], |_| false, cstr_to_str(cause));
+                    free_(cause);
+                }
+            }
+        }
+        Ok(cs.diagnostics)
+    }
+}
+
+unsafe fn cfg_check_commands(cs: &mut cfg_check_state, cmds: *mut cmd_parse_commands) {
+    unsafe {
+        for cmd in tailq_foreach(cmds).map(NonNull::as_ptr) {
+            cfg_check_command(cs, cmd);
+        }
+    }
+}
+
+/// Find the words of a command, which is the index'th on its line counting
+/// those inside braces.
+unsafe fn cfg_check_words(cs: &cfg_check_state, line: u32, index: usize) -> Vec<cmd_parse_token> {
+    unsafe {
+        let Some(text) = cs.lines.get(line as usize - 1) else {
+            return Vec::new();
+        };
+        let mut groups = vec![Vec::new()];
+        for token in cmd_parse_tokens(text) {
+            let group = groups.last_mut().unwrap();
+            match token.type_ {
+                cmd_parse_token_type::Separator | cmd_parse_token_type::Newline
+                    if !group.is_empty() =>
+                {
+                    groups.push(Vec::new());
+                }
+                cmd_parse_token_type::Word => group.push(token),
+                cmd_parse_token_type::Assignment if !group.is_empty() => group.push(token),
+                _ => (),
+            }
+        }
+        if index < groups.len() {
+            groups.swap_remove(index)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Report an error at the first argument matching find, or at the command if
+/// none does.
+fn cfg_check_report(
+    cs: &mut cfg_check_state,
+    line: u32,
+    words: &[cmd_parse_token],
+    find: impl Fn(&str) -> bool,
+    message: &str,
+) {
+    let text = cs.lines.get(line as usize - 1).copied().unwrap_or_default();
+    let start = words
+        .iter()
+        .skip(1)
+        .find(|w| w.value.as_deref().is_some_and(&find))
+        .or(words.first())
+        .map(|w| w.start)
+        .unwrap_or_else(|| {
+            text.iter()
+                .position(|&ch| ch != b' ' && ch != b'\t')
+                .unwrap_or(0)
+        });
+    let column = String::from_utf8_lossy(&text[..start]).chars().count() + 1;
+    cs.diagnostics
+        .push(format!("{}:{}:{}: {}", cs.path, line, column, message));
+}
+
+unsafe fn cfg_check_command(cs: &mut cfg_check_state, cmd: *mut cmd_parse_command) {
+    unsafe {
+        let line = (*cmd).line;
+        let index = cs.seen.get(&line).copied().unwrap_or(0);
+        cs.seen.insert(line, index + 1);
+        let words = cfg_check_words(cs, line, index);
+
+        let mut values: Vec<args_value> = Vec::new();
+        for arg in tailq_foreach(&raw mut (*cmd).arguments).map(NonNull::as_ptr) {
+            let mut value: args_value = zeroed();
+            match &(*arg).type_ {
+                cmd_parse_argument_type::String(string) => {
+                    value.type_ = args_type::ARGS_STRING;
+                    value.union_.string = xstrdup(*string).as_ptr();
+                }
+                _ => {
+                    value.type_ = args_type::ARGS_COMMANDS;
+                    value.union_.cmdlist = cmd_list_new();
+                }
+            }
+            values.push(value);
+        }
+
+        'out: {
+            if values.is_empty() || values[0].type_ != args_type::ARGS_STRING {
+                break 'out;
+            }
+            let name = values[0].union_.string;
+            let entry = match cmd_find(name) {
+                Ok(entry) => entry,
+                Err(cause) => {
+                    let alias = cmd_get_alias(name);
+                    if alias.is_null() {
+                        cfg_check_report(cs, line, &words, |_| false, cstr_to_str(cause));
+                    }
+                    free_(alias);
+                    free_(cause);
+                    break 'out;
+                }
+            };
+
+            let mut cause = null_mut();
+            let args = args_parse(
+                &raw mut (*entry).args,
+                values.as_mut_ptr(),
+                values.len() as u32,
+                &raw mut cause,
+            );
+            if args.is_null() {
+                if cause.is_null() {
+                    let message = format!("usage: {} {}", _s((*entry).name), _s((*entry).usage));
+                    cfg_check_report(cs, line, &words, |_| false, &message);
+                } else {
+                    // Point at the flag named in the error, if any.
+                    let error = cstr_to_str(cause);
+                    let flag = error
+                        .split(' ')
+                        .find_map(|w| w.strip_prefix('-').and_then(|f| f.chars().next()));
+                    let message = format!("command {}: {}", _s((*entry).name), error);
+                    cfg_check_report(
+                        cs,
+                        line,
+                        &words,
+                        |w| flag.is_some_and(|f| w.starts_with('-') && w.contains(f)),
+                        &message,
+                    );
+                    free_(cause);
+                }
+                break 'out;
+            }
+
+            let result = if entry == &raw mut cmd_set_option_entry
+                || entry == &raw mut cmd_set_window_option_entry
+            {
+                cfg_check_option(args)
+            } else if entry == &raw mut cmd_bind_key_entry
+                || (entry == &raw mut cmd_unbind_key_entry
+                    && !args_has_(args, 'a')
+                    && args_count(args) != 0)
+            {
+                cfg_check_key(args)
+            } else if entry == &raw mut cmd_define_command_entry {
+                // The parser does not declare commands when only parsing, so
+                // declare it here for later lines to use.
+                cmd_define_command_declare(args).map_err(|cause| (cause, String::new()))
+            } else {
+                Ok(())
+            };
+            if let Err((message, word)) = result {
+                cfg_check_report(cs, line, &words, |w| w == word, &message);
+            }
+            args_free(args);
+        }
+        for value in &mut values {
+            args_free_value(value);
+        }
+
+        for arg in tailq_foreach(&raw mut (*cmd).arguments).map(NonNull::as_ptr) {
+            if let cmd_parse_argument_type::Commands(commands) = &mut (*arg).type_ {
+                cfg_check_commands(cs, *commands);
+            }
+        }
+    }
+}
+
+/// Check the option name and value given to set-option. Returns the error
+/// and the argument it is about.
+unsafe fn cfg_check_option(args: *mut args) -> Result<(), (String, String)> {
+    unsafe {
+        let argument = args_string(args, 0);
+        if !libc::strstr(argument, c"#{".as_ptr()).is_null() {
+            return Ok(());
+        }
+        let argument_str = _s(argument).to_string();
+
+        let mut idx = -1;
+        let mut ambiguous = 0;
+        let name = options_match(argument, &raw mut idx, &raw mut ambiguous);
+        if name.is_null() {
+            if ambiguous != 0 {
+                return Err((format!("ambiguous option: {argument_str}"), argument_str));
+            }
+            return Err((format!("invalid option: {argument_str}"), argument_str));
+        }
+        let table = &raw const options_table;
+        let oe = (*table)
+            .iter()
+            .take_while(|oe| !oe.name.is_null())
+            .find(|oe| libc::strcmp(oe.name, name) == 0);
+        free_(name);
+        let Some(oe) = oe else {
+            return Ok(());
+        };
+        if args_has_(args, 'u') || args_has_(args, 'U') || args_has_(args, 'F') {
+            return Ok(());
+        }
+
+        let value = args_string(args, 1);
+        let word = if value.is_null() {
+            argument_str.clone()
+        } else {
+            _s(value).to_string()
+        };
+        let is_array = oe.flags & OPTIONS_TABLE_IS_ARRAY != 0;
+        if idx != -1 && !is_array {
+            return Err((format!("not an array: {argument_str}"), argument_str));
+        }
+
+        let oo = options_create(null_mut());
+        let o = options_default(oo, oe);
+        let mut cause = null_mut();
+        let error = if !is_array {
+            options_from_string(oo, oe, oe.name, value, args_has(args, b'a'), &raw mut cause)
+        } else if value.is_null() {
+            cause = xstrdup_(c"empty value").as_ptr();
+            -1
+        } else if idx == -1 {
+            if !args_has_(args, 'a') {
+                options_array_clear(o);
+            }
+            options_array_assign(o, value, &raw mut cause)
+        } else {
+            options_array_set(o, idx as u32, value, args_has(args, b'a'), &raw mut cause)
+        };
+        options_free(oo);
+
+        if error != 0 {
+            let message = format!("{}: {}", argument_str, _s(cause));
+            free_(cause);
+            return Err((message, word));
+        }
+        Ok(())
+    }
+}
+
+/// Check the key given to bind-key or unbind-key.
+unsafe fn cfg_check_key(args: *mut args) -> Result<(), (String, String)> {
+    unsafe {
+        let s = args_string(args, 0);
+        let key = key_string_lookup_string(s);
+        if key == KEYC_NONE || key == KEYC_UNKNOWN {
+            let word = _s(s).to_string();
+            return Err((format!("unknown key: {word}"), word));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cfg_check_diagnostics() {
+        unsafe {
+            let path = format!("/tmp/cfg-check-{}.conf", std::process::id());
+            std::fs::write(
+                &path,
+                concat!(
+                    "set -g status-position top\n",
+                    "set -g no-such-option 1\n",
+                    "set -g status-position middle\n",
+                    "  set -g mouse on ; set -g base-index x\n",
+                    "bind -n NoSuchKey display hi\n",
+                    "bind -T root C-a { split-window -z }\n",
+                    "unbind -a\n",
+                    "set -g status-left-length\n",
+                    "set -g @user-option anything\n",
+                    "set -g status-left '#{session_name}'\n",
+                    "if -F 1 { set -g status-position top }\n",
+                    "define-command check-hello 'display hi'\n",
+                    "check-hello -x\n",
+                ),
+            )
+            .unwrap();
+            let diagnostics = cfg_check_diagnostics(&path).unwrap();
+            let expected = [
+                "2:8: invalid option: no-such-option",
+                "3:24: status-position: unknown value: middle",
+                "4:39: base-index: value is invalid: x",
+                "5:9: unknown key: NoSuchKey",
+                "6:33: command split-window: unknown flag -z",
+                "8:8: status-left-length: empty value",
+                "13:13: command check-hello: unknown flag -x",
+            ];
+            let expected: Vec<String> = expected.iter().map(|d| format!("{path}:{d}")).collect();
+            assert_eq!(diagnostics, expected);
+
+            std::fs::write(&path, "set -g mouse on\nif 1 {\n").unwrap();
+            assert_eq!(
+                cfg_check_diagnostics(&path).unwrap(),
+                [format!("{path}:3:1: syntax error")]
+            );
+
+            std::fs::remove_file(&path).unwrap();
+            assert!(cfg_check_diagnostics(&path).is_err());
+        }
+    }
+
+    #[test]
+    #[expect(clippy::deref_addrof)]
+    fn test_cfg_watch_files() {
+        unsafe {
+            // Files that do not exist keep their path as the real path.
+            cfg_watch_add("/nonexistent/main.conf", null());
+            cfg_watch_add("/nonexistent/a.conf", c"/nonexistent/main.conf".as_ptr());
+            cfg_watch_add("/nonexistent/b.conf", c"/nonexistent/a.conf".as_ptr());
+            cfg_watch_add("/nonexistent/other.conf", null());
+            cfg_watch_add("-", null());
+            // Loading a file from itself does not make it its own parent.
+            cfg_watch_add("/nonexistent/main.conf", c"/nonexistent/main.conf".as_ptr());
+            assert_eq!((*&raw const cfg_watch_files).len(), 4);
+
+            assert_eq!(
+                cfg_watch_root("/nonexistent/b.conf"),
+                "/nonexistent/main.conf"
+            );
+            assert_eq!(
+                cfg_watch_root("/nonexistent/other.conf"),
+                "/nonexistent/other.conf"
+            );
+            assert_eq!(
+                cfg_watch_family("/nonexistent/main.conf"),
+                [
+                    "/nonexistent/main.conf",
+                    "/nonexistent/a.conf",
+                    "/nonexistent/b.conf"
+                ]
+            );
+
+            // A pattern is kept once and matches new files in its directory.
+            for _ in 0..2 {
+                cfg_watch_add_pattern(
+                    c"/nonexistent/a.conf".as_ptr(),
+                    c"/nonexistent/d/*.conf".as_ptr(),
+                );
+            }
+            cfg_watch_add_pattern(c"/nonexistent/unknown.conf".as_ptr(), c"/x/*".as_ptr());
+            assert_eq!(
+                cfg_watch_find("/nonexistent/a.conf").unwrap().patterns,
+                ["/nonexistent/d/*.conf"]
+            );
+
+            assert!(cfg_watch_changed_path("/nonexistent/d/new.conf"));
+            assert!(!cfg_watch_changed_path("/nonexistent/d/sub/new.conf"));
+            assert!(!cfg_watch_changed_path("/nonexistent/unrelated.conf"));
+            assert!(cfg_watch_changed_path("/nonexistent/b.conf"));
+            assert!(cfg_watch_changed_path("/nonexistent/other.conf"));
+            assert_eq!(
+                std::mem::take(&mut *&raw mut cfg_watch_changed),
+                ["/nonexistent/main.conf", "/nonexistent/other.conf"]
+            );
+
+            (*&raw mut cfg_watch_files).clear();
+        }
+    }
+}
@@ -310,10 +310,7 @@ pub unsafe extern "C-unwind" fn client_main(
         // #ifdef HAVE_SYSTEMD
         #[cfg(feature = "systemd")]
         {
-            unsafe extern "C" {
-                fn systemd_activated() -> i32;
-            }
-            if systemd_activated() != 0 {
+            if crate::compat::systemd::systemd_activated() != 0 {
                 fd = server_start(client_proc, flags, base, 0, null_mut());
             } else {
                 fd = client_connect(base, socket_path, client_flags);
@@ -467,7 +464,16 @@ pub unsafe extern "C-unwind" fn client_main(
                 kill(ppid, SIGHUP);
             }
         } else if (*&raw const client_flags).intersects(client_flag::CONTROL) {
-            if client_exitreason != client_exitreason::CLIENT_EXIT_NONE {
+            if (*&raw const client_flags).intersects(client_flag::CONTROL_JSON) {
+                let reason = if client_exitreason != client_exitreason::CLIENT_EXIT_NONE {
+                    control_json::cstr(client_exit_message())
+                } else {
+                    control_json::Null
+                };
+                let mut line = control_json_line("exit", &[("reason", reason)]);
+                line.push('\0');
+                printf(c"%s\n".as_ptr(), line.as_ptr());
+            } else if client_exitreason != client_exitreason::CLIENT_EXIT_NONE {
                 printf(c"%%exit %s\n".as_ptr(), client_exit_message());
             } else {
                 printf(c"%%exit\n".as_ptr());
@@ -20,8 +20,8 @@ pub static mut cmd_attach_session_entry: cmd_entry = cmd_entry {
     name: c"attach-session".as_ptr(),
     alias: c"attach".as_ptr(),
 
-    args: args_parse::new(c"c:dEf:rt:x", 0, 0, None),
-    usage: c"[-dErx] [-c working-directory] [-f flags] [-t target-session]".as_ptr(),
+    args: args_parse::new(c"c:dEf:rt:Vx", 0, 0, None),
+    usage: c"[-dErVx] [-c working-directory] [-f flags] [-t target-session]".as_ptr(),
 
     flags: cmd_flag::CMD_STARTSERVER.union(cmd_flag::CMD_READONLY),
     exec: Some(cmd_attach_session_exec),
@@ -30,6 +30,7 @@ pub static mut cmd_attach_session_entry: cmd_entry = cmd_entry {
 
 pub unsafe fn cmd_attach_session(
     item: *mut cmdq_item,
+    c: *mut client,
     tflag: *const c_char,
     dflag: c_int,
     xflag: c_int,
@@ -41,7 +42,6 @@ pub unsafe fn cmd_attach_session(
     unsafe {
         let current: *mut cmd_find_state = cmdq_get_current(item);
         let mut target: cmd_find_state = zeroed(); // TODO can be uninit
-        let c: *mut client = cmdq_get_client(item);
 
         let cwd: *mut c_char;
         let mut cause: *mut c_char = null_mut();
@@ -57,7 +57,7 @@ pub unsafe fn cmd_attach_session(
             return cmd_retval::CMD_RETURN_NORMAL;
         }
 
-        if server_client_check_nested(c) != 0 {
+        if !(*c).flags.intersects(client_flag::VIRTUAL) && server_client_check_nested(c) != 0 {
             cmdq_error!(
                 item,
                 "sessions should be nested with care, unset $TMUX to force",
@@ -173,9 +173,19 @@ pub unsafe fn cmd_attach_session(
 unsafe fn cmd_attach_session_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
     unsafe {
         let args = cmd_get_args(self_);
+        let mut c = cmdq_get_client(item);
 
-        cmd_attach_session(
+        if args_has_(args, 'V') {
+            let vc = cmd_attach_session_virtual(item, c);
+            if vc.is_null() {
+                return cmd_retval::CMD_RETURN_ERROR;
+            }
+            c = vc;
+        }
+
+        let retval = cmd_attach_session(
             item,
+            c,
             args_get(args, b't'),
             args_has(args, b'd'),
             args_has(args, b'x'),
@@ -183,6 +193,55 @@ unsafe fn cmd_attach_session_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_
             args_get(args, b'c'),
             args_has(args, b'E'),
             args_get(args, b'f'),
-        )
+        );
+        if args_has_(args, 'V') && retval == cmd_retval::CMD_RETURN_ERROR {
+            server_client_lost(c);
+        }
+        retval
+    }
+}
+
+/// Create a virtual client the size of the default-size option to attach in
+/// place of the client running the command.
+pub unsafe fn cmd_attach_session_virtual(item: *mut cmdq_item, c: *mut client) -> *mut client {
+    unsafe {
+        let mut sx: u32 = 0;
+        let mut sy: u32 = 0;
+
+        let size = options_get_string_(global_s_options, c"default-size");
+        if libc::sscanf(size, c"%ux%u".as_ptr(), &raw mut sx, &raw mut sy) != 2 {
+            sx = 80;
+            sy = 24;
+        }
+        cmd_attach_session_create_virtual(item, c, sx, sy)
+    }
+}
+
+/// Create a virtual client of the given size, inheriting the environment of
+/// the client running the command.
+pub unsafe fn cmd_attach_session_create_virtual(
+    item: *mut cmdq_item,
+    c: *mut client,
+    sx: u32,
+    sy: u32,
+) -> *mut client {
+    unsafe {
+        if sx < WINDOW_MINIMUM || sx > WINDOW_MAXIMUM || sy < WINDOW_MINIMUM || sy > WINDOW_MAXIMUM
+        {
+            cmdq_error!(item, "size too small or too big");
+            return null_mut();
+        }
+        let vc = server_client_create_virtual(sx, sy);
+        if vc.is_null() {
+            cmdq_error!(item, "can't create virtual client");
+            return null_mut();
+        }
+        if !c.is_null() {
+            environ_copy((*c).environ, (*vc).environ);
+            if !(*c).cwd.is_null() {
+                (*vc).cwd = xstrdup((*c).cwd).as_ptr();
+            }
+        }
+        vc
     }
 }
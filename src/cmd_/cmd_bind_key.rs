@@ -57,6 +57,7 @@ unsafe fn cmd_bind_key_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval
             c"prefix".as_ptr()
         };
         repeat = args_has(args, b'r');
+        cfg_watch_set_key(item, self_, tablename, key);
 
         if count == 1 {
             key_bindings_add(tablename, key, note, repeat, null_mut());
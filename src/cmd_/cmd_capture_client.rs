@@ -0,0 +1,211 @@
+use std::fmt::Write as _;
+
+use crate::*;
+
+pub static mut cmd_capture_client_entry: cmd_entry = cmd_entry {
+    name: c"capture-client".as_ptr(),
+    alias: c"capturec".as_ptr(),
+
+    args: args_parse::new(c"b:eJpt:", 0, 0, None),
+    usage: c"[-eJp] [-b buffer-name] [-t target-client]".as_ptr(),
+
+    flags: cmd_flag::CMD_AFTERHOOK.union(cmd_flag::CMD_CLIENT_TFLAG),
+    exec: Some(cmd_capture_client_exec),
+    ..unsafe { zeroed() }
+};
+
+/// Capture the screen as text, with escape sequences if -e is given.
+unsafe fn cmd_capture_client_text(s: *mut screen, sequences: bool) -> String {
+    unsafe {
+        let gd = (*s).grid;
+        let mut gc: *mut grid_cell = null_mut();
+        let mut flags = grid_string_flags::GRID_STRING_TRIM_SPACES;
+        if sequences {
+            flags |= grid_string_flags::GRID_STRING_WITH_SEQUENCES;
+        }
+
+        let mut out = String::new();
+        for y in 0..screen_size_y(s) {
+            let line = grid_string_cells(gd, 0, y, screen_size_x(s), &raw mut gc, flags, s);
+            out.push_str(&String::from_utf8_lossy(CStr::from_ptr(line).to_bytes()));
+            out.push('\n');
+            free_(line);
+        }
+        out
+    }
+}
+
+/// Add a run of cells with the same style to a JSON line.
+unsafe fn cmd_capture_client_run(out: &mut String, text: &[u8], gc: *const grid_cell) {
+    unsafe {
+        if !out.ends_with('[') {
+            out.push(',');
+        }
+        out.push_str("{\"text\":");
+        format_json_string(out, text);
+        out.push_str(",\"fg\":");
+        format_json_string(out, CStr::from_ptr(colour_tostring((*gc).fg)).to_bytes());
+        out.push_str(",\"bg\":");
+        format_json_string(out, CStr::from_ptr(colour_tostring((*gc).bg)).to_bytes());
+        out.push_str(",\"attributes\":");
+        format_json_string(
+            out,
+            CStr::from_ptr(attributes_tostring((*gc).attr)).to_bytes(),
+        );
+        out.push('}');
+    }
+}
+
+/// Capture the screen as JSON: each line is a list of runs of text sharing
+/// the same colours and attributes. Trailing default spaces are dropped.
+unsafe fn cmd_capture_client_json(c: *mut client, s: *mut screen) -> String {
+    unsafe {
+        let gd = (*s).grid;
+        let mut gc: grid_cell = zeroed();
+        let mut last: grid_cell = zeroed();
+
+        let mut out = String::from("{\"client\":");
+        format_json_string(&mut out, CStr::from_ptr((*c).name).to_bytes());
+        _ = write!(
+            out,
+            ",\"width\":{},\"height\":{},\"cursor\":{{\"x\":{},\"y\":{},\"visible\":{}}},\"lines\":[",
+            screen_size_x(s),
+            screen_size_y(s),
+            (*s).cx,
+            (*s).cy,
+            (*s).mode.intersects(mode_flag::MODE_CURSOR),
+        );
+        for y in 0..screen_size_y(s) {
+            if y != 0 {
+                out.push(',');
+            }
+            out.push('[');
+
+            let mut end = screen_size_x(s);
+            while end > 0 {
+                grid_get_cell(gd, end - 1, y, &raw mut gc);
+                if gc.data.size != 1 || gc.data.data[0] != b' ' || gc.bg != 8 || !gc.attr.is_empty()
+                {
+                    break;
+                }
+                end -= 1;
+            }
+
+            let mut text: Vec<u8> = Vec::new();
+            for x in 0..end {
+                grid_get_cell(gd, x, y, &raw mut gc);
+                if gc.flags.intersects(grid_flag::PADDING) {
+                    continue;
+                }
+                if !text.is_empty()
+                    && (gc.fg != last.fg || gc.bg != last.bg || gc.attr != last.attr)
+                {
+                    cmd_capture_client_run(&mut out, &text, &raw const last);
+                    text.clear();
+                }
+                text.extend_from_slice(&gc.data.data[..gc.data.size as usize]);
+                memcpy__(&raw mut last, &raw const gc);
+            }
+            if !text.is_empty() {
+                cmd_capture_client_run(&mut out, &text, &raw const last);
+            }
+            out.push(']');
+        }
+        out.push_str("]}\n");
+        out
+    }
+}
+
+unsafe fn cmd_capture_client_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
+    unsafe {
+        let args = cmd_get_args(self_);
+        let c = cmdq_get_client(item);
+        let tc = cmdq_get_target_client(item);
+
+        if !(*tc).flags.intersects(client_flag::VIRTUAL) {
+            cmdq_error!(item, "not a virtual client");
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+        let s = (*tc).tty.virtual_screen;
+        if s.is_null() {
+            cmdq_error!(item, "client not attached");
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+
+        let mut out = if args_has_(args, 'J') {
+            cmd_capture_client_json(tc, s)
+        } else {
+            cmd_capture_client_text(s, args_has_(args, 'e'))
+        };
+
+        if args_has_(args, 'p') {
+            out.pop();
+            if c.is_null() {
+                return cmd_retval::CMD_RETURN_NORMAL;
+            }
+            if (*c).flags.intersects(client_flag::CONTROL) {
+                control_print(c, out.as_ptr().cast(), out.len());
+            } else {
+                if file_can_print(c) == 0 {
+                    cmdq_error!(item, "can't write to client");
+                    return cmd_retval::CMD_RETURN_ERROR;
+                }
+                file_print_buffer(c, out.as_ptr().cast_mut().cast(), out.len());
+                file_print!(c, "\n");
+            }
+        } else {
+            let mut cause = null_mut();
+            let buf: *mut c_char = xmalloc(out.len().max(1)).as_ptr().cast();
+            memcpy_(buf, out.as_ptr().cast(), out.len());
+            if paste_set(buf, out.len(), args_get(args, b'b'), &raw mut cause) != 0 {
+                cmdq_error!(item, "{}", _s(cause));
+                free_(cause);
+                free_(buf);
+                return cmd_retval::CMD_RETURN_ERROR;
+            }
+        }
+
+        cmd_retval::CMD_RETURN_NORMAL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmd_capture_client_json() {
+        unsafe {
+            let mut c: client = zeroed();
+            c.name = c"virtual-0".as_ptr();
+            let mut s: screen = zeroed();
+            s.grid = grid_create(6, 2, 0);
+            s.cx = 4;
+            s.cy = 1;
+
+            let mut gc = grid_default_cell;
+            for (x, ch) in b"ab\"c".iter().enumerate() {
+                utf8_set(&raw mut gc.data, *ch);
+                gc.fg = if x < 2 { 8 } else { 1 };
+                grid_set_cell(s.grid, x as u32, 0, &gc);
+            }
+            utf8_set(&raw mut gc.data, b'x');
+            gc.fg = 8;
+            gc.attr = grid_attr::GRID_ATTR_BRIGHT;
+            grid_set_cell(s.grid, 0, 1, &gc);
+
+            assert_eq!(
+                cmd_capture_client_json(&raw mut c, &raw mut s),
+                concat!(
+                    r#"{"client":"virtual-0","width":6,"height":2,"#,
+                    r#""cursor":{"x":4,"y":1,"visible":false},"lines":["#,
+                    r#"[{"text":"ab","fg":"default","bg":"default","attributes":"none"},"#,
+                    r#"{"text":"\"c","fg":"red","bg":"default","attributes":"none"}],"#,
+                    r#"[{"text":"x","fg":"default","bg":"default","attributes":"bright"}]"#,
+                    "]}\n"
+                )
+            );
+            grid_destroy(s.grid);
+        }
+    }
+}
@@ -12,7 +12,7 @@
 // IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING
 // OUT OF OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use libc::{INT_MIN, strcmp, strlen};
+use libc::{INT_MAX, INT_MIN, localtime_r, strcmp, strlen};
 
 use crate::*;
 
@@ -20,8 +20,8 @@ pub static mut cmd_capture_pane_entry: cmd_entry = cmd_entry {
     name: c"capture-pane".as_ptr(),
     alias: c"capturep".as_ptr(),
 
-    args: args_parse::new(c"ab:CeE:JNpPqS:Tt:", 0, 0, None),
-    usage: c"[-aCeJNpPqT] [-b buffer-name] [-E end-line] [-S start-line] [-t target-pane]".as_ptr(),
+    args: args_parse::new(c"ab:CeE:JNo:pPqs:S:Tt:u:", 0, 0, None),
+    usage: c"[-aCeJNpPqT] [-b buffer-name] [-E end-line] [-o count] [-s since] [-S start-line] [-t target-pane] [-u until]".as_ptr(),
 
     source: unsafe { zeroed() },
     target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_PANE, 0),
@@ -102,6 +102,111 @@ unsafe fn cmd_capture_pane_pending(
     }
 }
 
+/// Parse a number made only of digits, since str::parse also allows a sign.
+fn cmd_capture_pane_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    if value.is_empty() || !value.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// Parse a time given to -s or -u: a number of seconds, minutes, hours or days
+/// ago (such as 10m), a time today (HH:MM or HH:MM:SS) or a date with an
+/// optional time (YYYY-MM-DD HH:MM).
+unsafe fn cmd_capture_pane_time(value: *const c_char, now: time_t) -> Option<time_t> {
+    unsafe {
+        let value = CStr::from_ptr(value).to_str().ok()?.trim();
+
+        let scale = match value.as_bytes().last()? {
+            b's' => 1,
+            b'm' => 60,
+            b'h' => 60 * 60,
+            b'd' => 24 * 60 * 60,
+            _ => 0,
+        };
+        if scale != 0 {
+            let n: time_t = cmd_capture_pane_number(&value[..value.len() - 1])?;
+            return now.checked_sub(n.checked_mul(scale)?);
+        }
+
+        let mut tm: libc::tm = zeroed();
+        localtime_r(&raw const now, &raw mut tm);
+
+        let (date, time) = match value.split_once([' ', 'T']) {
+            Some((date, time)) => (Some(date), Some(time)),
+            None if value.contains('-') => (Some(value), None),
+            None => (None, Some(value)),
+        };
+        if let Some(date) = date {
+            let fields: Vec<i32> = date
+                .split('-')
+                .map(cmd_capture_pane_number)
+                .collect::<Option<_>>()?;
+            let [year, month, day] = fields[..] else {
+                return None;
+            };
+            if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+                return None;
+            }
+            tm.tm_year = year - 1900;
+            tm.tm_mon = month - 1;
+            tm.tm_mday = day;
+            (tm.tm_hour, tm.tm_min, tm.tm_sec) = (0, 0, 0);
+        }
+        if let Some(time) = time {
+            let fields: Vec<i32> = time
+                .split(':')
+                .map(cmd_capture_pane_number)
+                .collect::<Option<_>>()?;
+            let (hour, minute, second) = match fields[..] {
+                [hour, minute] => (hour, minute, 0),
+                [hour, minute, second] => (hour, minute, second),
+                _ => return None,
+            };
+            if !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0..60).contains(&second)
+            {
+                return None;
+            }
+            (tm.tm_hour, tm.tm_min, tm.tm_sec) = (hour, minute, second);
+        }
+        tm.tm_isdst = -1;
+
+        match libc::mktime(&raw mut tm) {
+            -1 => None,
+            t => Some(t),
+        }
+    }
+}
+
+/// Find the lines between two times. A line's time is when it was scrolled
+/// into the history, so lines still on the screen count as now.
+unsafe fn cmd_capture_pane_time_lines(
+    gd: *mut grid,
+    since: time_t,
+    until: time_t,
+    now: time_t,
+) -> Option<(u32, u32)> {
+    unsafe {
+        let time = |yy: u32| {
+            let t = (*grid_get_line_marks(gd, yy)).time;
+            if yy >= (*gd).hsize || t == 0 { now } else { t }
+        };
+
+        let mut bottom = (*gd).hsize + (*gd).sy;
+        while bottom > 0 && time(bottom - 1) > until {
+            bottom -= 1;
+        }
+        let mut top = bottom;
+        while top > 0 && time(top - 1) >= since {
+            top -= 1;
+        }
+        if top == bottom {
+            return None;
+        }
+        Some((top, bottom - 1))
+    }
+}
+
 unsafe fn cmd_capture_pane_history(
     args: *mut args,
     item: *mut cmdq_item,
@@ -137,6 +242,7 @@ unsafe fn cmd_capture_pane_history(
         } else {
             gd = (*wp).base.grid;
         }
+        grid_reflow_finish(gd);
 
         let sflag: *const c_char = args_get(args, b'S');
         let mut top = 0;
@@ -157,7 +263,7 @@ unsafe fn cmd_capture_pane_history(
             } else if n < 0 && (-n) as u32 > (*gd).hsize {
                 top = 0;
             } else {
-                top = (*gd).hsize + n as u32;
+                top = ((*gd).hsize as i64 + n) as u32;
             }
             if top > (*gd).hsize + (*gd).sy - 1 {
                 top = (*gd).hsize + (*gd).sy - 1;
@@ -182,7 +288,7 @@ unsafe fn cmd_capture_pane_history(
             } else if n < 0 && (-n) as u32 > (*gd).hsize {
                 bottom = 0;
             } else {
-                bottom = (*gd).hsize + n as u32;
+                bottom = ((*gd).hsize as i64 + n) as u32;
             }
             if bottom > (*gd).hsize + (*gd).sy - 1 {
                 bottom = (*gd).hsize + (*gd).sy - 1;
@@ -195,6 +301,48 @@ unsafe fn cmd_capture_pane_history(
             top = tmp;
         }
 
+        // The output of a command or the lines between two times replace any
+        // line numbers.
+        if args_has(args, b'o') != 0 {
+            n = args_strtonum_and_expand(args, b'o', 1, INT_MAX as i64, item, &raw mut cause);
+            if !cause.is_null() {
+                cmdq_error!(item, "count {}", _s(cause));
+                free_(cause);
+                return null_mut();
+            }
+            let Some((start, end)) = grid_find_last_command(gd, n as u32) else {
+                if args_has(args, b'q') == 0 {
+                    cmdq_error!(item, "no command {}", n);
+                    return null_mut();
+                }
+                return xstrdup(c"".as_ptr()).as_ptr();
+            };
+            if start == end {
+                return xstrdup(c"".as_ptr()).as_ptr();
+            }
+            top = start;
+            bottom = end - 1;
+        } else if args_has(args, b's') != 0 || args_has(args, b'u') != 0 {
+            let now = libc::time(null_mut());
+            let mut since = time_t::MIN;
+            let mut until = time_t::MAX;
+            for (flag, value) in [(b's', &mut since), (b'u', &mut until)] {
+                let arg = args_get(args, flag);
+                if arg.is_null() {
+                    continue;
+                }
+                let Some(t) = cmd_capture_pane_time(arg, now) else {
+                    cmdq_error!(item, "invalid time: {}", _s(arg));
+                    return null_mut();
+                };
+                *value = t;
+            }
+            let Some(lines) = cmd_capture_pane_time_lines(gd, since, until, now) else {
+                return xstrdup(c"".as_ptr()).as_ptr();
+            };
+            (top, bottom) = lines;
+        }
+
         join_lines = args_has(args, b'J');
         if args_has(args, b'e') != 0 {
             flags |= grid_string_flags::GRID_STRING_WITH_SEQUENCES;
@@ -258,7 +406,8 @@ unsafe fn cmd_capture_pane_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_re
                 len -= 1;
             }
             if (*c).flags.intersects(client_flag::CONTROL) {
-                control_write!(c, "{1:0$}", len, _s(buf));
+                control_print(c, buf, len);
+                free_(buf);
             } else {
                 if file_can_print(c) == 0 {
                     cmdq_error!(item, "can't write to client");
@@ -287,3 +436,43 @@ unsafe fn cmd_capture_pane_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_re
         cmd_retval::CMD_RETURN_NORMAL
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmd_capture_pane_time() {
+        unsafe {
+            let now: time_t = 1_700_000_000;
+            let time = |value: &CStr| cmd_capture_pane_time(value.as_ptr(), now);
+
+            assert_eq!(time(c"30s"), Some(now - 30));
+            assert_eq!(time(c"10m"), Some(now - 600));
+            assert_eq!(time(c"2h"), Some(now - 7200));
+            assert_eq!(time(c"1d"), Some(now - 86400));
+
+            let mut tm: libc::tm = zeroed();
+            let t = time(c"12:34:56").unwrap();
+            localtime_r(&raw const t, &raw mut tm);
+            assert_eq!((tm.tm_hour, tm.tm_min, tm.tm_sec), (12, 34, 56));
+
+            let t = time(c"2024-02-29 08:15").unwrap();
+            localtime_r(&raw const t, &raw mut tm);
+            assert_eq!((tm.tm_year, tm.tm_mon, tm.tm_mday), (124, 1, 29));
+            assert_eq!((tm.tm_hour, tm.tm_min), (8, 15));
+            assert!(time(c"2024-02-29").unwrap() < t);
+
+            assert_eq!(time(c""), None);
+            assert_eq!(time(c"m"), None);
+            assert_eq!(time(c"10x"), None);
+            assert_eq!(time(c"25:00"), None);
+            assert_eq!(time(c"2024-13-01"), None);
+            assert_eq!(time(c"12:34:56:78"), None);
+            assert_eq!(time(c"-10m"), None);
+            assert_eq!(time(c"+5s"), None);
+            assert_eq!(time(c"12:+4"), None);
+            assert_eq!(time(c"2024-+2-01"), None);
+        }
+    }
+}
@@ -56,6 +56,20 @@ pub static mut cmd_choose_buffer_entry: cmd_entry = cmd_entry {
     exec: Some(cmd_choose_tree_exec),
 };
 
+pub static mut cmd_choose_fuzzy_entry: cmd_entry = cmd_entry {
+    name: c"choose-fuzzy".as_ptr(),
+    alias: null_mut(),
+
+    args: args_parse::new(c"bcNpst:wZ", 0, 1, Some(cmd_choose_tree_args_parse)),
+    usage: c"[-bcNpswZ] [-t target-pane] [template]".as_ptr(),
+
+    target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_PANE, 0),
+    source: unsafe { zeroed() },
+
+    flags: cmd_flag::empty(),
+    exec: Some(cmd_choose_tree_exec),
+};
+
 pub static mut cmd_customize_mode_entry: cmd_entry = cmd_entry {
     name: c"customize-mode".as_ptr(),
     alias: null_mut(),
@@ -94,6 +108,16 @@ unsafe fn cmd_choose_tree_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
                 return cmd_retval::CMD_RETURN_NORMAL;
             }
             &raw const window_client_mode
+        } else if cmd_get_entry(self_) == &raw mut cmd_choose_fuzzy_entry {
+            let only_buffers = args_has_(args, 'b')
+                && !args_has_(args, 's')
+                && !args_has_(args, 'w')
+                && !args_has_(args, 'p')
+                && !args_has_(args, 'c');
+            if only_buffers && paste_is_empty() != 0 {
+                return cmd_retval::CMD_RETURN_NORMAL;
+            }
+            &raw const window_fuzzy_mode
         } else if cmd_get_entry(self_) == &raw mut cmd_customize_mode_entry {
             &raw const window_customize_mode
         } else {
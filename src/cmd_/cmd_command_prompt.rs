@@ -151,6 +151,9 @@ unsafe fn cmd_command_prompt_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_
         } else {
             (*cdata).prompt_type = prompt_type::PROMPT_TYPE_COMMAND;
         }
+        if count == 0 && (*cdata).prompt_type == prompt_type::PROMPT_TYPE_COMMAND {
+            (*cdata).flags |= PROMPT_HIGHLIGHT;
+        }
 
         if args_has(args, b'1') != 0 {
             (*cdata).flags |= PROMPT_SINGLE;
@@ -42,6 +42,20 @@ pub static mut cmd_clock_mode_entry: cmd_entry = cmd_entry {
     exec: Some(cmd_copy_mode_exec),
 };
 
+pub static mut cmd_pane_timeline_entry: cmd_entry = cmd_entry {
+    name: c"pane-timeline".as_ptr(),
+    alias: null_mut(),
+
+    args: args_parse::new(c"m:t:", 0, 0, None),
+    usage: c"[-m minutes] [-t target-pane]".as_ptr(),
+
+    target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_PANE, 0),
+    source: unsafe { zeroed() },
+
+    flags: cmd_flag::CMD_AFTERHOOK,
+    exec: Some(cmd_copy_mode_exec),
+};
+
 unsafe fn cmd_copy_mode_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
     unsafe {
         let args = cmd_get_args(self_);
@@ -67,6 +81,26 @@ unsafe fn cmd_copy_mode_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retva
             }
         }
 
+        if cmd_get_entry(self_) == &raw mut cmd_pane_timeline_entry {
+            if args_has(args, b'm') != 0 {
+                let mut cause = null_mut();
+                args_strtonum(args, b'm', 1, 1440, &raw mut cause);
+                if !cause.is_null() {
+                    cmdq_error!(item, "minutes {}", _s(cause));
+                    free_(cause);
+                    return cmd_retval::CMD_RETURN_ERROR;
+                }
+            }
+            window_pane_set_mode(
+                wp,
+                null_mut(),
+                &raw const window_timeline_mode,
+                target,
+                args,
+            );
+            return cmd_retval::CMD_RETURN_NORMAL;
+        }
+
         if cmd_get_entry(self_) == &raw mut cmd_clock_mode_entry {
             window_pane_set_mode(
                 wp,
@@ -0,0 +1,514 @@
+use crate::*;
+
+use std::ffi::CString;
+
+use crate::compat::strtonum;
+
+// Defines commands made of other commands.
+//
+// A user command has a real cmd_entry so it is parsed like any built-in
+// command: its flags and argument counts come from the argument template and
+// are checked by args_parse. The template is a list of words:
+//
+//     -x           a flag without a value, #{arg_flag_x} is 1 or 0
+//     -x:type      a flag with a value, #{arg_flag_x} is the value or empty
+//     name:type    a positional argument, #{arg_1}, #{arg_2} and so on
+//     [name:type]  an optional positional argument
+//     name:type... any number of positional arguments, must be last
+//
+// The type is one of string (the default), number, key, pane, window or
+// session. Targets are resolved when the command runs and are given to the
+// body as IDs, so they still refer to the same object if the current pane
+// changes.
+//
+// Commands are declared when define-command is parsed so they can be used
+// later in the same file. Entries are never freed because parsed commands may
+// still point to them; removing or redefining a command only changes the
+// table.
+
+pub static mut cmd_define_command_entry: cmd_entry = cmd_entry {
+    name: c"define-command".as_ptr(),
+    alias: c"defc".as_ptr(),
+
+    args: args_parse::new(c"a:u", 1, 2, Some(cmd_define_command_args_parse)),
+    usage: c"[-u] [-a argument-template] name [command]".as_ptr(),
+
+    flags: cmd_flag::CMD_AFTERHOOK,
+    exec: Some(cmd_define_command_exec),
+    ..unsafe { zeroed() }
+};
+
+/// Most user commands that may run inside each other.
+const CMD_DEFINE_COMMAND_DEPTH: u32 = 100;
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum cmd_user_type {
+    Flag,
+    String,
+    Number,
+    Key,
+    Pane,
+    Window,
+    Session,
+}
+
+impl cmd_user_type {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "string" => Some(Self::String),
+            "number" => Some(Self::Number),
+            "key" => Some(Self::Key),
+            "pane" => Some(Self::Pane),
+            "window" => Some(Self::Window),
+            "session" => Some(Self::Session),
+            _ => None,
+        }
+    }
+}
+
+struct cmd_user_argument {
+    name: String,
+    type_: cmd_user_type,
+    flag: u8,
+    optional: bool,
+    rest: bool,
+}
+
+struct cmd_user_command {
+    entry: *mut cmd_entry,
+    arguments: Vec<cmd_user_argument>,
+    body: *mut cmd_list,
+}
+
+static mut cmd_user_commands: Vec<cmd_user_command> = Vec::new();
+
+unsafe fn cmd_define_command_args_parse(
+    _args: *mut args,
+    idx: u32,
+    _cause: *mut *mut c_char,
+) -> args_parse_type {
+    if idx == 1 {
+        return args_parse_type::ARGS_PARSE_COMMANDS_OR_STRING;
+    }
+    args_parse_type::ARGS_PARSE_STRING
+}
+
+/// Parse an argument template.
+fn cmd_define_command_template(template: &str) -> Result<Vec<cmd_user_argument>, String> {
+    let mut arguments: Vec<cmd_user_argument> = Vec::new();
+
+    for word in template.split_whitespace() {
+        if arguments.last().is_some_and(|a| a.rest) {
+            return Err(format!(
+                "argument after {}...",
+                arguments.last().unwrap().name
+            ));
+        }
+
+        let (word, optional) = match word.strip_prefix('[') {
+            Some(inner) => match inner.strip_suffix(']') {
+                Some(inner) => (inner, true),
+                None => return Err(format!("missing ] in {word}")),
+            },
+            None => (word, false),
+        };
+
+        if let Some(spec) = word.strip_prefix('-') {
+            let mut chars = spec.chars();
+            let flag = match chars.next() {
+                Some(ch) if ch.is_ascii_alphanumeric() => ch as u8,
+                _ => return Err(format!("bad flag: {word}")),
+            };
+            if arguments.iter().any(|a| a.flag == flag) {
+                return Err(format!("flag -{} given twice", flag as char));
+            }
+            let rest = chars.as_str();
+            let type_ = if rest.is_empty() {
+                None
+            } else if let Some(name) = rest.strip_prefix(':') {
+                match cmd_user_type::from_name(name) {
+                    Some(type_) => Some(type_),
+                    None => return Err(format!("unknown type: {name}")),
+                }
+            } else {
+                return Err(format!("bad flag: {word}"));
+            };
+            arguments.push(cmd_user_argument {
+                name: rest.trim_start_matches(':').to_string(),
+                type_: type_.unwrap_or(cmd_user_type::Flag),
+                flag,
+                optional: true,
+                rest: false,
+            });
+            continue;
+        }
+
+        let (word, rest) = match word.strip_suffix("...") {
+            Some(word) => (word, true),
+            None => (word, false),
+        };
+        let (name, type_) = match word.split_once(':') {
+            Some((name, type_)) => match cmd_user_type::from_name(type_) {
+                Some(type_) => (name, type_),
+                None => return Err(format!("unknown type: {type_}")),
+            },
+            None => (word, cmd_user_type::String),
+        };
+        if name.is_empty() {
+            return Err(format!("missing argument name: {word}"));
+        }
+        if !optional
+            && !rest
+            && arguments
+                .iter()
+                .any(|a| a.flag == 0 && (a.optional || a.rest))
+        {
+            return Err(format!(
+                "{name} is required but follows an optional argument"
+            ));
+        }
+        arguments.push(cmd_user_argument {
+            name: name.to_string(),
+            type_,
+            flag: 0,
+            optional,
+            rest,
+        });
+    }
+    Ok(arguments)
+}
+
+/// Build the getopt string, argument limits and usage for a template.
+fn cmd_define_command_usage(arguments: &[cmd_user_argument]) -> (String, i32, i32, String) {
+    let mut getopt = String::new();
+    let mut lower = 0;
+    let mut upper = 0;
+
+    let mut booleans = String::new();
+    let mut usage: Vec<String> = Vec::new();
+    for a in arguments {
+        if a.flag == 0 {
+            continue;
+        }
+        getopt.push(a.flag as char);
+        if a.type_ == cmd_user_type::Flag {
+            booleans.push(a.flag as char);
+        } else {
+            getopt.push(':');
+            usage.push(format!("[-{} {}]", a.flag as char, a.name));
+        }
+    }
+    if !booleans.is_empty() {
+        usage.insert(0, format!("[-{booleans}]"));
+    }
+
+    for a in arguments.iter().filter(|a| a.flag == 0) {
+        if a.rest {
+            if a.optional {
+                usage.push(format!("[{} ...]", a.name));
+            } else {
+                usage.push(format!("{} ...", a.name));
+                lower += 1;
+            }
+            upper = -1;
+            continue;
+        }
+        if a.optional {
+            usage.push(format!("[{}]", a.name));
+        } else {
+            usage.push(a.name.clone());
+            lower += 1;
+        }
+        upper += 1;
+    }
+    (getopt, lower, upper, usage.join(" "))
+}
+
+#[expect(clippy::deref_addrof)]
+unsafe fn cmd_define_command_find(name: &str) -> Option<&'static mut cmd_user_command> {
+    unsafe {
+        (*&raw mut cmd_user_commands)
+            .iter_mut()
+            .find(|uc| cstr_to_str((*uc.entry).name) == name)
+    }
+}
+
+/// Get the entries of the user commands.
+#[expect(clippy::deref_addrof)]
+pub unsafe fn cmd_define_command_entries() -> Vec<*mut cmd_entry> {
+    unsafe {
+        (*&raw const cmd_user_commands)
+            .iter()
+            .map(|uc| uc.entry)
+            .collect()
+    }
+}
+
+/// Add a user command or change the arguments of an existing one, leaving
+/// the body as it is.
+#[expect(clippy::deref_addrof)]
+pub unsafe fn cmd_define_command_declare(args: *mut args) -> Result<(), String> {
+    unsafe {
+        if args_has(args, b'u') != 0 {
+            return Ok(());
+        }
+        let name = cstr_to_str(args_string(args, 0));
+        if name.is_empty() || name.contains(|ch: char| ch.is_whitespace() || ch == ';') {
+            return Err(format!("bad command name: {name}"));
+        }
+        let table = &raw const cmd_table;
+        for entry in (*table).iter().take_while(|entry| !entry.is_null()) {
+            if cstr_to_str((**entry).name) == name
+                || (!(**entry).alias.is_null() && cstr_to_str((**entry).alias) == name)
+            {
+                return Err(format!("command already exists: {name}"));
+            }
+        }
+
+        let template = if args_has(args, b'a') != 0 {
+            cstr_to_str(args_get(args, b'a'))
+        } else {
+            ""
+        };
+        let arguments = cmd_define_command_template(template)?;
+        let (getopt, lower, upper, usage) = cmd_define_command_usage(&arguments);
+
+        let getopt = CString::new(getopt).unwrap().into_raw();
+        let usage = CString::new(usage).unwrap().into_raw();
+        if let Some(uc) = cmd_define_command_find(name) {
+            (*uc.entry).args = args_parse {
+                template: getopt,
+                lower,
+                upper,
+                cb: None,
+            };
+            (*uc.entry).usage = usage;
+            uc.arguments = arguments;
+            return Ok(());
+        }
+
+        let entry = Box::into_raw(Box::new(cmd_entry {
+            name: CString::new(name).unwrap().into_raw(),
+            alias: null(),
+
+            args: args_parse {
+                template: getopt,
+                lower,
+                upper,
+                cb: None,
+            },
+            usage,
+
+            flags: cmd_flag::empty(),
+            exec: Some(cmd_define_command_run),
+            ..zeroed()
+        }));
+        (*&raw mut cmd_user_commands).push(cmd_user_command {
+            entry,
+            arguments,
+            body: null_mut(),
+        });
+        Ok(())
+    }
+}
+
+#[expect(clippy::deref_addrof)]
+unsafe fn cmd_define_command_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
+    unsafe {
+        let args = cmd_get_args(self_);
+        let name = cstr_to_str(args_string(args, 0));
+
+        if args_has(args, b'u') != 0 {
+            let commands = &mut *&raw mut cmd_user_commands;
+            let Some(i) = commands
+                .iter()
+                .position(|uc| cstr_to_str((*uc.entry).name) == name)
+            else {
+                cmdq_error!(item, "unknown command: {}", name);
+                return cmd_retval::CMD_RETURN_ERROR;
+            };
+            let uc = commands.remove(i);
+            if !uc.body.is_null() {
+                cmd_list_free(uc.body);
+            }
+            return cmd_retval::CMD_RETURN_NORMAL;
+        }
+        if args_count(args) != 2 {
+            cmdq_error!(item, "no command given for {}", name);
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+
+        if let Err(cause) = cmd_define_command_declare(args) {
+            cmdq_error!(item, "{}", cause);
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+
+        let value = args_value(args, 1);
+        let body = if (*value).type_ == args_type::ARGS_COMMANDS {
+            (*(*value).union_.cmdlist).references += 1;
+            (*value).union_.cmdlist
+        } else {
+            match cmd_parse_from_string(cstr_to_str(args_string(args, 1)), None) {
+                Ok(cmdlist) => cmdlist,
+                Err(error) => {
+                    cmdq_error!(item, "{}", _s(error));
+                    free_(error);
+                    return cmd_retval::CMD_RETURN_ERROR;
+                }
+            }
+        };
+
+        let uc = cmd_define_command_find(name).unwrap();
+        if !uc.body.is_null() {
+            cmd_list_free(uc.body);
+        }
+        uc.body = body;
+        cmd_retval::CMD_RETURN_NORMAL
+    }
+}
+
+/// Check the value of a typed argument and convert it to the form given to
+/// the body.
+unsafe fn cmd_define_command_value(
+    item: *mut cmdq_item,
+    a: &cmd_user_argument,
+    value: *const c_char,
+) -> Option<String> {
+    unsafe {
+        let mut fs: cmd_find_state = zeroed();
+
+        let type_ = match a.type_ {
+            cmd_user_type::Flag | cmd_user_type::String => {
+                return Some(cstr_to_str(value).to_string());
+            }
+            cmd_user_type::Number => {
+                return match strtonum(value, i64::MIN, i64::MAX) {
+                    Ok(n) => Some(n.to_string()),
+                    Err(errstr) => {
+                        cmdq_error!(item, "{} is {}: {}", a.name, _s(errstr.as_ptr()), _s(value));
+                        None
+                    }
+                };
+            }
+            cmd_user_type::Key => {
+                let key = key_string_lookup_string(value);
+                if key == KEYC_NONE || key == KEYC_UNKNOWN {
+                    cmdq_error!(item, "{} is not a key: {}", a.name, _s(value));
+                    return None;
+                }
+                return Some(cstr_to_str(key_string_lookup_key(key, 0)).to_string());
+            }
+            cmd_user_type::Pane => cmd_find_type::CMD_FIND_PANE,
+            cmd_user_type::Window => cmd_find_type::CMD_FIND_WINDOW,
+            cmd_user_type::Session => cmd_find_type::CMD_FIND_SESSION,
+        };
+        if cmd_find_target(&raw mut fs, item, value, type_, 0) != 0 {
+            return None;
+        }
+        match a.type_ {
+            cmd_user_type::Pane => Some(format!("%{}", (*fs.wp).id)),
+            cmd_user_type::Window => Some(format!("@{}", (*fs.w).id)),
+            _ => Some(format!("${}", (*fs.s).id)),
+        }
+    }
+}
+
+/// Run a user command by inserting its body after the item.
+#[expect(clippy::deref_addrof)]
+unsafe fn cmd_define_command_run(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
+    unsafe {
+        let args = cmd_get_args(self_);
+        let entry = cmd_get_entry(self_);
+        let name = cstr_to_str((*entry).name);
+
+        let Some(uc) = (*&raw mut cmd_user_commands)
+            .iter()
+            .find(|uc| uc.entry == entry && !uc.body.is_null())
+        else {
+            cmdq_error!(item, "command not defined: {}", name);
+            return cmd_retval::CMD_RETURN_ERROR;
+        };
+        let depth = (*cmdq_get_state(item)).depth;
+        if depth >= CMD_DEFINE_COMMAND_DEPTH {
+            cmdq_error!(item, "{}: too many nested commands", name);
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+
+        let state = cmdq_copy_state(cmdq_get_state(item), null_mut());
+        (*state).depth = depth + 1;
+
+        let mut idx = 0;
+        for a in &uc.arguments {
+            if a.flag != 0 {
+                let key = format_nul!("arg_flag_{}", a.flag as char);
+                if a.type_ == cmd_user_type::Flag {
+                    cmdq_add_format!(state, key, "{}", args_has(args, a.flag));
+                } else if args_has(args, a.flag) == 0 {
+                    cmdq_add_format!(state, key, "");
+                } else {
+                    let Some(value) = cmd_define_command_value(item, a, args_get(args, a.flag))
+                    else {
+                        free_(key);
+                        cmdq_free_state(state);
+                        return cmd_retval::CMD_RETURN_ERROR;
+                    };
+                    cmdq_add_format!(state, key, "{}", value);
+                }
+                free_(key);
+                continue;
+            }
+            while idx < args_count(args) {
+                let Some(value) = cmd_define_command_value(item, a, args_string(args, idx)) else {
+                    cmdq_free_state(state);
+                    return cmd_retval::CMD_RETURN_ERROR;
+                };
+                idx += 1;
+                let key = format_nul!("arg_{}", idx);
+                cmdq_add_format!(state, key, "{}", value);
+                free_(key);
+                if !a.rest {
+                    break;
+                }
+            }
+        }
+        cmdq_add_format!(state, c"arg_count".as_ptr(), "{}", args_count(args));
+
+        // The body runs in the group of the command that ran it, so an error
+        // stops both the rest of the body and the commands after this one.
+        let new_item = cmdq_get_command(uc.body, state);
+        let mut loop_ = new_item;
+        while !loop_.is_null() {
+            (*loop_).group = (*item).group;
+            loop_ = (*loop_).next;
+        }
+        cmdq_free_state(state);
+        cmdq_insert_after(item, new_item);
+
+        cmd_retval::CMD_RETURN_NORMAL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmd_define_command_template() {
+        let arguments =
+            cmd_define_command_template("-v -t:pane src:pane [count:number] [words...]").unwrap();
+        let (getopt, lower, upper, usage) = cmd_define_command_usage(&arguments);
+        assert_eq!(getopt, "vt:");
+        assert_eq!((lower, upper), (1, -1));
+        assert_eq!(usage, "[-v] [-t pane] src [count] [words ...]");
+
+        let arguments = cmd_define_command_template("a b:string").unwrap();
+        assert_eq!(cmd_define_command_usage(&arguments).1, 2);
+        assert_eq!(cmd_define_command_usage(&arguments).2, 2);
+
+        assert!(cmd_define_command_template("a:colour").is_err());
+        assert!(cmd_define_command_template("[a] b").is_err());
+        assert!(cmd_define_command_template("a... b").is_err());
+        assert!(cmd_define_command_template("-x -x").is_err());
+        assert!(cmd_define_command_template("[a").is_err());
+    }
+}
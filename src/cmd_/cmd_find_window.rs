@@ -18,8 +18,8 @@ pub static mut cmd_find_window_entry: cmd_entry = cmd_entry {
     name: c"find-window".as_ptr(),
     alias: c"findw".as_ptr(),
 
-    args: args_parse::new(c"CiNrt:TZ", 1, 1, None),
-    usage: c"[-CiNrTZ] [-t target-pane] match-string".as_ptr(),
+    args: args_parse::new(c"CHiNrt:TZ", 1, 1, None),
+    usage: c"[-CHiNrTZ] [-t target-pane] match-string".as_ptr(),
 
     target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_PANE, 0),
 
@@ -28,6 +28,47 @@ pub static mut cmd_find_window_entry: cmd_entry = cmd_entry {
     ..unsafe { zeroed() }
 };
 
+/// Search the history of every pane and list the matching lines.
+unsafe fn cmd_find_window_history(
+    args: *mut args,
+    item: *mut cmdq_item,
+    wp: *mut window_pane,
+) -> cmd_retval {
+    unsafe {
+        let s = args_string(args, 0);
+
+        if args_has_(args, 'r')
+            && regex_compile(s, regex_get_syntax(), regex_flags::empty()).is_none()
+        {
+            cmdq_error!(item, "invalid regex: {}", _s(s));
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+
+        // Start again rather than showing the results of an earlier search.
+        let wme = tailq_first(&raw mut (*wp).modes);
+        if !wme.is_null() && std::ptr::eq((*wme).mode, &raw const window_search_mode) {
+            window_pane_reset_mode(wp);
+        }
+
+        let new_args = args_copy(args, 0, null_mut());
+        window_pane_set_mode(
+            wp,
+            null_mut(),
+            &raw const window_search_mode,
+            cmdq_get_target(item),
+            new_args,
+        );
+        args_free(new_args);
+
+        if !window_search_has_matches(wp) {
+            window_pane_reset_mode(wp);
+            cmdq_error!(item, "no matches: {}", _s(s));
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+        cmd_retval::CMD_RETURN_NORMAL
+    }
+}
+
 unsafe fn cmd_find_window_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
     unsafe {
         let args = cmd_get_args(self_);
@@ -35,6 +76,10 @@ unsafe fn cmd_find_window_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
         let wp = (*target).wp;
         let s = args_string(args, 0);
         let mut suffix = c"".as_ptr();
+
+        if args_has_(args, 'H') {
+            return cmd_find_window_history(args, item, wp);
+        }
         let mut star = c"*".as_ptr();
 
         let mut c = args_has_(args, 'C');
@@ -18,8 +18,8 @@ pub static mut cmd_list_buffers_entry: cmd_entry = cmd_entry {
     name: c"list-buffers".as_ptr(),
     alias: c"lsb".as_ptr(),
 
-    args: args_parse::new(c"F:f:", 0, 0, None),
-    usage: c"[-F format] [-f filter]".as_ptr(),
+    args: args_parse::new(c"F:Jf:", 0, 0, None),
+    usage: c"[-J] [-F format] [-f filter]".as_ptr(),
 
     flags: cmd_flag::CMD_AFTERHOOK,
     exec: Some(cmd_list_buffers_exec),
@@ -58,9 +58,15 @@ unsafe fn cmd_list_buffers_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_re
                 flag = 1;
             }
             if flag != 0 {
-                let line = format_expand(ft, template);
-                cmdq_print!(item, "{}", _s(line));
-                free_(line);
+                if args_has_(args, 'J') {
+                    let line = format_json(ft);
+                    cmdq_print_escaped(item, line);
+                    free_(line);
+                } else {
+                    let line = format_expand(ft, template);
+                    cmdq_print!(item, "{}", _s(line));
+                    free_(line);
+                }
             }
 
             format_free(ft);
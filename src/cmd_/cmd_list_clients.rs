@@ -22,8 +22,8 @@ pub static mut cmd_list_clients_entry: cmd_entry = cmd_entry {
     name: c"list-clients".as_ptr(),
     alias: c"lsc".as_ptr(),
 
-    args: args_parse::new(c"F:f:t:", 0, 0, None),
-    usage: c"[-F format] [-f filter] [-t target-session]".as_ptr(),
+    args: args_parse::new(c"F:f:Jt:", 0, 0, None),
+    usage: c"[-J] [-F format] [-f filter] [-t target-session]".as_ptr(),
 
     target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_SESSION, 0),
 
@@ -73,9 +73,15 @@ unsafe fn cmd_list_clients_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_re
                 flag = 1;
             }
             if flag != 0 {
-                let line = format_expand(ft, template);
-                cmdq_print!(item, "{}", _s(line));
-                free_(line);
+                if args_has_(args, 'J') {
+                    let line = format_json(ft);
+                    cmdq_print_escaped(item, line);
+                    free_(line);
+                } else {
+                    let line = format_expand(ft, template);
+                    cmdq_print!(item, "{}", _s(line));
+                    free_(line);
+                }
             }
 
             format_free(ft);
@@ -20,8 +20,8 @@ pub static mut cmd_list_keys_entry: cmd_entry = cmd_entry {
     name: c"list-keys".as_ptr(),
     alias: c"lsk".as_ptr(),
 
-    args: args_parse::new(c"1aNP:T:", 0, 1, None),
-    usage: c"[-1aN] [-P prefix-string] [-T key-table] [key]".as_ptr(),
+    args: args_parse::new(c"1aJNP:T:", 0, 1, None),
+    usage: c"[-1aJN] [-P prefix-string] [-T key-table] [key]".as_ptr(),
 
     flags: cmd_flag::CMD_STARTSERVER.union(cmd_flag::CMD_AFTERHOOK),
     exec: Some(cmd_list_keys_exec),
@@ -122,6 +122,48 @@ unsafe fn cmd_list_keys_print_notes(
     }
 }
 
+unsafe fn cmd_list_keys_print_json(
+    item: *mut cmdq_item,
+    table: *mut key_table,
+    bd: *mut key_binding,
+) {
+    unsafe {
+        let ft = format_create(
+            cmdq_get_client(item),
+            item,
+            FORMAT_NONE,
+            format_flags::empty(),
+        );
+        format_defaults(ft, null_mut(), None, None, None);
+
+        format_add!(ft, c"key_table".as_ptr(), "{}", _s((*table).name));
+        format_add!(
+            ft,
+            c"key_string".as_ptr(),
+            "{}",
+            _s(key_string_lookup_key((*bd).key, 0))
+        );
+        format_add!(
+            ft,
+            c"key_repeat".as_ptr(),
+            "{}",
+            ((*bd).flags & KEY_BINDING_REPEAT != 0) as i32
+        );
+        if !(*bd).note.is_null() {
+            format_add!(ft, c"key_note".as_ptr(), "{}", _s((*bd).note));
+        }
+        let cp = cmd_list_print(&mut *(*bd).cmdlist, 0);
+        format_add!(ft, c"key_command".as_ptr(), "{}", _s(cp));
+        free_(cp);
+
+        let line = format_json(ft);
+        cmdq_print_escaped(item, line);
+        free_(line);
+
+        format_free(ft);
+    }
+}
+
 unsafe fn cmd_list_keys_get_prefix(args: *mut args, prefix: *mut key_code) -> NonNull<c_char> {
     unsafe {
         *prefix = options_get_number_(global_s_options, c"prefix") as _;
@@ -153,6 +195,11 @@ unsafe fn cmd_list_keys_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retva
             return cmd_list_keys_commands(self_, item);
         }
 
+        if args_has_(args, 'J') && args_has_(args, 'N') {
+            cmdq_error!(item, "-J and -N cannot be used together");
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+
         'out: {
             let keystr = args_string(args, 0);
             if !keystr.is_null() {
@@ -278,6 +325,11 @@ unsafe fn cmd_list_keys_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retva
                         continue;
                     }
                     found = 1;
+                    if args_has_(args, 'J') {
+                        cmd_list_keys_print_json(item, table, bd);
+                        bd = key_bindings_next(table, bd);
+                        continue;
+                    }
                     let key = args_escape(key_string_lookup_key((*bd).key, 0));
 
                     let r = if repeat == 0 {
@@ -375,14 +427,11 @@ unsafe fn cmd_list_keys_commands(self_: *mut cmd, item: *mut cmdq_item) -> cmd_r
         format_defaults(ft, null_mut(), None, None, None);
 
         let command = args_string(args, 0);
-        let mut entryp = &raw const cmd_table as *const *const cmd_entry;
-        while !(*entryp).is_null() {
-            let entry = *entryp;
+        for entry in cmd_entries() {
             if !command.is_null()
                 && (strcmp((*entry).name, command) != 0
                     && ((*entry).alias.is_null() || strcmp((*entry).alias, command) != 0))
             {
-                entryp = entryp.add(1);
                 continue;
             }
 
@@ -405,7 +454,6 @@ unsafe fn cmd_list_keys_commands(self_: *mut cmd, item: *mut cmdq_item) -> cmd_r
                 cmdq_print!(item, "{}", _s(line));
             }
             free_(line);
-            entryp = entryp.add(1);
         }
 
         format_free(ft);
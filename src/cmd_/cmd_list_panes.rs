@@ -19,8 +19,8 @@ pub static mut cmd_list_panes_entry: cmd_entry = cmd_entry {
     name: c"list-panes".as_ptr(),
     alias: c"lsp".as_ptr(),
 
-    args: args_parse::new(c"asF:f:t:", 0, 0, None),
-    usage: c"[-as] [-F format] [-f filter] [-t target-window]".as_ptr(),
+    args: args_parse::new(c"asF:f:Jt:", 0, 0, None),
+    usage: c"[-asJ] [-F format] [-f filter] [-t target-window]".as_ptr(),
 
     target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_WINDOW, 0),
 
@@ -139,9 +139,15 @@ fn cmd_list_panes_window(
                 flag = 1;
             }
             if flag != 0 {
-                let line = format_expand(ft, template);
-                cmdq_print!(item, "{}", _s(line));
-                free_(line);
+                if args_has_(args, 'J') {
+                    let line = format_json(ft);
+                    cmdq_print_escaped(item, line);
+                    free_(line);
+                } else {
+                    let line = format_expand(ft, template);
+                    cmdq_print!(item, "{}", _s(line));
+                    free_(line);
+                }
             }
 
             format_free(ft);
@@ -22,8 +22,8 @@ pub static mut cmd_list_windows_entry: cmd_entry = cmd_entry {
     name: c"list-windows".as_ptr(),
     alias: c"lsw".as_ptr(),
 
-    args: args_parse::new(c"F:f:at:", 0, 0, None),
-    usage: c"[-a] [-F format] [-f filter] [-t target-session]".as_ptr(),
+    args: args_parse::new(c"F:f:Jat:", 0, 0, None),
+    usage: c"[-aJ] [-F format] [-f filter] [-t target-session]".as_ptr(),
 
     target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_SESSION, 0),
 
@@ -97,9 +97,15 @@ unsafe fn cmd_list_windows_session(
                 flag = 1;
             }
             if flag != 0 {
-                let line = format_expand(ft, template);
-                cmdq_print!(item, "{}", _s(line));
-                free_(line);
+                if args_has_(args, 'J') {
+                    let line = format_json(ft);
+                    cmdq_print_escaped(item, line);
+                    free_(line);
+                } else {
+                    let line = format_expand(ft, template);
+                    cmdq_print!(item, "{}", _s(line));
+                    free_(line);
+                }
             }
 
             format_free(ft);
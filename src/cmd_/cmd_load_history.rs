@@ -0,0 +1,97 @@
+use crate::*;
+
+pub static mut cmd_load_history_entry: cmd_entry = cmd_entry {
+    name: c"load-history".as_ptr(),
+    alias: c"loadh".as_ptr(),
+
+    args: args_parse::new(c"t:", 1, 1, None),
+    usage: c"[-t target-pane] path".as_ptr(),
+
+    target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_PANE, 0),
+
+    flags: cmd_flag::CMD_AFTERHOOK,
+    exec: Some(cmd_load_history_exec),
+    ..unsafe { zeroed() }
+};
+
+#[repr(C)]
+pub struct cmd_load_history_data {
+    pub item: *mut cmdq_item,
+    pub wp: u32,
+}
+
+unsafe fn cmd_load_history_done(
+    _c: *mut client,
+    path: *mut c_char,
+    error: i32,
+    closed: i32,
+    buffer: *mut evbuffer,
+    data: *mut c_void,
+) {
+    unsafe {
+        let cdata = data as *mut cmd_load_history_data;
+        let item = (*cdata).item;
+        let wp = window_pane_find_by_id((*cdata).wp);
+
+        if closed == 0 {
+            return;
+        }
+
+        if error != 0 {
+            cmdq_error!(item, "{}: {}", _s(path), _s(strerror(error)));
+        } else if wp.is_null() {
+            cmdq_error!(item, "pane %{} no longer exists", (*cdata).wp);
+        } else {
+            let mut cause = null_mut();
+            if grid_load_history(
+                (*wp).base.grid,
+                EVBUFFER_DATA(buffer),
+                EVBUFFER_LENGTH(buffer),
+                &raw mut cause,
+            ) != 0
+            {
+                cmdq_error!(item, "{}: {}", _s(path), _s(cause));
+                free_(cause);
+            } else {
+                (*wp).flags |= window_pane_flags::PANE_REDRAW;
+            }
+        }
+        cmdq_continue(item);
+
+        free_(cdata);
+    }
+}
+
+/// Load saved history from a file into a pane. Returns CMD_RETURN_WAIT, the
+/// item is continued once the file has been read.
+pub unsafe fn cmd_load_history_file(
+    item: *mut cmdq_item,
+    wp: *mut window_pane,
+    path: *const c_char,
+) -> cmd_retval {
+    unsafe {
+        let cdata = xcalloc_::<cmd_load_history_data>(1).as_ptr();
+        (*cdata).item = item;
+        (*cdata).wp = (*wp).id;
+
+        let path = format_single_from_target(item, path);
+        file_read(
+            cmdq_get_client(item),
+            path,
+            Some(cmd_load_history_done),
+            cdata.cast(),
+        );
+        free_(path);
+    }
+
+    cmd_retval::CMD_RETURN_WAIT
+}
+
+unsafe fn cmd_load_history_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
+    unsafe {
+        let args = cmd_get_args(self_);
+        let wp = (*cmdq_get_target(item)).wp;
+
+        cmd_load_history_file(item, wp, args_string(args, 0))
+    }
+}
@@ -23,8 +23,8 @@ pub static mut cmd_new_session_entry: cmd_entry = cmd_entry {
     name: c"new-session".as_ptr(),
     alias: c"new".as_ptr(),
 
-    args: args_parse::new(c"Ac:dDe:EF:f:n:Ps:t:x:Xy:", 0, -1, None),
-    usage: c"[-AdDEPX] [-c start-directory] [-e environment] [-F format] [-f flags] [-n window-name] [-s session-name] [-t target-session] [-x width] [-y height] [shell-command]".as_ptr(),
+    args: args_parse::new(c"Ac:dDe:EF:f:H:n:Ps:t:x:Xy:", 0, -1, None),
+    usage: c"[-AdDEPX] [-c start-directory] [-e environment] [-F format] [-f flags] [-H widthxheight] [-n window-name] [-s session-name] [-t target-session] [-x width] [-y height] [shell-command]".as_ptr(),
 
     target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_SESSION, CMD_FIND_CANFAIL),
 
@@ -55,7 +55,8 @@ unsafe fn cmd_new_session_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
         let args = cmd_get_args(self_);
         let current = cmdq_get_current(item);
         let target = cmdq_get_target(item);
-        let c = cmdq_get_client(item);
+        let mut c = cmdq_get_client(item);
+        let mut vc: *mut client = null_mut();
         let mut s = null_mut();
         let mut as_ = null_mut();
         let mut groupwith = null_mut();
@@ -110,6 +111,19 @@ unsafe fn cmd_new_session_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
                 }
                 free_(name);
             }
+            if args_has_(args, 'H') {
+                tmp = args_get_(args, 'H');
+                if sscanf(tmp, c"%ux%u".as_ptr(), &raw mut sx, &raw mut sy) != 2 {
+                    cmdq_error!(item, "bad size argument: {}", _s(tmp));
+                    free_(newname);
+                    return cmd_retval::CMD_RETURN_ERROR;
+                }
+                vc = cmd_attach_session_create_virtual(item, c, sx, sy);
+                if vc.is_null() {
+                    free_(newname);
+                    return cmd_retval::CMD_RETURN_ERROR;
+                }
+            }
             if args_has_(args, 'A') {
                 as_ = if !newname.is_null() {
                     session_find(newname)
@@ -119,6 +133,7 @@ unsafe fn cmd_new_session_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
                 if !as_.is_null() {
                     retval = cmd_attach_session(
                         item,
+                        if vc.is_null() { c } else { vc },
                         (*as_).name,
                         args_has(args, b'D'),
                         args_has(args, b'X'),
@@ -127,6 +142,9 @@ unsafe fn cmd_new_session_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
                         args_has(args, b'E'),
                         args_get(args, b'f'),
                     );
+                    if !vc.is_null() && retval == cmd_retval::CMD_RETURN_ERROR {
+                        server_client_lost(vc);
+                    }
                     free_(newname);
                     return retval;
                 }
@@ -180,6 +198,14 @@ unsafe fn cmd_new_session_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
                 xstrdup(server_client_get_cwd(c, null_mut())).as_ptr()
             };
 
+            /* A virtual client takes the new session instead of this one. */
+            if !vc.is_null() {
+                c = vc;
+                detached = false;
+                already_attached = false;
+                is_control = false;
+            }
+
             /*
              * If this is a new client, check for nesting and save the termios
              * settings (part of which is used for new windows in this session).
@@ -397,6 +423,9 @@ unsafe fn cmd_new_session_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
         }
 
         //fail:
+        if !vc.is_null() {
+            server_client_lost(vc);
+        }
         if !sc.argv.is_null() {
             cmd_free_argv(sc.argc, sc.argv);
         }
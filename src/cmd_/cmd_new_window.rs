@@ -21,8 +21,8 @@ pub static mut cmd_new_window_entry: cmd_entry = cmd_entry {
     name: c"new-window".as_ptr(),
     alias: c"neww".as_ptr(),
 
-    args: args_parse::new(c"abc:de:F:kn:PSt:", 0, -1, None),
-    usage: c"[-abdkPS] [-c start-directory] [-e environment] [-F format] [-n window-name] [-t target-window] [shell-command]".as_ptr(),
+    args: args_parse::new(c"abc:de:F:H:kn:PSt:", 0, -1, None),
+    usage: c"[-abdkPS] [-c start-directory] [-e environment] [-F format] [-H history-file] [-n window-name] [-t target-window] [shell-command]".as_ptr(),
 
     target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_WINDOW, CMD_FIND_WINDOW_INDEX),
 
@@ -154,6 +154,9 @@ unsafe fn cmd_new_window_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retv
             cmd_free_argv(sc.argc, sc.argv);
         }
         environ_free(sc.environ);
+        if args_has_(args, 'H') {
+            return cmd_load_history_file(item, (*(*new_wl).window).active, args_get_(args, 'H'));
+        }
         cmd_retval::CMD_RETURN_NORMAL
     }
 }
@@ -86,6 +86,12 @@ pub struct cmdq_state {
 
     pub event: key_event,
     pub current: cmd_find_state,
+
+    /// Tag given by a control client, echoed in its replies.
+    pub tag: *mut c_char,
+
+    /// How many user commands this state is nested inside.
+    pub depth: u32,
 }
 
 #[repr(C)]
@@ -240,6 +246,7 @@ pub unsafe fn cmdq_free_state(state: *mut cmdq_state) {
         if !(*state).formats.is_null() {
             format_free((*state).formats);
         }
+        free_((*state).tag);
         free_(state);
     }
 }
@@ -502,6 +509,34 @@ pub unsafe fn cmdq_remove_group(item: *mut cmdq_item) {
     }
 }
 
+/// Remove items with a tag that have not started yet. Returns the number
+/// removed.
+pub unsafe fn cmdq_cancel(c: *mut client, tag: *const c_char) -> u32 {
+    unsafe {
+        let queue = cmdq_get(c);
+        let mut removed = 0;
+
+        for item in tailq_foreach(&raw mut (*queue).list).map(NonNull::as_ptr) {
+            if item == (*queue).item || (*item).flags & CMDQ_FIRED != 0 {
+                continue;
+            }
+            let item_tag = (*(*item).state).tag;
+            if item_tag.is_null() || libc::strcmp(item_tag, tag) != 0 {
+                continue;
+            }
+            log_debug!(
+                "{} {}: cancel {}",
+                "cmdq_cancel",
+                _s(cmdq_name(c)),
+                _s((*item).name)
+            );
+            cmdq_remove(item);
+            removed += 1;
+        }
+        removed
+    }
+}
+
 pub unsafe fn cmdq_empty_command(_item: *mut cmdq_item, _data: *mut c_void) -> cmd_retval {
     cmd_retval::CMD_RETURN_NORMAL
 }
@@ -856,8 +891,24 @@ pub unsafe fn cmdq_guard(item: *mut cmdq_item, guard: *const c_char, flags: bool
         let t = (*item).time;
         let number = (*item).number;
 
-        if !c.is_null() && (*c).flags.intersects(client_flag::CONTROL) {
-            control_write!(c, "%{} {} {} {}", _s(guard), t, number, flags as i32);
+        let tag = (*(*item).state).tag;
+
+        if !c.is_null() && (*c).flags.intersects(client_flag::CONTROL_JSON) {
+            control_reply(c, guard, t, number, flags, tag);
+        } else if !c.is_null() && (*c).flags.intersects(client_flag::CONTROL) {
+            if tag.is_null() {
+                control_write!(c, "%{} {} {} {}", _s(guard), t, number, flags as i32);
+            } else {
+                control_write!(
+                    c,
+                    "%{} {} {} {} {}",
+                    _s(guard),
+                    t,
+                    number,
+                    flags as i32,
+                    _s(tag),
+                );
+            }
         }
     }
 }
@@ -888,6 +939,21 @@ pub unsafe fn cmdq_print_(item: *mut cmdq_item, args: std::fmt::Arguments) {
     }
 }
 
+/// Show a line that is already escaped, such as JSON.
+pub unsafe fn cmdq_print_escaped(item: *mut cmdq_item, line: *const c_char) {
+    unsafe {
+        let evb = evbuffer_new();
+        if evb.is_null() {
+            fatalx(c"out of memory");
+        }
+
+        evbuffer_add(evb, line.cast(), strlen(line));
+
+        cmdq_print_data(item, 1, evb);
+        evbuffer_free(evb);
+    }
+}
+
 macro_rules! cmdq_error {
    ($item:expr, $fmt:literal $(, $args:expr)* $(,)?) => {
         crate::cmd_::cmd_queue::cmdq_error_($item, format_args!($fmt $(, $args)*))
@@ -908,7 +974,7 @@ pub unsafe fn cmdq_error_(item: *mut cmdq_item, args: std::fmt::Arguments) {
 
         log_debug!("cmdq_error: {}", _s(msg));
 
-        if c.is_null() {
+        if c.is_null() || cfg_watch_collecting(item) {
             cmd_get_source(cmd, &raw mut file, &line);
             cfg_add_cause!("{}:{}: {}", _s(file), line.into_inner(), _s(msg));
         } else if (*c).session.is_null() || (*c).flags.intersects(client_flag::CONTROL) {
@@ -919,7 +985,7 @@ pub unsafe fn cmdq_error_(item: *mut cmdq_item, args: std::fmt::Arguments) {
                 free_(tmp);
             }
             if (*c).flags.intersects(client_flag::CONTROL) {
-                control_write!(c, "{}", _s(msg));
+                control_print(c, msg, strlen(msg));
             } else {
                 file_error!(c, "{}\n", _s(msg));
             }
@@ -143,6 +143,9 @@ pub unsafe fn cmd_refresh_client_control_client_size(
         tty_set_size(&raw mut (*tc).tty, x, y, 0, 0);
         (*tc).flags |= client_flag::SIZECHANGED;
         recalculate_sizes_now(1);
+        if (*tc).flags.intersects(client_flag::VIRTUAL) {
+            server_redraw_client(tc);
+        }
     }
     cmd_retval::CMD_RETURN_NORMAL
 }
@@ -367,7 +370,10 @@ pub unsafe fn cmd_refresh_client_exec(self_: *mut cmd, item: *mut cmdq_item) ->
                 return cmd_retval::CMD_RETURN_NORMAL;
             }
             if args_has_(args, 'C') {
-                if !(*tc).flags.intersects(client_flag::CONTROL) {
+                if !(*tc)
+                    .flags
+                    .intersects(client_flag::CONTROL | client_flag::VIRTUAL)
+                {
                     break 'not_control_client;
                 }
                 return cmd_refresh_client_control_client_size(self_, item);
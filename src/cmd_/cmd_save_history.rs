@@ -0,0 +1,69 @@
+use libc::O_TRUNC;
+
+use crate::*;
+
+pub static mut cmd_save_history_entry: cmd_entry = cmd_entry {
+    name: c"save-history".as_ptr(),
+    alias: c"saveh".as_ptr(),
+
+    args: args_parse::new(c"t:", 1, 1, None),
+    usage: c"[-t target-pane] path".as_ptr(),
+
+    target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_PANE, 0),
+
+    flags: cmd_flag::CMD_AFTERHOOK,
+    exec: Some(cmd_save_history_exec),
+    ..unsafe { zeroed() }
+};
+
+unsafe fn cmd_save_history_done(
+    _c: *mut client,
+    path: *mut c_char,
+    error: i32,
+    closed: i32,
+    _buffer: *mut evbuffer,
+    data: *mut c_void,
+) {
+    let item = data as *mut cmdq_item;
+
+    if closed == 0 {
+        return;
+    }
+
+    unsafe {
+        if error != 0 {
+            cmdq_error!(item, "{}: {}", _s(path), _s(strerror(error)));
+        }
+        cmdq_continue(item);
+    }
+}
+
+unsafe fn cmd_save_history_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
+    unsafe {
+        let args = cmd_get_args(self_);
+        let wp = (*cmdq_get_target(item)).wp;
+
+        let limit = options_get_number_((*wp).options, c"history-save-limit") as u32;
+
+        let evb = evbuffer_new();
+        if evb.is_null() {
+            fatalx(c"out of memory");
+        }
+        grid_save_history((*wp).base.grid, limit, evb);
+
+        let path = format_single_from_target(item, args_string(args, 0));
+        file_write(
+            cmdq_get_client(item),
+            path,
+            O_TRUNC,
+            EVBUFFER_DATA(evb).cast(),
+            EVBUFFER_LENGTH(evb),
+            Some(cmd_save_history_done),
+            item.cast(),
+        );
+        free_(path);
+        evbuffer_free(evb);
+
+        cmd_retval::CMD_RETURN_WAIT
+    }
+}
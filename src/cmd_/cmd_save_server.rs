@@ -0,0 +1,788 @@
+use crate::*;
+
+use libc::{O_TRUNC, strcmp};
+
+use crate::compat::{
+    queue::tailq_foreach,
+    strunvis,
+    tree::{rb_foreach, rb_min, rb_next},
+};
+
+// Saves the server state (sessions, windows, panes and options) to a file and
+// rebuilds it later.
+//
+// The file is line based. The first line is "tmux-rs-server VERSION" and each
+// following line is a record made of tab separated fields, each escaped with
+// vis(3):
+//
+//   session NAME CWD ACTIVE-WINDOW
+//   window INDEX NAME LAYOUT
+//   pane CWD ACTIVE COMMAND
+//   option SCOPE NAME VALUE
+//
+// Panes belong to the last window and windows to the last session. Option
+// scopes are server, global-session and global-window for the global options,
+// or session, window and pane for the last object of that type.
+
+const SAVE_SERVER_VERSION: u32 = 1;
+const SAVE_SERVER_PATH: &CStr = c".tmux-rs-server";
+
+pub static mut cmd_save_server_entry: cmd_entry = cmd_entry {
+    name: c"save-server".as_ptr(),
+    alias: null(),
+
+    args: args_parse::new(c"f:", 0, 0, None),
+    usage: c"[-f path]".as_ptr(),
+
+    flags: cmd_flag::CMD_AFTERHOOK,
+    exec: Some(cmd_save_server_exec),
+    ..unsafe { zeroed() }
+};
+
+pub static mut cmd_restore_server_entry: cmd_entry = cmd_entry {
+    name: c"restore-server".as_ptr(),
+    alias: null(),
+
+    args: args_parse::new(c"f:", 0, 0, None),
+    usage: c"[-f path]".as_ptr(),
+
+    flags: cmd_flag::CMD_STARTSERVER,
+    exec: Some(cmd_restore_server_exec),
+    ..unsafe { zeroed() }
+};
+
+/// Get the state file path, either from -f or in the home directory.
+unsafe fn cmd_save_server_path(item: *mut cmdq_item, args: *mut args) -> *mut c_char {
+    unsafe {
+        if args_has_(args, 'f') {
+            return format_single_from_target(item, args_get_(args, 'f'));
+        }
+        let home = find_home();
+        if home.is_null() {
+            return null_mut();
+        }
+        format_nul!("{}/{}", _s(home), _s(SAVE_SERVER_PATH.as_ptr()))
+    }
+}
+
+/// Add one record to the buffer, escaping each field.
+unsafe fn cmd_save_server_add(evb: *mut evbuffer, fields: &[*const c_char]) {
+    unsafe {
+        for (i, field) in fields.iter().enumerate() {
+            let mut escaped = null_mut();
+            utf8_stravis(
+                &raw mut escaped,
+                *field,
+                vis_flags::VIS_OCTAL
+                    | vis_flags::VIS_CSTYLE
+                    | vis_flags::VIS_TAB
+                    | vis_flags::VIS_NL,
+            );
+            if i != 0 {
+                evbuffer_add_printf!(evb, "\t");
+            }
+            evbuffer_add_printf!(evb, "{}", _s(escaped));
+            free_(escaped);
+        }
+        evbuffer_add_printf!(evb, "\n");
+    }
+}
+
+/// Add the options from a set. If global is set, only options which differ
+/// from their defaults are saved.
+unsafe fn cmd_save_server_add_options(
+    evb: *mut evbuffer,
+    scope: &CStr,
+    oo: *mut options,
+    global: bool,
+) {
+    unsafe {
+        let mut o = options_first(oo);
+        while !o.is_null() {
+            let name = options_name(o);
+            let oe = options_table_entry(o);
+
+            if options_is_array(o) == 0 {
+                let value = options_to_string(o, -1, 0);
+                let mut save = true;
+                if global && !oe.is_null() {
+                    let default = options_default_to_string(oe).as_ptr();
+                    save = strcmp(value, default) != 0;
+                    free_(default);
+                }
+                if save {
+                    cmd_save_server_add(evb, &[c"option".as_ptr(), scope.as_ptr(), name, value]);
+                }
+                free_(value);
+            } else {
+                let mut a = options_array_first(o);
+                while !a.is_null() {
+                    let idx = options_array_item_index(a);
+                    let full = format_nul!("{}[{}]", _s(name), idx);
+                    let value = options_to_string(o, idx as i32, 0);
+                    cmd_save_server_add(evb, &[c"option".as_ptr(), scope.as_ptr(), full, value]);
+                    free_(value);
+                    free_(full);
+                    a = options_array_next(a);
+                }
+            }
+
+            o = options_next(o);
+        }
+    }
+}
+
+/// Add the panes in a layout in the order they appear in the layout string.
+unsafe fn cmd_save_server_add_panes(evb: *mut evbuffer, w: *mut window, lc: *mut layout_cell) {
+    unsafe {
+        if (*lc).type_ != layout_type::LAYOUT_WINDOWPANE {
+            for lcchild in tailq_foreach(&raw mut (*lc).cells).map(NonNull::as_ptr) {
+                cmd_save_server_add_panes(evb, w, lcchild);
+            }
+            return;
+        }
+        let wp = (*lc).wp;
+        if wp.is_null() {
+            return;
+        }
+
+        let mut cwd = osdep_get_cwd((*wp).fd);
+        if cwd.is_null() {
+            cwd = (*wp).cwd;
+        }
+        if cwd.is_null() {
+            cwd = c"".as_ptr();
+        }
+        // A single argument is already a shell command, so keep it as is.
+        let command = if (*wp).argc == 1 {
+            xstrdup(*(*wp).argv).as_ptr()
+        } else {
+            cmd_stringify_argv((*wp).argc, (*wp).argv)
+        };
+        let active = if wp == (*w).active { c"1" } else { c"0" };
+        cmd_save_server_add(evb, &[c"pane".as_ptr(), cwd, active.as_ptr(), command]);
+        free_(command);
+
+        cmd_save_server_add_options(evb, c"pane", (*wp).options, false);
+    }
+}
+
+unsafe fn cmd_save_server_done(
+    _c: *mut client,
+    path: *mut c_char,
+    error: i32,
+    closed: i32,
+    _buffer: *mut evbuffer,
+    data: *mut c_void,
+) {
+    let item = data as *mut cmdq_item;
+
+    if closed == 0 {
+        return;
+    }
+
+    unsafe {
+        if error != 0 {
+            cmdq_error!(item, "{}: {}", _s(path), _s(strerror(error)));
+        }
+        cmdq_continue(item);
+    }
+}
+
+unsafe fn cmd_save_server_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
+    unsafe {
+        let args = cmd_get_args(self_);
+
+        let path = cmd_save_server_path(item, args);
+        if path.is_null() {
+            cmdq_error!(item, "no state file");
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+
+        let evb = evbuffer_new();
+        if evb.is_null() {
+            fatalx(c"out of memory");
+        }
+        evbuffer_add_printf!(evb, "tmux-rs-server {}\n", SAVE_SERVER_VERSION);
+
+        cmd_save_server_add_options(evb, c"server", global_options, true);
+        cmd_save_server_add_options(evb, c"global-session", global_s_options, true);
+        cmd_save_server_add_options(evb, c"global-window", global_w_options, true);
+
+        for s in rb_foreach(&raw mut sessions).map(NonNull::as_ptr) {
+            let curw = if (*s).curw.is_null() {
+                xstrdup_(c"").as_ptr()
+            } else {
+                format_nul!("{}", (*(*s).curw).idx)
+            };
+            cmd_save_server_add(evb, &[c"session".as_ptr(), (*s).name, (*s).cwd, curw]);
+            free_(curw);
+            cmd_save_server_add_options(evb, c"session", (*s).options, false);
+
+            for wl in rb_foreach(&raw mut (*s).windows).map(NonNull::as_ptr) {
+                let w = (*wl).window;
+
+                let root = if (*w).flags.intersects(window_flag::ZOOMED) {
+                    (*w).saved_layout_root
+                } else {
+                    (*w).layout_root
+                };
+                let layout = layout_dump(root);
+                if layout.is_null() {
+                    continue;
+                }
+                let idx = format_nul!("{}", (*wl).idx);
+                cmd_save_server_add(evb, &[c"window".as_ptr(), idx, (*w).name, layout]);
+                free_(idx);
+                free_(layout);
+
+                cmd_save_server_add_panes(evb, w, root);
+                cmd_save_server_add_options(evb, c"window", (*w).options, false);
+            }
+        }
+
+        file_write(
+            cmdq_get_client(item),
+            path,
+            O_TRUNC,
+            EVBUFFER_DATA(evb) as _,
+            EVBUFFER_LENGTH(evb),
+            Some(cmd_save_server_done),
+            item as _,
+        );
+        evbuffer_free(evb);
+        free_(path);
+
+        cmd_retval::CMD_RETURN_WAIT
+    }
+}
+
+/// Restore state, this is the object most recently created by each record
+/// type.
+#[repr(C)]
+struct cmd_restore_server_state {
+    item: *mut cmdq_item,
+
+    s: *mut session,
+    curw: i32,
+
+    wl: *mut winlink,
+    name: *mut c_char,
+    layout: *mut c_char,
+    idx: i32,
+    skip: bool,
+
+    wp: *mut window_pane,
+    active: *mut window_pane,
+
+    array: *mut options_entry,
+}
+
+/// Set an option from a record.
+unsafe fn cmd_restore_server_option(
+    st: *mut cmd_restore_server_state,
+    scope: *const c_char,
+    fullname: *const c_char,
+    value: *const c_char,
+    cause: *mut *mut c_char,
+) -> i32 {
+    unsafe {
+        let oo = if streq_(scope, "server") {
+            global_options
+        } else if streq_(scope, "global-session") {
+            global_s_options
+        } else if streq_(scope, "global-window") {
+            global_w_options
+        } else if streq_(scope, "session") {
+            if (*st).s.is_null() {
+                return 0;
+            }
+            (*(*st).s).options
+        } else if streq_(scope, "window") {
+            if (*st).wl.is_null() {
+                return 0;
+            }
+            (*(*(*st).wl).window).options
+        } else if streq_(scope, "pane") {
+            if (*st).wp.is_null() {
+                return 0;
+            }
+            (*(*st).wp).options
+        } else {
+            *cause = format_nul!("unknown option scope: {}", _s(scope));
+            return -1;
+        };
+
+        let mut idx = -1;
+        let name = options_parse(fullname, &raw mut idx);
+        if name.is_null() {
+            *cause = format_nul!("invalid option: {}", _s(fullname));
+            return -1;
+        }
+
+        let mut error = 0;
+        if *name == b'@' as c_char {
+            options_set_string!(oo, name, 0, "{}", _s(value));
+        } else {
+            let parent = options_get(oo, name);
+            if parent.is_null() {
+                *cause = format_nul!("invalid option: {}", _s(fullname));
+                free_(name);
+                return -1;
+            }
+            if options_is_array(parent) == 0 {
+                error = options_from_string(
+                    oo,
+                    options_table_entry(parent),
+                    (*options_table_entry(parent)).name,
+                    value,
+                    0,
+                    cause,
+                );
+            } else {
+                let mut o = options_get_only(oo, name);
+                if o.is_null() {
+                    o = options_empty(oo, options_table_entry(parent));
+                }
+
+                // The saved items replace the whole array, so clear it the
+                // first time it is seen.
+                if o != (*st).array {
+                    options_array_clear(o);
+                    (*st).array = o;
+                }
+                if idx == -1 {
+                    error = options_array_assign(o, value, cause);
+                } else {
+                    error = options_array_set(o, idx as u32, value, 0, cause);
+                }
+            }
+        }
+        if error == 0 {
+            options_push_changes(name);
+        }
+        free_(name);
+        error
+    }
+}
+
+/// Finish the current window, applying its layout and active pane.
+unsafe fn cmd_restore_server_finish_window(st: *mut cmd_restore_server_state) {
+    unsafe {
+        let item = (*st).item;
+        let wl = (*st).wl;
+
+        if !wl.is_null() {
+            let w = (*wl).window;
+            let mut cause = null_mut();
+
+            if layout_parse(w, (*st).layout, &raw mut cause) == -1 {
+                cmdq_error!(
+                    item,
+                    "window {}:{}: {}",
+                    _s((*(*st).s).name),
+                    (*wl).idx,
+                    _s(cause)
+                );
+                free_(cause);
+            }
+            if !(*st).active.is_null() {
+                window_set_active_pane(w, (*st).active, 0);
+            }
+            server_redraw_window(w);
+        }
+
+        free_((*st).name);
+        (*st).name = null_mut();
+        free_((*st).layout);
+        (*st).layout = null_mut();
+
+        (*st).wl = null_mut();
+        (*st).wp = null_mut();
+        (*st).active = null_mut();
+        (*st).skip = false;
+    }
+}
+
+/// Finish the current session, selecting its current window.
+unsafe fn cmd_restore_server_finish_session(st: *mut cmd_restore_server_state) {
+    unsafe {
+        cmd_restore_server_finish_window(st);
+
+        let s = (*st).s;
+        if !s.is_null() {
+            if (*st).curw != -1 {
+                session_select(s, (*st).curw);
+            }
+            if (*s).curw.is_null() {
+                (*s).curw = rb_min(&raw mut (*s).windows);
+            }
+            if (*s).curw.is_null() {
+                session_destroy(s, 0, c"cmd_restore_server_finish_session".as_ptr());
+            } else {
+                notify_session(c"session-created", s);
+                server_status_session(s);
+            }
+        }
+        (*st).s = null_mut();
+        (*st).curw = -1;
+    }
+}
+
+/// Create a new session.
+unsafe fn cmd_restore_server_session(
+    st: *mut cmd_restore_server_state,
+    name: *const c_char,
+    cwd: *const c_char,
+    curw: *const c_char,
+    cause: *mut *mut c_char,
+) -> i32 {
+    unsafe {
+        let c = cmdq_get_client((*st).item);
+
+        let newname = session_check_name(name);
+        if newname.is_null() {
+            *cause = format_nul!("invalid session: {}", _s(name));
+            return -1;
+        }
+        if !session_find(newname).is_null() {
+            *cause = format_nul!("duplicate session: {}", _s(newname));
+            free_(newname);
+            return -1;
+        }
+
+        let env = environ_create().as_ptr();
+        if !c.is_null() {
+            environ_update(global_s_options, (*c).environ, env);
+        }
+        let oo = options_create(global_s_options);
+        (*st).s = session_create(null(), newname, cwd, env, oo, null_mut());
+        free_(newname);
+
+        (*st).curw = strtonum(curw, 0, i32::MAX).unwrap_or(-1);
+        0
+    }
+}
+
+/// Create a new pane. The window is created with its first pane.
+unsafe fn cmd_restore_server_pane(
+    st: *mut cmd_restore_server_state,
+    cwd: *const c_char,
+    active: *const c_char,
+    command: *const c_char,
+    cause: *mut *mut c_char,
+) -> i32 {
+    let __func__ = c"cmd_restore_server_pane".as_ptr();
+    unsafe {
+        let s = (*st).s;
+        let mut sc: spawn_context = zeroed();
+        let mut argv: [*mut c_char; 1] = [command as *mut c_char];
+
+        sc.item = (*st).item;
+        sc.s = s;
+        if *command != b'\0' as c_char {
+            sc.argc = 1;
+            sc.argv = argv.as_mut_ptr();
+        }
+        if *cwd != b'\0' as c_char {
+            sc.cwd = cwd;
+        }
+        sc.idx = -1;
+        sc.flags = SPAWN_DETACHED;
+
+        let wp = if (*st).wl.is_null() {
+            sc.idx = (*st).idx;
+            let wl = spawn_window(&raw mut sc, cause);
+            if wl.is_null() {
+                (*st).skip = true;
+                return -1;
+            }
+            (*st).wl = wl;
+
+            let w = (*wl).window;
+            free_((*w).name);
+            (*w).name = xstrdup((*st).name).as_ptr();
+            (*w).active
+        } else {
+            let w = (*(*st).wl).window;
+
+            // Split the last pane. If it is too small, tile the panes to make
+            // space; the real layout is applied once every pane exists.
+            let mut lc = layout_split_pane((*st).wp, layout_type::LAYOUT_TOPBOTTOM, -1, 0);
+            if lc.is_null() {
+                layout_set_select(w, layout_set_lookup(c"tiled".as_ptr()) as u32);
+                lc = layout_split_pane((*st).wp, layout_type::LAYOUT_TOPBOTTOM, -1, 0);
+            }
+            if lc.is_null() {
+                *cause = format_nul!("no space for new pane");
+                (*st).skip = true;
+                return -1;
+            }
+            sc.wl = (*st).wl;
+            sc.wp0 = (*st).wp;
+            sc.lc = lc;
+
+            let wp = spawn_pane(&raw mut sc, cause);
+            if wp.is_null() {
+                (*st).skip = true;
+                return -1;
+            }
+            wp
+        };
+        log_debug!("{}: %{} in {}", _s(__func__), (*wp).id, _s((*s).name));
+
+        (*st).wp = wp;
+        if streq_(active, "1") {
+            (*st).active = wp;
+        }
+        0
+    }
+}
+
+/// Process one record.
+unsafe fn cmd_restore_server_line(
+    st: *mut cmd_restore_server_state,
+    argc: usize,
+    argv: *mut *mut c_char,
+    cause: *mut *mut c_char,
+) -> i32 {
+    unsafe {
+        let type_ = *argv;
+
+        if streq_(type_, "option") && argc == 4 {
+            if (*st).skip {
+                return 0;
+            }
+            return cmd_restore_server_option(st, *argv.add(1), *argv.add(2), *argv.add(3), cause);
+        }
+        if streq_(type_, "session") && argc == 4 {
+            cmd_restore_server_finish_session(st);
+            if cmd_restore_server_session(st, *argv.add(1), *argv.add(2), *argv.add(3), cause) != 0
+            {
+                return -1;
+            }
+            return 0;
+        }
+        if streq_(type_, "window") && argc == 4 {
+            cmd_restore_server_finish_window(st);
+            if (*st).s.is_null() {
+                (*st).skip = true;
+                return 0;
+            }
+            let Ok(idx) = strtonum(*argv.add(1), 0, i32::MAX) else {
+                *cause = format_nul!("invalid window index: {}", _s(*argv.add(1)));
+                (*st).skip = true;
+                return -1;
+            };
+            (*st).idx = idx;
+            (*st).name = xstrdup(*argv.add(2)).as_ptr();
+            (*st).layout = xstrdup(*argv.add(3)).as_ptr();
+            return 0;
+        }
+        if streq_(type_, "pane") && argc == 4 {
+            if (*st).s.is_null() || (*st).skip {
+                return 0;
+            }
+            return cmd_restore_server_pane(st, *argv.add(1), *argv.add(2), *argv.add(3), cause);
+        }
+
+        *cause = format_nul!("invalid record: {}", _s(type_));
+        -1
+    }
+}
+
+/// Check the first line of a state file is for a version that can be read.
+unsafe fn cmd_restore_server_header(line: *const c_char) -> Result<(), *mut c_char> {
+    unsafe {
+        let mut version = 0u32;
+        if libc::sscanf(line, c"tmux-rs-server %u".as_ptr(), &raw mut version) != 1 {
+            return Err(format_nul!("not a server state file"));
+        }
+        if version == 0 || version > SAVE_SERVER_VERSION {
+            return Err(format_nul!("unsupported version {}", version));
+        }
+        Ok(())
+    }
+}
+
+/// Split a record into its fields and unescape them. Returns the number of
+/// fields, or None if there are too many or one is badly escaped.
+unsafe fn cmd_restore_server_split(
+    line: *mut c_char,
+    argv: &mut [*mut c_char; 4],
+) -> Option<usize> {
+    unsafe {
+        let mut argc = 0usize;
+        let mut next = line;
+        while !next.is_null() {
+            let field = strsep(&raw mut next, c"\t".as_ptr());
+            if argc == argv.len() {
+                break;
+            }
+            argv[argc] = xmalloc(strlen(field) + 1).as_ptr().cast();
+            argc += 1;
+            if strunvis(argv[argc - 1], field) == -1 {
+                break;
+            }
+            if next.is_null() {
+                return Some(argc);
+            }
+        }
+        for arg in argv.iter().take(argc) {
+            free_(*arg);
+        }
+        None
+    }
+}
+
+unsafe fn cmd_restore_server_done(
+    _c: *mut client,
+    path: *mut c_char,
+    error: i32,
+    closed: i32,
+    buffer: *mut evbuffer,
+    data: *mut c_void,
+) {
+    let item = data as *mut cmdq_item;
+
+    if closed == 0 {
+        return;
+    }
+
+    unsafe {
+        if error != 0 {
+            cmdq_error!(item, "{}: {}", _s(path), _s(strerror(error)));
+            cmdq_continue(item);
+            return;
+        }
+
+        let mut st: cmd_restore_server_state = zeroed();
+        st.item = item;
+        st.curw = -1;
+
+        let mut line_number = 0u32;
+        loop {
+            let mut size = 0usize;
+            let line = evbuffer_readln(buffer, &raw mut size, evbuffer_eol_style_EVBUFFER_EOL_LF);
+            if line.is_null() {
+                break;
+            }
+            line_number += 1;
+
+            if line_number == 1 {
+                let header = cmd_restore_server_header(line);
+                free_(line);
+                if let Err(cause) = header {
+                    cmdq_error!(item, "{}: {}", _s(path), _s(cause));
+                    free_(cause);
+                    break;
+                }
+                continue;
+            }
+            if *line == b'\0' as c_char {
+                free_(line);
+                continue;
+            }
+
+            let mut argv: [*mut c_char; 4] = [null_mut(); 4];
+            let Some(argc) = cmd_restore_server_split(line, &mut argv) else {
+                cmdq_error!(item, "{}:{}: invalid record", _s(path), line_number);
+                free_(line);
+                continue;
+            };
+
+            let mut cause = null_mut();
+            if cmd_restore_server_line(&raw mut st, argc, argv.as_mut_ptr(), &raw mut cause) != 0 {
+                cmdq_error!(item, "{}:{}: {}", _s(path), line_number, _s(cause));
+                free_(cause);
+            }
+
+            for arg in argv.iter().take(argc) {
+                free_(*arg);
+            }
+            free_(line);
+        }
+        cmd_restore_server_finish_session(&raw mut st);
+
+        recalculate_sizes();
+        cmdq_continue(item);
+    }
+}
+
+unsafe fn cmd_restore_server_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retval {
+    unsafe {
+        let args = cmd_get_args(self_);
+
+        let path = cmd_save_server_path(item, args);
+        if path.is_null() {
+            cmdq_error!(item, "no state file");
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+
+        file_read(
+            cmdq_get_client(item),
+            path,
+            Some(cmd_restore_server_done),
+            item as _,
+        );
+        free_(path);
+
+        cmd_retval::CMD_RETURN_WAIT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmd_restore_server_split() {
+        unsafe {
+            let fields = [
+                c"pane".as_ptr(),
+                c"/tmp/a\tb\\c".as_ptr(),
+                c"".as_ptr(),
+                c"vi 'x y'\n\x01".as_ptr(),
+            ];
+            let evb = evbuffer_new();
+            cmd_save_server_add(evb, &fields);
+
+            let mut size = 0usize;
+            let line = evbuffer_readln(evb, &raw mut size, evbuffer_eol_style_EVBUFFER_EOL_LF);
+            assert!(!line.is_null());
+            assert_eq!(evbuffer_get_length(evb), 0);
+
+            let mut argv: [*mut c_char; 4] = [null_mut(); 4];
+            assert_eq!(cmd_restore_server_split(line, &mut argv), Some(4));
+            for (arg, field) in argv.iter().zip(fields) {
+                assert_eq!(CStr::from_ptr(*arg), CStr::from_ptr(field));
+                free_(*arg);
+            }
+            free_(line);
+            evbuffer_free(evb);
+
+            for bad in [c"a\tb\tc\td\te", c"option\tx\t\\Mz"] {
+                let line = xstrdup(bad.as_ptr()).as_ptr();
+                assert_eq!(cmd_restore_server_split(line, &mut argv), None);
+                free_(line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cmd_restore_server_header() {
+        unsafe {
+            assert!(cmd_restore_server_header(c"tmux-rs-server 1".as_ptr()).is_ok());
+            for (line, error) in [
+                (c"tmux-rs-server 0", "unsupported version 0"),
+                (c"tmux-rs-server 2", "unsupported version 2"),
+                (c"tmux-rs-server", "not a server state file"),
+                (c"session\tmain", "not a server state file"),
+            ] {
+                let cause = cmd_restore_server_header(line.as_ptr()).unwrap_err();
+                assert_eq!(cstr_to_str(cause), error);
+                free_(cause);
+            }
+        }
+    }
+}
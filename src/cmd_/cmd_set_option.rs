@@ -239,6 +239,7 @@ pub unsafe fn cmd_set_option_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_
                     }
                 }
 
+                cfg_watch_set_option(item, self_, oo, name);
                 options_push_changes(name);
             }
             // out:
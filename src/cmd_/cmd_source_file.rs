@@ -40,6 +40,9 @@ pub struct cmd_source_file_data {
     pub current: u32,
     pub files: *mut *mut c_char,
     pub nfiles: u32,
+
+    /// Configuration file this command is in, if any.
+    pub parent: *mut c_char,
 }
 
 unsafe fn cmd_source_file_complete_cb(item: *mut cmdq_item, data: *mut c_void) -> cmd_retval {
@@ -51,7 +54,9 @@ unsafe fn cmd_source_file_complete_cb(item: *mut cmdq_item, data: *mut c_void) -
 
 unsafe fn cmd_source_file_complete(c: *mut client, cdata: *mut cmd_source_file_data) {
     unsafe {
-        if cfg_finished != 0 {
+        // Files sourced from another configuration file leave their causes
+        // for whatever loaded that file to show.
+        if cfg_finished != 0 && (*cdata).parent.is_null() {
             if (*cdata).retval == cmd_retval::CMD_RETURN_ERROR
                 && !c.is_null()
                 && (*c).session.is_null()
@@ -66,6 +71,7 @@ unsafe fn cmd_source_file_complete(c: *mut client, cdata: *mut cmd_source_file_d
             free_(*(*cdata).files.add(i as usize));
         }
         free_((*cdata).files);
+        free_((*cdata).parent);
         free_(cdata);
     }
 }
@@ -90,6 +96,7 @@ unsafe fn cmd_source_file_done(
             return;
         }
 
+        cfg_watch_add(cstr_to_str(path), (*cdata).parent);
         if error != 0 {
             cmdq_error!(item, "{}: {}", _s(path), _s(strerror(error)));
         } else if bsize != 0 {
@@ -152,6 +159,7 @@ unsafe fn cmd_source_file_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
 
         let cdata = xcalloc_::<cmd_source_file_data>(1).as_ptr();
         (*cdata).item = item;
+        (*cdata).parent = cfg_watch_parent(item, self_);
 
         if args_has_(args, 'q') {
             (*cdata).flags |= cmd_parse_input_flags::CMD_PARSE_QUIET;
@@ -187,6 +195,9 @@ unsafe fn cmd_source_file_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_ret
                 pattern = format_nul!("{}/{}", _s(cwd), _s(path));
             }
             log_debug!("{}: {}", __func__, _s(pattern));
+            if !(*cdata).parent.is_null() {
+                cfg_watch_add_pattern((*cdata).parent, pattern);
+            }
 
             result = glob(pattern, 0, None, g.as_mut_ptr());
             if result != 0 {
@@ -19,8 +19,8 @@ pub static mut cmd_split_window_entry: cmd_entry = cmd_entry {
     name: c"split-window".as_ptr(),
     alias: c"splitw".as_ptr(),
 
-    args: args_parse::new(c"bc:de:fF:hIl:p:Pt:vZ", 0, -1, None),
-    usage: c"[-bdefhIPvZ] [-c start-directory] [-e environment] [-F format] [-l size] [-t target-pane][shell-command]".as_ptr(),
+    args: args_parse::new(c"bc:de:fF:hH:Il:p:Pt:vZ", 0, -1, None),
+    usage: c"[-bdefhIPvZ] [-c start-directory] [-e environment] [-F format] [-H history-file] [-l size] [-t target-pane][shell-command]".as_ptr(),
 
     target: cmd_entry_flag::new(b't', cmd_find_type::CMD_FIND_PANE, 0),
 
@@ -96,8 +96,12 @@ unsafe fn cmd_split_window_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_re
             return cmd_retval::CMD_RETURN_ERROR;
         }
 
-        window_push_zoom((*wp).window, 1, args_has(args, b'Z'));
         let mut input = args_has_(args, 'I') && count == 0;
+        if input && args_has_(args, 'H') {
+            cmdq_error!(item, "-H and -I cannot be used together");
+            return cmd_retval::CMD_RETURN_ERROR;
+        }
+        window_push_zoom((*wp).window, 1, args_has(args, b'Z'));
 
         let mut flags = 0;
         if args_has_(args, 'b') {
@@ -202,6 +206,9 @@ unsafe fn cmd_split_window_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_re
         if input {
             return cmd_retval::CMD_RETURN_WAIT;
         }
+        if args_has_(args, 'H') {
+            return cmd_load_history_file(item, new_wp, args_get_(args, 'H'));
+        }
         cmd_retval::CMD_RETURN_NORMAL
     }
 }
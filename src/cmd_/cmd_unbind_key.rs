@@ -88,6 +88,7 @@ unsafe fn cmd_unbind_key_exec(self_: *mut cmd, item: *mut cmdq_item) -> cmd_retv
         } else {
             tablename = c"prefix".as_ptr();
         }
+        cfg_watch_set_key(item, self_, tablename, key);
         key_bindings_remove(tablename, key);
         cmd_retval::CMD_RETURN_NORMAL
     }
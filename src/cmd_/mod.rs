@@ -28,11 +28,13 @@ use crate::xmalloc::{xrealloc_, xreallocarray_};
 pub mod cmd_attach_session;
 pub mod cmd_bind_key;
 pub mod cmd_break_pane;
+pub mod cmd_capture_client;
 pub mod cmd_capture_pane;
 pub mod cmd_choose_tree;
 pub mod cmd_command_prompt;
 pub mod cmd_confirm_before;
 pub mod cmd_copy_mode;
+pub mod cmd_define_command;
 pub mod cmd_detach_client;
 pub mod cmd_display_menu;
 pub mod cmd_display_message;
@@ -52,6 +54,7 @@ pub mod cmd_list_panes;
 pub mod cmd_list_sessions;
 pub mod cmd_list_windows;
 pub mod cmd_load_buffer;
+pub mod cmd_load_history;
 pub mod cmd_lock_server;
 pub mod cmd_move_window;
 pub mod cmd_new_session;
@@ -69,6 +72,8 @@ pub mod cmd_respawn_window;
 pub mod cmd_rotate_window;
 pub mod cmd_run_shell;
 pub mod cmd_save_buffer;
+pub mod cmd_save_history;
+pub mod cmd_save_server;
 pub mod cmd_select_layout;
 pub mod cmd_select_pane;
 pub mod cmd_select_window;
@@ -92,14 +97,16 @@ pub mod cmd_wait_for;
 use cmd_attach_session::cmd_attach_session_entry;
 use cmd_bind_key::cmd_bind_key_entry;
 use cmd_break_pane::cmd_break_pane_entry;
+use cmd_capture_client::cmd_capture_client_entry;
 use cmd_capture_pane::{cmd_capture_pane_entry, cmd_clear_history_entry};
 use cmd_choose_tree::{
-    cmd_choose_buffer_entry, cmd_choose_client_entry, cmd_choose_tree_entry,
-    cmd_customize_mode_entry,
+    cmd_choose_buffer_entry, cmd_choose_client_entry, cmd_choose_fuzzy_entry,
+    cmd_choose_tree_entry, cmd_customize_mode_entry,
 };
 use cmd_command_prompt::cmd_command_prompt_entry;
 use cmd_confirm_before::cmd_confirm_before_entry;
-use cmd_copy_mode::{cmd_clock_mode_entry, cmd_copy_mode_entry};
+use cmd_copy_mode::{cmd_clock_mode_entry, cmd_copy_mode_entry, cmd_pane_timeline_entry};
+use cmd_define_command::cmd_define_command_entry;
 use cmd_detach_client::cmd_detach_client_entry;
 use cmd_detach_client::cmd_suspend_client_entry;
 use cmd_display_menu::{cmd_display_menu_entry, cmd_display_popup_entry};
@@ -121,6 +128,7 @@ use cmd_list_panes::cmd_list_panes_entry;
 use cmd_list_sessions::cmd_list_sessions_entry;
 use cmd_list_windows::cmd_list_windows_entry;
 use cmd_load_buffer::cmd_load_buffer_entry;
+use cmd_load_history::cmd_load_history_entry;
 use cmd_lock_server::{cmd_lock_client_entry, cmd_lock_server_entry, cmd_lock_session_entry};
 use cmd_move_window::cmd_link_window_entry;
 use cmd_move_window::cmd_move_window_entry;
@@ -140,6 +148,8 @@ use cmd_rotate_window::cmd_rotate_window_entry;
 use cmd_run_shell::cmd_run_shell_entry;
 use cmd_save_buffer::cmd_save_buffer_entry;
 use cmd_save_buffer::cmd_show_buffer_entry;
+use cmd_save_history::cmd_save_history_entry;
+use cmd_save_server::{cmd_restore_server_entry, cmd_save_server_entry};
 use cmd_select_layout::cmd_next_layout_entry;
 use cmd_select_layout::cmd_previous_layout_entry;
 use cmd_select_layout::cmd_select_layout_entry;
@@ -172,13 +182,15 @@ use cmd_switch_client::cmd_switch_client_entry;
 use cmd_unbind_key::cmd_unbind_key_entry;
 use cmd_wait_for::cmd_wait_for_entry;
 
-pub static mut cmd_table: [*const cmd_entry; 91] = [
+pub static mut cmd_table: [*const cmd_entry; 99] = [
     &raw const cmd_attach_session_entry,
     &raw const cmd_bind_key_entry,
     &raw const cmd_break_pane_entry,
+    &raw const cmd_capture_client_entry,
     &raw const cmd_capture_pane_entry,
     &raw const cmd_choose_buffer_entry,
     &raw const cmd_choose_client_entry,
+    &raw const cmd_choose_fuzzy_entry,
     &raw const cmd_choose_tree_entry,
     &raw const cmd_clear_history_entry,
     &raw const cmd_clear_prompt_history_entry,
@@ -187,6 +199,7 @@ pub static mut cmd_table: [*const cmd_entry; 91] = [
     &raw const cmd_confirm_before_entry,
     &raw const cmd_copy_mode_entry,
     &raw const cmd_customize_mode_entry,
+    &raw const cmd_define_command_entry,
     &raw const cmd_delete_buffer_entry,
     &raw const cmd_detach_client_entry,
     &raw const cmd_display_menu_entry,
@@ -212,6 +225,7 @@ pub static mut cmd_table: [*const cmd_entry; 91] = [
     &raw const cmd_list_sessions_entry,
     &raw const cmd_list_windows_entry,
     &raw const cmd_load_buffer_entry,
+    &raw const cmd_load_history_entry,
     &raw const cmd_lock_client_entry,
     &raw const cmd_lock_server_entry,
     &raw const cmd_lock_session_entry,
@@ -221,6 +235,7 @@ pub static mut cmd_table: [*const cmd_entry; 91] = [
     &raw const cmd_new_window_entry,
     &raw const cmd_next_layout_entry,
     &raw const cmd_next_window_entry,
+    &raw const cmd_pane_timeline_entry,
     &raw const cmd_paste_buffer_entry,
     &raw const cmd_pipe_pane_entry,
     &raw const cmd_previous_layout_entry,
@@ -232,9 +247,12 @@ pub static mut cmd_table: [*const cmd_entry; 91] = [
     &raw const cmd_resize_window_entry,
     &raw const cmd_respawn_pane_entry,
     &raw const cmd_respawn_window_entry,
+    &raw const cmd_restore_server_entry,
     &raw const cmd_rotate_window_entry,
     &raw const cmd_run_shell_entry,
     &raw const cmd_save_buffer_entry,
+    &raw const cmd_save_history_entry,
+    &raw const cmd_save_server_entry,
     &raw const cmd_select_layout_entry,
     &raw const cmd_select_pane_entry,
     &raw const cmd_select_window_entry,
@@ -483,9 +501,22 @@ pub unsafe fn cmd_get_alias(name: *const c_char) -> *mut c_char {
     }
 }
 
+/// Get the built-in commands followed by the commands added with
+/// define-command.
+pub unsafe fn cmd_entries() -> Vec<*mut cmd_entry> {
+    unsafe {
+        let table = &raw const cmd_table;
+        let mut entries: Vec<*mut cmd_entry> = (*table)
+            .iter()
+            .take_while(|entry| !entry.is_null())
+            .map(|entry| entry.cast_mut())
+            .collect();
+        entries.extend(cmd_define_command::cmd_define_command_entries());
+        entries
+    }
+}
+
 pub unsafe fn cmd_find(name: *const c_char) -> Result<*mut cmd_entry, *mut c_char> {
-    let mut loop_: *mut *mut cmd_entry;
-    let mut entry: *mut cmd_entry;
     let mut found: *mut cmd_entry = null_mut();
 
     let mut ambiguous: i32 = 0;
@@ -493,10 +524,9 @@ pub unsafe fn cmd_find(name: *const c_char) -> Result<*mut cmd_entry, *mut c_cha
     let mut s: s_buf = [0; 8192];
 
     unsafe {
+        let entries = cmd_entries();
         'ambiguous: {
-            loop_ = &raw mut cmd_table as _; // TODO casting const pointer to mut ptr
-            while !(*loop_).is_null() {
-                entry = *loop_;
+            for &entry in &entries {
                 if !(*entry).alias.is_null() && strcmp((*entry).alias, name) == 0 {
                     ambiguous = 0;
                     found = entry;
@@ -504,7 +534,6 @@ pub unsafe fn cmd_find(name: *const c_char) -> Result<*mut cmd_entry, *mut c_cha
                 }
 
                 if strncmp((*entry).name, name, strlen(name)) != 0 {
-                    loop_ = loop_.add(1);
                     continue;
                 }
                 if !found.is_null() {
@@ -515,8 +544,6 @@ pub unsafe fn cmd_find(name: *const c_char) -> Result<*mut cmd_entry, *mut c_cha
                 if strcmp((*entry).name, name) == 0 {
                     break;
                 }
-
-                loop_ = loop_.add(1);
             }
             if ambiguous != 0 {
                 break 'ambiguous;
@@ -531,9 +558,7 @@ pub unsafe fn cmd_find(name: *const c_char) -> Result<*mut cmd_entry, *mut c_cha
 
         // ambiguous:
         s[0] = b'\0' as c_char;
-        loop_ = &raw mut cmd_table as _;
-        while !(*loop_).is_null() {
-            entry = *loop_;
+        for entry in entries {
             if strncmp((*entry).name, name, strlen(name)) != 0 {
                 continue;
             }
@@ -543,7 +568,6 @@ pub unsafe fn cmd_find(name: *const c_char) -> Result<*mut cmd_entry, *mut c_cha
             if strlcat(&raw mut s as _, c", ".as_ptr(), size_of::<s_buf>()) >= size_of::<s_buf>() {
                 break;
             }
-            loop_ = loop_.add(1);
         }
         s[strlen(&raw mut s as _) - 2] = b'\0' as c_char;
 
@@ -903,20 +927,19 @@ pub unsafe fn cmd_template_replace(
         while *ptr != b'\0' as c_char {
             let ch = *ptr;
             ptr = ptr.add(1);
-            if matches!(ch as c_uchar, b'%') {
-                if *ptr < b'1' as c_char
-                    || *ptr > b'9' as c_char
-                    || *ptr as i32 - b'0' as i32 != idx
-                {
-                    if *ptr != b'%' as c_char || replaced != 0 {
-                        break;
-                    }
+            let numbered = *ptr >= b'1' as c_char
+                && *ptr <= b'9' as c_char
+                && *ptr as i32 - b'0' as i32 == idx;
+            if matches!(ch as c_uchar, b'%')
+                && (numbered || (*ptr == b'%' as c_char && replaced == 0))
+            {
+                if !numbered {
                     replaced = 1;
                 }
                 ptr = ptr.add(1);
 
                 let quoted = *ptr == b'%' as c_char;
-                if !quoted {
+                if quoted {
                     ptr = ptr.add(1);
                 }
 
@@ -943,3 +966,51 @@ pub unsafe fn cmd_template_replace(
         buf
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmd_find() {
+        unsafe {
+            let entry = cmd_find(c"save-s".as_ptr()).unwrap();
+            assert_eq!(cstr_to_str((*entry).name), "save-server");
+
+            // Every command matching an ambiguous prefix is listed, not just
+            // the first.
+            let cause = cmd_find(c"save-".as_ptr()).unwrap_err();
+            assert_eq!(
+                cstr_to_str(cause),
+                "ambiguous command: save-, could be: save-buffer, save-history, save-server"
+            );
+            free_(cause);
+
+            let cause = cmd_find(c"nonexistent".as_ptr()).unwrap_err();
+            assert_eq!(cstr_to_str(cause), "unknown command: nonexistent");
+            free_(cause);
+        }
+    }
+
+    #[test]
+    fn test_cmd_template_replace() {
+        unsafe {
+            let replace = |template: &CStr, s: &CStr, idx| {
+                let out = cmd_template_replace(template.as_ptr(), s.as_ptr(), idx);
+                let text = cstr_to_str(out).to_string();
+                free_(out);
+                text
+            };
+
+            // Templates may end straight after the replaced marker.
+            assert_eq!(replace(c"rename %1", c"x", 1), "rename x");
+            assert_eq!(replace(c"rename %%", c"x", 1), "rename x");
+
+            // Only a doubled marker quotes, and other markers are kept.
+            assert_eq!(replace(c"send %1", c"a\"b", 1), "send a\"b");
+            assert_eq!(replace(c"send %1%", c"a\"b", 1), "send a\\\"b");
+            assert_eq!(replace(c"100% %2 %1", c"x", 1), "100% %2 x");
+            assert_eq!(replace(c"%% %%", c"x", 1), "x %%");
+        }
+    }
+}
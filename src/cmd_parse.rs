@@ -14,6 +14,8 @@
 #![allow(clippy::uninlined_format_args)] // for lalrpop generated code
 use crate::*;
 
+use std::collections::BTreeMap;
+use std::ffi::CString;
 use std::io::Read as _;
 use std::ops::BitAndAssign as _;
 use std::ops::BitOrAssign as _;
@@ -22,6 +24,7 @@ use std::sync::atomic::Ordering;
 use lalrpop_util::lalrpop_mod;
 use libc::_SC_MB_LEN_MAX;
 
+use crate::cmd_::cmd_define_command::{cmd_define_command_declare, cmd_define_command_entry};
 use crate::compat::queue::{
     tailq_empty, tailq_first, tailq_foreach, tailq_init, tailq_insert_tail, tailq_last,
     tailq_remove,
@@ -107,8 +110,60 @@ pub struct cmd_parse_state<'a> {
 
     pub scope: Option<&'a mut cmd_parse_scope>,
     pub stack: tailq_head<cmd_parse_scope>,
+
+    /// Return directives as tokens rather than running them.
+    pub skip_directives: bool,
+    pub directives: Option<Box<cmd_parse_directives>>,
+}
+
+/// Most %for, %while and macro texts that may be read inside each other.
+const CMD_PARSE_MAX_FRAMES: usize = 100;
+
+/// Most times a %while loop may run.
+const CMD_PARSE_MAX_LOOPS: u32 = 10000;
+
+/// Text read instead of the input, for %for and %while bodies, macros and the
+/// arguments of directives.
+pub struct cmd_parse_frame {
+    text: Vec<u8>,
+    off: usize,
+    kind: cmd_parse_frame_type,
+
+    /// Variables that only exist inside this text.
+    variables: Vec<(String, Vec<String>)>,
+
+    /// Line where the text starts and line to go back to at the end.
+    line: u32,
+    saved_line: u32,
 }
 
+enum cmd_parse_frame_type {
+    Words,
+    Macro,
+    For { items: Vec<String>, next: usize },
+    While { condition: String, count: u32 },
+}
+
+pub struct cmd_parse_macro {
+    parameters: Vec<String>,
+    body: Vec<u8>,
+    line: u32,
+}
+
+/// Directive state for one parse. Variables and macros defined when only
+/// parsing are kept here and forgotten afterwards.
+#[derive(Default)]
+pub struct cmd_parse_directives {
+    frames: Vec<cmd_parse_frame>,
+    variables: BTreeMap<String, Vec<String>>,
+    macros: BTreeMap<String, cmd_parse_macro>,
+}
+
+/// Variables set with %set and macros defined with %define. These last until
+/// the server exits so files loaded with source-file can use them.
+static mut cmd_parse_variables: BTreeMap<String, Vec<String>> = BTreeMap::new();
+static mut cmd_parse_macros: BTreeMap<String, cmd_parse_macro> = BTreeMap::new();
+
 pub unsafe fn cmd_parse_get_error(file: Option<&str>, line: u32, error: &str) -> *mut c_char {
     match file {
         None => {
@@ -120,6 +175,96 @@ pub unsafe fn cmd_parse_get_error(file: Option<&str>, line: u32, error: &str) ->
     }
 }
 
+/// Find a variable set with %set or bound by %for or a macro.
+#[expect(clippy::deref_addrof)]
+unsafe fn cmd_parse_get_variable(ps: &cmd_parse_state, name: &str) -> Option<Vec<String>> {
+    unsafe {
+        if let Some(directives) = ps.directives.as_ref() {
+            for frame in directives.frames.iter().rev() {
+                if let Some((_, value)) = frame.variables.iter().find(|(n, _)| n == name) {
+                    return Some(value.clone());
+                }
+            }
+            if let Some(value) = directives.variables.get(name) {
+                return Some(value.clone());
+            }
+        }
+        (*&raw const cmd_parse_variables).get(name).cloned()
+    }
+}
+
+/// Set a variable. A variable bound by %for or a macro is changed where it
+/// is, otherwise it is set for the rest of the parse and, unless only
+/// parsing, kept for later.
+#[expect(clippy::deref_addrof)]
+unsafe fn cmd_parse_set_variable(ps: &mut cmd_parse_state, name: &str, value: Vec<String>) {
+    unsafe {
+        let parse_only = cmd_parse_only(ps);
+        let directives = ps.directives.get_or_insert_default();
+        for frame in directives.frames.iter_mut().rev() {
+            if let Some((_, old)) = frame.variables.iter_mut().find(|(n, _)| n == name) {
+                *old = value;
+                return;
+            }
+        }
+        if parse_only {
+            directives.variables.insert(name.to_string(), value);
+        } else {
+            (*&raw mut cmd_parse_variables).insert(name.to_string(), value);
+        }
+    }
+}
+
+fn cmd_parse_only(ps: &cmd_parse_state) -> bool {
+    ps.input.is_some_and(|pi| {
+        pi.flags
+            .intersects(cmd_parse_input_flags::CMD_PARSE_PARSEONLY)
+    })
+}
+
+/// Expand a format for a condition or directive. Variables are available as
+/// format variables of the same name.
+#[expect(clippy::deref_addrof)]
+pub unsafe fn cmd_parse_expand(ps: &cmd_parse_state, s: *const c_char) -> *mut c_char {
+    unsafe {
+        let pi = ps.input.unwrap();
+        let flags = format_flags::FORMAT_NOJOBS;
+
+        let mut fs = MaybeUninit::<cmd_find_state>::uninit();
+        let fsp = if cmd_find_valid_state(&raw const pi.fs) {
+            &raw const pi.fs
+        } else {
+            cmd_find_from_client(fs.as_mut_ptr(), pi.c, 0);
+            fs.as_ptr()
+        };
+        let ft = format_create(null_mut(), pi.item, FORMAT_NONE, flags);
+        format_defaults(
+            ft,
+            pi.c,
+            NonNull::new((*fsp).s),
+            NonNull::new((*fsp).wl),
+            NonNull::new((*fsp).wp),
+        );
+
+        let mut variables: Vec<(&String, &Vec<String>)> =
+            (*&raw const cmd_parse_variables).iter().collect();
+        if let Some(directives) = ps.directives.as_ref() {
+            variables.extend(directives.variables.iter());
+            for frame in &directives.frames {
+                variables.extend(frame.variables.iter().map(|(n, v)| (n, v)));
+            }
+        }
+        for (name, value) in variables {
+            let key = CString::new(name.as_str()).unwrap();
+            format_add!(ft, key.as_ptr(), "{}", value.join(" "));
+        }
+
+        let value = format_expand(ft, s);
+        format_free(ft);
+        value
+    }
+}
+
 pub fn cmd_parse_print_commands(pi: &cmd_parse_input, cmdlist: &mut cmd_list) {
     if pi.item.is_null()
         || !pi
@@ -382,6 +527,21 @@ pub unsafe fn cmd_parse_build_command(
 
             match cmd_parse(values, count, pi.file, pi.line.load(Ordering::SeqCst)) {
                 Ok(add) => {
+                    // Declare user commands now so later lines can use them.
+                    if !pi
+                        .flags
+                        .intersects(cmd_parse_input_flags::CMD_PARSE_PARSEONLY)
+                        && cmd_get_entry(add) == &raw mut cmd_define_command_entry
+                        && let Err(cause) = cmd_define_command_declare(cmd_get_args(add))
+                    {
+                        *pr = Err(cmd_parse_get_error(
+                            pi.file,
+                            pi.line.load(Ordering::SeqCst),
+                            &format!("command define-command: {cause}"),
+                        ));
+                        cmd_free(add);
+                        break 'out;
+                    }
                     let cmdlist = cmd_list_new();
                     *pr = Ok(cmdlist);
                     cmd_list_append(cmdlist, add);
@@ -501,6 +661,88 @@ pub unsafe fn cmd_parse_from_string(s: &str, pi: Option<&cmd_parse_input>) -> cm
     }
 }
 
+/// Kind of a token returned by cmd_parse_tokens.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum cmd_parse_token_type {
+    Word,
+    Assignment,
+    Format,
+    Condition,
+    Separator,
+    Newline,
+    Error,
+}
+
+/// A token and its byte offsets in the buffer.
+pub struct cmd_parse_token {
+    pub type_: cmd_parse_token_type,
+    pub start: usize,
+    pub end: usize,
+    pub value: Option<String>,
+}
+
+/// Split a buffer into tokens with the same lexer the parser uses, keeping
+/// where each one is. Stops at the first token the lexer cannot read.
+pub unsafe fn cmd_parse_tokens(buf: &[u8]) -> Vec<cmd_parse_token> {
+    unsafe {
+        let input: cmd_parse_input = zeroed();
+        let mut ps: Box<cmd_parse_state> = Box::new(zeroed());
+        ps.input = Some(&input);
+        ps.buf = Some(buf);
+        ps.skip_directives = true;
+
+        let mut tokens = Vec::new();
+        loop {
+            let mut start = ps.off;
+            while start < buf.len() && (buf[start] == b' ' || buf[start] == b'\t') {
+                start += 1;
+            }
+            let Some(tok) = yylex_(&mut ps) else {
+                break;
+            };
+            let end = ps.off.max(start);
+
+            let mut value = None;
+            let type_ = match tok {
+                Tok::Zero | Tok::Error => cmd_parse_token_type::Error,
+                Tok::Newline => cmd_parse_token_type::Newline,
+                Tok::Semicolon | Tok::LeftBrace | Tok::RightBrace => {
+                    cmd_parse_token_type::Separator
+                }
+                Tok::Hidden | Tok::If | Tok::Else | Tok::Elif | Tok::Endif | Tok::Directive => {
+                    cmd_parse_token_type::Condition
+                }
+                Tok::Format(s) | Tok::Token(s) | Tok::Equals(s) => {
+                    let s = transmute_ptr(s);
+                    value =
+                        Some(String::from_utf8_lossy(CStr::from_ptr(s).to_bytes()).into_owned());
+                    free_(s);
+                    match tok {
+                        Tok::Format(_) => cmd_parse_token_type::Format,
+                        Tok::Equals(_) => cmd_parse_token_type::Assignment,
+                        _ => cmd_parse_token_type::Word,
+                    }
+                }
+            };
+            tokens.push(cmd_parse_token {
+                type_,
+                start,
+                end: if type_ == cmd_parse_token_type::Error {
+                    buf.len()
+                } else {
+                    end
+                },
+                value,
+            });
+            if type_ == cmd_parse_token_type::Error {
+                break;
+            }
+        }
+        free_(ps.error);
+        tokens
+    }
+}
+
 pub unsafe fn cmd_parse_and_insert(
     s: &str,
     pi: Option<&cmd_parse_input>,
@@ -669,6 +911,7 @@ mod lexer {
         Else,
         Elif,
         Endif,
+        Directive,
 
         Format(Option<NonNull<c_char>>),
         Token(Option<NonNull<c_char>>),
@@ -688,6 +931,7 @@ mod lexer {
                 Tok::Else => write!(f, "%else"),
                 Tok::Elif => write!(f, "%elif"),
                 Tok::Endif => write!(f, "%endif"),
+                Tok::Directive => write!(f, "%directive"),
                 Tok::Format(non_null) => {
                     write!(f, "format({})", crate::_s(transmute_ptr(*non_null)))
                 }
@@ -761,6 +1005,13 @@ unsafe fn yylex_append1(buf: *mut *mut c_char, len: *mut usize, add: c_char) {
 
 fn yylex_getc1(ps: &mut cmd_parse_state) -> i32 {
     let ch;
+    if let Some(frame) = yylex_frame(ps) {
+        let Some(&byte) = frame.text.get(frame.off) else {
+            return libc::EOF;
+        };
+        frame.off += 1;
+        return byte as i32;
+    }
     if let Some(f) = ps.f.as_mut() {
         if let Some(c) = ps.unget_buf.take() {
             return c;
@@ -791,7 +1042,11 @@ fn yylex_getc1(ps: &mut cmd_parse_state) -> i32 {
 }
 
 fn yylex_ungetc(ps: &mut cmd_parse_state, ch: i32) {
-    if let Some(f) = ps.f.as_mut() {
+    if let Some(frame) = yylex_frame(ps) {
+        if frame.off > 0 && ch != libc::EOF {
+            frame.off -= 1;
+        }
+    } else if let Some(f) = ps.f.as_mut() {
         ps.unget_buf = Some(ch)
     } else if ps.off > 0 && ch != libc::EOF {
         ps.off -= 1;
@@ -869,6 +1124,13 @@ unsafe fn yylex_(ps: &mut cmd_parse_state) -> Option<Tok> {
         loop {
             let mut ch = yylex_getc(ps);
 
+            if ch == libc::EOF && yylex_frame(ps).is_some() {
+                // The end of a loop body or macro ends the line.
+                if !yylex_frame_end(ps) {
+                    return Some(Tok::Error);
+                }
+                return Some(Tok::Newline);
+            }
             if ch == libc::EOF {
                 /*
                  * Ensure every file or string is terminated by a
@@ -981,8 +1243,13 @@ unsafe fn yylex_(ps: &mut cmd_parse_state) -> Option<Tok> {
                     free_(yylval_token);
                     return Some(Tok::Endif);
                 }
+                let name = cstr_to_str(yylval_token.add(1)).to_string();
                 free_(yylval_token);
-                return Some(Tok::Error);
+                ps.condition = 0;
+                match yylex_directive(ps, &name) {
+                    Some(tok) => return Some(tok),
+                    None => continue,
+                }
             }
 
             // Otherwise this is a token.
@@ -1108,6 +1375,12 @@ unsafe fn yylex_token_variable(
         }
         name[namelen] = b'\0' as i8;
 
+        if let Some(value) = cmd_parse_get_variable(ps, cstr_to_str((&raw const name).cast())) {
+            let value = value.join(" ");
+            yylex_append(buf, len, value.as_ptr().cast(), value.len());
+            return true;
+        }
+
         let mut envent = environ_find(global_environ, (&raw const name).cast());
         if !envent.is_null() && (*envent).value.is_some() {
             let value = (*envent).value;
@@ -1406,7 +1679,543 @@ unsafe fn yylex_token_escape(
     }
 }
 
+fn yylex_frame<'b>(ps: &'b mut cmd_parse_state) -> Option<&'b mut cmd_parse_frame> {
+    ps.directives.as_mut()?.frames.last_mut()
+}
+
+fn yylex_set_line(ps: &cmd_parse_state, line: u32) {
+    if let Some(pi) = ps.input {
+        pi.line.store(line, Ordering::SeqCst);
+    }
+}
+
+fn yylex_get_line(ps: &cmd_parse_state) -> u32 {
+    ps.input.map_or(0, |pi| pi.line.load(Ordering::SeqCst))
+}
+
+/// Whether every %if around the current line is true.
+unsafe fn yylex_scope_true(ps: &mut cmd_parse_state) -> bool {
+    unsafe {
+        if ps.scope.as_ref().is_some_and(|scope| scope.flag == 0) {
+            return false;
+        }
+        tailq_foreach(&raw mut ps.stack).all(|scope| (*scope.as_ptr()).flag != 0)
+    }
+}
+
+/// Start reading from a text.
+unsafe fn yylex_push_frame(
+    ps: &mut cmd_parse_state,
+    text: Vec<u8>,
+    kind: cmd_parse_frame_type,
+    variables: Vec<(String, Vec<String>)>,
+    line: u32,
+    saved_line: u32,
+) -> bool {
+    unsafe {
+        let directives = ps.directives.get_or_insert_default();
+        if directives.frames.len() == CMD_PARSE_MAX_FRAMES {
+            yyerror!(ps, "too many nested loops or macros");
+            return false;
+        }
+        directives.frames.push(cmd_parse_frame {
+            text,
+            off: 0,
+            kind,
+            variables,
+            line,
+            saved_line,
+        });
+        yylex_set_line(ps, line);
+        true
+    }
+}
+
+/// Reached the end of a text: start the next time round a loop or go back to
+/// the text underneath.
+unsafe fn yylex_frame_end(ps: &mut cmd_parse_state) -> bool {
+    unsafe {
+        let frame = yylex_frame(ps).unwrap();
+        let again = match &mut frame.kind {
+            cmd_parse_frame_type::For { items, next } => {
+                if let Some(item) = items.get(*next) {
+                    frame.variables[0].1 = vec![item.clone()];
+                    *next += 1;
+                    true
+                } else {
+                    false
+                }
+            }
+            cmd_parse_frame_type::While { condition, count } => {
+                *count += 1;
+                if *count == CMD_PARSE_MAX_LOOPS {
+                    yyerror!(ps, "%while ran too many times");
+                    return false;
+                }
+                let condition = CString::new(condition.as_str()).unwrap();
+                let value = cmd_parse_expand(ps, condition.as_ptr());
+                let result = format_true(value) != 0;
+                free_(value);
+                result
+            }
+            _ => false,
+        };
+
+        let frame = yylex_frame(ps).unwrap();
+        if again {
+            frame.off = 0;
+            let line = frame.line;
+            yylex_set_line(ps, line);
+        } else {
+            let saved_line = frame.saved_line;
+            ps.directives.as_mut().unwrap().frames.pop();
+            yylex_set_line(ps, saved_line);
+        }
+        true
+    }
+}
+
+/// Read the rest of a directive up to the end of the line, or up to the {
+/// which opens its body if block is set. Returns the text and whether the
+/// end of the line was reached.
+unsafe fn yylex_directive_line(ps: &mut cmd_parse_state, block: bool) -> Option<(Vec<u8>, bool)> {
+    unsafe {
+        let mut text = Vec::new();
+        let mut quote = 0;
+        let mut brackets = 0;
+
+        loop {
+            let ch = yylex_getc(ps);
+            if ch == libc::EOF || ch == '\n' as i32 {
+                if block {
+                    yyerror!(ps, "missing {{");
+                    return None;
+                }
+                return Some((text, ch == '\n' as i32));
+            }
+            if ch == '\\' as i32 && quote != '\'' as i32 {
+                text.push(ch as u8);
+                let ch = yylex_getc(ps);
+                if ch != libc::EOF {
+                    text.push(ch as u8);
+                }
+                continue;
+            }
+            if quote != 0 {
+                if ch == quote {
+                    quote = 0;
+                }
+            } else if ch == '\'' as i32 || ch == '"' as i32 {
+                quote = ch;
+            } else if ch == '#' as i32 {
+                let next = yylex_getc(ps);
+                if next == '{' as i32 {
+                    brackets += 1;
+                }
+                yylex_ungetc(ps, next);
+            } else if ch == '}' as i32 && brackets != 0 {
+                brackets -= 1;
+            } else if ch == '{' as i32 && block && brackets == 0 {
+                return Some((text, false));
+            }
+            text.push(ch as u8);
+        }
+    }
+}
+
+/// Read a directive body up to the matching }. Returns the text and the
+/// number of lines in it.
+unsafe fn yylex_directive_block(ps: &mut cmd_parse_state) -> Option<(Vec<u8>, u32)> {
+    unsafe {
+        let mut text = Vec::new();
+        let mut lines = 0;
+        let mut depth = 1;
+        let mut quote = 0;
+        let mut comment = false;
+        let mut start = true;
+
+        loop {
+            let ch = yylex_getc1(ps);
+            if ch == libc::EOF {
+                yyerror!(ps, "missing }}");
+                return None;
+            }
+            if ch == '\n' as i32 {
+                lines += 1;
+            }
+
+            if comment {
+                comment = ch != '\n' as i32;
+            } else if ch == '\\' as i32 && quote != '\'' as i32 {
+                text.push(ch as u8);
+                let ch = yylex_getc1(ps);
+                if ch == libc::EOF {
+                    continue;
+                }
+                if ch == '\n' as i32 {
+                    lines += 1;
+                }
+                text.push(ch as u8);
+                start = false;
+                continue;
+            } else if quote != 0 {
+                if ch == quote {
+                    quote = 0;
+                }
+            } else if ch == '\'' as i32 || ch == '"' as i32 {
+                quote = ch;
+            } else if ch == '#' as i32 && start {
+                let next = yylex_getc1(ps);
+                yylex_ungetc(ps, next);
+                comment = next != '{' as i32;
+            } else if ch == '{' as i32 {
+                depth += 1;
+            } else if ch == '}' as i32 {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((text, lines));
+                }
+            }
+            text.push(ch as u8);
+            start = b" \t\n;{}".contains(&(ch as u8));
+        }
+    }
+}
+
+/// Split the arguments of a directive into words. Each word is expanded like
+/// a token. A word which is a format is expanded and split at spaces and a
+/// word which is only a variable gives one word for each item.
+unsafe fn yylex_directive_words(ps: &mut cmd_parse_state, text: Vec<u8>) -> Option<Vec<String>> {
+    unsafe {
+        let line = yylex_get_line(ps);
+        if !yylex_push_frame(
+            ps,
+            text,
+            cmd_parse_frame_type::Words,
+            Vec::new(),
+            line,
+            line,
+        ) {
+            return None;
+        }
+        let words = yylex_directive_words1(ps);
+        ps.directives.as_mut().unwrap().frames.pop();
+        yylex_set_line(ps, line);
+        words
+    }
+}
+
+unsafe fn yylex_directive_words1(ps: &mut cmd_parse_state) -> Option<Vec<String>> {
+    unsafe {
+        let mut words = Vec::new();
+
+        loop {
+            let ch = yylex_getc(ps);
+            if ch == ' ' as i32 || ch == '\t' as i32 {
+                continue;
+            }
+            if ch == libc::EOF {
+                return Some(words);
+            }
+            if ch == ';' as i32 || ch == '{' as i32 || ch == '}' as i32 {
+                yyerror!(ps, "unexpected {}", ch as u8 as char);
+                return None;
+            }
+
+            if ch == '#' as i32 {
+                let next = yylex_getc(ps);
+                if next == '{' as i32 {
+                    let format = yylex_format(ps)?;
+                    let value = cmd_parse_expand(ps, format.as_ptr());
+                    free_(format.as_ptr());
+                    words.extend(cstr_to_str(value).split_whitespace().map(str::to_string));
+                    free_(value);
+                    continue;
+                }
+                yylex_ungetc(ps, next);
+            }
+
+            if ch == '$' as i32 {
+                let frame = yylex_frame(ps).unwrap();
+                let rest = &frame.text[frame.off..];
+                let (name, used) = match rest.strip_prefix(b"{") {
+                    Some(inner) => match inner.iter().position(|&c| c == b'}') {
+                        Some(end) => (&inner[..end], end + 2),
+                        None => (&inner[..0], 0),
+                    },
+                    None => {
+                        let end = rest
+                            .iter()
+                            .position(|&c| !c.is_ascii_alphanumeric() && c != b'_')
+                            .unwrap_or(rest.len());
+                        (&rest[..end], end)
+                    }
+                };
+                let after = rest.get(used).copied();
+                if !name.is_empty()
+                    && (after.is_none() || after == Some(b' ') || after == Some(b'\t'))
+                {
+                    let name = String::from_utf8_lossy(name).into_owned();
+                    if let Some(value) = cmd_parse_get_variable(ps, &name) {
+                        yylex_frame(ps).unwrap().off += used;
+                        words.extend(value);
+                        continue;
+                    }
+                }
+            }
+
+            let token = yylex_token(ps, ch);
+            if token.is_null() {
+                yyerror!(ps, "invalid argument");
+                return None;
+            }
+            words.push(cstr_to_str(token).to_string());
+            free_(token);
+        }
+    }
+}
+
+fn yylex_is_name(name: &str) -> bool {
+    let mut chars = name.bytes();
+    chars
+        .next()
+        .is_some_and(|ch| yylex_is_var(ch as c_char, true))
+        && chars.all(|ch| yylex_is_var(ch as c_char, false))
+}
+
+#[expect(clippy::deref_addrof)]
+unsafe fn cmd_parse_get_macro<'b>(
+    ps: &'b cmd_parse_state,
+    name: &str,
+) -> Option<&'b cmd_parse_macro> {
+    unsafe {
+        if let Some(found) = ps.directives.as_ref().and_then(|d| d.macros.get(name)) {
+            return Some(found);
+        }
+        (*&raw const cmd_parse_macros).get(name)
+    }
+}
+
+/// Run a directive other than the %if family. Returns the token to give the
+/// parser, or None to carry on reading.
+#[expect(clippy::deref_addrof)]
+unsafe fn yylex_directive(ps: &mut cmd_parse_state, name: &str) -> Option<Tok> {
+    unsafe {
+        let known = matches!(name, "set" | "for" | "while" | "define");
+        if !known && cmd_parse_get_macro(ps, name).is_none() {
+            return Some(Tok::Error);
+        }
+        if ps.skip_directives {
+            return Some(Tok::Directive);
+        }
+        let active = yylex_scope_true(ps);
+
+        match name {
+            "set" => {
+                let Some((text, eol)) = yylex_directive_line(ps, false) else {
+                    return Some(Tok::Error);
+                };
+                if active {
+                    let Some(mut words) = yylex_directive_words(ps, text) else {
+                        return Some(Tok::Error);
+                    };
+                    if words.is_empty() || !yylex_is_name(&words[0]) {
+                        yyerror!(ps, "%set needs a variable name");
+                        return Some(Tok::Error);
+                    }
+                    let variable = words.remove(0);
+                    cmd_parse_set_variable(ps, &variable, words);
+                }
+                ps.eol = eol as i32;
+                Some(Tok::Newline)
+            }
+            "for" | "while" | "define" => {
+                let Some((text, _)) = yylex_directive_line(ps, true) else {
+                    return Some(Tok::Error);
+                };
+                let line = yylex_get_line(ps);
+                let Some((body, lines)) = yylex_directive_block(ps) else {
+                    return Some(Tok::Error);
+                };
+                if !active {
+                    yylex_set_line(ps, line + lines);
+                    return None;
+                }
+
+                if name == "while" {
+                    let condition = String::from_utf8_lossy(&text).trim().to_string();
+                    let value =
+                        cmd_parse_expand(ps, CString::new(condition.as_str()).unwrap().as_ptr());
+                    let result = format_true(value) != 0;
+                    free_(value);
+                    if !result {
+                        yylex_set_line(ps, line + lines);
+                        return None;
+                    }
+                    let kind = cmd_parse_frame_type::While {
+                        condition,
+                        count: 0,
+                    };
+                    if !yylex_push_frame(ps, body, kind, Vec::new(), line, line + lines) {
+                        return Some(Tok::Error);
+                    }
+                    return None;
+                }
+
+                let Some(mut words) = yylex_directive_words(ps, text) else {
+                    return Some(Tok::Error);
+                };
+                if words.is_empty() || !yylex_is_name(&words[0]) {
+                    yyerror!(ps, "%{} needs a name", name);
+                    return Some(Tok::Error);
+                }
+                let variable = words.remove(0);
+
+                if name == "define" {
+                    for (i, parameter) in words.iter().enumerate() {
+                        let rest = parameter.strip_suffix("...");
+                        if !yylex_is_name(rest.unwrap_or(parameter))
+                            || (rest.is_some() && i != words.len() - 1)
+                        {
+                            yyerror!(ps, "bad parameter: {}", parameter);
+                            return Some(Tok::Error);
+                        }
+                    }
+                    let found = cmd_parse_macro {
+                        parameters: words,
+                        body,
+                        line,
+                    };
+                    if cmd_parse_only(ps) {
+                        let directives = ps.directives.get_or_insert_default();
+                        directives.macros.insert(variable, found);
+                    } else {
+                        (*&raw mut cmd_parse_macros).insert(variable, found);
+                    }
+                    yylex_set_line(ps, line + lines);
+                    return None;
+                }
+
+                if words.first().map(String::as_str) != Some("in") {
+                    yyerror!(ps, "%for needs in");
+                    return Some(Tok::Error);
+                }
+                words.remove(0);
+                if words.is_empty() {
+                    yylex_set_line(ps, line + lines);
+                    return None;
+                }
+                let variables = vec![(variable, vec![words[0].clone()])];
+                let kind = cmd_parse_frame_type::For {
+                    items: words,
+                    next: 1,
+                };
+                if !yylex_push_frame(ps, body, kind, variables, line, line + lines) {
+                    return Some(Tok::Error);
+                }
+                None
+            }
+            _ => {
+                let Some((text, eol)) = yylex_directive_line(ps, false) else {
+                    return Some(Tok::Error);
+                };
+                if !active {
+                    ps.eol = eol as i32;
+                    return Some(Tok::Newline);
+                }
+                let Some(mut words) = yylex_directive_words(ps, text) else {
+                    return Some(Tok::Error);
+                };
+
+                let found = cmd_parse_get_macro(ps, name).unwrap();
+                let mut variables = Vec::new();
+                for parameter in &found.parameters {
+                    if let Some(rest) = parameter.strip_suffix("...") {
+                        variables.push((rest.to_string(), std::mem::take(&mut words)));
+                        break;
+                    }
+                    if words.is_empty() {
+                        yyerror!(ps, "%{}: too few arguments", name);
+                        return Some(Tok::Error);
+                    }
+                    variables.push((parameter.clone(), vec![words.remove(0)]));
+                }
+                if !words.is_empty() {
+                    yyerror!(ps, "%{}: too many arguments", name);
+                    return Some(Tok::Error);
+                }
+
+                let body = found.body.clone();
+                let line = found.line;
+                let saved_line = yylex_get_line(ps) + eol as u32;
+                if !yylex_push_frame(
+                    ps,
+                    body,
+                    cmd_parse_frame_type::Macro,
+                    variables,
+                    line,
+                    saved_line,
+                ) {
+                    return Some(Tok::Error);
+                }
+                None
+            }
+        }
+    }
+}
+
 // # Notes:
 //
 // <https://matklad.github.io/2020/04/13/simple-but-powerful-pratt-parsing.html>
 // <https://github.com/lalrpop/lalrpop/blob/master/README.md>
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmd_parse_tokens() {
+        unsafe {
+            let buf = b"neww -n 'a b'; X=1 \"c";
+            let tokens = cmd_parse_tokens(buf);
+            let found: Vec<_> = tokens
+                .iter()
+                .map(|t| (t.type_, &buf[t.start..t.end], t.value.as_deref()))
+                .collect();
+            assert!(matches!(
+                found.as_slice(),
+                [
+                    (cmd_parse_token_type::Word, b"neww", Some("neww")),
+                    (cmd_parse_token_type::Word, b"-n", Some("-n")),
+                    (cmd_parse_token_type::Word, b"'a b'", Some("a b")),
+                    (cmd_parse_token_type::Separator, b";", None),
+                    (cmd_parse_token_type::Assignment, b"X=1", Some("X=1")),
+                    (cmd_parse_token_type::Word, b"\"c", Some("c")),
+                    (cmd_parse_token_type::Newline, b"", None),
+                ]
+            ));
+        }
+    }
+
+    #[test]
+    fn test_cmd_parse_directives() {
+        unsafe {
+            let buf = b"%set names a b\n\
+                %define m x rest... {\n  set -g @m-$x \"$rest\"\n}\n\
+                %for n in $names {\n  %m $n 1 2\n}\n";
+            let mut input: cmd_parse_input = zeroed();
+            input.flags = (cmd_parse_input_flags::CMD_PARSE_PARSEONLY
+                | cmd_parse_input_flags::CMD_PARSE_NOALIAS)
+                .into();
+            let cmdlist = cmd_parse_from_buffer(buf, Some(&input)).unwrap();
+            let printed = cmd_list_print(&mut *cmdlist, 0);
+            assert_eq!(
+                cstr_to_str(printed),
+                "set-option -g @m-a \"1 2\" ; set-option -g @m-b \"1 2\""
+            );
+            free_(printed);
+            cmd_list_free(cmdlist);
+            assert!((*&raw const cmd_parse_variables).is_empty());
+        }
+    }
+}
@@ -13,7 +13,7 @@ pub unsafe fn b64_ntop(
     let dst = unsafe { std::slice::from_raw_parts_mut(target.cast::<MaybeUninit<u8>>(), targsize) };
 
     match ntop(src, dst) {
-        Ok(out) => (out.len() - 1) as i32,
+        Ok(out) => out.len() as i32,
         Err(_) => -1,
     }
 }
@@ -28,7 +28,7 @@ pub unsafe fn b64_pton(src: *const c_char, target: *mut u8, targsize: usize) ->
     let dst = unsafe { std::slice::from_raw_parts_mut(target.cast::<MaybeUninit<u8>>(), targsize) };
 
     match pton(src, dst) {
-        Ok(out) => (out.len() - 1) as i32,
+        Ok(out) => out.len() as i32,
         Err(_) => -1,
     }
 }
@@ -47,11 +47,6 @@ const REVERSE: [u8; 80] = const {
 };
 
 fn pton<'out>(src: &'_ [u8], dst: &'out mut [MaybeUninit<u8>]) -> Result<&'out mut [u8], ()> {
-    // dst must be at least 3/4 of src, and room for NUL byte
-    if src.len().div_ceil(4) * 3 + 1 > dst.len() {
-        return Err(());
-    }
-
     let mut i = 0;
     let mut it = src.iter().cloned().filter(|b| !b.is_ascii_whitespace());
 
@@ -60,33 +55,48 @@ fn pton<'out>(src: &'_ [u8], dst: &'out mut [MaybeUninit<u8>]) -> Result<&'out m
             ch,
             it.next().ok_or(())?,
             it.next().ok_or(())?,
-            it.next().ok_or(())?, // TODO consider special handling for missing =
+            it.next().ok_or(())?,
         ];
 
-        for g in chunk {
-            if !matches!(g, b'A'..=b'Z' | b'a'..=b'z' | b'+' | b'/') {
+        // up to two trailing = pad the final group
+        let pad = chunk.iter().rev().take_while(|&&g| g == b'=').count();
+        if pad > 2 {
+            return Err(());
+        }
+
+        let mut v = [0u8; 4];
+        for (n, &g) in chunk[..4 - pad].iter().enumerate() {
+            if !matches!(g, b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'+' | b'/') {
                 return Err(());
             }
+            v[n] = REVERSE[(g - b'+') as usize];
         }
-
-        let a = REVERSE[(chunk[0] - b'+') as usize];
-        let b = REVERSE[(chunk[1] - b'+') as usize];
-        let c = REVERSE[(chunk[2] - b'+') as usize];
-        let d = REVERSE[(chunk[3] - b'+') as usize];
+        let [a, b, c, d] = v;
 
         //        a                 b                 c                 d
         // X X 0 0 0 0 0 0 | X X 0 0 0 0 0 0 | X X 0 0 0 0 0 0 | X X 0 0 0 0 0 0
         //
-        // ( a << 2  ) ( b >> 4 )    (b<<4) (    c >> 2    )     (c<<4)(      d       )
+        // ( a << 2  ) ( b >> 4 )    (b<<4) (    c >> 2    )     (c<<6)(      d       )
         // 0  0  0  0  0  0  0  0  |  0  0  0  0  0  0  0  0  |  0  0  0  0  0  0  0  0
         //
-        dst[i] = MaybeUninit::new((a << 2) | (b >> 4));
-        dst[i + 1] = MaybeUninit::new((b << 4) | (c >> 2));
-        dst[i + 2] = MaybeUninit::new((c << 4) | d);
-        i += 3;
+        let bytes = [(a << 2) | (b >> 4), (b << 4) | (c >> 2), (c << 6) | d];
+        let n = 3 - pad;
+        if i + n > dst.len() {
+            return Err(());
+        }
+        for (k, byte) in bytes[..n].iter().enumerate() {
+            dst[i + k] = MaybeUninit::new(*byte);
+        }
+        i += n;
+
+        if pad != 0 {
+            if it.next().is_some() {
+                return Err(());
+            }
+            break;
+        }
     }
 
-    dst[i] = MaybeUninit::new(0);
     Ok(unsafe { std::slice::from_raw_parts_mut(dst.as_mut_ptr().cast::<u8>(), i) })
 }
 
@@ -138,6 +148,46 @@ fn ntop<'out>(src: &'_ [u8], dst: &'out mut [MaybeUninit<u8>]) -> Result<&'out m
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_b64_ntop() {
+        let mut output = [0i8; 9];
+
+        unsafe {
+            let result = b64_ntop(b"Man".as_ptr(), 3, output.as_mut_ptr(), output.len());
+            assert_eq!(result, 4);
+            assert_eq!(crate::_s(output.as_ptr()).to_string(), "TWFu");
+
+            let result = b64_ntop(b"Ma".as_ptr(), 2, output.as_mut_ptr(), output.len());
+            assert_eq!(result, 4);
+            assert_eq!(crate::_s(output.as_ptr()).to_string(), "TWE=");
+
+            let result = b64_ntop(b"".as_ptr(), 0, output.as_mut_ptr(), output.len());
+            assert_eq!(result, 0);
+
+            let result = b64_ntop(b"Man".as_ptr(), 3, output.as_mut_ptr(), 4);
+            assert_eq!(result, -1);
+        }
+    }
+
+    #[test]
+    fn test_b64_ntop_round_trip() {
+        let src: Vec<u8> = (0..=255).collect();
+        let mut encoded = [0i8; 345];
+        let mut decoded = [0u8; 256];
+
+        unsafe {
+            for len in [0, 1, 2, 3, 4, 5, 100, 256] {
+                let result = b64_ntop(src.as_ptr(), len, encoded.as_mut_ptr(), encoded.len());
+                assert_eq!(result, libc::strlen(encoded.as_ptr()) as i32);
+                assert_eq!(result as usize, len.div_ceil(3) * 4);
+
+                let result = b64_pton(encoded.as_ptr(), decoded.as_mut_ptr(), decoded.len());
+                assert_eq!(result as usize, len);
+                assert_eq!(&decoded[..len], &src[..len]);
+            }
+        }
+    }
+
     #[test]
     fn test_b64_pton_valid() {
         let input = c"TWFu";
@@ -174,4 +224,31 @@ mod tests {
             assert_eq!(&output, &expected);
         }
     }
+
+    #[test]
+    fn test_b64_pton() {
+        let mut output = [0u8; 8];
+
+        unsafe {
+            // Digits, the low bits of the third character and whitespace.
+            let result = b64_pton(c"AQID\n BAUG".as_ptr(), output.as_mut_ptr(), output.len());
+            assert_eq!(result, 6);
+            assert_eq!(&output[..6], &[1, 2, 3, 4, 5, 6]);
+
+            let result = b64_pton(c"/w==".as_ptr(), output.as_mut_ptr(), output.len());
+            assert_eq!(result, 1);
+            assert_eq!(output[0], 0xff);
+
+            // The output may fill the target exactly, with no room for a NUL.
+            let result = b64_pton(c"TWFu".as_ptr(), output.as_mut_ptr(), 3);
+            assert_eq!(result, 3);
+            let result = b64_pton(c"TWFuTWE=".as_ptr(), output.as_mut_ptr(), 4);
+            assert_eq!(result, -1);
+
+            for bad in [c"TWF", c"T===", c"TW=u", c"TWE=TWFu", c"TWE=\n=", c"TW-E"] {
+                let result = b64_pton(bad.as_ptr(), output.as_mut_ptr(), output.len());
+                assert_eq!(result, -1, "{bad:?}");
+            }
+        }
+    }
 }
@@ -7,6 +7,7 @@ pub mod getprogname;
 pub mod imsg;
 pub mod imsg_buffer;
 pub mod queue;
+#[cfg(feature = "systemd")]
 pub mod systemd;
 pub mod tree;
 
@@ -31,7 +32,6 @@ pub use setproctitle::setproctitle_;
 pub use strlcat::strlcat;
 pub use strlcpy::strlcpy;
 pub use strtonum::strtonum;
-pub use systemd::systemd_create_socket;
 pub use unvis::strunvis;
 pub use vis::*;
 
@@ -1,11 +1,800 @@
-unsafe extern "C" {
-    pub fn systemd_create_socket(flags: i32, cause: *mut *mut core::ffi::c_char) -> i32;
+use core::ffi::{c_char, c_int};
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+use libc::{
+    AF_UNIX, F_GETFD, F_SETFD, FD_CLOEXEC, SO_ACCEPTCONN, SO_TYPE, SOCK_STREAM, SOL_SOCKET, fcntl,
+    getenv, getpid, getsockname, getsockopt, sockaddr_un, socklen_t, strcmp,
+};
+
+use crate::compat::strtonum;
+use crate::*;
+
+/// First file descriptor passed by systemd, see sd_listen_fds(3).
+const SD_LISTEN_FDS_START: c_int = 3;
+
+/// Number of sockets passed by systemd, or -1 if not yet checked. This is
+/// worked out once so the server still sees it after forking from the client.
+static mut systemd_listen_count: c_int = -1;
+
+/// Count the sockets passed in LISTEN_FDS if LISTEN_PID is this process. The
+/// variables are left in the environment and are removed from the child
+/// environment when panes are spawned.
+unsafe fn systemd_listen_fds() -> c_int {
+    unsafe {
+        if systemd_listen_count != -1 {
+            return systemd_listen_count;
+        }
+        systemd_listen_count = 0;
+
+        let pid = getenv(c"LISTEN_PID".as_ptr());
+        let fds = getenv(c"LISTEN_FDS".as_ptr());
+        if pid.is_null() || fds.is_null() {
+            return 0;
+        }
+        if strtonum(pid, 1, i32::MAX) != Ok(getpid()) {
+            return 0;
+        }
+        let Ok(n) = strtonum(fds, 1, i32::MAX - SD_LISTEN_FDS_START) else {
+            return 0;
+        };
+
+        for fd in SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + n {
+            let flags = fcntl(fd, F_GETFD);
+            if flags == -1 {
+                log_debug!("systemd socket {} is not open", fd);
+                return 0;
+            }
+            fcntl(fd, F_SETFD, flags | FD_CLOEXEC);
+        }
+        log_debug!("systemd passed {} sockets", n);
+
+        systemd_listen_count = n;
+        n
+    }
+}
+
+/// Was the server started by systemd socket activation?
+pub unsafe fn systemd_activated() -> c_int {
+    unsafe { (systemd_listen_fds() >= 1) as c_int }
+}
+
+/// Adopt the socket passed by systemd, or create one if there is none.
+pub unsafe fn systemd_create_socket(flags: client_flag, cause: *mut *mut c_char) -> c_int {
+    unsafe {
+        let fds = systemd_listen_fds();
+        if fds > 1 {
+            *cause = format_nul!("too many systemd sockets ({})", fds);
+            return -1;
+        }
+        if fds == 0 {
+            return server_create_socket(flags, cause);
+        }
+        let fd = SD_LISTEN_FDS_START;
+
+        let mut value: c_int = 0;
+        let mut len = size_of::<c_int>() as socklen_t;
+        if getsockopt(
+            fd,
+            SOL_SOCKET,
+            SO_TYPE,
+            (&raw mut value).cast(),
+            &raw mut len,
+        ) == -1
+            || value != SOCK_STREAM
+        {
+            *cause = format_nul!("systemd socket is not a stream socket");
+            return -1;
+        }
+        len = size_of::<c_int>() as socklen_t;
+        if getsockopt(
+            fd,
+            SOL_SOCKET,
+            SO_ACCEPTCONN,
+            (&raw mut value).cast(),
+            &raw mut len,
+        ) == -1
+            || value == 0
+        {
+            *cause = format_nul!("systemd socket is not listening");
+            return -1;
+        }
+
+        let mut sa: sockaddr_un = zeroed();
+        let mut addrlen = size_of::<sockaddr_un>() as socklen_t;
+        if getsockname(fd, (&raw mut sa).cast(), &raw mut addrlen) == -1 {
+            *cause = format_nul!("getsockname failed: {}", _s(strerror(errno!())));
+            return -1;
+        }
+        if sa.sun_family != AF_UNIX as _ {
+            *cause = format_nul!("systemd socket is not a unix socket");
+            return -1;
+        }
+
+        // Clients find the server from the socket path, so use the one
+        // systemd is listening on.
+        if sa.sun_path[0] != 0 && strcmp(sa.sun_path.as_ptr(), socket_path) != 0 {
+            log_debug!(
+                "using systemd socket {} instead of {}",
+                _s(sa.sun_path.as_ptr()),
+                _s(socket_path)
+            );
+            socket_path = xstrdup(sa.sun_path.as_ptr()).as_ptr();
+        }
+        setblocking(fd, 0);
+
+        fd
+    }
+}
+
+/// D-Bus message types.
+const DBUS_METHOD_CALL: u8 = 1;
+const DBUS_METHOD_RETURN: u8 = 2;
+const DBUS_ERROR: u8 = 3;
+const DBUS_SIGNAL: u8 = 4;
+
+/// D-Bus header fields.
+const DBUS_FIELD_PATH: u8 = 1;
+const DBUS_FIELD_INTERFACE: u8 = 2;
+const DBUS_FIELD_MEMBER: u8 = 3;
+const DBUS_FIELD_ERROR_NAME: u8 = 4;
+const DBUS_FIELD_REPLY_SERIAL: u8 = 5;
+const DBUS_FIELD_DESTINATION: u8 = 6;
+const DBUS_FIELD_SIGNATURE: u8 = 8;
+
+/// How long to wait for systemd to create a scope.
+const SYSTEMD_SCOPE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Little-endian D-Bus message writer. Alignment is relative to the start of
+/// the buffer, so a body must be written to its own writer.
+#[derive(Default)]
+struct systemd_bus_writer {
+    data: Vec<u8>,
+}
+
+impl systemd_bus_writer {
+    fn align(&mut self, n: usize) {
+        while self.data.len() % n != 0 {
+            self.data.push(0);
+        }
+    }
+
+    fn byte(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn uint32(&mut self, value: u32) {
+        self.align(4);
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn boolean(&mut self, value: bool) {
+        self.uint32(value as u32);
+    }
+
+    /// Write a string or an object path.
+    fn string(&mut self, value: &str) {
+        self.uint32(value.len() as u32);
+        self.data.extend_from_slice(value.as_bytes());
+        self.data.push(0);
+    }
+
+    fn signature(&mut self, value: &str) {
+        self.byte(value.len() as u8);
+        self.data.extend_from_slice(value.as_bytes());
+        self.data.push(0);
+    }
+
+    /// Write an array; the length does not include the padding before the
+    /// first element.
+    fn array(&mut self, align: usize, f: impl FnOnce(&mut Self)) {
+        self.uint32(0);
+        let at = self.data.len() - 4;
+        self.align(align);
+        let start = self.data.len();
+        f(self);
+        let len = (self.data.len() - start) as u32;
+        self.data[at..at + 4].copy_from_slice(&len.to_le_bytes());
+    }
+
+    /// Write a (sv) property.
+    fn property(&mut self, name: &str, signature: &str, f: impl FnOnce(&mut Self)) {
+        self.align(8);
+        self.string(name);
+        self.signature(signature);
+        f(self);
+    }
 }
 
-/*
-#[no_mangle]
-pub extern "C" fn systemd_create_socket(flags: i32, cause: *mut *mut core::ffi::c_char) -> i32 {
-    //
-    todo!()
+/// D-Bus message reader, either byte order.
+struct systemd_bus_reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    big: bool,
+}
+
+impl<'a> systemd_bus_reader<'a> {
+    fn align(&mut self, n: usize) -> Option<()> {
+        self.offset = self.offset.next_multiple_of(n);
+        (self.offset <= self.data.len()).then_some(())
+    }
+
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.offset.checked_add(n)?;
+        let bytes = self.data.get(self.offset..end)?;
+        self.offset = end;
+        Some(bytes)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn uint32(&mut self) -> Option<u32> {
+        self.align(4)?;
+        let bytes = self.bytes(4)?.try_into().ok()?;
+        Some(if self.big {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.uint32()? as usize;
+        let value = String::from_utf8_lossy(self.bytes(len)?).into_owned();
+        self.byte()?;
+        Some(value)
+    }
+
+    fn signature(&mut self) -> Option<String> {
+        let len = self.byte()? as usize;
+        let value = String::from_utf8_lossy(self.bytes(len)?).into_owned();
+        self.byte()?;
+        Some(value)
+    }
+}
+
+/// Received D-Bus message, with only the header fields used here.
+#[derive(Default)]
+struct systemd_bus_message {
+    type_: u8,
+    big: bool,
+    reply_serial: u32,
+    member: String,
+    error: String,
+    signature: String,
+    body: Vec<u8>,
+}
+
+impl systemd_bus_message {
+    fn reader(&self) -> systemd_bus_reader<'_> {
+        systemd_bus_reader {
+            data: &self.body,
+            offset: 0,
+            big: self.big,
+        }
+    }
+}
+
+/// Build a method call message.
+fn systemd_bus_method_call(
+    serial: u32,
+    destination: &str,
+    path: &str,
+    interface: &str,
+    member: &str,
+    signature: &str,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut w = systemd_bus_writer::default();
+    w.byte(b'l');
+    w.byte(DBUS_METHOD_CALL);
+    w.byte(0);
+    w.byte(1);
+    w.uint32(body.len() as u32);
+    w.uint32(serial);
+
+    let field = |w: &mut systemd_bus_writer, code, signature, value: &str| {
+        w.align(8);
+        w.byte(code);
+        w.signature(signature);
+        if signature == "g" {
+            w.signature(value);
+        } else {
+            w.string(value);
+        }
+    };
+    w.array(8, |w| {
+        field(w, DBUS_FIELD_PATH, "o", path);
+        field(w, DBUS_FIELD_INTERFACE, "s", interface);
+        field(w, DBUS_FIELD_MEMBER, "s", member);
+        field(w, DBUS_FIELD_DESTINATION, "s", destination);
+        if !signature.is_empty() {
+            field(w, DBUS_FIELD_SIGNATURE, "g", signature);
+        }
+    });
+    w.align(8);
+    w.data.extend_from_slice(body);
+
+    w.data
+}
+
+/// Parse one complete message, returning it and its size, or None if more
+/// data is needed.
+fn systemd_bus_parse(data: &[u8]) -> Result<Option<(systemd_bus_message, usize)>, String> {
+    if data.len() < 16 {
+        return Ok(None);
+    }
+    let big = match data[0] {
+        b'l' => false,
+        b'B' => true,
+        _ => return Err("bad D-Bus message".to_string()),
+    };
+    let mut r = systemd_bus_reader {
+        data,
+        offset: 4,
+        big,
+    };
+    let body_len = r.uint32().unwrap_or(0) as usize;
+    r.uint32();
+    let fields_len = r.uint32().unwrap_or(0) as usize;
+    let size = (16 + fields_len).next_multiple_of(8) + body_len;
+    if data.len() < size {
+        return Ok(None);
+    }
+
+    let mut msg = systemd_bus_message {
+        type_: data[1],
+        big,
+        ..Default::default()
+    };
+    let mut r = systemd_bus_reader {
+        data: &data[..16 + fields_len],
+        offset: 16,
+        big,
+    };
+    let bad = || "bad D-Bus header".to_string();
+    while r.align(8).is_some() && r.offset < r.data.len() {
+        let code = r.byte().ok_or_else(bad)?;
+        let signature = r.signature().ok_or_else(bad)?;
+        match signature.as_str() {
+            "u" => {
+                let value = r.uint32().ok_or_else(bad)?;
+                if code == DBUS_FIELD_REPLY_SERIAL {
+                    msg.reply_serial = value;
+                }
+            }
+            "s" | "o" => {
+                let value = r.string().ok_or_else(bad)?;
+                match code {
+                    DBUS_FIELD_MEMBER => msg.member = value,
+                    DBUS_FIELD_ERROR_NAME => msg.error = value,
+                    _ => (),
+                }
+            }
+            "g" => {
+                let value = r.signature().ok_or_else(bad)?;
+                if code == DBUS_FIELD_SIGNATURE {
+                    msg.signature = value;
+                }
+            }
+            _ => return Err(bad()),
+        }
+    }
+    msg.body = data[size - body_len..size].to_vec();
+
+    Ok(Some((msg, size)))
+}
+
+/// Connection to the session bus.
+struct systemd_bus {
+    stream: UnixStream,
+    serial: u32,
+    buffer: Vec<u8>,
+    deadline: Instant,
+    removed: Vec<(String, String)>,
+}
+
+/// Undo %-escaping in a D-Bus address value.
+fn systemd_bus_unescape(value: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut bytes = value.bytes();
+    while let Some(ch) = bytes.next() {
+        if ch == b'%' {
+            let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+            if let Some(ch) = std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                out.push(ch);
+            }
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// Connect to the first usable unix address in a D-Bus address list.
+fn systemd_bus_connect_address(addresses: &str) -> Option<UnixStream> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::SocketAddr;
+
+    for address in addresses.split(';') {
+        let Some(keys) = address.strip_prefix("unix:") else {
+            continue;
+        };
+        for key in keys.split(',') {
+            let stream = if let Some(path) = key.strip_prefix("path=") {
+                let path = systemd_bus_unescape(path);
+                UnixStream::connect(std::ffi::OsStr::from_bytes(&path))
+            } else if let Some(name) = key.strip_prefix("abstract=") {
+                let name = systemd_bus_unescape(name);
+                SocketAddr::from_abstract_name(name).and_then(|sa| UnixStream::connect_addr(&sa))
+            } else {
+                continue;
+            };
+            if let Ok(stream) = stream {
+                return Some(stream);
+            }
+        }
+    }
+    None
+}
+
+impl systemd_bus {
+    /// Connect and authenticate to the session bus, if there is one.
+    fn open() -> Result<Self, String> {
+        let stream = match std::env::var("DBUS_SESSION_BUS_ADDRESS") {
+            Ok(addresses) => systemd_bus_connect_address(&addresses),
+            Err(_) => std::env::var("XDG_RUNTIME_DIR")
+                .ok()
+                .and_then(|dir| UnixStream::connect(format!("{dir}/bus")).ok()),
+        };
+        let Some(stream) = stream else {
+            return Err("no session bus".to_string());
+        };
+
+        let mut bus = systemd_bus {
+            stream,
+            serial: 0,
+            buffer: Vec::new(),
+            deadline: Instant::now() + SYSTEMD_SCOPE_TIMEOUT,
+            removed: Vec::new(),
+        };
+
+        let uid = unsafe { libc::getuid() }.to_string();
+        let hex: String = uid.bytes().map(|ch| format!("{ch:02x}")).collect();
+        bus.write(format!("\0AUTH EXTERNAL {hex}\r\n").as_bytes())?;
+        loop {
+            if let Some(end) = bus.buffer.windows(2).position(|w| w == b"\r\n") {
+                if !bus.buffer.starts_with(b"OK ") {
+                    return Err("session bus authentication failed".to_string());
+                }
+                bus.buffer.drain(..end + 2);
+                break;
+            }
+            bus.fill()?;
+        }
+        bus.write(b"BEGIN\r\n")?;
+
+        bus.call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "Hello",
+            "",
+            &[],
+        )?;
+        Ok(bus)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.stream
+            .write_all(data)
+            .map_err(|e| format!("session bus write failed: {e}"))
+    }
+
+    /// Read more data, giving up at the deadline.
+    fn fill(&mut self) -> Result<(), String> {
+        let Some(left) = self.deadline.checked_duration_since(Instant::now()) else {
+            return Err("timed out".to_string());
+        };
+        self.stream
+            .set_read_timeout(Some(left.max(Duration::from_millis(1))))
+            .map_err(|e| e.to_string())?;
+
+        let mut data = [0u8; 4096];
+        match self.stream.read(&mut data) {
+            Ok(0) => Err("session bus closed".to_string()),
+            Ok(n) => {
+                self.buffer.extend_from_slice(&data[..n]);
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Err("timed out".to_string()),
+            Err(e) => Err(format!("session bus read failed: {e}")),
+        }
+    }
+
+    /// Read the next message. JobRemoved signals are remembered since they
+    /// may arrive before the reply which starts the job.
+    fn next(&mut self) -> Result<systemd_bus_message, String> {
+        loop {
+            if let Some((msg, size)) = systemd_bus_parse(&self.buffer)? {
+                self.buffer.drain(..size);
+                if msg.type_ == DBUS_SIGNAL && msg.member == "JobRemoved" && msg.signature == "uoss"
+                {
+                    let mut r = msg.reader();
+                    r.uint32();
+                    if let (Some(job), Some(_), Some(result)) = (r.string(), r.string(), r.string())
+                    {
+                        self.removed.push((job, result));
+                    }
+                }
+                return Ok(msg);
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Call a method and wait for its reply.
+    fn call(
+        &mut self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        member: &str,
+        signature: &str,
+        body: &[u8],
+    ) -> Result<systemd_bus_message, String> {
+        self.serial += 1;
+        let serial = self.serial;
+        let msg = systemd_bus_method_call(
+            serial,
+            destination,
+            path,
+            interface,
+            member,
+            signature,
+            body,
+        );
+        self.write(&msg)?;
+
+        loop {
+            let msg = self.next()?;
+            if msg.reply_serial != serial {
+                continue;
+            }
+            if msg.type_ == DBUS_ERROR {
+                let mut error = msg.error.clone();
+                if msg.signature.starts_with('s')
+                    && let Some(message) = msg.reader().string()
+                {
+                    error = format!("{error}: {message}");
+                }
+                return Err(error);
+            }
+            if msg.type_ == DBUS_METHOD_RETURN {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Wait for a job to finish.
+    fn wait(&mut self, job: &str) -> Result<(), String> {
+        loop {
+            if let Some((_, result)) = self.removed.iter().find(|(j, _)| j == job) {
+                if result != "done" {
+                    return Err(format!("job {result}"));
+                }
+                return Ok(());
+            }
+            self.next()?;
+        }
+    }
+}
+
+/// Find the slice of a process in the user manager.
+fn systemd_user_slice(pid: libc::pid_t) -> Option<String> {
+    let cgroup = std::fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    let path = cgroup.lines().find_map(|line| line.strip_prefix("0::"))?;
+
+    let mut user = path
+        .split('/')
+        .skip_while(|unit| !(unit.starts_with("user@") && unit.ends_with(".service")));
+    user.next()?;
+    user.filter(|unit| unit.ends_with(".slice"))
+        .last()
+        .map(str::to_string)
+}
+
+/// Build a name for a new scope.
+fn systemd_scope_name() -> String {
+    let mut uuid = [0u8; 16];
+    unsafe { libc::getrandom(uuid.as_mut_ptr().cast(), uuid.len(), 0) };
+    uuid[6] = (uuid[6] & 0x0f) | 0x40;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+
+    let mut name = String::from("tmux-spawn-");
+    for (i, byte) in uuid.iter().enumerate() {
+        if matches!(i, 4 | 6 | 8 | 10) {
+            name.push('-');
+        }
+        name.push_str(&format!("{byte:02x}"));
+    }
+    name.push_str(".scope");
+    name
+}
+
+/// Ask the user manager to start a transient scope for the new pane.
+fn systemd_start_scope(pid: libc::pid_t) -> Result<(), String> {
+    let mut bus = systemd_bus::open()?;
+
+    bus.call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "AddMatch",
+        "s",
+        &{
+            let mut w = systemd_bus_writer::default();
+            w.string(
+                "type='signal',sender='org.freedesktop.systemd1',\
+                 path='/org/freedesktop/systemd1',\
+                 interface='org.freedesktop.systemd1.Manager',member='JobRemoved'",
+            );
+            w.data
+        },
+    )?;
+
+    let parent = unsafe { getpid() };
+    let slice = systemd_user_slice(parent).unwrap_or_else(|| "app-tmux.slice".to_string());
+    let description = format!("tmux child pane {pid} launched by process {parent}");
+
+    let mut w = systemd_bus_writer::default();
+    w.string(&systemd_scope_name());
+    w.string("fail");
+    w.array(8, |w| {
+        w.property("Description", "s", |w| w.string(&description));
+        // Shells often ignore SIGTERM, so make sure they get SIGHUP.
+        w.property("SendSIGHUP", "b", |w| w.boolean(true));
+        w.property("Slice", "s", |w| w.string(&slice));
+        w.property("PIDs", "au", |w| w.array(4, |w| w.uint32(pid as u32)));
+        w.property("CollectMode", "s", |w| w.string("inactive-or-failed"));
+    });
+    w.array(8, |_| ());
+
+    let reply = bus
+        .call(
+            "org.freedesktop.systemd1",
+            "/org/freedesktop/systemd1",
+            "org.freedesktop.systemd1.Manager",
+            "StartTransientUnit",
+            "ssa(sv)a(sa(sv))",
+            &w.data,
+        )
+        .map_err(|e| format!("StartTransientUnit failed: {e}"))?;
+    let Some(job) = reply.reader().string() else {
+        return Err("StartTransientUnit returned no job".to_string());
+    };
+
+    bus.wait(&job)
+        .map_err(|e| format!("waiting for {job} failed: {e}"))
+}
+
+/// Move a new pane process into its own transient scope so systemd-oomd and
+/// friends can deal with it separately from the server.
+#[cfg(feature = "cgroups")]
+pub unsafe fn systemd_move_pid_to_new_cgroup(pid: libc::pid_t, cause: *mut *mut c_char) -> c_int {
+    match systemd_start_scope(pid) {
+        Ok(()) => 0,
+        Err(e) => {
+            unsafe { *cause = format_nul!("{}", e) };
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::fd::AsRawFd;
+    use std::os::unix::net::UnixListener;
+
+    /// Run with a listening socket passed as systemd would. This changes the
+    /// environment, the first systemd descriptor and the socket path, so it is
+    /// done in a child process where no other test can see them.
+    fn with_listen_fds(other_pid: bool, f: impl FnOnce(&std::path::Path)) {
+        unsafe {
+            let pid = libc::fork();
+            assert_ne!(pid, -1);
+            if pid == 0 {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    let path = std::env::temp_dir()
+                        .join(format!("tmux-rs-systemd-{}", std::process::id()));
+                    let _ = std::fs::remove_file(&path);
+                    let listener = UnixListener::bind(&path).unwrap();
+                    libc::dup2(listener.as_raw_fd(), SD_LISTEN_FDS_START);
+
+                    let listen_pid = getpid() + if other_pid { 1 } else { 0 };
+                    std::env::set_var("LISTEN_PID", listen_pid.to_string());
+                    std::env::set_var("LISTEN_FDS", "1");
+                    systemd_listen_count = -1;
+
+                    f(&path);
+                    let _ = std::fs::remove_file(&path);
+                }));
+                libc::_exit(if result.is_ok() { 0 } else { 1 });
+            }
+
+            let mut status = 0;
+            assert_eq!(libc::waitpid(pid, &raw mut status, 0), pid);
+            assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+        }
+    }
+
+    #[test]
+    fn test_systemd_create_socket() {
+        with_listen_fds(false, |path| unsafe {
+            assert_eq!(systemd_activated(), 1);
+            assert_ne!(fcntl(SD_LISTEN_FDS_START, F_GETFD) & FD_CLOEXEC, 0);
+
+            socket_path = c"/nonexistent".as_ptr();
+            let mut cause = null_mut();
+            let fd = systemd_create_socket(client_flag::empty(), &raw mut cause);
+            assert_eq!(fd, SD_LISTEN_FDS_START);
+            assert_eq!(
+                std::ffi::CStr::from_ptr(socket_path).to_str().unwrap(),
+                path.to_str().unwrap()
+            );
+
+            let _client = UnixStream::connect(path).unwrap();
+            assert_ne!(libc::accept(fd, null_mut(), null_mut()), -1);
+        });
+    }
+
+    #[test]
+    fn test_systemd_listen_fds_other_pid() {
+        with_listen_fds(true, |_| unsafe {
+            assert_eq!(systemd_activated(), 0);
+        });
+    }
+
+    #[test]
+    fn test_systemd_bus_message() {
+        let mut w = systemd_bus_writer::default();
+        w.string("name");
+        w.array(8, |w| {
+            w.property("PIDs", "au", |w| w.array(4, |w| w.uint32(42)))
+        });
+        let data =
+            systemd_bus_method_call(7, "dest", "/path", "iface", "Member", "sa(sv)", &w.data);
+        assert_eq!((data.len() - w.data.len()) % 8, 0);
+
+        let (msg, size) = systemd_bus_parse(&data).unwrap().unwrap();
+        assert_eq!(size, data.len());
+        assert_eq!(msg.type_, DBUS_METHOD_CALL);
+        assert_eq!(msg.member, "Member");
+        assert_eq!(msg.signature, "sa(sv)");
+
+        let mut r = msg.reader();
+        assert_eq!(r.string().as_deref(), Some("name"));
+        assert_eq!(r.uint32(), Some(24));
+        r.align(8);
+        assert_eq!(r.string().as_deref(), Some("PIDs"));
+        assert_eq!(r.signature().as_deref(), Some("au"));
+        assert_eq!(r.uint32(), Some(4));
+        assert_eq!(r.uint32(), Some(42));
+
+        assert!(
+            systemd_bus_parse(&data[..data.len() - 1])
+                .unwrap()
+                .is_none()
+        );
+    }
 }
-*/
@@ -15,6 +15,7 @@
 use crate::*;
 use std::cmp::Ordering;
 
+use crate::compat::b64::b64_ntop;
 use crate::compat::{
     queue::{tailq_empty, tailq_first, tailq_foreach, tailq_init, tailq_insert_tail, tailq_remove},
     tree::{rb_empty, rb_find, rb_foreach, rb_init, rb_insert, rb_remove},
@@ -121,6 +122,9 @@ pub struct control_state {
 
     pub subs: control_subs,
     pub subs_timer: event,
+
+    pub reply: *mut evbuffer,
+    pub reply_lines: u32,
 }
 
 /// Low and high watermarks.
@@ -345,7 +349,11 @@ pub unsafe fn control_continue_pane(c: *mut client, wp: *mut window_pane) {
             (*cp).flags &= !CONTROL_PANE_PAUSED;
             memcpy__(&raw mut (*cp).offset, &raw const (*wp).offset);
             memcpy__(&raw mut (*cp).queued, &raw const (*wp).offset);
-            control_write!(c, "%continue %{}", (*wp).id);
+            if (*c).flags.intersects(client_flag::CONTROL_JSON) {
+                control_write_json(c, "continue", &[("pane", control_json::Id(b'%', (*wp).id))]);
+            } else {
+                control_write!(c, "%continue %{}", (*wp).id);
+            }
         }
     }
 }
@@ -356,7 +364,7 @@ pub unsafe fn control_pause_pane(c: *mut client, wp: *mut window_pane) {
         if !(*cp).flags & CONTROL_PANE_PAUSED != 0 {
             (*cp).flags |= CONTROL_PANE_PAUSED;
             control_discard_pane(c, cp);
-            control_write!(c, "%pause %{}", (*wp).id);
+            control_write_pause(c, wp);
         }
     }
 }
@@ -365,22 +373,19 @@ pub unsafe fn control_vwrite(c: *mut client, args: std::fmt::Arguments) {
     unsafe {
         let cs = (*c).control_state;
 
-        let mut s = args.to_string();
-        s.push('\0');
-        let s = s.as_mut_ptr().cast();
+        let s = args.to_string();
 
         log_debug!(
             "{}: {}: writing line: {}",
             "control_vwrite",
             _s((*c).name),
-            _s(s)
+            s
         );
 
-        bufferevent_write((*cs).write_event, s.cast(), strlen(s));
+        bufferevent_write((*cs).write_event, s.as_ptr().cast(), s.len());
         bufferevent_write((*cs).write_event, c"\n".as_ptr().cast(), 1);
 
         bufferevent_enable((*cs).write_event, EV_WRITE);
-        free_(s);
     }
 }
 
@@ -417,6 +422,153 @@ pub unsafe fn control_write_(c: *mut client, args: std::fmt::Arguments) {
     }
 }
 
+/// A value in a JSON control mode line.
+pub enum control_json<'a> {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(&'a [u8]),
+    /// An id with its prefix, such as @1 or %2.
+    Id(u8, u32),
+    /// Text that is already JSON, such as a command id.
+    Raw(&'a str),
+}
+
+impl control_json<'_> {
+    /// A C string, or null if the pointer is null.
+    pub unsafe fn cstr(s: *const c_char) -> Self {
+        if s.is_null() {
+            control_json::Null
+        } else {
+            control_json::String(unsafe { CStr::from_ptr(s).to_bytes() })
+        }
+    }
+}
+
+/// Build a JSON control mode line with the given type and fields.
+pub fn control_json_line(type_: &str, fields: &[(&str, control_json)]) -> String {
+    let mut out = String::from("{\"type\":");
+    format_json_string(&mut out, type_.as_bytes());
+    for (key, value) in fields {
+        out.push(',');
+        format_json_string(&mut out, key.as_bytes());
+        out.push(':');
+        match value {
+            control_json::Null => out.push_str("null"),
+            control_json::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            control_json::Number(n) => out.push_str(&n.to_string()),
+            control_json::String(s) => format_json_string(&mut out, s),
+            control_json::Id(prefix, id) => {
+                out.push_str(&format!("\"{}{}\"", *prefix as char, id));
+            }
+            control_json::Raw(s) => out.push_str(s),
+        }
+    }
+    out.push('}');
+    out
+}
+
+/// Write a JSON line to a control client.
+pub unsafe fn control_write_json(c: *mut client, type_: &str, fields: &[(&str, control_json)]) {
+    unsafe {
+        control_write!(c, "{}", control_json_line(type_, fields));
+    }
+}
+
+/// Start or finish the reply to a command for a JSON control client. The
+/// output is collected and written as one line when the command finishes.
+pub unsafe fn control_reply(
+    c: *mut client,
+    guard: *const c_char,
+    t: time_t,
+    number: u32,
+    flags: bool,
+    tag: *const c_char,
+) {
+    unsafe {
+        let cs = (*c).control_state;
+
+        if libc::strcmp(guard, c"begin".as_ptr()) == 0 {
+            if !(*cs).reply.is_null() {
+                evbuffer_free((*cs).reply);
+            }
+            (*cs).reply = evbuffer_new();
+            if (*cs).reply.is_null() {
+                fatalx(c"out of memory");
+            }
+            (*cs).reply_lines = 0;
+
+            let id = if tag.is_null() {
+                control_json::Null
+            } else {
+                control_json::Raw(cstr_to_str(tag))
+            };
+            let mut line = control_json_line(
+                "reply",
+                &[
+                    ("id", id),
+                    ("time", control_json::Number(t)),
+                    ("number", control_json::Number(number as i64)),
+                    ("flags", control_json::Number(flags as i64)),
+                ],
+            );
+            line.pop();
+            line.push_str(",\"output\":[");
+            evbuffer_add((*cs).reply, line.as_ptr().cast(), line.len());
+            return;
+        }
+
+        if (*cs).reply.is_null() {
+            return;
+        }
+        let error = libc::strcmp(guard, c"error".as_ptr()) == 0;
+        evbuffer_add_printf!((*cs).reply, "],\"error\":{}}}", error);
+        control_write!(
+            c,
+            "{1:0$}",
+            EVBUFFER_LENGTH((*cs).reply),
+            _s(EVBUFFER_DATA((*cs).reply).cast::<c_char>()),
+        );
+        evbuffer_free((*cs).reply);
+        (*cs).reply = null_mut();
+    }
+}
+
+/// Print a line of command output to a control client.
+pub unsafe fn control_print(c: *mut client, data: *const c_char, len: usize) {
+    unsafe {
+        let cs = (*c).control_state;
+
+        if !(*c).flags.intersects(client_flag::CONTROL_JSON) {
+            control_write!(c, "{1:0$}", len, _s(data));
+            return;
+        }
+        let data = std::slice::from_raw_parts(data.cast::<u8>(), len);
+
+        if (*cs).reply.is_null() {
+            control_write_json(c, "print", &[("text", control_json::String(data))]);
+            return;
+        }
+        let mut line = String::new();
+        if (*cs).reply_lines != 0 {
+            line.push(',');
+        }
+        format_json_string(&mut line, data);
+        evbuffer_add((*cs).reply, line.as_ptr().cast(), line.len());
+        (*cs).reply_lines += 1;
+    }
+}
+
+unsafe fn control_write_pause(c: *mut client, wp: *mut window_pane) {
+    unsafe {
+        if (*c).flags.intersects(client_flag::CONTROL_JSON) {
+            control_write_json(c, "pause", &[("pane", control_json::Id(b'%', (*wp).id))]);
+        } else {
+            control_write!(c, "%pause %{}", (*wp).id);
+        }
+    }
+}
+
 pub unsafe fn control_check_age(
     c: *mut client,
     wp: *mut window_pane,
@@ -448,7 +600,7 @@ pub unsafe fn control_check_age(
             }
             (*cp).flags |= CONTROL_PANE_PAUSED;
             control_discard_pane(c, cp);
-            control_write!(c, "%pause %{}", (*wp).id);
+            control_write_pause(c, wp);
         } else {
             if age < CONTROL_MAXIMUM_AGE {
                 return 0;
@@ -540,8 +692,9 @@ pub unsafe fn control_error(item: *mut cmdq_item, data: *mut c_void) -> cmd_retv
         let c = cmdq_get_client(item);
         let error = data as *mut c_char;
 
+        let msg = format!("parse error: {}", _s(error));
         cmdq_guard(item, c"begin".as_ptr(), true);
-        control_write!(c, "parse error: {}", _s(error));
+        control_print(c, msg.as_ptr().cast(), msg.len());
         cmdq_guard(item, c"error".as_ptr(), true);
 
         free_(error);
@@ -582,11 +735,38 @@ pub unsafe extern "C" fn control_read_callback(bufev: *mut bufferevent, data: *m
                 break;
             }
 
+            let json = (*c).flags.intersects(client_flag::CONTROL_JSON);
+            let request = if json && *line == b'{' as c_char {
+                control_parse_json(CStr::from_ptr(line).to_bytes())
+            } else {
+                control_parse_line(cstr_to_str(line), json)
+            };
+
             let state =
                 cmdq_new_state(null_mut(), null_mut(), cmdq_state_flags::CMDQ_STATE_CONTROL);
-            let status = cmd_parse_and_append(cstr_to_str(line), None, c, state, &raw mut error);
+            let status = match request {
+                Ok(control_request::Command(command, tag)) => {
+                    if let Some(tag) = tag {
+                        (*state).tag = format_nul!("{}", tag);
+                    }
+                    cmd_parse_and_append(&command, None, c, state, &raw mut error)
+                }
+                Ok(control_request::Cancel(tag)) => {
+                    control_cancel(c, &tag);
+                    cmd_parse_status::CMD_PARSE_SUCCESS
+                }
+                Err(cause) => {
+                    error = format_nul!("{}", cause);
+                    cmd_parse_status::CMD_PARSE_ERROR
+                }
+            };
             if status == cmd_parse_status::CMD_PARSE_ERROR {
-                cmdq_append(c, cmdq_get_callback!(control_error, error).as_ptr());
+                let item = cmdq_get_callback!(control_error, error).as_ptr();
+                if !(*state).tag.is_null() {
+                    (*(*item).state).tag = xstrdup((*state).tag).as_ptr();
+                }
+                cmdq_append(c, item);
+                error = null_mut();
             }
             cmdq_free_state(state);
 
@@ -595,6 +775,229 @@ pub unsafe extern "C" fn control_read_callback(bufev: *mut bufferevent, data: *m
     }
 }
 
+/// A line read from a control client.
+#[derive(Debug, PartialEq)]
+enum control_request {
+    /// A command and the tag to echo in its reply.
+    Command(String, Option<String>),
+    /// Cancel queued commands with a tag.
+    Cancel(String),
+}
+
+/// Parse a line from a control client. A command may be given a tag with
+/// "%tag TAG command" and queued commands with a tag are removed with
+/// "%cancel TAG". For JSON clients the tag is encoded as a JSON string.
+fn control_parse_line(line: &str, json: bool) -> Result<control_request, &'static str> {
+    let mut words = line.splitn(3, ' ');
+    let (request, tag) = match words.next() {
+        Some("%tag") => {
+            let tag = words.next().filter(|tag| !tag.is_empty());
+            let command = words.next().filter(|command| !command.is_empty());
+            (
+                control_request::Command(command.ok_or("missing command")?.to_string(), None),
+                tag.ok_or("missing tag")?,
+            )
+        }
+        Some("%cancel") => {
+            let tag = words.next().filter(|tag| !tag.is_empty());
+            if words.next().is_some() {
+                return Err("too many arguments");
+            }
+            (
+                control_request::Cancel(String::new()),
+                tag.ok_or("missing tag")?,
+            )
+        }
+        _ => return Ok(control_request::Command(line.to_string(), None)),
+    };
+    if tag.bytes().any(|ch| !ch.is_ascii_graphic()) {
+        return Err("invalid tag");
+    }
+
+    let tag = if json {
+        let mut encoded = String::new();
+        format_json_string(&mut encoded, tag.as_bytes());
+        encoded
+    } else {
+        tag.to_string()
+    };
+    Ok(match request {
+        control_request::Command(command, _) => control_request::Command(command, Some(tag)),
+        control_request::Cancel(_) => control_request::Cancel(tag),
+    })
+}
+
+/// Remove queued commands with a tag and report how many there were.
+unsafe fn control_cancel(c: *mut client, tag: &str) {
+    unsafe {
+        let mut ctag = tag.to_string();
+        ctag.push('\0');
+        let count = cmdq_cancel(c, ctag.as_ptr().cast());
+
+        if (*c).flags.intersects(client_flag::CONTROL_JSON) {
+            control_write_json(
+                c,
+                "cancelled",
+                &[
+                    ("id", control_json::Raw(tag)),
+                    ("count", control_json::Number(count as i64)),
+                ],
+            );
+        } else {
+            control_write!(c, "%cancelled {} {}", tag, count);
+        }
+    }
+}
+
+/// Parse a line from a JSON control client, either a command such as
+/// {"id":1,"command":"list-windows"} or {"cancel":1}. The id may be a string
+/// or a number and is kept encoded as JSON.
+fn control_parse_json(line: &[u8]) -> Result<control_request, &'static str> {
+    fn skip(p: &mut &[u8]) {
+        while let [b' ' | b'\t' | b'\r' | b'\n', rest @ ..] = *p {
+            *p = rest;
+        }
+    }
+
+    fn expect(p: &mut &[u8], ch: u8) -> Result<(), &'static str> {
+        skip(p);
+        match p.split_first() {
+            Some((&next, rest)) if next == ch => {
+                *p = rest;
+                Ok(())
+            }
+            _ => Err("invalid JSON"),
+        }
+    }
+
+    fn hex(p: &mut &[u8]) -> Result<u16, &'static str> {
+        let digits = p.get(..4).ok_or("invalid JSON escape")?;
+        let digits = std::str::from_utf8(digits).map_err(|_| "invalid JSON escape")?;
+        let value = u16::from_str_radix(digits, 16).map_err(|_| "invalid JSON escape")?;
+        *p = &p[4..];
+        Ok(value)
+    }
+
+    fn string(p: &mut &[u8]) -> Result<String, &'static str> {
+        expect(p, b'"')?;
+        let mut out = Vec::new();
+        loop {
+            let (&ch, rest) = p.split_first().ok_or("unterminated JSON string")?;
+            *p = rest;
+            match ch {
+                b'"' => break,
+                b'\\' => {
+                    let (&ch, rest) = p.split_first().ok_or("invalid JSON escape")?;
+                    *p = rest;
+                    let ch = match ch {
+                        b'"' | b'\\' | b'/' => ch as char,
+                        b'b' => '\x08',
+                        b'f' => '\x0c',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut units = vec![hex(p)?];
+                            if (0xd800..0xdc00).contains(&units[0]) && p.starts_with(b"\\u") {
+                                *p = &p[2..];
+                                units.push(hex(p)?);
+                            }
+                            char::decode_utf16(units)
+                                .next()
+                                .and_then(Result::ok)
+                                .ok_or("invalid JSON escape")?
+                        }
+                        _ => return Err("invalid JSON escape"),
+                    };
+                    out.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                0..0x20 => return Err("invalid JSON string"),
+                _ => out.push(ch),
+            }
+        }
+        String::from_utf8(out).map_err(|_| "invalid UTF-8 in JSON string")
+    }
+
+    fn number(p: &mut &[u8]) -> Result<String, &'static str> {
+        let len = p
+            .iter()
+            .position(|ch| !matches!(ch, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+            .unwrap_or(p.len());
+        let token = std::str::from_utf8(&p[..len]).map_err(|_| "invalid JSON number")?;
+        token.parse::<f64>().map_err(|_| "invalid JSON number")?;
+        *p = &p[len..];
+        Ok(token.to_string())
+    }
+
+    let mut p = line;
+    let mut command = None;
+    let mut id = None;
+    let mut cancel = None;
+
+    expect(&mut p, b'{')?;
+    skip(&mut p);
+    if p.first() == Some(&b'}') {
+        p = &p[1..];
+    } else {
+        loop {
+            let key = string(&mut p)?;
+            expect(&mut p, b':')?;
+            skip(&mut p);
+            let value = match p.first() {
+                Some(b'"') => {
+                    let s = string(&mut p)?;
+                    if key == "command" {
+                        command = Some(s.clone());
+                    }
+                    let mut encoded = String::new();
+                    format_json_string(&mut encoded, s.as_bytes());
+                    encoded
+                }
+                Some(b'-' | b'0'..=b'9') => number(&mut p)?,
+                _ => {
+                    let literal = ["null", "true", "false"]
+                        .into_iter()
+                        .find(|literal| p.starts_with(literal.as_bytes()))
+                        .ok_or("unsupported JSON value")?;
+                    p = &p[literal.len()..];
+                    literal.to_string()
+                }
+            };
+            if key == "id" && value != "null" {
+                id = Some(value);
+            } else if key == "cancel" && value != "null" {
+                cancel = Some(value);
+            }
+
+            skip(&mut p);
+            match p.split_first() {
+                Some((b',', rest)) => p = rest,
+                Some((b'}', rest)) => {
+                    p = rest;
+                    break;
+                }
+                _ => return Err("invalid JSON"),
+            }
+        }
+    }
+    skip(&mut p);
+    if !p.is_empty() {
+        return Err("invalid JSON");
+    }
+
+    if let Some(cancel) = cancel {
+        if command.is_some() {
+            return Err("command and cancel given");
+        }
+        return Ok(control_request::Cancel(cancel));
+    }
+    let command = command.ok_or("missing command")?;
+    if command.contains('\0') {
+        return Err("invalid command");
+    }
+    Ok(control_request::Command(command, id))
+}
+
 pub unsafe fn control_all_done(c: *mut client) -> i32 {
     unsafe {
         let cs = (*c).control_state;
@@ -645,7 +1048,9 @@ pub unsafe fn control_append_data(
             if message.is_null() {
                 fatalx(c"out of memory");
             }
-            if (*c).flags.intersects(client_flag::CONTROL_PAUSEAFTER) {
+            if (*c).flags.intersects(client_flag::CONTROL_JSON) {
+                // The data is encoded when the message is written.
+            } else if (*c).flags.intersects(client_flag::CONTROL_PAUSEAFTER) {
                 evbuffer_add_printf!(message, "%extended-output %{} {} : ", (*wp).id, age);
             } else {
                 evbuffer_add_printf!(message, "%output %{} ", (*wp).id);
@@ -658,6 +1063,11 @@ pub unsafe fn control_append_data(
         if new_size < size {
             fatalx_!("not enough data: {} < {}", new_size, size);
         }
+        if (*c).flags.intersects(client_flag::CONTROL_JSON) {
+            evbuffer_add(message, new_data.cast(), size);
+            window_pane_update_used_data(wp, &raw mut (*cp).offset, size);
+            return message;
+        }
         for i in 0..size {
             if *new_data.add(i) < b' ' || *new_data.add(i) == b'\\' {
                 evbuffer_add_printf!(message, "\\{:03o}", *new_data.add(i) as i32);
@@ -670,10 +1080,31 @@ pub unsafe fn control_append_data(
     }
 }
 
-pub unsafe fn control_write_data(c: *mut client, message: *mut evbuffer) {
+pub unsafe fn control_write_data(c: *mut client, pane: u32, age: u64, message: *mut evbuffer) {
     unsafe {
         let cs = (*c).control_state;
 
+        if (*c).flags.intersects(client_flag::CONTROL_JSON) {
+            let size = EVBUFFER_LENGTH(message);
+            let mut data = vec![0 as c_char; size.div_ceil(3) * 4 + 1];
+            let len = b64_ntop(EVBUFFER_DATA(message), size, data.as_mut_ptr(), data.len());
+            let data = std::slice::from_raw_parts(data.as_ptr().cast::<u8>(), len as usize);
+
+            let mut fields = vec![("pane", control_json::Id(b'%', pane))];
+            if (*c).flags.intersects(client_flag::CONTROL_PAUSEAFTER) {
+                fields.push(("age", control_json::Number(age as i64)));
+            }
+            fields.push(("data", control_json::String(data)));
+            let line = control_json_line("output", &fields);
+
+            log_debug!("control_write_data: {}: {}", _s((*c).name), line);
+
+            bufferevent_write((*cs).write_event, line.as_ptr().cast(), line.len());
+            bufferevent_write((*cs).write_event, c"\n".as_ptr().cast(), 1);
+            evbuffer_free(message);
+            return;
+        }
+
         log_debug!(
             "control_write_data: {0}: {2:1$}",
             _s((*c).name),
@@ -691,6 +1122,7 @@ pub unsafe fn control_write_pending(c: *mut client, cp: *mut control_pane, limit
     unsafe {
         let cs = (*c).control_state;
         let mut message: *mut evbuffer = null_mut();
+        let mut message_age = 0;
         let mut used = 0;
         let mut size;
         let mut cb = null_mut();
@@ -735,6 +1167,9 @@ pub unsafe fn control_write_pending(c: *mut client, cp: *mut control_pane, limit
             }
             used += size;
 
+            if message.is_null() {
+                message_age = age;
+            }
             message = control_append_data(c, cp, age, message, transmute_ptr(wp), size);
 
             (*cb).size -= size;
@@ -745,7 +1180,7 @@ pub unsafe fn control_write_pending(c: *mut client, cp: *mut control_pane, limit
                 cb = tailq_first(&raw mut (*cs).all_blocks);
                 if !cb.is_null() && (*cb).size == 0 {
                     if wp.is_some() && !message.is_null() {
-                        control_write_data(c, message);
+                        control_write_data(c, (*cp).pane, message_age, message);
                         message = null_mut();
                     }
                     control_flush_all_blocks(c);
@@ -753,7 +1188,7 @@ pub unsafe fn control_write_pending(c: *mut client, cp: *mut control_pane, limit
             }
         }
         if !message.is_null() {
-            control_write_data(c, message);
+            control_write_data(c, (*cp).pane, message_age, message);
         }
         !tailq_empty(&raw mut (*cp).blocks) as i32
     }
@@ -894,10 +1329,75 @@ pub unsafe fn control_stop(c: *mut client) {
         }
         control_reset_offsets(c);
 
+        if !(*cs).reply.is_null() {
+            evbuffer_free((*cs).reply);
+        }
         free_(cs);
     }
 }
 
+/// Report a changed subscription value, for a window or pane if given.
+unsafe fn control_write_sub(
+    c: *mut client,
+    csub: *mut control_sub,
+    wl: *mut winlink,
+    wp: *mut window_pane,
+    value: *const c_char,
+) {
+    unsafe {
+        let s = (*c).session;
+
+        if (*c).flags.intersects(client_flag::CONTROL_JSON) {
+            let (window, index) = if wl.is_null() {
+                (control_json::Null, control_json::Null)
+            } else {
+                (
+                    control_json::Id(b'@', (*(*wl).window).id),
+                    control_json::Number((*wl).idx as i64),
+                )
+            };
+            let pane = if wp.is_null() {
+                control_json::Null
+            } else {
+                control_json::Id(b'%', (*wp).id)
+            };
+            control_write_json(
+                c,
+                "subscription-changed",
+                &[
+                    ("name", control_json::cstr((*csub).name)),
+                    ("session", control_json::Id(b'$', (*s).id)),
+                    ("window", window),
+                    ("index", index),
+                    ("pane", pane),
+                    ("value", control_json::cstr(value)),
+                ],
+            );
+            return;
+        }
+
+        let window = if wl.is_null() {
+            "- -".to_string()
+        } else {
+            format!("@{} {}", (*(*wl).window).id, (*wl).idx)
+        };
+        let pane = if wp.is_null() {
+            "-".to_string()
+        } else {
+            format!("%{}", (*wp).id)
+        };
+        control_write!(
+            c,
+            "%subscription-changed {} ${} {} {} : {}",
+            _s((*csub).name),
+            (*s).id,
+            window,
+            pane,
+            _s(value),
+        );
+    }
+}
+
 pub unsafe fn control_check_subs_session(c: *mut client, csub: *mut control_sub) {
     unsafe {
         let s = (*c).session;
@@ -910,13 +1410,7 @@ pub unsafe fn control_check_subs_session(c: *mut client, csub: *mut control_sub)
             free_(value);
             return;
         }
-        control_write!(
-            c,
-            "%subscription-changed {} ${} - - - : {}",
-            _s((*csub).name),
-            (*s).id,
-            _s(value),
-        );
+        control_write_sub(c, csub, null_mut(), null_mut(), value);
         free_((*csub).last);
         (*csub).last = value;
     }
@@ -957,16 +1451,7 @@ pub unsafe fn control_check_subs_pane(c: *mut client, csub: *mut control_sub) {
                 free_(value);
                 continue;
             }
-            control_write!(
-                c,
-                "%subscription-changed {} ${} @{} {} %{} : {}",
-                _s((*csub).name),
-                (*s).id,
-                (*w).id,
-                (*wl).idx,
-                (*wp).id,
-                _s(value),
-            );
+            control_write_sub(c, csub, wl, wp, value);
             free_((*csp).last);
             (*csp).last = value;
         }
@@ -1000,16 +1485,7 @@ pub unsafe fn control_check_subs_all_panes(c: *mut client, csub: *mut control_su
                     free_(value);
                     continue;
                 }
-                control_write!(
-                    c,
-                    "%subscription-changed {} ${} @{} {} %{} : {}",
-                    _s((*csub).name),
-                    (*s).id,
-                    (*w).id,
-                    (*wl).idx,
-                    (*wp).id,
-                    _s(value),
-                );
+                control_write_sub(c, csub, wl, wp, value);
                 free_((*csp).last);
                 (*csp).last = value;
             }
@@ -1053,15 +1529,7 @@ pub unsafe fn control_check_subs_window(c: *mut client, csub: *mut control_sub)
                 free_(value);
                 continue;
             }
-            control_write!(
-                c,
-                "%subscription-changed {} ${} @{} {} - : {}",
-                _s((*csub).name),
-                (*s).id,
-                (*w).id,
-                (*wl).idx,
-                _s(value),
-            );
+            control_write_sub(c, csub, wl, null_mut(), value);
             free_((*csw).last);
             (*csw).last = value;
         }
@@ -1095,15 +1563,7 @@ pub unsafe fn control_check_subs_all_windows(c: *mut client, csub: *mut control_
                 free_(value);
                 continue;
             }
-            control_write!(
-                c,
-                "%subscription-changed {} ${} @{} {} - : {}",
-                _s((*csub).name),
-                (*s).id,
-                (*w).id,
-                (*wl).idx,
-                _s(value),
-            );
+            control_write_sub(c, csub, wl, null_mut(), value);
             free_((*csw).last);
             (*csw).last = value;
         }
@@ -1196,3 +1656,124 @@ pub unsafe fn control_remove_sub(c: *mut client, name: *mut c_char) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_vwrite() {
+        unsafe {
+            let mut fds = [0i32; 2];
+            assert_eq!(
+                libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()),
+                0
+            );
+            event_init();
+
+            let mut cs: control_state = zeroed();
+            tailq_init(&raw mut cs.all_blocks);
+            cs.write_event = bufferevent_new(fds[0], None, None, None, null_mut());
+            let mut c: client = zeroed();
+            c.name = c"test".as_ptr();
+            c.control_state = &raw mut cs;
+
+            // Each line is written with its newline and nothing else.
+            control_write!(&raw mut c, "%begin {} {}", 1, 2);
+            control_write!(&raw mut c, "");
+            control_write!(&raw mut c, "%end");
+
+            let output = bufferevent_get_output(cs.write_event);
+            let size = evbuffer_get_length(output);
+            let data = evbuffer_pullup(output, size as isize);
+            assert_eq!(
+                std::slice::from_raw_parts(data, size),
+                b"%begin 1 2\n\n%end\n"
+            );
+
+            bufferevent_free(cs.write_event);
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    #[test]
+    fn test_control_parse_json() {
+        assert_eq!(
+            control_parse_json(br#"{"id":1,"command":"list-windows"}"#),
+            Ok(control_request::Command(
+                "list-windows".to_string(),
+                Some("1".to_string())
+            ))
+        );
+        assert_eq!(
+            control_parse_json(
+                br#" { "command" : "display -p \"\u00e9\ud83d\ude00\"" , "id" : "a\"b" } "#
+            ),
+            Ok(control_request::Command(
+                "display -p \"\u{e9}\u{1f600}\"".to_string(),
+                Some(r#""a\"b""#.to_string())
+            ))
+        );
+        assert_eq!(
+            control_parse_json(br#"{"command":"kill-server","id":null}"#),
+            Ok(control_request::Command("kill-server".to_string(), None))
+        );
+        assert_eq!(
+            control_parse_json(br#"{"cancel":"x"}"#),
+            Ok(control_request::Cancel(r#""x""#.to_string()))
+        );
+        assert!(control_parse_json(br#"{"cancel":1,"command":"a"}"#).is_err());
+        assert!(control_parse_json(br#"{"id":1}"#).is_err());
+        assert!(control_parse_json(br#"{"command":"a","id":[1]}"#).is_err());
+        assert!(control_parse_json(br#"{"command":"a"} x"#).is_err());
+        assert!(control_parse_json(br#"{"command":"a\u0000"}"#).is_err());
+    }
+
+    #[test]
+    fn test_control_parse_line() {
+        assert_eq!(
+            control_parse_line("new-window -d", false),
+            Ok(control_request::Command("new-window -d".to_string(), None))
+        );
+        assert_eq!(
+            control_parse_line("%tag t1 display -p 'a b'", false),
+            Ok(control_request::Command(
+                "display -p 'a b'".to_string(),
+                Some("t1".to_string())
+            ))
+        );
+        assert_eq!(
+            control_parse_line("%tag t1 list-windows", true),
+            Ok(control_request::Command(
+                "list-windows".to_string(),
+                Some(r#""t1""#.to_string())
+            ))
+        );
+        assert_eq!(
+            control_parse_line("%cancel t1", false),
+            Ok(control_request::Cancel("t1".to_string()))
+        );
+        assert!(control_parse_line("%tag t1", false).is_err());
+        assert!(control_parse_line("%tag  list-windows", false).is_err());
+        assert!(control_parse_line("%cancel t1 t2", false).is_err());
+    }
+
+    #[test]
+    fn test_control_json_line() {
+        assert_eq!(
+            control_json_line(
+                "output",
+                &[
+                    ("pane", control_json::Id(b'%', 1)),
+                    ("age", control_json::Number(-2)),
+                    ("data", control_json::String(b"a\"\n")),
+                    ("id", control_json::Raw("\"x\"")),
+                    ("error", control_json::Bool(false)),
+                    ("window", control_json::Null),
+                ],
+            ),
+            r#"{"type":"output","pane":"%1","age":-2,"data":"a\"\n","id":"x","error":false,"window":null}"#
+        );
+    }
+}
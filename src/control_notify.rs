@@ -23,6 +23,12 @@ macro_rules! CONTROL_SHOULD_NOTIFY_CLIENT {
     };
 }
 
+macro_rules! CONTROL_JSON {
+    ($c:expr) => {
+        (*$c).flags.intersects(client_flag::CONTROL_JSON)
+    };
+}
+
 pub unsafe fn control_notify_pane_mode_changed(pane: c_int) {
     unsafe {
         for c in tailq_foreach(&raw mut clients).map(NonNull::as_ptr) {
@@ -31,7 +37,12 @@ pub unsafe fn control_notify_pane_mode_changed(pane: c_int) {
                     continue;
                 }
 
-                control_write!(c, "%pane-mode-changed %{}", pane);
+                if CONTROL_JSON!(c) {
+                    let pane = control_json::Id(b'%', pane as u32);
+                    control_write_json(c, "pane-mode-changed", &[("pane", pane)]);
+                } else {
+                    control_write!(c, "%pane-mode-changed %{}", pane);
+                }
             }
         }
     }
@@ -60,6 +71,28 @@ pub unsafe fn control_notify_window_layout_changed(w: *mut window) {
                 }
 
                 if let Some(wl) = winlink_find_by_window(&raw mut (*s).windows, w) {
+                    if CONTROL_JSON!(c) {
+                        let ft = format_create_defaults(null_mut(), c, s, wl.as_ptr(), null_mut());
+                        let layout = format_expand(ft, c"#{window_layout}".as_ptr());
+                        let visible_layout =
+                            format_expand(ft, c"#{window_visible_layout}".as_ptr());
+                        let flags = format_expand(ft, c"#{window_raw_flags}".as_ptr());
+                        control_write_json(
+                            c,
+                            "layout-change",
+                            &[
+                                ("window", control_json::Id(b'@', (*w).id)),
+                                ("layout", control_json::cstr(layout)),
+                                ("visible_layout", control_json::cstr(visible_layout)),
+                                ("flags", control_json::cstr(flags)),
+                            ],
+                        );
+                        free_(layout);
+                        free_(visible_layout);
+                        free_(flags);
+                        format_free(ft);
+                        continue;
+                    }
                     let cp =
                         format_single(null_mut(), template, c, null_mut(), wl.as_ptr(), null_mut());
                     control_write!(c, "{}", _s(cp));
@@ -78,12 +111,23 @@ pub unsafe fn control_notify_window_pane_changed(w: *mut window) {
                     continue;
                 }
 
-                control_write!(
-                    c,
-                    "%window-pane-changed @{} %{}",
-                    (*w).id,
-                    (*(*w).active).id,
-                );
+                if CONTROL_JSON!(c) {
+                    control_write_json(
+                        c,
+                        "window-pane-changed",
+                        &[
+                            ("window", control_json::Id(b'@', (*w).id)),
+                            ("pane", control_json::Id(b'%', (*(*w).active).id)),
+                        ],
+                    );
+                } else {
+                    control_write!(
+                        c,
+                        "%window-pane-changed @{} %{}",
+                        (*w).id,
+                        (*(*w).active).id,
+                    );
+                }
             }
         }
     }
@@ -98,7 +142,15 @@ pub unsafe fn control_notify_window_unlinked(s: *mut session, w: *mut window) {
                 }
                 let cs = (*c).session;
 
-                if !winlink_find_by_window_id(&raw mut (*cs).windows, (*w).id).is_null() {
+                let linked = !winlink_find_by_window_id(&raw mut (*cs).windows, (*w).id).is_null();
+                if CONTROL_JSON!(c) {
+                    let type_ = if linked {
+                        "window-close"
+                    } else {
+                        "unlinked-window-close"
+                    };
+                    control_write_json(c, type_, &[("window", control_json::Id(b'@', (*w).id))]);
+                } else if linked {
                     control_write!(c, "%window-close @{}", (*w).id);
                 } else {
                     control_write!(c, "%unlinked-window-close @{}", (*w).id);
@@ -117,7 +169,15 @@ pub unsafe fn control_notify_window_linked(s: *mut session, w: *mut window) {
                 }
                 let cs = (*c).session;
 
-                if !winlink_find_by_window_id(&raw mut (*cs).windows, (*w).id).is_null() {
+                let linked = !winlink_find_by_window_id(&raw mut (*cs).windows, (*w).id).is_null();
+                if CONTROL_JSON!(c) {
+                    let type_ = if linked {
+                        "window-add"
+                    } else {
+                        "unlinked-window-add"
+                    };
+                    control_write_json(c, type_, &[("window", control_json::Id(b'@', (*w).id))]);
+                } else if linked {
                     control_write!(c, "%window-add @{}", (*w).id);
                 } else {
                     control_write!(c, "%unlinked-window-add @{}", (*w).id);
@@ -136,7 +196,22 @@ pub unsafe fn control_notify_window_renamed(w: *mut window) {
                 }
                 let cs = (*c).session;
 
-                if !winlink_find_by_window_id(&raw mut (*cs).windows, (*w).id).is_null() {
+                let linked = !winlink_find_by_window_id(&raw mut (*cs).windows, (*w).id).is_null();
+                if CONTROL_JSON!(c) {
+                    let type_ = if linked {
+                        "window-renamed"
+                    } else {
+                        "unlinked-window-renamed"
+                    };
+                    control_write_json(
+                        c,
+                        type_,
+                        &[
+                            ("window", control_json::Id(b'@', (*w).id)),
+                            ("name", control_json::cstr((*w).name)),
+                        ],
+                    );
+                } else if linked {
                     control_write!(c, "%window-renamed @{} {}", (*w).id, _s((*w).name));
                 } else {
                     control_write!(c, "%unlinked-window-renamed @{} {}", (*w).id, _s((*w).name),);
@@ -159,7 +234,27 @@ pub unsafe fn control_notify_client_session_changed(cc: *mut client) {
                     continue;
                 }
 
-                if cc == c {
+                if CONTROL_JSON!(c) {
+                    let session = control_json::Id(b'$', (*s).id);
+                    let name = control_json::cstr((*s).name);
+                    if cc == c {
+                        control_write_json(
+                            c,
+                            "session-changed",
+                            &[("session", session), ("name", name)],
+                        );
+                    } else {
+                        control_write_json(
+                            c,
+                            "client-session-changed",
+                            &[
+                                ("client", control_json::cstr((*cc).name)),
+                                ("session", session),
+                                ("name", name),
+                            ],
+                        );
+                    }
+                } else if cc == c {
                     control_write!(c, "%session-changed ${} {}", (*s).id, _s((*s).name));
                 } else {
                     control_write!(
@@ -180,7 +275,12 @@ pub unsafe fn control_notify_client_detached(cc: *mut client) {
         for c in tailq_foreach(&raw mut clients).map(NonNull::as_ptr) {
             {
                 if CONTROL_SHOULD_NOTIFY_CLIENT!(c) {
-                    control_write!(c, "%client-detached {}", _s((*cc).name));
+                    if CONTROL_JSON!(c) {
+                        let client = control_json::cstr((*cc).name);
+                        control_write_json(c, "client-detached", &[("client", client)]);
+                    } else {
+                        control_write!(c, "%client-detached {}", _s((*cc).name));
+                    }
                 }
             }
         }
@@ -195,7 +295,18 @@ pub unsafe fn control_notify_session_renamed(s: *mut session) {
                     continue;
                 }
 
-                control_write!(c, "%session-renamed ${} {}", (*s).id, _s((*s).name));
+                if CONTROL_JSON!(c) {
+                    control_write_json(
+                        c,
+                        "session-renamed",
+                        &[
+                            ("session", control_json::Id(b'$', (*s).id)),
+                            ("name", control_json::cstr((*s).name)),
+                        ],
+                    );
+                } else {
+                    control_write!(c, "%session-renamed ${} {}", (*s).id, _s((*s).name));
+                }
             }
         }
     }
@@ -209,7 +320,11 @@ pub unsafe fn control_notify_session_created(_: *mut session) {
                     continue;
                 }
 
-                control_write!(c, "%sessions-changed");
+                if CONTROL_JSON!(c) {
+                    control_write_json(c, "sessions-changed", &[]);
+                } else {
+                    control_write!(c, "%sessions-changed");
+                }
             }
         }
     }
@@ -223,7 +338,11 @@ pub unsafe fn control_notify_session_closed(_: *mut session) {
                     continue;
                 }
 
-                control_write!(c, "%sessions-changed");
+                if CONTROL_JSON!(c) {
+                    control_write_json(c, "sessions-changed", &[]);
+                } else {
+                    control_write!(c, "%sessions-changed");
+                }
             }
         }
     }
@@ -237,12 +356,23 @@ pub unsafe fn control_notify_session_window_changed(s: *mut session) {
                     continue;
                 }
 
-                control_write!(
-                    c,
-                    "%session-window-changed ${} @{}",
-                    (*s).id,
-                    (*(*(*s).curw).window).id,
-                );
+                if CONTROL_JSON!(c) {
+                    control_write_json(
+                        c,
+                        "session-window-changed",
+                        &[
+                            ("session", control_json::Id(b'$', (*s).id)),
+                            ("window", control_json::Id(b'@', (*(*(*s).curw).window).id)),
+                        ],
+                    );
+                } else {
+                    control_write!(
+                        c,
+                        "%session-window-changed ${} @{}",
+                        (*s).id,
+                        (*(*(*s).curw).window).id,
+                    );
+                }
             }
         }
     }
@@ -256,7 +386,12 @@ pub unsafe fn control_notify_paste_buffer_changed(name: *const c_char) {
                     continue;
                 }
 
-                control_write!(c, "%paste-buffer-changed {}", _s(name));
+                if CONTROL_JSON!(c) {
+                    let name = control_json::cstr(name);
+                    control_write_json(c, "paste-buffer-changed", &[("name", name)]);
+                } else {
+                    control_write!(c, "%paste-buffer-changed {}", _s(name));
+                }
             }
         }
     }
@@ -270,7 +405,12 @@ pub unsafe fn control_notify_paste_buffer_deleted(name: *const c_char) {
                     continue;
                 }
 
-                control_write!(c, "%paste-buffer-deleted {}", _s(name));
+                if CONTROL_JSON!(c) {
+                    let name = control_json::cstr(name);
+                    control_write_json(c, "paste-buffer-deleted", &[("name", name)]);
+                } else {
+                    control_write!(c, "%paste-buffer-deleted {}", _s(name));
+                }
             }
         }
     }
@@ -17,8 +17,8 @@ use crate::*;
 use std::cmp::Ordering;
 
 use libc::{
-    FNM_CASEFOLD, REG_NOSUB, ctime_r, getpwuid, getuid, ispunct, localtime_r, memcpy, regcomp,
-    regex_t, regexec, regfree, strchr, strcmp, strcspn, strftime, strstr, strtod, tm,
+    FNM_CASEFOLD, ctime_r, getpwuid, getuid, ispunct, localtime_r, memcpy, strchr, strcmp, strcspn,
+    strftime, strstr, strtod, tm,
 };
 
 use crate::{
@@ -569,7 +569,7 @@ pub unsafe fn format_cb_host_short(ft: *mut format_tree) -> *mut c_void {
 
 /// Callback for pid.
 pub unsafe fn format_cb_pid(ft: *mut format_tree) -> *mut c_void {
-    unsafe { libc::getpid().to_string().leak().as_mut_ptr().cast() }
+    format_nul!("{}", std::process::id()).cast()
 }
 
 /// Callback for session_attached_list.
@@ -952,9 +952,9 @@ pub unsafe fn format_cb_history_bytes(ft: *mut format_tree) -> *mut c_void {
         let mut size: usize = 0;
 
         for i in 0..((*gd).hsize + (*gd).sy) {
-            let gl = grid_get_line(gd, i);
-            size += (*gl).cellsize as usize * std::mem::size_of::<grid_cell>();
-            size += (*gl).extdsize as usize * std::mem::size_of::<grid_cell>();
+            let (cellsize, extdsize) = grid_line_sizes(gd, i);
+            size += cellsize as usize * std::mem::size_of::<grid_cell>();
+            size += extdsize as usize * std::mem::size_of::<grid_cell>();
         }
         size += ((*gd).hsize + (*gd).sy) as usize * std::mem::size_of::<grid_line>();
 
@@ -977,9 +977,9 @@ pub unsafe fn format_cb_history_all_bytes(ft: *mut format_tree) -> *mut c_void {
         let mut extended_cells = 0;
 
         for i in 0..lines {
-            let gl = grid_get_line(gd, i);
-            cells += (*gl).cellsize;
-            extended_cells += (*gl).extdsize;
+            let (cellsize, extdsize) = grid_line_sizes(gd, i);
+            cells += cellsize;
+            extended_cells += extdsize;
         }
 
         format_nul!(
@@ -1952,6 +1952,16 @@ pub unsafe fn format_cb_pane_active(ft: *mut format_tree) -> *mut c_void {
     }
 }
 
+/// Callback for pane_activity_rate.
+pub unsafe fn format_cb_pane_activity_rate(ft: *mut format_tree) -> *mut c_void {
+    unsafe {
+        if !(*ft).wp.is_null() {
+            return format_printf!("{}", window_pane_activity_rate((*ft).wp)).cast();
+        }
+        null_mut()
+    }
+}
+
 /// Callback for pane_at_left.
 pub unsafe fn format_cb_pane_at_left(ft: *mut format_tree) -> *mut c_void {
     unsafe {
@@ -2067,6 +2077,16 @@ pub unsafe fn format_cb_pane_height(ft: *mut format_tree) -> *mut c_void {
     }
 }
 
+/// Callback for pane_history_bytes.
+pub unsafe fn format_cb_pane_history_bytes(ft: *mut format_tree) -> *mut c_void {
+    unsafe {
+        if !(*ft).wp.is_null() {
+            return format_printf!("{}", grid_history_bytes((*(*ft).wp).base.grid)).cast();
+        }
+        null_mut()
+    }
+}
+
 /// Callback for pane_id.
 pub unsafe fn format_cb_pane_id(ft: *mut format_tree) -> *mut c_void {
     unsafe {
@@ -2124,6 +2144,10 @@ pub unsafe fn format_cb_pane_unseen_changes(ft: *mut format_tree) -> *mut c_void
 pub unsafe fn format_cb_pane_key_mode(ft: *mut format_tree) -> *mut c_void {
     unsafe {
         if !(*ft).wp.is_null() && !(*(*ft).wp).screen.is_null() {
+            let kflags = screen_kitty_keys_flags((*(*ft).wp).screen);
+            if kflags != 0 {
+                return format_nul!("Kitty {}", kflags).cast();
+            }
             match (*(*(*ft).wp).screen).mode & EXTENDED_KEY_MODES {
                 mode_flag::MODE_KEYS_EXTENDED => return xstrdup(c"Ext 1".as_ptr()).as_ptr().cast(),
                 mode_flag::MODE_KEYS_EXTENDED_2 => {
@@ -2149,6 +2173,26 @@ pub unsafe fn format_cb_pane_last(ft: *mut format_tree) -> *mut c_void {
     }
 }
 
+/// Callback for pane_last_command.
+pub unsafe fn format_cb_pane_last_command(ft: *mut format_tree) -> *mut c_void {
+    unsafe {
+        if !(*ft).wp.is_null() && !(*(*ft).wp).last_command.is_null() {
+            return xstrdup((*(*ft).wp).last_command).as_ptr().cast();
+        }
+        null_mut()
+    }
+}
+
+/// Callback for pane_last_exit_status.
+pub unsafe fn format_cb_pane_last_exit_status(ft: *mut format_tree) -> *mut c_void {
+    unsafe {
+        if !(*ft).wp.is_null() && (*(*ft).wp).last_status != -1 {
+            return format_printf!("{}", (*(*ft).wp).last_status).cast();
+        }
+        null_mut()
+    }
+}
+
 /// Callback for pane_left.
 pub unsafe fn format_cb_pane_left(ft: *mut format_tree) -> *mut c_void {
     unsafe {
@@ -2957,6 +3001,8 @@ pub unsafe fn format_cb_user(_ft: *mut format_tree) -> *mut c_void {
 pub enum format_table_type {
     FORMAT_TABLE_STRING,
     FORMAT_TABLE_TIME,
+    FORMAT_TABLE_NUMBER,
+    FORMAT_TABLE_FLAG,
 }
 
 /// Format table entry.
@@ -2987,178 +3033,182 @@ impl format_table_entry {
  * here. Only variables which are added by the caller go into the tree.
  */
 #[rustfmt::skip]
-static format_table: [format_table_entry ; 171] = [
-    format_table_entry::new(c"active_window_index", format_table_type::FORMAT_TABLE_STRING, format_cb_active_window_index),
-     format_table_entry::new(c"alternate_on", format_table_type::FORMAT_TABLE_STRING, format_cb_alternate_on),
-     format_table_entry::new(c"alternate_saved_x", format_table_type::FORMAT_TABLE_STRING, format_cb_alternate_saved_x),
-     format_table_entry::new(c"alternate_saved_y", format_table_type::FORMAT_TABLE_STRING, format_cb_alternate_saved_y),
+static format_table: [format_table_entry ; 175] = [
+    format_table_entry::new(c"active_window_index", format_table_type::FORMAT_TABLE_NUMBER, format_cb_active_window_index),
+     format_table_entry::new(c"alternate_on", format_table_type::FORMAT_TABLE_FLAG, format_cb_alternate_on),
+     format_table_entry::new(c"alternate_saved_x", format_table_type::FORMAT_TABLE_NUMBER, format_cb_alternate_saved_x),
+     format_table_entry::new(c"alternate_saved_y", format_table_type::FORMAT_TABLE_NUMBER, format_cb_alternate_saved_y),
      format_table_entry::new(c"buffer_created", format_table_type::FORMAT_TABLE_TIME, format_cb_buffer_created),
      format_table_entry::new(c"buffer_mode_format", format_table_type::FORMAT_TABLE_STRING, format_cb_buffer_mode_format),
      format_table_entry::new(c"buffer_name", format_table_type::FORMAT_TABLE_STRING, format_cb_buffer_name),
      format_table_entry::new(c"buffer_sample", format_table_type::FORMAT_TABLE_STRING, format_cb_buffer_sample),
-     format_table_entry::new(c"buffer_size", format_table_type::FORMAT_TABLE_STRING, format_cb_buffer_size),
+     format_table_entry::new(c"buffer_size", format_table_type::FORMAT_TABLE_NUMBER, format_cb_buffer_size),
      format_table_entry::new(c"client_activity", format_table_type::FORMAT_TABLE_TIME, format_cb_client_activity),
-     format_table_entry::new(c"client_cell_height", format_table_type::FORMAT_TABLE_STRING, format_cb_client_cell_height),
-     format_table_entry::new(c"client_cell_width", format_table_type::FORMAT_TABLE_STRING, format_cb_client_cell_width),
-     format_table_entry::new(c"client_control_mode", format_table_type::FORMAT_TABLE_STRING, format_cb_client_control_mode),
+     format_table_entry::new(c"client_cell_height", format_table_type::FORMAT_TABLE_NUMBER, format_cb_client_cell_height),
+     format_table_entry::new(c"client_cell_width", format_table_type::FORMAT_TABLE_NUMBER, format_cb_client_cell_width),
+     format_table_entry::new(c"client_control_mode", format_table_type::FORMAT_TABLE_FLAG, format_cb_client_control_mode),
      format_table_entry::new(c"client_created", format_table_type::FORMAT_TABLE_TIME, format_cb_client_created),
-     format_table_entry::new(c"client_discarded", format_table_type::FORMAT_TABLE_STRING, format_cb_client_discarded),
+     format_table_entry::new(c"client_discarded", format_table_type::FORMAT_TABLE_NUMBER, format_cb_client_discarded),
      format_table_entry::new(c"client_flags", format_table_type::FORMAT_TABLE_STRING, format_cb_client_flags),
-     format_table_entry::new(c"client_height", format_table_type::FORMAT_TABLE_STRING, format_cb_client_height),
+     format_table_entry::new(c"client_height", format_table_type::FORMAT_TABLE_NUMBER, format_cb_client_height),
      format_table_entry::new(c"client_key_table", format_table_type::FORMAT_TABLE_STRING, format_cb_client_key_table),
      format_table_entry::new(c"client_last_session", format_table_type::FORMAT_TABLE_STRING, format_cb_client_last_session),
      format_table_entry::new(c"client_mode_format", format_table_type::FORMAT_TABLE_STRING, format_cb_client_mode_format),
      format_table_entry::new(c"client_name", format_table_type::FORMAT_TABLE_STRING, format_cb_client_name),
-     format_table_entry::new(c"client_pid", format_table_type::FORMAT_TABLE_STRING, format_cb_client_pid),
-     format_table_entry::new(c"client_prefix", format_table_type::FORMAT_TABLE_STRING, format_cb_client_prefix),
-     format_table_entry::new(c"client_readonly", format_table_type::FORMAT_TABLE_STRING, format_cb_client_readonly),
+     format_table_entry::new(c"client_pid", format_table_type::FORMAT_TABLE_NUMBER, format_cb_client_pid),
+     format_table_entry::new(c"client_prefix", format_table_type::FORMAT_TABLE_FLAG, format_cb_client_prefix),
+     format_table_entry::new(c"client_readonly", format_table_type::FORMAT_TABLE_FLAG, format_cb_client_readonly),
      format_table_entry::new(c"client_session", format_table_type::FORMAT_TABLE_STRING, format_cb_client_session),
      format_table_entry::new(c"client_termfeatures", format_table_type::FORMAT_TABLE_STRING, format_cb_client_termfeatures),
      format_table_entry::new(c"client_termname", format_table_type::FORMAT_TABLE_STRING, format_cb_client_termname),
      format_table_entry::new(c"client_termtype", format_table_type::FORMAT_TABLE_STRING, format_cb_client_termtype),
      format_table_entry::new(c"client_tty", format_table_type::FORMAT_TABLE_STRING, format_cb_client_tty),
-     format_table_entry::new(c"client_uid", format_table_type::FORMAT_TABLE_STRING, format_cb_client_uid),
+     format_table_entry::new(c"client_uid", format_table_type::FORMAT_TABLE_NUMBER, format_cb_client_uid),
      format_table_entry::new(c"client_user", format_table_type::FORMAT_TABLE_STRING, format_cb_client_user),
-     format_table_entry::new(c"client_utf8", format_table_type::FORMAT_TABLE_STRING, format_cb_client_utf8),
-     format_table_entry::new(c"client_width", format_table_type::FORMAT_TABLE_STRING, format_cb_client_width),
-     format_table_entry::new(c"client_written", format_table_type::FORMAT_TABLE_STRING, format_cb_client_written),
+     format_table_entry::new(c"client_utf8", format_table_type::FORMAT_TABLE_FLAG, format_cb_client_utf8),
+     format_table_entry::new(c"client_width", format_table_type::FORMAT_TABLE_NUMBER, format_cb_client_width),
+     format_table_entry::new(c"client_written", format_table_type::FORMAT_TABLE_NUMBER, format_cb_client_written),
      format_table_entry::new(c"config_files", format_table_type::FORMAT_TABLE_STRING, format_cb_config_files),
      format_table_entry::new(c"cursor_character", format_table_type::FORMAT_TABLE_STRING, format_cb_cursor_character),
-     format_table_entry::new(c"cursor_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_cursor_flag),
-     format_table_entry::new(c"cursor_x", format_table_type::FORMAT_TABLE_STRING, format_cb_cursor_x),
-     format_table_entry::new(c"cursor_y", format_table_type::FORMAT_TABLE_STRING, format_cb_cursor_y),
+     format_table_entry::new(c"cursor_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_cursor_flag),
+     format_table_entry::new(c"cursor_x", format_table_type::FORMAT_TABLE_NUMBER, format_cb_cursor_x),
+     format_table_entry::new(c"cursor_y", format_table_type::FORMAT_TABLE_NUMBER, format_cb_cursor_y),
      format_table_entry::new(c"history_all_bytes", format_table_type::FORMAT_TABLE_STRING, format_cb_history_all_bytes),
-     format_table_entry::new(c"history_bytes", format_table_type::FORMAT_TABLE_STRING, format_cb_history_bytes),
-     format_table_entry::new(c"history_limit", format_table_type::FORMAT_TABLE_STRING, format_cb_history_limit),
-     format_table_entry::new(c"history_size", format_table_type::FORMAT_TABLE_STRING, format_cb_history_size),
+     format_table_entry::new(c"history_bytes", format_table_type::FORMAT_TABLE_NUMBER, format_cb_history_bytes),
+     format_table_entry::new(c"history_limit", format_table_type::FORMAT_TABLE_NUMBER, format_cb_history_limit),
+     format_table_entry::new(c"history_size", format_table_type::FORMAT_TABLE_NUMBER, format_cb_history_size),
      format_table_entry::new(c"host", format_table_type::FORMAT_TABLE_STRING, format_cb_host),
      format_table_entry::new(c"host_short", format_table_type::FORMAT_TABLE_STRING, format_cb_host_short),
-     format_table_entry::new(c"insert_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_insert_flag),
-     format_table_entry::new(c"keypad_cursor_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_keypad_cursor_flag),
-     format_table_entry::new(c"keypad_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_keypad_flag),
-     format_table_entry::new(c"last_window_index", format_table_type::FORMAT_TABLE_STRING, format_cb_last_window_index),
-     format_table_entry::new(c"mouse_all_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_all_flag),
-     format_table_entry::new(c"mouse_any_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_any_flag),
-     format_table_entry::new(c"mouse_button_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_button_flag),
+     format_table_entry::new(c"insert_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_insert_flag),
+     format_table_entry::new(c"keypad_cursor_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_keypad_cursor_flag),
+     format_table_entry::new(c"keypad_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_keypad_flag),
+     format_table_entry::new(c"last_window_index", format_table_type::FORMAT_TABLE_NUMBER, format_cb_last_window_index),
+     format_table_entry::new(c"mouse_all_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_mouse_all_flag),
+     format_table_entry::new(c"mouse_any_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_mouse_any_flag),
+     format_table_entry::new(c"mouse_button_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_mouse_button_flag),
      format_table_entry::new(c"mouse_hyperlink", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_hyperlink),
      format_table_entry::new(c"mouse_line", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_line),
      format_table_entry::new(c"mouse_pane", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_pane),
-     format_table_entry::new(c"mouse_sgr_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_sgr_flag),
-     format_table_entry::new(c"mouse_standard_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_standard_flag),
+     format_table_entry::new(c"mouse_sgr_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_mouse_sgr_flag),
+     format_table_entry::new(c"mouse_standard_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_mouse_standard_flag),
      format_table_entry::new(c"mouse_status_line", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_status_line),
      format_table_entry::new(c"mouse_status_range", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_status_range),
-     format_table_entry::new(c"mouse_utf8_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_utf8_flag),
+     format_table_entry::new(c"mouse_utf8_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_mouse_utf8_flag),
      format_table_entry::new(c"mouse_word", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_word),
-     format_table_entry::new(c"mouse_x", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_x),
-     format_table_entry::new(c"mouse_y", format_table_type::FORMAT_TABLE_STRING, format_cb_mouse_y),
+     format_table_entry::new(c"mouse_x", format_table_type::FORMAT_TABLE_NUMBER, format_cb_mouse_x),
+     format_table_entry::new(c"mouse_y", format_table_type::FORMAT_TABLE_NUMBER, format_cb_mouse_y),
      format_table_entry::new(c"next_session_id", format_table_type::FORMAT_TABLE_STRING, format_cb_next_session_id),
-     format_table_entry::new(c"origin_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_origin_flag),
-     format_table_entry::new(c"pane_active", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_active),
-     format_table_entry::new(c"pane_at_bottom", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_at_bottom),
-     format_table_entry::new(c"pane_at_left", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_at_left),
-     format_table_entry::new(c"pane_at_right", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_at_right),
-     format_table_entry::new(c"pane_at_top", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_at_top),
+     format_table_entry::new(c"origin_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_origin_flag),
+     format_table_entry::new(c"pane_active", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_active),
+     format_table_entry::new(c"pane_activity_rate", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_activity_rate),
+     format_table_entry::new(c"pane_at_bottom", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_at_bottom),
+     format_table_entry::new(c"pane_at_left", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_at_left),
+     format_table_entry::new(c"pane_at_right", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_at_right),
+     format_table_entry::new(c"pane_at_top", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_at_top),
      format_table_entry::new(c"pane_bg", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_bg),
-     format_table_entry::new(c"pane_bottom", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_bottom),
+     format_table_entry::new(c"pane_bottom", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_bottom),
      format_table_entry::new(c"pane_current_command", format_table_type::FORMAT_TABLE_STRING, format_cb_current_command),
      format_table_entry::new(c"pane_current_path", format_table_type::FORMAT_TABLE_STRING, format_cb_current_path),
-     format_table_entry::new(c"pane_dead", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_dead),
-     format_table_entry::new(c"pane_dead_signal", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_dead_signal),
-     format_table_entry::new(c"pane_dead_status", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_dead_status),
+     format_table_entry::new(c"pane_dead", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_dead),
+     format_table_entry::new(c"pane_dead_signal", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_dead_signal),
+     format_table_entry::new(c"pane_dead_status", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_dead_status),
      format_table_entry::new(c"pane_dead_time", format_table_type::FORMAT_TABLE_TIME, format_cb_pane_dead_time),
      format_table_entry::new(c"pane_fg", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_fg),
-     format_table_entry::new(c"pane_format", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_format),
-     format_table_entry::new(c"pane_height", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_height),
+     format_table_entry::new(c"pane_format", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_format),
+     format_table_entry::new(c"pane_height", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_height),
+     format_table_entry::new(c"pane_history_bytes", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_history_bytes),
      format_table_entry::new(c"pane_id", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_id),
-     format_table_entry::new(c"pane_in_mode", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_in_mode),
-     format_table_entry::new(c"pane_index", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_index),
-     format_table_entry::new(c"pane_input_off", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_input_off),
+     format_table_entry::new(c"pane_in_mode", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_in_mode),
+     format_table_entry::new(c"pane_index", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_index),
+     format_table_entry::new(c"pane_input_off", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_input_off),
      format_table_entry::new(c"pane_key_mode", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_key_mode),
-     format_table_entry::new(c"pane_last", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_last),
-     format_table_entry::new(c"pane_left", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_left),
-     format_table_entry::new(c"pane_marked", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_marked),
-     format_table_entry::new(c"pane_marked_set", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_marked_set),
+     format_table_entry::new(c"pane_last", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_last),
+     format_table_entry::new(c"pane_last_command", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_last_command),
+     format_table_entry::new(c"pane_last_exit_status", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_last_exit_status),
+     format_table_entry::new(c"pane_left", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_left),
+     format_table_entry::new(c"pane_marked", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_marked),
+     format_table_entry::new(c"pane_marked_set", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_marked_set),
      format_table_entry::new(c"pane_mode", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_mode),
      format_table_entry::new(c"pane_path", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_path),
-     format_table_entry::new(c"pane_pid", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_pid),
-     format_table_entry::new(c"pane_pipe", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_pipe),
-     format_table_entry::new(c"pane_right", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_right),
+     format_table_entry::new(c"pane_pid", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_pid),
+     format_table_entry::new(c"pane_pipe", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_pipe),
+     format_table_entry::new(c"pane_right", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_right),
      format_table_entry::new(c"pane_search_string", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_search_string),
      format_table_entry::new(c"pane_start_command", format_table_type::FORMAT_TABLE_STRING, format_cb_start_command),
      format_table_entry::new(c"pane_start_path", format_table_type::FORMAT_TABLE_STRING, format_cb_start_path),
-     format_table_entry::new(c"pane_synchronized", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_synchronized),
+     format_table_entry::new(c"pane_synchronized", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_synchronized),
      format_table_entry::new(c"pane_tabs", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_tabs),
      format_table_entry::new(c"pane_title", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_title),
-     format_table_entry::new(c"pane_top", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_top),
+     format_table_entry::new(c"pane_top", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_top),
      format_table_entry::new(c"pane_tty", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_tty),
-     format_table_entry::new(c"pane_unseen_changes", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_unseen_changes),
-     format_table_entry::new(c"pane_width", format_table_type::FORMAT_TABLE_STRING, format_cb_pane_width),
-     format_table_entry::new(c"pid", format_table_type::FORMAT_TABLE_STRING, format_cb_pid),
-     format_table_entry::new(c"scroll_region_lower", format_table_type::FORMAT_TABLE_STRING, format_cb_scroll_region_lower),
-     format_table_entry::new(c"scroll_region_upper", format_table_type::FORMAT_TABLE_STRING, format_cb_scroll_region_upper),
-     format_table_entry::new(c"server_sessions", format_table_type::FORMAT_TABLE_STRING, format_cb_server_sessions),
+     format_table_entry::new(c"pane_unseen_changes", format_table_type::FORMAT_TABLE_FLAG, format_cb_pane_unseen_changes),
+     format_table_entry::new(c"pane_width", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pane_width),
+     format_table_entry::new(c"pid", format_table_type::FORMAT_TABLE_NUMBER, format_cb_pid),
+     format_table_entry::new(c"scroll_region_lower", format_table_type::FORMAT_TABLE_NUMBER, format_cb_scroll_region_lower),
+     format_table_entry::new(c"scroll_region_upper", format_table_type::FORMAT_TABLE_NUMBER, format_cb_scroll_region_upper),
+     format_table_entry::new(c"server_sessions", format_table_type::FORMAT_TABLE_NUMBER, format_cb_server_sessions),
      format_table_entry::new(c"session_activity", format_table_type::FORMAT_TABLE_TIME, format_cb_session_activity),
      format_table_entry::new(c"session_alerts", format_table_type::FORMAT_TABLE_STRING, format_cb_session_alerts),
-     format_table_entry::new(c"session_attached", format_table_type::FORMAT_TABLE_STRING, format_cb_session_attached),
+     format_table_entry::new(c"session_attached", format_table_type::FORMAT_TABLE_NUMBER, format_cb_session_attached),
      format_table_entry::new(c"session_attached_list", format_table_type::FORMAT_TABLE_STRING, format_cb_session_attached_list),
      format_table_entry::new(c"session_created", format_table_type::FORMAT_TABLE_TIME, format_cb_session_created),
-     format_table_entry::new(c"session_format", format_table_type::FORMAT_TABLE_STRING, format_cb_session_format),
+     format_table_entry::new(c"session_format", format_table_type::FORMAT_TABLE_FLAG, format_cb_session_format),
      format_table_entry::new(c"session_group", format_table_type::FORMAT_TABLE_STRING, format_cb_session_group),
-     format_table_entry::new(c"session_group_attached", format_table_type::FORMAT_TABLE_STRING, format_cb_session_group_attached),
+     format_table_entry::new(c"session_group_attached", format_table_type::FORMAT_TABLE_NUMBER, format_cb_session_group_attached),
      format_table_entry::new(c"session_group_attached_list", format_table_type::FORMAT_TABLE_STRING, format_cb_session_group_attached_list),
      format_table_entry::new(c"session_group_list", format_table_type::FORMAT_TABLE_STRING, format_cb_session_group_list),
-     format_table_entry::new(c"session_group_many_attached", format_table_type::FORMAT_TABLE_STRING, format_cb_session_group_many_attached),
-     format_table_entry::new(c"session_group_size", format_table_type::FORMAT_TABLE_STRING, format_cb_session_group_size),
-     format_table_entry::new(c"session_grouped", format_table_type::FORMAT_TABLE_STRING, format_cb_session_grouped),
+     format_table_entry::new(c"session_group_many_attached", format_table_type::FORMAT_TABLE_FLAG, format_cb_session_group_many_attached),
+     format_table_entry::new(c"session_group_size", format_table_type::FORMAT_TABLE_NUMBER, format_cb_session_group_size),
+     format_table_entry::new(c"session_grouped", format_table_type::FORMAT_TABLE_FLAG, format_cb_session_grouped),
      format_table_entry::new(c"session_id", format_table_type::FORMAT_TABLE_STRING, format_cb_session_id),
      format_table_entry::new(c"session_last_attached", format_table_type::FORMAT_TABLE_TIME, format_cb_session_last_attached),
-     format_table_entry::new(c"session_many_attached", format_table_type::FORMAT_TABLE_STRING, format_cb_session_many_attached),
-     format_table_entry::new(c"session_marked", format_table_type::FORMAT_TABLE_STRING, format_cb_session_marked),
+     format_table_entry::new(c"session_many_attached", format_table_type::FORMAT_TABLE_FLAG, format_cb_session_many_attached),
+     format_table_entry::new(c"session_marked", format_table_type::FORMAT_TABLE_FLAG, format_cb_session_marked),
      format_table_entry::new(c"session_name", format_table_type::FORMAT_TABLE_STRING, format_cb_session_name),
      format_table_entry::new(c"session_path", format_table_type::FORMAT_TABLE_STRING, format_cb_session_path),
      format_table_entry::new(c"session_stack", format_table_type::FORMAT_TABLE_STRING, format_cb_session_stack),
-     format_table_entry::new(c"session_windows", format_table_type::FORMAT_TABLE_STRING, format_cb_session_windows),
+     format_table_entry::new(c"session_windows", format_table_type::FORMAT_TABLE_NUMBER, format_cb_session_windows),
      format_table_entry::new(c"socket_path", format_table_type::FORMAT_TABLE_STRING, format_cb_socket_path),
      format_table_entry::new(c"start_time", format_table_type::FORMAT_TABLE_TIME, format_cb_start_time),
      format_table_entry::new(c"tree_mode_format", format_table_type::FORMAT_TABLE_STRING, format_cb_tree_mode_format),
-     format_table_entry::new(c"uid", format_table_type::FORMAT_TABLE_STRING, format_cb_uid),
+     format_table_entry::new(c"uid", format_table_type::FORMAT_TABLE_NUMBER, format_cb_uid),
      format_table_entry::new(c"user", format_table_type::FORMAT_TABLE_STRING, format_cb_user),
      format_table_entry::new(c"version", format_table_type::FORMAT_TABLE_STRING, format_cb_version),
-     format_table_entry::new(c"window_active", format_table_type::FORMAT_TABLE_STRING, format_cb_window_active),
-     format_table_entry::new(c"window_active_clients", format_table_type::FORMAT_TABLE_STRING, format_cb_window_active_clients),
+     format_table_entry::new(c"window_active", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_active),
+     format_table_entry::new(c"window_active_clients", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_active_clients),
      format_table_entry::new(c"window_active_clients_list", format_table_type::FORMAT_TABLE_STRING, format_cb_window_active_clients_list),
-     format_table_entry::new(c"window_active_sessions", format_table_type::FORMAT_TABLE_STRING, format_cb_window_active_sessions),
+     format_table_entry::new(c"window_active_sessions", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_active_sessions),
      format_table_entry::new(c"window_active_sessions_list", format_table_type::FORMAT_TABLE_STRING, format_cb_window_active_sessions_list),
      format_table_entry::new(c"window_activity", format_table_type::FORMAT_TABLE_TIME, format_cb_window_activity),
-     format_table_entry::new(c"window_activity_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_window_activity_flag),
-     format_table_entry::new(c"window_bell_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_window_bell_flag),
-     format_table_entry::new(c"window_bigger", format_table_type::FORMAT_TABLE_STRING, format_cb_window_bigger),
-     format_table_entry::new(c"window_cell_height", format_table_type::FORMAT_TABLE_STRING, format_cb_window_cell_height),
-     format_table_entry::new(c"window_cell_width", format_table_type::FORMAT_TABLE_STRING, format_cb_window_cell_width),
-     format_table_entry::new(c"window_end_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_window_end_flag),
+     format_table_entry::new(c"window_activity_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_activity_flag),
+     format_table_entry::new(c"window_bell_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_bell_flag),
+     format_table_entry::new(c"window_bigger", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_bigger),
+     format_table_entry::new(c"window_cell_height", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_cell_height),
+     format_table_entry::new(c"window_cell_width", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_cell_width),
+     format_table_entry::new(c"window_end_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_end_flag),
      format_table_entry::new(c"window_flags", format_table_type::FORMAT_TABLE_STRING, format_cb_window_flags),
-     format_table_entry::new(c"window_format", format_table_type::FORMAT_TABLE_STRING, format_cb_window_format),
-     format_table_entry::new(c"window_height", format_table_type::FORMAT_TABLE_STRING, format_cb_window_height),
+     format_table_entry::new(c"window_format", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_format),
+     format_table_entry::new(c"window_height", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_height),
      format_table_entry::new(c"window_id", format_table_type::FORMAT_TABLE_STRING, format_cb_window_id),
-     format_table_entry::new(c"window_index", format_table_type::FORMAT_TABLE_STRING, format_cb_window_index),
-     format_table_entry::new(c"window_last_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_window_last_flag),
+     format_table_entry::new(c"window_index", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_index),
+     format_table_entry::new(c"window_last_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_last_flag),
      format_table_entry::new(c"window_layout", format_table_type::FORMAT_TABLE_STRING, format_cb_window_layout),
-     format_table_entry::new(c"window_linked", format_table_type::FORMAT_TABLE_STRING, format_cb_window_linked),
-     format_table_entry::new(c"window_linked_sessions", format_table_type::FORMAT_TABLE_STRING, format_cb_window_linked_sessions),
+     format_table_entry::new(c"window_linked", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_linked),
+     format_table_entry::new(c"window_linked_sessions", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_linked_sessions),
      format_table_entry::new(c"window_linked_sessions_list", format_table_type::FORMAT_TABLE_STRING, format_cb_window_linked_sessions_list),
-     format_table_entry::new(c"window_marked_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_window_marked_flag),
+     format_table_entry::new(c"window_marked_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_marked_flag),
      format_table_entry::new(c"window_name", format_table_type::FORMAT_TABLE_STRING, format_cb_window_name),
-     format_table_entry::new(c"window_offset_x", format_table_type::FORMAT_TABLE_STRING, format_cb_window_offset_x),
-     format_table_entry::new(c"window_offset_y", format_table_type::FORMAT_TABLE_STRING, format_cb_window_offset_y),
-     format_table_entry::new(c"window_panes", format_table_type::FORMAT_TABLE_STRING, format_cb_window_panes),
+     format_table_entry::new(c"window_offset_x", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_offset_x),
+     format_table_entry::new(c"window_offset_y", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_offset_y),
+     format_table_entry::new(c"window_panes", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_panes),
      format_table_entry::new(c"window_raw_flags", format_table_type::FORMAT_TABLE_STRING, format_cb_window_raw_flags),
-     format_table_entry::new(c"window_silence_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_window_silence_flag),
-     format_table_entry::new(c"window_stack_index", format_table_type::FORMAT_TABLE_STRING, format_cb_window_stack_index),
-     format_table_entry::new(c"window_start_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_window_start_flag),
+     format_table_entry::new(c"window_silence_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_silence_flag),
+     format_table_entry::new(c"window_stack_index", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_stack_index),
+     format_table_entry::new(c"window_start_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_start_flag),
      format_table_entry::new(c"window_visible_layout", format_table_type::FORMAT_TABLE_STRING, format_cb_window_visible_layout),
-     format_table_entry::new(c"window_width", format_table_type::FORMAT_TABLE_STRING, format_cb_window_width),
-     format_table_entry::new(c"window_zoomed_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_window_zoomed_flag),
-     format_table_entry::new(c"wrap_flag", format_table_type::FORMAT_TABLE_STRING, format_cb_wrap_flag)
+     format_table_entry::new(c"window_width", format_table_type::FORMAT_TABLE_NUMBER, format_cb_window_width),
+     format_table_entry::new(c"window_zoomed_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_window_zoomed_flag),
+     format_table_entry::new(c"wrap_flag", format_table_type::FORMAT_TABLE_FLAG, format_cb_wrap_flag)
 ];
 
 pub unsafe extern "C" fn format_table_compare(key0: *const c_void, entry0: *const c_void) -> i32 {
@@ -3300,6 +3350,98 @@ pub unsafe fn format_each(
     }
 }
 
+/// Types of format variables added to the tree rather than in the table.
+static format_json_types: [(&CStr, format_table_type); 17] = [
+    (c"copy_cursor_x", format_table_type::FORMAT_TABLE_NUMBER),
+    (c"copy_cursor_y", format_table_type::FORMAT_TABLE_NUMBER),
+    (c"key_repeat", format_table_type::FORMAT_TABLE_FLAG),
+    (c"line", format_table_type::FORMAT_TABLE_NUMBER),
+    (c"message_number", format_table_type::FORMAT_TABLE_NUMBER),
+    (c"message_time", format_table_type::FORMAT_TABLE_TIME),
+    (c"option_is_array", format_table_type::FORMAT_TABLE_FLAG),
+    (c"option_is_global", format_table_type::FORMAT_TABLE_FLAG),
+    (c"rectangle_toggle", format_table_type::FORMAT_TABLE_FLAG),
+    (c"scroll_position", format_table_type::FORMAT_TABLE_NUMBER),
+    (c"search_count", format_table_type::FORMAT_TABLE_NUMBER),
+    (c"selection_active", format_table_type::FORMAT_TABLE_FLAG),
+    (c"selection_end_x", format_table_type::FORMAT_TABLE_NUMBER),
+    (c"selection_end_y", format_table_type::FORMAT_TABLE_NUMBER),
+    (c"selection_present", format_table_type::FORMAT_TABLE_FLAG),
+    (c"selection_start_x", format_table_type::FORMAT_TABLE_NUMBER),
+    (c"selection_start_y", format_table_type::FORMAT_TABLE_NUMBER),
+];
+
+/// Append a string to a JSON document, quoted and escaped to plain ASCII.
+pub fn format_json_string(out: &mut String, s: &[u8]) {
+    out.push('"');
+    for ch in String::from_utf8_lossy(s).chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ' '..='~' => out.push(ch),
+            _ => {
+                let mut units = [0u16; 2];
+                for unit in ch.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{unit:04x}"));
+                }
+            }
+        }
+    }
+    out.push('"');
+}
+
+unsafe fn format_json_cb(key: *const c_char, value: *const c_char, arg: *mut c_void) {
+    unsafe {
+        let out = &mut *(arg as *mut String);
+
+        let fte = format_table_get(key);
+        let type_ = if !fte.is_null() {
+            (*fte).type_
+        } else {
+            let key = CStr::from_ptr(key);
+            format_json_types
+                .iter()
+                .find(|(name, _)| *name == key)
+                .map_or(format_table_type::FORMAT_TABLE_STRING, |(_, type_)| *type_)
+        };
+
+        if out.len() > 1 {
+            out.push(',');
+        }
+        format_json_string(out, CStr::from_ptr(key).to_bytes());
+        out.push(':');
+
+        let value = CStr::from_ptr(value).to_bytes();
+        match type_ {
+            format_table_type::FORMAT_TABLE_FLAG if value == b"0" => out.push_str("false"),
+            format_table_type::FORMAT_TABLE_FLAG if value == b"1" => out.push_str("true"),
+            format_table_type::FORMAT_TABLE_STRING => format_json_string(out, value),
+            _ => match std::str::from_utf8(value)
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+            {
+                Some(n) => out.push_str(&n.to_string()),
+                None => format_json_string(out, value),
+            },
+        }
+    }
+}
+
+/// Expand every format variable into a single line JSON object. Numbers,
+/// times and flags keep their types, everything else is a string. Caller
+/// frees.
+pub unsafe fn format_json(ft: *mut format_tree) -> *mut c_char {
+    unsafe {
+        let mut out = String::from("{");
+        format_each(ft, Some(format_json_cb), (&raw mut out).cast());
+        out.push('}');
+        format_nul!("{out}")
+    }
+}
+
 macro_rules! format_add {
    ($state:expr, $key:expr, $fmt:literal $(, $args:expr)* $(,)?) => {
         crate::format::format_add_($state, $key, format_args!($fmt $(, $args)*))
@@ -3944,8 +4086,6 @@ pub unsafe fn format_match(
 ) -> *mut c_char {
     unsafe {
         let mut s = c"".as_ptr() as *const c_char;
-        let mut r = MaybeUninit::<regex_t>::uninit();
-        let r = r.as_mut_ptr();
         let mut flags: i32 = 0;
 
         if (*fm).argc >= 1 {
@@ -3959,18 +4099,16 @@ pub unsafe fn format_match(
                 return xstrdup(c"0".as_ptr()).as_ptr();
             }
         } else {
-            flags = REG_EXTENDED | REG_NOSUB;
+            let mut rflags = regex_flags::REGEX_NOSUB;
             if !strchr(s, b'i' as i32).is_null() {
-                flags |= REG_ICASE;
+                rflags |= regex_flags::REGEX_ICASE;
             }
-            if regcomp(r, pattern, flags) != 0 {
+            let Some(r) = regex_compile(pattern, regex_syntax_from_flags(s), rflags) else {
                 return xstrdup(c"0".as_ptr()).as_ptr();
-            }
-            if regexec(r, text, 0, null_mut(), 0) != 0 {
-                regfree(r);
+            };
+            if !regex_exec(&r, text, 0, &mut []) {
                 return xstrdup(c"0".as_ptr()).as_ptr();
             }
-            regfree(r);
         }
         xstrdup(c"1".as_ptr()).as_ptr()
     }
@@ -3983,12 +4121,16 @@ pub unsafe fn format_sub(
     with: *const c_char,
 ) -> *mut c_char {
     unsafe {
-        let mut flags: i32 = REG_EXTENDED;
+        let mut flags = regex_flags::empty();
+        let mut syntax = regex_get_syntax();
 
-        if (*fm).argc >= 3 && !strchr(*(*fm).argv.add(2), b'i' as i32).is_null() {
-            flags |= REG_ICASE;
+        if (*fm).argc >= 3 {
+            if !strchr(*(*fm).argv.add(2), b'i' as i32).is_null() {
+                flags |= regex_flags::REGEX_ICASE;
+            }
+            syntax = regex_syntax_from_flags(*(*fm).argv.add(2));
         }
-        let value = regsub(pattern, with, text, flags);
+        let value = regsub(pattern, with, text, syntax, flags);
         if value.is_null() {
             xstrdup(text).as_ptr()
         } else {
@@ -4004,7 +4146,7 @@ pub unsafe fn format_search(
 ) -> *mut c_char {
     unsafe {
         let mut ignore = 0;
-        let mut regex = 0;
+        let mut regex = None;
         let mut value: *mut c_char = null_mut();
 
         if (*fm).argc >= 1 {
@@ -4012,7 +4154,7 @@ pub unsafe fn format_search(
                 ignore = 1;
             }
             if !strchr(*(*fm).argv, b'r' as i32).is_null() {
-                regex = 1;
+                regex = Some(regex_syntax_from_flags(*(*fm).argv));
             }
         }
         format_nul!("{}", window_pane_search(wp, s, regex, ignore))
@@ -5614,3 +5756,65 @@ pub unsafe fn format_grid_hyperlink(gd: *mut grid, x: u32, y: u32, s: *mut scree
         xstrdup(uri).as_ptr()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_json_string() {
+        let mut out = String::new();
+        format_json_string(&mut out, b"a \"b\"\\\n\r\t\x01\x7f");
+        assert_eq!(out, r#""a \"b\"\\\n\r\t\u0001\u007f""#);
+
+        out.clear();
+        format_json_string(&mut out, "\u{e9}\u{1f600}".as_bytes());
+        assert_eq!(out, r#""\u00e9\ud83d\ude00""#);
+
+        // Invalid UTF-8 becomes the replacement character.
+        out.clear();
+        format_json_string(&mut out, b"x\xffy");
+        assert_eq!(out, r#""x\ufffdy""#);
+    }
+
+    #[test]
+    fn test_format_json_cb() {
+        unsafe {
+            let mut out = String::from("{");
+            for (key, value) in [
+                (c"window_index", c"3"),
+                (c"window_active", c"1"),
+                (c"key_repeat", c"0"),
+                (c"session_created", c"1700000000"),
+                (c"pid", c"unknown"),
+                (c"pane_title", c"42"),
+                (c"user_option", c"x\"y"),
+            ] {
+                format_json_cb(key.as_ptr(), value.as_ptr(), (&raw mut out).cast());
+            }
+            out.push('}');
+            assert_eq!(
+                out,
+                concat!(
+                    r#"{"window_index":3,"window_active":true,"key_repeat":false,"#,
+                    r#""session_created":1700000000,"pid":"unknown","pane_title":"42","#,
+                    r#""user_option":"x\"y"}"#
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_table_sorted() {
+        // format_table_get searches the table with bsearch.
+        for pair in format_table.windows(2) {
+            assert!(unsafe { libc::strcmp(pair[0].key.as_ptr(), pair[1].key.as_ptr()) } < 0);
+        }
+        unsafe {
+            let fte = format_table_get(c"window_active".as_ptr());
+            assert!(!fte.is_null());
+            assert!((*fte).type_ == format_table_type::FORMAT_TABLE_FLAG);
+            assert!(format_table_get(c"no_such_variable".as_ptr()).is_null());
+        }
+    }
+}
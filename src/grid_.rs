@@ -14,6 +14,10 @@
 
 use crate::*;
 
+use std::collections::VecDeque;
+use std::sync::atomic;
+use std::sync::atomic::AtomicU64;
+
 use libc::strlen;
 
 use crate::compat::strlcat;
@@ -168,63 +172,1167 @@ pub unsafe fn grid_compact_line(gl: *mut grid_line) {
         if (*gl).extdsize == 0 {
             return;
         }
-
-        // Count extended cells
-        for px in 0..(*gl).cellsize {
-            let gce = &raw mut *(*gl).celldata.add(px as usize);
-            if (*gce).flags.contains(grid_flag::EXTENDED) {
-                new_extdsize += 1;
+
+        // Count extended cells
+        for px in 0..(*gl).cellsize {
+            let gce = &raw mut *(*gl).celldata.add(px as usize);
+            if (*gce).flags.contains(grid_flag::EXTENDED) {
+                new_extdsize += 1;
+            }
+        }
+
+        if new_extdsize == 0 {
+            free_((*gl).extddata);
+            (*gl).extddata = null_mut();
+            (*gl).extdsize = 0;
+            return;
+        }
+
+        // Allocate new array
+        let new_extddata: *mut grid_extd_entry =
+            xreallocarray_(null_mut(), new_extdsize as usize).as_ptr();
+
+        let mut idx = 0;
+        for px in 0..(*gl).cellsize {
+            let gce = (*gl).celldata.add(px as usize);
+            if (*gce).flags.contains(grid_flag::EXTENDED) {
+                let gee = (*gl).extddata.add((*gce).union_.offset as usize);
+                std::ptr::copy_nonoverlapping(
+                    gee as *const grid_extd_entry,
+                    new_extddata.add(idx as usize),
+                    1,
+                );
+                (*gce).union_.offset = idx;
+                idx += 1;
+            }
+        }
+
+        free_((*gl).extddata);
+        (*gl).extddata = new_extddata;
+        (*gl).extdsize = new_extdsize;
+    }
+}
+
+/// Command run from a shell prompt, as marked by OSC 133. It belongs to the
+/// line where its output starts.
+#[derive(Clone, Default)]
+pub struct grid_command {
+    /// Command line, empty if it is not known.
+    pub text: String,
+    /// Whether the end of the output has been marked.
+    pub finished: bool,
+    /// Exit status, if the shell gave one when the command finished.
+    pub status: Option<i32>,
+}
+
+const GRID_COMMAND_PRESENT: u8 = 0x1;
+const GRID_COMMAND_FINISHED: u8 = 0x2;
+const GRID_COMMAND_STATUS: u8 = 0x4;
+
+/// Free the command of a line.
+unsafe fn grid_command_free(gl: *mut grid_line) {
+    unsafe {
+        if !(*gl).command.is_null() {
+            drop(Box::from_raw((*gl).command));
+            (*gl).command = null_mut();
+        }
+    }
+}
+
+/// Give a line its own copy of the command it was copied with.
+unsafe fn grid_command_duplicate(gl: *mut grid_line) {
+    unsafe {
+        if !(*gl).command.is_null() {
+            (*gl).command = Box::into_raw(Box::new((*(*gl).command).clone()));
+        }
+    }
+}
+
+/// Append the command of a line for saving or spilling: a byte of
+/// GRID_COMMAND_* flags, then if there is a command the exit status (i32) if
+/// known and the length (u32) and text of the command line.
+unsafe fn grid_command_encode(gl: *const grid_line, out: &mut Vec<u8>) {
+    unsafe {
+        if (*gl).command.is_null() {
+            out.push(0);
+            return;
+        }
+        let command = &*(*gl).command;
+
+        let mut flags = GRID_COMMAND_PRESENT;
+        if command.finished {
+            flags |= GRID_COMMAND_FINISHED;
+        }
+        if command.status.is_some() {
+            flags |= GRID_COMMAND_STATUS;
+        }
+        out.push(flags);
+        if let Some(status) = command.status {
+            out.extend_from_slice(&status.to_le_bytes());
+        }
+        out.extend_from_slice(&(command.text.len() as u32).to_le_bytes());
+        out.extend_from_slice(command.text.as_bytes());
+    }
+}
+
+/// Read a command appended by grid_command_encode into a line.
+unsafe fn grid_command_decode(r: &mut grid_history_reader, gl: *mut grid_line) -> Option<()> {
+    unsafe {
+        let flags = r.u8()?;
+        if flags & GRID_COMMAND_PRESENT == 0 {
+            return Some(());
+        }
+        let status = if flags & GRID_COMMAND_STATUS != 0 {
+            Some(r.i32()?)
+        } else {
+            None
+        };
+        let length = r.u32()?;
+        let text = String::from_utf8_lossy(r.bytes(length as usize)?).into_owned();
+
+        (*gl).command = Box::into_raw(Box::new(grid_command {
+            text,
+            finished: flags & GRID_COMMAND_FINISHED != 0,
+            status,
+        }));
+        Some(())
+    }
+}
+
+/// Number of the newest history lines which are never compressed.
+const GRID_HOT_LINES: u32 = 1000;
+
+/// Number of history lines compressed together into one block.
+const GRID_COLD_LINES: u32 = 128;
+
+/// Number of history lines above the visible screen reflowed as soon as the
+/// width changes.
+const GRID_REFLOW_LINES: u32 = 1000;
+
+/// Block of compressed history lines. Lines copied by grid_duplicate_lines
+/// share the block, so it counts the lines still using it.
+pub struct grid_cold_block {
+    data: Vec<u8>,
+    size: usize,
+    offsets: Vec<u32>,
+    references: u32,
+}
+
+/// Last block decompressed. Lines from one block are usually read one after
+/// another, so this saves decompressing it for each.
+static mut grid_cold_cache: (*mut grid_cold_block, Vec<u8>) = (null_mut(), Vec::new());
+
+/// Compress with a simple LZ77. Each control byte below 0x80 is followed by
+/// that many plus one literal bytes; otherwise it is a copy of (control &
+/// 0x7f) + 4 bytes from an earlier position given by the next two bytes.
+fn grid_lz_compress(src: &[u8]) -> Vec<u8> {
+    const HASH_BITS: u32 = 12;
+    let mut out = Vec::with_capacity(src.len() / 2);
+    let mut table = [usize::MAX; 1 << HASH_BITS];
+
+    let flush = |out: &mut Vec<u8>, literals: &[u8]| {
+        for chunk in literals.chunks(128) {
+            out.push(chunk.len() as u8 - 1);
+            out.extend_from_slice(chunk);
+        }
+    };
+
+    let mut literal = 0;
+    let mut i = 0;
+    while i + 4 <= src.len() {
+        let key = u32::from_le_bytes(src[i..i + 4].try_into().unwrap());
+        let hash = (key.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize;
+        let candidate = table[hash];
+        table[hash] = i;
+
+        if candidate == usize::MAX
+            || i - candidate > u16::MAX as usize
+            || src[candidate..candidate + 4] != src[i..i + 4]
+        {
+            i += 1;
+            continue;
+        }
+        let mut len = 4;
+        while i + len < src.len() && len < 0x7f + 4 && src[candidate + len] == src[i + len] {
+            len += 1;
+        }
+
+        flush(&mut out, &src[literal..i]);
+        out.push(0x80 | (len - 4) as u8);
+        out.extend_from_slice(&((i - candidate) as u16).to_le_bytes());
+        i += len;
+        literal = i;
+    }
+    flush(&mut out, &src[literal..]);
+    out
+}
+
+/// Decompress data from grid_lz_compress.
+fn grid_lz_decompress(src: &[u8], size: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(size);
+    let mut i = 0;
+
+    while i < src.len() {
+        let control = src[i] as usize;
+        i += 1;
+        if control < 0x80 {
+            out.extend_from_slice(src.get(i..i + control + 1)?);
+            i += control + 1;
+            continue;
+        }
+        let distance = u16::from_le_bytes(src.get(i..i + 2)?.try_into().ok()?) as usize;
+        i += 2;
+        if distance == 0 || distance > out.len() {
+            return None;
+        }
+        let start = out.len() - distance;
+        for j in 0..(control & 0x7f) + 4 {
+            out.push(out[start + j]);
+        }
+    }
+    (out.len() == size).then_some(out)
+}
+
+fn grid_cold_put_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn grid_cold_get_varint(r: &mut grid_history_reader) -> Option<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = r.u8()?;
+        value |= ((byte & 0x7f) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Encode the cells of a line. The attributes of each cell (or the offset for
+/// extended cells) are run-length encoded, followed by the characters and
+/// the extended cells.
+unsafe fn grid_cold_encode(gl: *const grid_line, out: &mut Vec<u8>) {
+    unsafe {
+        grid_cold_put_varint(out, (*gl).cellsize);
+        grid_cold_put_varint(out, (*gl).extdsize);
+
+        let key = |gce: *const grid_cell_entry| -> [u8; 5] {
+            let flags = (*gce).flags.bits();
+            if (*gce).flags.intersects(grid_flag::EXTENDED) {
+                let offset = (*gce).union_.offset.to_le_bytes();
+                [flags, offset[0], offset[1], offset[2], offset[3]]
+            } else {
+                let data = (*gce).union_.data;
+                [flags, data.attr, data.fg, data.bg, 0]
+            }
+        };
+        let mut px = 0;
+        while px < (*gl).cellsize {
+            let run = key((*gl).celldata.add(px as usize));
+            let mut n = 1;
+            while px + n < (*gl).cellsize && key((*gl).celldata.add((px + n) as usize)) == run {
+                n += 1;
+            }
+            grid_cold_put_varint(out, n);
+            out.extend_from_slice(&run);
+            px += n;
+        }
+
+        for px in 0..(*gl).cellsize {
+            let gce = (*gl).celldata.add(px as usize);
+            if !(*gce).flags.intersects(grid_flag::EXTENDED) {
+                out.push((*gce).union_.data.data);
+            }
+        }
+
+        for i in 0..(*gl).extdsize {
+            let gee = (*gl).extddata.add(i as usize);
+            out.extend_from_slice(&(*gee).data.to_le_bytes());
+            out.extend_from_slice(&(*gee).attr.to_le_bytes());
+            out.push((*gee).flags);
+            out.extend_from_slice(&(*gee).fg.to_le_bytes());
+            out.extend_from_slice(&(*gee).bg.to_le_bytes());
+            out.extend_from_slice(&(*gee).us.to_le_bytes());
+            out.extend_from_slice(&(*gee).link.to_le_bytes());
+        }
+    }
+}
+
+/// Decode the cells of a line encoded by grid_cold_encode into gl.
+unsafe fn grid_cold_decode(r: &mut grid_history_reader, gl: *mut grid_line) -> Option<()> {
+    unsafe {
+        let cellsize = grid_cold_get_varint(r)?;
+        let extdsize = grid_cold_get_varint(r)?;
+        if cellsize as usize > r.data.len() || extdsize > cellsize {
+            return None;
+        }
+
+        let celldata: *mut grid_cell_entry = if cellsize != 0 {
+            xreallocarray_(null_mut(), cellsize as usize).as_ptr()
+        } else {
+            null_mut()
+        };
+        (*gl).celldata = celldata;
+        (*gl).cellsize = cellsize;
+        (*gl).extdsize = 0;
+
+        let mut px = 0;
+        while px < cellsize {
+            let n = grid_cold_get_varint(r)?;
+            let run = r.bytes(5)?;
+            if n == 0 || n > cellsize - px {
+                return None;
+            }
+            let flags = grid_flag::from_bits_truncate(run[0]);
+            for _ in 0..n {
+                let gce = celldata.add(px as usize);
+                (*gce).flags = flags;
+                if flags.intersects(grid_flag::EXTENDED) {
+                    (*gce).union_.offset = u32::from_le_bytes(run[1..5].try_into().unwrap());
+                } else {
+                    (*gce).union_.data = grid_cell_entry_data {
+                        attr: run[1],
+                        fg: run[2],
+                        bg: run[3],
+                        data: 0,
+                    };
+                }
+                px += 1;
+            }
+        }
+
+        for px in 0..cellsize {
+            let gce = celldata.add(px as usize);
+            if !(*gce).flags.intersects(grid_flag::EXTENDED) {
+                (*gce).union_.data.data = r.u8()?;
+            } else if (*gce).union_.offset >= extdsize {
+                return None;
+            }
+        }
+
+        if extdsize != 0 {
+            (*gl).extddata = xreallocarray_(null_mut(), extdsize as usize).as_ptr();
+        }
+        for i in 0..extdsize {
+            let gee = (*gl).extddata.add(i as usize);
+            (*gee).data = r.u32()?;
+            (*gee).attr = r.u16()?;
+            (*gee).flags = r.u8()?;
+            (*gee).fg = r.i32()?;
+            (*gee).bg = r.i32()?;
+            (*gee).us = r.i32()?;
+            (*gee).link = r.u32()?;
+            (*gl).extdsize = i + 1;
+        }
+
+        Some(())
+    }
+}
+
+/// Fill in the cells of a cold line, leaving the line itself unchanged.
+#[expect(clippy::deref_addrof)]
+unsafe fn grid_cold_read(gl: *const grid_line, to: *mut grid_line) {
+    unsafe {
+        let block = (*gl).cold;
+        let cache = &mut *&raw mut grid_cold_cache;
+        if cache.0 != block {
+            let data = grid_lz_decompress(&(*block).data, (*block).size);
+            if data.is_none() {
+                log_debug!("grid_cold_read: bad block");
+            }
+            *cache = (block, data.unwrap_or_default());
+        }
+
+        let mut r = grid_history_reader {
+            data: &cache.1,
+            offset: (&(*block).offsets)[(*gl).coldindex as usize] as usize,
+        };
+        (*to).celldata = null_mut();
+        (*to).extddata = null_mut();
+        if grid_cold_decode(&mut r, to).is_none() {
+            log_debug!("grid_cold_read: bad line");
+            free_((*to).celldata);
+            free_((*to).extddata);
+            (*to).celldata = null_mut();
+            (*to).cellsize = 0;
+            (*to).cellused = 0;
+            (*to).extddata = null_mut();
+            (*to).extdsize = 0;
+        }
+        (*to).cold = null_mut();
+        (*to).coldindex = 0;
+    }
+}
+
+/// Stop a line using its block and free the block if nothing else does.
+#[expect(clippy::deref_addrof)]
+unsafe fn grid_cold_release(gl: *mut grid_line) {
+    unsafe {
+        let block = (*gl).cold;
+        (*gl).cold = null_mut();
+
+        (*block).references -= 1;
+        if (*block).references == 0 {
+            let cache = &mut *&raw mut grid_cold_cache;
+            if cache.0 == block {
+                *cache = (null_mut(), Vec::new());
+            }
+            drop(Box::from_raw(block));
+        }
+    }
+}
+
+/// Uncompress a cold line so it can be read or changed. It will be
+/// compressed again later once it is old enough.
+unsafe fn grid_cold_unpack(gd: *mut grid, py: c_uint) {
+    unsafe {
+        let gl = grid_raw_line(gd, py);
+        let block = (*gl).cold;
+
+        grid_cold_read(gl, gl);
+        (*gl).cold = block;
+        grid_cold_release(gl);
+
+        if py < (*gd).hcold {
+            (*gd).hcold = py;
+        }
+    }
+}
+
+/// Compress the next block of history lines once there are enough lines newer
+/// than them.
+unsafe fn grid_cold_pack(gd: *mut grid) {
+    unsafe {
+        (*gd).hcold = (*gd).hcold.max((*gd).hspill);
+        if (*gd).hsize < (*gd).hcold + GRID_COLD_LINES + GRID_HOT_LINES {
+            return;
+        }
+        let first = (*gd).hcold;
+        (*gd).hcold += GRID_COLD_LINES;
+
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        let mut lines = Vec::new();
+        for yy in first..first + GRID_COLD_LINES {
+            let gl = grid_raw_line(gd, yy);
+            if !(*gl).cold.is_null()
+                || (*gl).cellsize == 0
+                || (*gl).flags.intersects(grid_line_flag::DEAD)
+            {
+                continue;
+            }
+            offsets.push(data.len() as u32);
+            grid_cold_encode(gl, &mut data);
+            lines.push(gl);
+        }
+        if lines.is_empty() {
+            return;
+        }
+
+        let block = Box::into_raw(Box::new(grid_cold_block {
+            data: grid_lz_compress(&data),
+            size: data.len(),
+            offsets,
+            references: lines.len() as u32,
+        }));
+        for (i, gl) in lines.into_iter().enumerate() {
+            free_((*gl).celldata);
+            (*gl).celldata = null_mut();
+            free_((*gl).extddata);
+            (*gl).extddata = null_mut();
+            (*gl).cold = block;
+            (*gl).coldindex = i as u32;
+        }
+    }
+}
+
+/// Get the number of cells and extended cells in a line without uncompressing
+/// it. Spilled lines are not in memory so have none.
+pub unsafe fn grid_line_sizes(gd: *mut grid, py: c_uint) -> (u32, u32) {
+    unsafe {
+        if py < (*gd).hspill {
+            return (0, 0);
+        }
+        let gl = grid_raw_line(gd, py);
+        ((*gl).cellsize, (*gl).extdsize)
+    }
+}
+
+/// Get the memory used by the lines of a grid.
+pub unsafe fn grid_history_bytes(gd: *mut grid) -> usize {
+    unsafe {
+        let mut size = 0;
+        let mut last: *mut grid_cold_block = null_mut();
+
+        for yy in (*gd).hspill..(*gd).hsize + (*gd).sy {
+            let gl = grid_raw_line(gd, yy);
+            size += size_of::<grid_line>();
+            if (*gl).cold.is_null() {
+                size += (*gl).cellsize as usize * size_of::<grid_cell_entry>();
+                size += (*gl).extdsize as usize * size_of::<grid_extd_entry>();
+            } else if (*gl).cold != last {
+                last = (*gl).cold;
+                size += size_of::<grid_cold_block>();
+                size += (*last).data.capacity() + (*last).offsets.capacity() * size_of::<u32>();
+            }
+        }
+        size
+    }
+}
+
+/// Number of spilled blocks kept uncompressed in memory for each grid.
+const GRID_SPILL_CACHE: usize = 4;
+
+/// Disk file holding spilled lines. Grids copied by grid_duplicate_spill share
+/// the file, so it counts the grids still using it.
+struct grid_spill_file {
+    fd: i32,
+    size: u64,
+    references: u32,
+}
+
+/// Block of spilled lines in the file.
+#[derive(Copy, Clone)]
+struct grid_spill_block {
+    first: u64,
+    lines: u32,
+    offset: u64,
+    length: u32,
+    size: u32,
+}
+
+/// Lines of a grid spilled to disk. Lines are numbered from the first ever
+/// spilled, so dropping the oldest blocks does not change the number of the
+/// others; base is the number of the first line still spilled.
+///
+/// The flags, time, used cells and command of each spilled line stay in marks
+/// so looking for prompts and commands never reads the file. Blocks pushed out
+/// of the cache are retired rather than freed, since a line from them may
+/// still be in use, and are only freed once the server has been back to the
+/// event loop.
+pub struct grid_spill {
+    file: *mut grid_spill_file,
+    blocks: VecDeque<grid_spill_block>,
+    base: u64,
+    marks: VecDeque<grid_line>,
+    cache: VecDeque<(u64, Vec<grid_line>)>,
+    retired: Vec<Vec<grid_line>>,
+    epoch: u64,
+}
+
+/// Number of times the server has been back to the event loop, when nothing
+/// can still be using a spilled line read before.
+static grid_spill_epoch: AtomicU64 = AtomicU64::new(0);
+
+/// Allow retired spilled lines read before now to be freed. Called each time
+/// the server goes back to the event loop.
+pub fn grid_spill_expire() {
+    grid_spill_epoch.fetch_add(1, atomic::Ordering::Relaxed);
+}
+
+/// Free retired blocks if they were read before the server last went back to
+/// the event loop.
+unsafe fn grid_spill_collect(spill: &mut grid_spill) {
+    unsafe {
+        let epoch = grid_spill_epoch.load(atomic::Ordering::Relaxed);
+        if spill.epoch != epoch {
+            for lines in std::mem::take(&mut spill.retired) {
+                grid_spill_free_lines(lines);
+            }
+            spill.epoch = epoch;
+        }
+    }
+}
+
+/// Open a new spill file next to the server socket. It is removed as soon as
+/// it is open so nothing is left behind when the server exits.
+unsafe fn grid_spill_open() -> *mut grid_spill_file {
+    static grid_spill_next: AtomicU32 = AtomicU32::new(0);
+
+    unsafe {
+        let path = format_nul!(
+            "{}-spill-{}",
+            _s(socket_path),
+            grid_spill_next.fetch_add(1, atomic::Ordering::Relaxed)
+        );
+
+        let fd = libc::open(
+            path,
+            libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+            0o600,
+        );
+        if fd == -1 {
+            log_debug!("grid_spill_open: {}: {}", _s(path), _s(strerror(errno!())));
+            free_(path);
+            return null_mut();
+        }
+        libc::unlink(path);
+        free_(path);
+
+        Box::into_raw(Box::new(grid_spill_file {
+            fd,
+            size: 0,
+            references: 1,
+        }))
+    }
+}
+
+/// Free the lines in a cached block.
+unsafe fn grid_spill_free_lines(lines: Vec<grid_line>) {
+    unsafe {
+        for mut gl in lines {
+            free_(gl.celldata);
+            free_(gl.extddata);
+            grid_command_free(&raw mut gl);
+        }
+    }
+}
+
+/// Stop a grid using its spilled lines and close the file if nothing else is
+/// using it.
+unsafe fn grid_spill_free(gd: *mut grid) {
+    unsafe {
+        let spill = Box::from_raw((*gd).spill);
+        (*gd).spill = null_mut();
+
+        for (_, lines) in spill.cache {
+            grid_spill_free_lines(lines);
+        }
+        for lines in spill.retired {
+            grid_spill_free_lines(lines);
+        }
+        grid_spill_free_lines(spill.marks.into());
+        (*spill.file).references -= 1;
+        if (*spill.file).references == 0 {
+            libc::close((*spill.file).fd);
+            drop(Box::from_raw(spill.file));
+        }
+    }
+}
+
+/// Encode a line for spilling. Unlike cold blocks, the line flags, time and
+/// command are included since the line itself is not kept.
+unsafe fn grid_spill_encode(mut gl: *const grid_line, out: &mut Vec<u8>) {
+    unsafe {
+        let mut copy: grid_line = zeroed();
+        if !(*gl).cold.is_null() {
+            copy = std::ptr::read(gl);
+            grid_cold_read(gl, &raw mut copy);
+            gl = &raw const copy;
+        }
+
+        let flags = (*gl).flags & !grid_line_flag::DEAD;
+        grid_cold_put_varint(out, flags.bits() as u32);
+        out.extend_from_slice(&(*gl).time.to_le_bytes());
+        grid_cold_put_varint(out, (*gl).cellused);
+        grid_cold_encode(gl, out);
+        grid_command_encode(gl, out);
+
+        free_(copy.celldata);
+        free_(copy.extddata);
+    }
+}
+
+/// Decode a line encoded by grid_spill_encode into gl.
+unsafe fn grid_spill_decode(r: &mut grid_history_reader, gl: *mut grid_line) -> Option<()> {
+    unsafe {
+        let flags = grid_cold_get_varint(r)?;
+        let time = r.i64()?;
+        let cellused = grid_cold_get_varint(r)?;
+        grid_cold_decode(r, gl)?;
+        grid_command_decode(r, gl)?;
+
+        (*gl).flags = grid_line_flag::from_bits_truncate(flags as i32);
+        (*gl).time = time as time_t;
+        (*gl).cellused = cellused.min((*gl).cellsize);
+        Some(())
+    }
+}
+
+/// Replace a line which could not be read with a marker saying so, rather
+/// than leaving it blank as if nothing had been there.
+unsafe fn grid_spill_lost(gl: *mut grid_line) {
+    const TEXT: &[u8] = b"[history could not be read]";
+    unsafe {
+        free_((*gl).celldata);
+        free_((*gl).extddata);
+        grid_command_free(gl);
+        *gl = zeroed();
+
+        (*gl).celldata = xreallocarray_(null_mut(), TEXT.len()).as_ptr();
+        for (i, c) in TEXT.iter().enumerate() {
+            grid_store_cell((*gl).celldata.add(i), &grid_default_cell, *c);
+        }
+        (*gl).cellsize = TEXT.len() as u32;
+        (*gl).cellused = TEXT.len() as u32;
+    }
+}
+
+/// Read the lines of a block from the file. Lines which cannot be read are
+/// replaced by a marker.
+unsafe fn grid_spill_read(file: *mut grid_spill_file, block: &grid_spill_block) -> Vec<grid_line> {
+    unsafe {
+        let mut lines: Vec<grid_line> = (0..block.lines).map(|_| zeroed()).collect();
+
+        let mut buf = vec![0u8; block.length as usize];
+        let n = libc::pread(
+            (*file).fd,
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+            block.offset as libc::off_t,
+        );
+        let data = if n == buf.len() as isize {
+            grid_lz_decompress(&buf, block.size as usize)
+        } else {
+            None
+        };
+        let Some(data) = data else {
+            log_debug!("grid_spill_read: bad block at {}", block.offset);
+            for gl in &mut lines {
+                grid_spill_lost(gl);
+            }
+            return lines;
+        };
+
+        let mut r = grid_history_reader {
+            data: &data,
+            offset: 0,
+        };
+        let mut bad = false;
+        for gl in &mut lines {
+            if bad || grid_spill_decode(&mut r, gl).is_none() {
+                if !bad {
+                    log_debug!("grid_spill_read: bad line in block at {}", block.offset);
+                }
+                bad = true;
+                grid_spill_lost(gl);
+            }
+        }
+        lines
+    }
+}
+
+/// Copy what grid_get_line_marks needs of a line being spilled.
+unsafe fn grid_spill_mark(gl: *const grid_line) -> grid_line {
+    unsafe {
+        let mut mark: grid_line = zeroed();
+        mark.flags = (*gl).flags & !grid_line_flag::DEAD;
+        mark.time = (*gl).time;
+        mark.cellused = (*gl).cellused;
+        mark.command = (*gl).command;
+        grid_command_duplicate(&raw mut mark);
+        mark
+    }
+}
+
+/// Get a spilled line, reading its block if it is not cached. The line is a
+/// copy which stays valid until the server goes back to the event loop, and it
+/// must not be changed.
+unsafe fn grid_spill_get(gd: *mut grid, py: c_uint) -> *mut grid_line {
+    unsafe {
+        let spill = &mut *(*gd).spill;
+        grid_spill_collect(spill);
+        let number = spill.base + py as u64;
+        let block = spill.blocks[spill
+            .blocks
+            .partition_point(|block| block.first + block.lines as u64 <= number)];
+
+        let lines = match spill
+            .cache
+            .iter()
+            .position(|(first, _)| *first == block.first)
+        {
+            Some(i) => {
+                let entry = spill.cache.remove(i).unwrap();
+                spill.cache.push_front(entry);
+                &mut spill.cache[0].1
+            }
+            None => {
+                if spill.cache.len() == GRID_SPILL_CACHE
+                    && let Some((_, lines)) = spill.cache.pop_back()
+                {
+                    spill.retired.push(lines);
+                }
+                spill
+                    .cache
+                    .push_front((block.first, grid_spill_read(spill.file, &block)));
+                &mut spill.cache[0].1
+            }
+        };
+        lines.as_mut_ptr().add((number - block.first) as usize)
+    }
+}
+
+/// Spill the oldest lines in memory to disk, stopping if they cannot be
+/// written. Returns the number of lines spilled.
+unsafe fn grid_spill_lines(gd: *mut grid, ny: c_uint) -> c_uint {
+    unsafe {
+        if (*gd).spill.is_null() {
+            let file = grid_spill_open();
+            if file.is_null() {
+                return 0;
+            }
+            (*gd).spill = Box::into_raw(Box::new(grid_spill {
+                file,
+                blocks: VecDeque::new(),
+                base: 0,
+                marks: VecDeque::new(),
+                cache: VecDeque::new(),
+                retired: Vec::new(),
+                epoch: 0,
+            }));
+        }
+        let spill = &mut *(*gd).spill;
+        let file = spill.file;
+        grid_spill_collect(spill);
+
+        let mut done = 0;
+        while done < ny {
+            let lines = (ny - done).min(GRID_COLD_LINES);
+            let mut data = Vec::new();
+            for yy in 0..lines {
+                grid_spill_encode(grid_raw_line(gd, (*gd).hspill + done + yy), &mut data);
+            }
+            let compressed = grid_lz_compress(&data);
+
+            let n = libc::pwrite(
+                (*file).fd,
+                compressed.as_ptr().cast(),
+                compressed.len(),
+                (*file).size as libc::off_t,
+            );
+            if n != compressed.len() as isize {
+                log_debug!("grid_spill_lines: write failed: {}", _s(strerror(errno!())));
+                break;
+            }
+            spill.blocks.push_back(grid_spill_block {
+                first: spill.base + ((*gd).hspill + done) as u64,
+                lines,
+                offset: (*file).size,
+                length: compressed.len() as u32,
+                size: data.len() as u32,
+            });
+            (*file).size += compressed.len() as u64;
+            for yy in 0..lines {
+                spill
+                    .marks
+                    .push_back(grid_spill_mark(grid_raw_line(gd, (*gd).hspill + done + yy)));
+            }
+            done += lines;
+        }
+
+        // The spilled lines are now gone from memory but keep their numbers.
+        if done != 0 {
+            grid_trim_history(gd, done);
+            (*gd).hspill += done;
+            (*gd).hcold += done;
+            (*gd).hreflow += done;
+        }
+        done
+    }
+}
+
+/// Drop the oldest spilled blocks until no more than keep lines are spilled,
+/// then reclaim the space in the file once it is mostly unused.
+unsafe fn grid_spill_trim(gd: *mut grid, keep: c_uint) {
+    unsafe {
+        if (*gd).spill.is_null() {
+            return;
+        }
+        let spill = &mut *(*gd).spill;
+        let file = spill.file;
+        grid_spill_collect(spill);
+
+        let mut ny = 0;
+        while (*gd).hspill - ny > keep
+            && let Some(block) = spill.blocks.pop_front()
+        {
+            ny += block.lines;
+        }
+        if ny == 0 {
+            return;
+        }
+        grid_spill_free_lines(spill.marks.drain(..ny as usize).collect());
+        spill.base += ny as u64;
+        (*gd).hspill -= ny;
+        (*gd).hsize -= ny;
+        (*gd).hcold -= ny.min((*gd).hcold);
+        (*gd).hreflow -= ny.min((*gd).hreflow);
+        (*gd).hscrolled = (*gd).hscrolled.min((*gd).hsize - (*gd).hspill);
+
+        if (*file).references != 1 {
+            return;
+        }
+        let start = spill
+            .blocks
+            .front()
+            .map_or((*file).size, |block| block.offset);
+        if start == 0 || start < (*file).size - start {
+            return;
+        }
+        let mut buf = vec![0u8; 65536];
+        let mut offset = start;
+        while offset < (*file).size {
+            let n = libc::pread(
+                (*file).fd,
+                buf.as_mut_ptr().cast(),
+                buf.len().min(((*file).size - offset) as usize),
+                offset as libc::off_t,
+            );
+            if n <= 0
+                || libc::pwrite(
+                    (*file).fd,
+                    buf.as_ptr().cast(),
+                    n as usize,
+                    (offset - start) as libc::off_t,
+                ) != n
+            {
+                log_debug!("grid_spill_trim: move failed: {}", _s(strerror(errno!())));
+                return;
+            }
+            offset += n as u64;
+        }
+        (*file).size -= start;
+        libc::ftruncate((*file).fd, (*file).size as libc::off_t);
+        for block in &mut spill.blocks {
+            block.offset -= start;
+        }
+    }
+}
+
+/// Share the spilled lines of src with dst, which must not have any history
+/// yet. The same number of lines from the top of dst are moved into its
+/// history to take their place. Returns the number of lines shared.
+pub unsafe fn grid_duplicate_spill(dst: *mut grid, src: *mut grid) -> c_uint {
+    unsafe {
+        let ny = (*src).hspill;
+        if ny == 0 || (*dst).hsize != 0 || !(*dst).spill.is_null() || (*dst).sy < ny {
+            return 0;
+        }
+        let spill = &*(*src).spill;
+
+        grid_trim_history(dst, ny);
+        (*dst).sy -= ny;
+        (*dst).hsize = ny;
+        (*dst).hspill = ny;
+        (*dst).hcold = ny;
+        (*dst).hreflow = ny;
+        (*dst).slimit = (*src).slimit;
+        grid_adjust_lines(dst, (*dst).hsize + (*dst).sy);
+
+        (*spill.file).references += 1;
+        (*dst).spill = Box::into_raw(Box::new(grid_spill {
+            file: spill.file,
+            blocks: spill.blocks.clone(),
+            base: spill.base,
+            marks: spill.marks.iter().map(|gl| grid_spill_mark(gl)).collect(),
+            cache: VecDeque::new(),
+            retired: Vec::new(),
+            epoch: 0,
+        }));
+        ny
+    }
+}
+
+/// Get a line which is in memory without uncompressing it.
+unsafe fn grid_raw_line(gd: *mut grid, py: c_uint) -> *mut grid_line {
+    unsafe { (*gd).linedata.add((py - (*gd).hspill) as usize) }
+}
+
+/// Get line data, uncompressing it if it is in cold history or reading it if
+/// it has been spilled. A spilled line is a copy which must not be changed.
+pub unsafe fn grid_get_line(gd: *mut grid, line: c_uint) -> *mut grid_line {
+    unsafe {
+        if line < (*gd).hspill {
+            return grid_spill_get(gd, line);
+        }
+        let gl = grid_raw_line(gd, line);
+        if !(*gl).cold.is_null() {
+            grid_cold_unpack(gd, line);
+        }
+        gl
+    }
+}
+
+/// Get a line to look at its flags or command. Unlike grid_get_line, a cold
+/// line is not uncompressed and a spilled line is not read, so its cells must
+/// not be used.
+pub unsafe fn grid_get_line_marks(gd: *mut grid, line: c_uint) -> *mut grid_line {
+    unsafe {
+        if line < (*gd).hspill {
+            return &raw mut (&mut (*(*gd).spill).marks)[line as usize];
+        }
+        grid_raw_line(gd, line)
+    }
+}
+
+/// Mark the output of a command as starting on a line.
+pub unsafe fn grid_start_command(gd: *mut grid, py: c_uint, text: String) {
+    unsafe {
+        if grid_check_write(gd, c"grid_start_command".as_ptr(), py) != 0 {
+            return;
+        }
+        let gl = grid_get_line(gd, py);
+
+        grid_command_free(gl);
+        (*gl).command = Box::into_raw(Box::new(grid_command {
+            text,
+            ..Default::default()
+        }));
+        (*gl).flags |= grid_line_flag::START_OUTPUT;
+    }
+}
+
+/// Mark the end of the output of the unfinished command above a line. If the
+/// output ended at the start of the line, the line itself is not part of it.
+/// Returns the command or null if there is none.
+pub unsafe fn grid_end_command(
+    gd: *mut grid,
+    py: c_uint,
+    at_start: bool,
+    status: Option<i32>,
+) -> *mut grid_command {
+    unsafe {
+        let mut yy = py;
+        while yy >= (*gd).hspill {
+            let gl = grid_raw_line(gd, yy);
+            if (*gl).flags.intersects(grid_line_flag::START_OUTPUT) {
+                let command = (*gl).command;
+                if command.is_null() || (*command).finished {
+                    return null_mut();
+                }
+                (*command).finished = true;
+                (*command).status = status;
+
+                if !at_start {
+                    (*grid_raw_line(gd, py)).flags |= grid_line_flag::END_OUTPUT;
+                } else if py != yy {
+                    (*grid_raw_line(gd, py - 1)).flags |= grid_line_flag::END_OUTPUT;
+                }
+                return command;
+            }
+            if yy == 0 {
+                break;
+            }
+            yy -= 1;
+        }
+        null_mut()
+    }
+}
+
+/// Find the output of the command a line belongs to, returning its first line
+/// and the line after its last. A prompt or command line belongs to the
+/// command run from it.
+pub unsafe fn grid_find_command(gd: *mut grid, py: c_uint) -> Option<(u32, u32)> {
+    unsafe {
+        let total = (*gd).hsize + (*gd).sy;
+        let prompt = grid_line_flag::START_PROMPT | grid_line_flag::START_INPUT;
+
+        let mut start = None;
+        let mut yy = py;
+        loop {
+            let flags = (*grid_get_line_marks(gd, yy)).flags;
+            if flags.intersects(prompt) {
+                break;
+            }
+            if flags.intersects(grid_line_flag::START_OUTPUT) {
+                start = Some(yy);
+                break;
+            }
+            if yy == 0 {
+                return None;
+            }
+            yy -= 1;
+        }
+        if start.is_none() {
+            for yy in py + 1..total {
+                let flags = (*grid_get_line_marks(gd, yy)).flags;
+                if flags.intersects(grid_line_flag::START_OUTPUT) {
+                    start = Some(yy);
+                    break;
+                }
+                if flags.intersects(grid_line_flag::START_PROMPT) {
+                    return None;
+                }
             }
         }
+        let start = start?;
+        Some((start, grid_command_end(gd, start)))
+    }
+}
 
-        if new_extdsize == 0 {
-            free_((*gl).extddata);
-            (*gl).extddata = null_mut();
-            (*gl).extdsize = 0;
-            return;
+/// Find the output of the nth last command, counting from one.
+pub unsafe fn grid_find_last_command(gd: *mut grid, n: u32) -> Option<(u32, u32)> {
+    unsafe {
+        if n == 0 {
+            return None;
         }
-
-        // Allocate new array
-        let new_extddata: *mut grid_extd_entry =
-            xreallocarray_(null_mut(), new_extdsize as usize).as_ptr();
-
-        let mut idx = 0;
-        for px in 0..(*gl).cellsize {
-            let gce = (*gl).celldata.add(px as usize);
-            if (*gce).flags.contains(grid_flag::EXTENDED) {
-                let gee = (*gl).extddata.add((*gce).union_.offset as usize);
-                std::ptr::copy_nonoverlapping(
-                    gee as *const grid_extd_entry,
-                    new_extddata.add(idx as usize),
-                    1,
-                );
-                (*gce).union_.offset = idx;
-                idx += 1;
+        let mut left = n;
+        for yy in (0..(*gd).hsize + (*gd).sy).rev() {
+            if (*grid_get_line_marks(gd, yy))
+                .flags
+                .intersects(grid_line_flag::START_OUTPUT)
+            {
+                left -= 1;
+                if left == 0 {
+                    return Some((yy, grid_command_end(gd, yy)));
+                }
             }
         }
-
-        free_((*gl).extddata);
-        (*gl).extddata = new_extddata;
-        (*gl).extdsize = new_extdsize;
+        None
     }
 }
 
-/// Get line data.
-pub unsafe fn grid_get_line(gd: *mut grid, line: c_uint) -> *mut grid_line {
-    unsafe { (*gd).linedata.add(line as usize) }
+/// Find the line after the last line of output of a command starting at a
+/// line, ignoring any empty lines at the end.
+unsafe fn grid_command_end(gd: *mut grid, start: u32) -> u32 {
+    unsafe {
+        let total = (*gd).hsize + (*gd).sy;
+
+        let mut end = total;
+        for yy in start..total {
+            let flags = (*grid_get_line_marks(gd, yy)).flags;
+            if flags.intersects(grid_line_flag::END_OUTPUT) {
+                end = yy + 1;
+                while end < total
+                    && (*grid_get_line_marks(gd, end - 1))
+                        .flags
+                        .intersects(grid_line_flag::WRAPPED)
+                {
+                    end += 1;
+                }
+                break;
+            }
+            if flags.intersects(grid_line_flag::START_PROMPT)
+                || (yy != start && flags.intersects(grid_line_flag::START_OUTPUT))
+            {
+                end = yy;
+                break;
+            }
+        }
+        while end > start && (*grid_get_line_marks(gd, end - 1)).cellused == 0 {
+            end -= 1;
+        }
+        end
+    }
 }
 
 /// Adjust number of lines.
 pub unsafe fn grid_adjust_lines(gd: *mut grid, lines: c_uint) {
     unsafe {
-        (*gd).linedata = xreallocarray_((*gd).linedata, lines as usize).as_ptr();
+        (*gd).linedata = xreallocarray_((*gd).linedata, (lines - (*gd).hspill) as usize).as_ptr();
     }
 }
 
 /// Copy default into a cell.
 pub unsafe fn grid_clear_cell(gd: *mut grid, px: c_uint, py: c_uint, bg: c_uint) {
     unsafe {
-        let gl = (*gd).linedata.add(py as usize);
+        let gl = grid_get_line(gd, py);
         let gce = (*gl).celldata.add(px as usize);
         std::ptr::copy_nonoverlapping(&raw const grid_cleared_entry, gce, 1);
         if bg != 8 {
@@ -253,6 +1361,18 @@ pub unsafe fn grid_check_y(gd: *mut grid, from: *const c_char, py: c_uint) -> c_
     0
 }
 
+/// Check a line can be changed. Spilled lines are only copies of what is on
+/// disk, so changes to them would be lost.
+unsafe fn grid_check_write(gd: *mut grid, from: *const c_char, py: c_uint) -> c_int {
+    unsafe {
+        if py < (*gd).hspill {
+            log_debug!("{}: y is spilled: {}", _s(from), py);
+            return -1;
+        }
+    }
+    0
+}
+
 /// Check if two styles are (visibly) the same.
 pub unsafe fn grid_cells_look_equal(gc1: *const grid_cell, gc2: *const grid_cell) -> c_int {
     unsafe {
@@ -297,10 +1417,18 @@ pub unsafe fn grid_cells_equal(gc1: *const grid_cell, gc2: *const grid_cell) ->
 /// Free one line.
 pub unsafe fn grid_free_line(gd: *mut grid, py: c_uint) {
     unsafe {
-        free_((*(*gd).linedata.add(py as usize)).celldata);
-        (*(*gd).linedata.add(py as usize)).celldata = null_mut();
-        free_((*(*gd).linedata.add(py as usize)).extddata);
-        (*(*gd).linedata.add(py as usize)).extddata = null_mut();
+        if py < (*gd).hspill {
+            return;
+        }
+        let gl = grid_raw_line(gd, py);
+        if !(*gl).cold.is_null() {
+            grid_cold_release(gl);
+        }
+        free_((*gl).celldata);
+        (*gl).celldata = null_mut();
+        free_((*gl).extddata);
+        (*gl).extddata = null_mut();
+        grid_command_free(gl);
     }
 }
 
@@ -324,6 +1452,11 @@ pub unsafe fn grid_create(sx: u32, sy: u32, hlimit: u32) -> *mut grid {
         (*gd).hscrolled = 0;
         (*gd).hsize = 0;
         (*gd).hlimit = hlimit;
+        (*gd).hcold = 0;
+        (*gd).hspill = 0;
+        (*gd).hreflow = 0;
+        (*gd).slimit = 0;
+        (*gd).spill = null_mut();
 
         if (*gd).sy != 0 {
             (*gd).linedata = xcalloc_::<grid_line>((*gd).sy as usize).as_ptr();
@@ -338,7 +1471,10 @@ pub unsafe fn grid_create(sx: u32, sy: u32, hlimit: u32) -> *mut grid {
 /// Destroy grid.
 pub unsafe fn grid_destroy(gd: *mut grid) {
     unsafe {
-        grid_free_lines(gd, 0, (*gd).hsize + (*gd).sy);
+        grid_free_lines(gd, (*gd).hspill, (*gd).hsize - (*gd).hspill + (*gd).sy);
+        if !(*gd).spill.is_null() {
+            grid_spill_free(gd);
+        }
         free_((*gd).linedata);
         free_(gd);
     }
@@ -352,8 +1488,8 @@ pub unsafe fn grid_compare(ga: *mut grid, gb: *mut grid) -> c_int {
         }
 
         for yy in 0..(*ga).sy {
-            let gla = &mut (*(*ga).linedata.add(yy as usize));
-            let glb = &mut (*(*gb).linedata.add(yy as usize));
+            let gla = &mut (*grid_get_line(ga, yy));
+            let glb = &mut (*grid_get_line(gb, yy));
 
             if gla.cellsize != glb.cellsize {
                 return 1;
@@ -392,22 +1528,26 @@ pub unsafe fn grid_compare(ga: *mut grid, gb: *mut grid) -> c_int {
     }
 }
 
-/// Trim lines from the history.
+/// Trim the oldest lines in memory from the history.
 unsafe fn grid_trim_history(gd: *mut grid, ny: c_uint) {
     unsafe {
-        grid_free_lines(gd, 0, ny);
+        grid_free_lines(gd, (*gd).hspill, ny);
         libc::memmove(
             (*gd).linedata as *mut c_void,
             (*gd).linedata.add(ny as usize) as *const c_void,
-            ((*gd).hsize + (*gd).sy - ny) as usize * size_of::<grid_line>(),
+            ((*gd).hsize - (*gd).hspill + (*gd).sy - ny) as usize * size_of::<grid_line>(),
         );
+        (*gd).hcold -= ny.min((*gd).hcold - (*gd).hspill);
+        (*gd).hreflow -= ny.min((*gd).hreflow - (*gd).hspill);
     }
 }
 
-/// Collect lines from the history if at the limit. Free the top (oldest) 10% and shift up.
+/// Collect lines from the history if at the limit. Spill the top (oldest) 10%
+/// of the lines in memory to disk if enabled, otherwise free them and shift up.
 pub unsafe fn grid_collect_history(gd: *mut grid) {
     unsafe {
-        if (*gd).hsize == 0 || (*gd).hsize < (*gd).hlimit {
+        let hsize = (*gd).hsize - (*gd).hspill;
+        if hsize == 0 || hsize < (*gd).hlimit {
             return;
         }
 
@@ -415,16 +1555,22 @@ pub unsafe fn grid_collect_history(gd: *mut grid) {
         if ny < 1 {
             ny = 1;
         }
-        if ny > (*gd).hsize {
-            ny = (*gd).hsize;
+        if ny > hsize {
+            ny = hsize;
+        }
+
+        // Any lines which could not be spilled are freed instead.
+        if (*gd).slimit != 0 {
+            ny -= grid_spill_lines(gd, ny);
+            grid_spill_trim(gd, (*gd).slimit);
         }
 
         // Free the lines from 0 to ny then move the remaining lines over them.
         grid_trim_history(gd, ny);
 
         (*gd).hsize -= ny;
-        if (*gd).hscrolled > (*gd).hsize {
-            (*gd).hscrolled = (*gd).hsize;
+        if (*gd).hscrolled > (*gd).hsize - (*gd).hspill {
+            (*gd).hscrolled = (*gd).hsize - (*gd).hspill;
         }
     }
 }
@@ -432,13 +1578,15 @@ pub unsafe fn grid_collect_history(gd: *mut grid) {
 /// Remove lines from the bottom of the history.
 pub unsafe fn grid_remove_history(gd: *mut grid, ny: c_uint) {
     unsafe {
-        if ny > (*gd).hsize {
+        if ny > (*gd).hsize - (*gd).hspill {
             return;
         }
         for yy in 0..ny {
             grid_free_line(gd, (*gd).hsize + (*gd).sy - 1 - yy);
         }
         (*gd).hsize -= ny;
+        (*gd).hcold = (*gd).hcold.clamp((*gd).hspill, (*gd).hsize);
+        (*gd).hreflow = (*gd).hreflow.clamp((*gd).hspill, (*gd).hsize);
     }
 }
 
@@ -447,24 +1595,29 @@ pub unsafe fn grid_remove_history(gd: *mut grid, ny: c_uint) {
 pub unsafe fn grid_scroll_history(gd: *mut grid, bg: c_uint) {
     unsafe {
         let yy = (*gd).hsize + (*gd).sy;
-        (*gd).linedata = xreallocarray_((*gd).linedata, (yy + 1) as usize).as_ptr();
+        grid_adjust_lines(gd, yy + 1);
 
         grid_empty_line(gd, yy, bg);
 
         (*gd).hscrolled += 1;
-        grid_compact_line(&mut (*(*gd).linedata.add((*gd).hsize as usize)));
-        (*(*gd).linedata.add((*gd).hsize as usize)).time = current_time;
+        grid_compact_line(grid_get_line(gd, (*gd).hsize));
+        (*grid_raw_line(gd, (*gd).hsize)).time = current_time;
         (*gd).hsize += 1;
+
+        grid_cold_pack(gd);
     }
 }
 
 /// Clear the history.
 pub unsafe fn grid_clear_history(gd: *mut grid) {
     unsafe {
+        grid_spill_trim(gd, 0);
         grid_trim_history(gd, (*gd).hsize);
 
         (*gd).hscrolled = 0;
         (*gd).hsize = 0;
+        (*gd).hcold = 0;
+        (*gd).hreflow = 0;
 
         (*gd).linedata = xreallocarray_((*gd).linedata, (*gd).sy as usize).as_ptr();
     }
@@ -481,15 +1634,15 @@ pub unsafe fn grid_scroll_history_region(
         let yy = (*gd).hsize + (*gd).sy;
 
         // Create space for new line
-        (*gd).linedata = xreallocarray_((*gd).linedata, (yy + 1) as usize).as_ptr();
+        grid_adjust_lines(gd, yy + 1);
 
         // Move screen down to free space
-        let gl_history = (*gd).linedata.add((*gd).hsize as usize);
+        let gl_history = grid_raw_line(gd, (*gd).hsize);
         std::ptr::copy(gl_history, gl_history.add(1), (*gd).sy as usize);
 
         // Adjust region and find start/end
         upper += 1;
-        let gl_upper = (*gd).linedata.add(upper as usize);
+        let gl_upper = grid_raw_line(gd, upper);
         lower += 1;
 
         // Move line into history
@@ -503,13 +1656,15 @@ pub unsafe fn grid_scroll_history_region(
         // Move history offset down
         (*gd).hscrolled += 1;
         (*gd).hsize += 1;
+
+        grid_cold_pack(gd);
     }
 }
 
 /// Expand line to fit to cell.
 unsafe fn grid_expand_line(gd: *mut grid, py: c_uint, mut sx: c_uint, bg: c_uint) {
     unsafe {
-        let gl = (*gd).linedata.add(py as usize);
+        let gl = grid_get_line(gd, py);
         if sx <= (*gl).cellsize {
             return;
         }
@@ -534,7 +1689,7 @@ unsafe fn grid_expand_line(gd: *mut grid, py: c_uint, mut sx: c_uint, bg: c_uint
 /// Empty a line and set background colour if needed.
 pub unsafe fn grid_empty_line(gd: *mut grid, py: c_uint, bg: c_uint) {
     unsafe {
-        (*gd).linedata.add(py as usize).write(zeroed());
+        grid_raw_line(gd, py).write(zeroed());
         if !COLOUR_DEFAULT(bg as i32) {
             grid_expand_line(gd, py, (*gd).sx, bg);
         }
@@ -547,7 +1702,7 @@ pub unsafe fn grid_peek_line(gd: *mut grid, py: c_uint) -> *mut grid_line {
         if grid_check_y(gd, c"grid_peek_line".as_ptr(), py) != 0 {
             return null_mut();
         }
-        (*gd).linedata.add(py as usize)
+        grid_get_line(gd, py)
     }
 }
 
@@ -591,12 +1746,15 @@ unsafe fn grid_get_cell1(gl: *mut grid_line, px: c_uint, gc: *mut grid_cell) {
 /// Get cell for reading.
 pub unsafe fn grid_get_cell(gd: *mut grid, px: c_uint, py: c_uint, gc: *mut grid_cell) {
     unsafe {
-        if grid_check_y(gd, c"grid_get_cell".as_ptr(), py) != 0
-            || px >= (*(*gd).linedata.add(py as usize)).cellsize
-        {
+        if grid_check_y(gd, c"grid_get_cell".as_ptr(), py) != 0 {
+            std::ptr::copy(&raw const grid_default_cell, gc, 1);
+            return;
+        }
+        let gl = grid_get_line(gd, py);
+        if px >= (*gl).cellsize {
             std::ptr::copy(&raw const grid_default_cell, gc, 1);
         } else {
-            grid_get_cell1((*gd).linedata.add(py as usize), px, gc);
+            grid_get_cell1(gl, px, gc);
         }
     }
 }
@@ -604,13 +1762,15 @@ pub unsafe fn grid_get_cell(gd: *mut grid, px: c_uint, py: c_uint, gc: *mut grid
 /// Set cell at position.
 pub unsafe fn grid_set_cell(gd: *mut grid, px: c_uint, py: c_uint, gc: *const grid_cell) {
     unsafe {
-        if grid_check_y(gd, c"grid_set_cell".as_ptr(), py) != 0 {
+        if grid_check_y(gd, c"grid_set_cell".as_ptr(), py) != 0
+            || grid_check_write(gd, c"grid_set_cell".as_ptr(), py) != 0
+        {
             return;
         }
 
         grid_expand_line(gd, py, px + 1, 8);
 
-        let gl = &mut (*(*gd).linedata.add(py as usize));
+        let gl = &mut (*grid_get_line(gd, py));
         if px + 1 > gl.cellused {
             gl.cellused = px + 1;
         }
@@ -641,13 +1801,15 @@ pub unsafe fn grid_set_cells(
     slen: usize,
 ) {
     unsafe {
-        if grid_check_y(gd, c"grid_set_cells".as_ptr(), py) != 0 {
+        if grid_check_y(gd, c"grid_set_cells".as_ptr(), py) != 0
+            || grid_check_write(gd, c"grid_set_cells".as_ptr(), py) != 0
+        {
             return;
         }
 
         grid_expand_line(gd, py, px + slen as c_uint, 8);
 
-        let gl = (*gd).linedata.add(py as usize);
+        let gl = grid_get_line(gd, py);
         if px + slen as c_uint > (*gl).cellused {
             (*gl).cellused = px + slen as c_uint;
         }
@@ -683,7 +1845,9 @@ pub unsafe fn grid_clear(
             return;
         }
 
-        if grid_check_y(gd, c"grid_clear".as_ptr(), py) != 0 {
+        if grid_check_y(gd, c"grid_clear".as_ptr(), py) != 0
+            || grid_check_write(gd, c"grid_clear".as_ptr(), py) != 0
+        {
             return;
         }
         if grid_check_y(gd, c"grid_clear".as_ptr(), py + ny - 1) != 0 {
@@ -691,7 +1855,7 @@ pub unsafe fn grid_clear(
         }
 
         for yy in py..py + ny {
-            let gl = (*gd).linedata.add(yy as usize);
+            let gl = grid_get_line(gd, yy);
 
             let mut sx = (*gd).sx;
             if sx > (*gl).cellsize {
@@ -722,7 +1886,9 @@ pub unsafe fn grid_clear_lines(gd: *mut grid, py: c_uint, ny: c_uint, bg: c_uint
             return;
         }
 
-        if grid_check_y(gd, c"grid_clear_lines".as_ptr(), py) != 0 {
+        if grid_check_y(gd, c"grid_clear_lines".as_ptr(), py) != 0
+            || grid_check_write(gd, c"grid_clear_lines".as_ptr(), py) != 0
+        {
             return;
         }
         if grid_check_y(gd, c"grid_clear_lines".as_ptr(), py + ny - 1) != 0 {
@@ -733,8 +1899,8 @@ pub unsafe fn grid_clear_lines(gd: *mut grid, py: c_uint, ny: c_uint, bg: c_uint
             grid_free_line(gd, yy);
             grid_empty_line(gd, yy, bg);
         }
-        if py != 0 {
-            (*(*gd).linedata.add(py as usize - 1)).flags &= !grid_line_flag::WRAPPED;
+        if py > (*gd).hspill {
+            (*grid_get_line(gd, py - 1)).flags &= !grid_line_flag::WRAPPED;
         }
     }
 }
@@ -746,13 +1912,17 @@ pub unsafe fn grid_move_lines(gd: *mut grid, dy: c_uint, py: c_uint, ny: c_uint,
             return;
         }
 
-        if grid_check_y(gd, c"grid_move_lines".as_ptr(), py) != 0 {
+        if grid_check_y(gd, c"grid_move_lines".as_ptr(), py) != 0
+            || grid_check_write(gd, c"grid_move_lines".as_ptr(), py) != 0
+        {
             return;
         }
         if grid_check_y(gd, c"grid_move_lines".as_ptr(), py + ny - 1) != 0 {
             return;
         }
-        if grid_check_y(gd, c"grid_move_lines".as_ptr(), dy) != 0 {
+        if grid_check_y(gd, c"grid_move_lines".as_ptr(), dy) != 0
+            || grid_check_write(gd, c"grid_move_lines".as_ptr(), dy) != 0
+        {
             return;
         }
         if grid_check_y(gd, c"grid_move_lines".as_ptr(), dy + ny - 1) != 0 {
@@ -766,13 +1936,13 @@ pub unsafe fn grid_move_lines(gd: *mut grid, dy: c_uint, py: c_uint, ny: c_uint,
             }
             grid_free_line(gd, yy);
         }
-        if dy != 0 {
-            (*(*gd).linedata.add(dy as usize - 1)).flags &= !grid_line_flag::WRAPPED;
+        if dy > (*gd).hspill {
+            (*grid_get_line(gd, dy - 1)).flags &= !grid_line_flag::WRAPPED;
         }
 
         // Move the lines
-        let src = (*gd).linedata.add(py as usize);
-        let dst = (*gd).linedata.add(dy as usize);
+        let src = grid_raw_line(gd, py);
+        let dst = grid_raw_line(gd, dy);
         std::ptr::copy(src, dst, ny as usize);
 
         // Wipe any lines that have been moved (without freeing them - they are still present)
@@ -781,8 +1951,8 @@ pub unsafe fn grid_move_lines(gd: *mut grid, dy: c_uint, py: c_uint, ny: c_uint,
                 grid_empty_line(gd, yy, bg);
             }
         }
-        if py != 0 && (py < dy || py >= dy + ny) {
-            (*(*gd).linedata.add(py as usize - 1)).flags &= !grid_line_flag::WRAPPED;
+        if py > (*gd).hspill && (py < dy || py >= dy + ny) {
+            (*grid_get_line(gd, py - 1)).flags &= !grid_line_flag::WRAPPED;
         }
     }
 }
@@ -801,10 +1971,12 @@ pub unsafe fn grid_move_cells(
             return;
         }
 
-        if grid_check_y(gd, c"grid_move_cells".as_ptr(), py) != 0 {
+        if grid_check_y(gd, c"grid_move_cells".as_ptr(), py) != 0
+            || grid_check_write(gd, c"grid_move_cells".as_ptr(), py) != 0
+        {
             return;
         }
-        let gl = (*gd).linedata.add(py as usize);
+        let gl = grid_get_line(gd, py);
 
         grid_expand_line(gd, py, px + nx, 8);
         grid_expand_line(gd, py, dx + nx, 8);
@@ -1343,11 +2515,18 @@ pub unsafe fn grid_duplicate_lines(
         grid_free_lines(dst, dy, ny);
 
         for _ in 0..ny {
-            let srcl = (*src).linedata.add(sy as usize);
-            let dstl = (*dst).linedata.add(dy as usize);
+            let srcl = if sy < (*src).hspill {
+                grid_get_line(src, sy)
+            } else {
+                grid_raw_line(src, sy)
+            };
+            let dstl = grid_raw_line(dst, dy);
 
             std::ptr::copy_nonoverlapping(srcl, dstl, 1);
-            if (*srcl).cellsize != 0 {
+            grid_command_duplicate(dstl);
+            if !(*srcl).cold.is_null() {
+                (*(*srcl).cold).references += 1;
+            } else if (*srcl).cellsize != 0 {
                 (*dstl).celldata =
                     xreallocarray_::<grid_cell_entry>(null_mut(), (*srcl).cellsize as usize)
                         .as_ptr();
@@ -1359,7 +2538,7 @@ pub unsafe fn grid_duplicate_lines(
             } else {
                 (*dstl).celldata = null_mut();
             }
-            if (*srcl).extdsize != 0 {
+            if (*srcl).cold.is_null() && (*srcl).extdsize != 0 {
                 (*dstl).extdsize = (*srcl).extdsize;
                 (*dstl).extddata =
                     xreallocarray_::<grid_extd_entry>(null_mut(), (*dstl).extdsize as usize)
@@ -1433,7 +2612,7 @@ unsafe fn grid_reflow_join(
         // Add a new target line
         let (to, gl) = if already == 0 {
             let to = (*target).sy;
-            let gl = grid_reflow_move(target, (*gd).linedata.add(yy as usize));
+            let gl = grid_reflow_move(target, grid_raw_line(gd, yy));
             (to, gl)
         } else {
             let to = (*target).sy - 1;
@@ -1451,13 +2630,13 @@ unsafe fn grid_reflow_join(
             let line = yy + 1 + lines;
 
             // If next line is empty, skip it
-            if !(*(*gd).linedata.add(line as usize))
+            if !(*grid_raw_line(gd, line))
                 .flags
                 .intersects(grid_line_flag::WRAPPED)
             {
                 wrapped = 0;
             }
-            if (*(*gd).linedata.add(line as usize)).cellused == 0 {
+            if (*grid_raw_line(gd, line)).cellused == 0 {
                 if wrapped == 0 {
                     break;
                 }
@@ -1466,7 +2645,7 @@ unsafe fn grid_reflow_join(
             }
 
             // Is destination line now full? Copy first char separately
-            grid_get_cell1((*gd).linedata.add(line as usize), 0, &mut gc);
+            grid_get_cell1(grid_get_line(gd, line), 0, &mut gc);
             if width + gc.data.width as u32 > sx {
                 break;
             }
@@ -1475,7 +2654,7 @@ unsafe fn grid_reflow_join(
             at += 1;
 
             // Join as much more as possible onto current line
-            from = (*gd).linedata.add(line as usize);
+            from = grid_get_line(gd, line);
             want = 1;
             while want < (*from).cellused {
                 grid_get_cell1(from, want, &mut gc);
@@ -1512,11 +2691,12 @@ unsafe fn grid_reflow_join(
             (*gl).flags &= !grid_line_flag::WRAPPED;
         }
 
-        // Remove lines that were completely consumed
+        // Remove lines that were completely consumed, keeping the end of any
+        // command output on them
         for i in (yy + 1)..(yy + 1 + lines) {
-            free((*(*gd).linedata.add(i as usize)).celldata.cast());
-            free((*(*gd).linedata.add(i as usize)).extddata.cast());
-            grid_reflow_dead((*gd).linedata.add(i as usize));
+            (*gl).flags |= (*grid_raw_line(gd, i)).flags & grid_line_flag::END_OUTPUT;
+            grid_free_line(gd, i);
+            grid_reflow_dead(grid_raw_line(gd, i));
         }
 
         // Adjust scroll position
@@ -1531,7 +2711,7 @@ unsafe fn grid_reflow_join(
 /// Split this line into several new ones
 pub unsafe fn grid_reflow_split(target: *mut grid, gd: *mut grid, sx: u32, yy: u32, at: u32) {
     unsafe {
-        let gl = (*gd).linedata.add(yy as usize);
+        let gl = grid_get_line(gd, yy);
         let mut gc = zeroed();
         let used = (*gl).cellused;
         let flags = (*gl).flags;
@@ -1597,19 +2777,44 @@ pub unsafe fn grid_reflow_split(target: *mut grid, gd: *mut grid, sx: u32, yy: u
     }
 }
 
-/// Reflow lines on grid to new width
-pub unsafe fn grid_reflow(gd: *mut grid, sx: u32) {
+/// Find the first line of the wrapped line containing a line in memory.
+unsafe fn grid_reflow_first(gd: *mut grid, mut py: u32) -> u32 {
+    unsafe {
+        while py > (*gd).hspill
+            && (*grid_raw_line(gd, py - 1))
+                .flags
+                .intersects(grid_line_flag::WRAPPED)
+        {
+            py -= 1;
+        }
+        py
+    }
+}
+
+/// Reflow the lines from start to end to a new width. Both must be the first
+/// line of a wrapped line or the end of the grid.
+unsafe fn grid_reflow_lines(gd: *mut grid, sx: u32, start: u32, end: u32) {
     unsafe {
         // Create destination grid - just used as container for line data
         let target = grid_create((*gd).sx, 0, 0);
 
         // Loop over each source line
-        for yy in 0..((*gd).hsize + (*gd).sy) {
-            let gl = (*gd).linedata.add(yy as usize);
+        for yy in start..end {
+            let mut gl = grid_raw_line(gd, yy);
             if (*gl).flags.intersects(grid_line_flag::DEAD) {
                 continue;
             }
 
+            // Cold lines can be moved across as they are unless they need to
+            // be split or joined
+            if (*gl).cellused > sx
+                || (*gl)
+                    .flags
+                    .intersects(grid_line_flag::EXTENDED | grid_line_flag::WRAPPED)
+            {
+                gl = grid_get_line(gd, yy);
+            }
+
             // Work out width of this line. at is point where available width is hit,
             // width is full line width
             let mut at = 0;
@@ -1653,39 +2858,102 @@ pub unsafe fn grid_reflow(gd: *mut grid, sx: u32) {
             }
         }
 
-        // Replace old grid with new
-        if (*target).sy < (*gd).sy {
-            grid_reflow_add(target, (*gd).sy - (*target).sy);
-        }
-        (*gd).hsize = (*target).sy - (*gd).sy;
-        if (*gd).hscrolled > (*gd).hsize {
-            (*gd).hscrolled = (*gd).hsize;
+        // Replace the old lines with the new, keeping those either side
+        let before = start - (*gd).hspill;
+        let after = (*gd).hsize + (*gd).sy - end;
+        if before + (*target).sy + after < (*gd).sy {
+            grid_reflow_add(target, (*gd).sy - before - (*target).sy - after);
         }
+        let lines = before + (*target).sy + after;
+        let linedata: *mut grid_line = xreallocarray_(null_mut(), lines as usize).as_ptr();
+        std::ptr::copy_nonoverlapping((*gd).linedata, linedata, before as usize);
+        std::ptr::copy_nonoverlapping(
+            (*target).linedata,
+            linedata.add(before as usize),
+            (*target).sy as usize,
+        );
+        std::ptr::copy_nonoverlapping(
+            (*gd).linedata.add((end - (*gd).hspill) as usize),
+            linedata.add((before + (*target).sy) as usize),
+            after as usize,
+        );
         free((*gd).linedata.cast());
-        (*gd).linedata = (*target).linedata;
+        (*gd).linedata = linedata;
+        free((*target).linedata.cast());
         free(target.cast());
+
+        (*gd).hsize = (*gd).hspill + lines - (*gd).sy;
+        if (*gd).hscrolled > (*gd).hsize - (*gd).hspill {
+            (*gd).hscrolled = (*gd).hsize - (*gd).hspill;
+        }
+        (*gd).hcold = (*gd).hcold.min(start).clamp((*gd).hspill, (*gd).hsize);
+    }
+}
+
+/// Reflow lines on grid to new width. Only the visible lines and those just
+/// above them are reflowed now, the rest of the history is left to
+/// grid_reflow_chunk or grid_reflow_finish. Spilled lines are left as they
+/// are.
+pub unsafe fn grid_reflow(gd: *mut grid, sx: u32) {
+    unsafe {
+        let end = (*gd).hsize + (*gd).sy;
+        let start = end
+            .saturating_sub((*gd).sy + GRID_REFLOW_LINES)
+            .max((*gd).hspill);
+        let start = grid_reflow_first(gd, start);
+
+        grid_reflow_lines(gd, sx, start, end);
+        (*gd).hreflow = start;
+    }
+}
+
+/// Reflow some of the history left by grid_reflow, working up from the
+/// newest lines. Returns true if there is still more to do.
+pub unsafe fn grid_reflow_chunk(gd: *mut grid, lines: u32) -> bool {
+    unsafe {
+        if (*gd).hreflow <= (*gd).hspill {
+            return false;
+        }
+        let end = (*gd).hreflow;
+        let start = grid_reflow_first(gd, end.saturating_sub(lines).max((*gd).hspill));
+
+        grid_reflow_lines(gd, (*gd).sx, start, end);
+        (*gd).hreflow = start;
+        (*gd).hreflow > (*gd).hspill
+    }
+}
+
+/// Reflow all of the history left by grid_reflow.
+pub unsafe fn grid_reflow_finish(gd: *mut grid) {
+    unsafe {
+        if (*gd).hreflow > (*gd).hspill {
+            grid_reflow_lines(gd, (*gd).sx, (*gd).hspill, (*gd).hreflow);
+            (*gd).hreflow = (*gd).hspill;
+        }
     }
 }
 
-/// Convert to position based on wrapped lines
+/// Convert to position based on wrapped lines. Spilled lines are not reflowed
+/// so each is treated as unwrapped.
 pub unsafe fn grid_wrap_position(gd: *mut grid, px: u32, py: u32, wx: *mut u32, wy: *mut u32) {
     unsafe {
         let mut ax = 0;
-        let mut ay = 0;
+        let first = py.min((*gd).hspill);
+        let mut ay = first;
 
-        for yy in 0..py {
-            if (*(*gd).linedata.add(yy as usize))
+        for yy in first..py {
+            if (*grid_raw_line(gd, yy))
                 .flags
                 .intersects(grid_line_flag::WRAPPED)
             {
-                ax += (*(*gd).linedata.add(yy as usize)).cellused;
+                ax += (*grid_raw_line(gd, yy)).cellused;
             } else {
                 ax = 0;
                 ay += 1;
             }
         }
 
-        if px >= (*(*gd).linedata.add(py as usize)).cellused {
+        if px >= (*grid_get_line(gd, py)).cellused {
             ax = u32::MAX;
         } else {
             ax += px;
@@ -1704,14 +2972,22 @@ pub unsafe fn grid_unwrap_position(
     wy: u32,
 ) {
     unsafe {
-        let mut ay = 0;
-        let mut yy = 0;
+        if wy < (*gd).hspill {
+            if wx == u32::MAX {
+                wx = (*grid_get_line(gd, wy)).cellused;
+            }
+            *px = wx;
+            *py = wy;
+            return;
+        }
+        let mut ay = (*gd).hspill;
+        let mut yy = (*gd).hspill;
 
         while yy < (*gd).hsize + (*gd).sy - 1 {
             if ay == wy {
                 break;
             }
-            if !(*(*gd).linedata.add(yy as usize))
+            if !(*grid_raw_line(gd, yy))
                 .flags
                 .intersects(grid_line_flag::WRAPPED)
             {
@@ -1723,22 +2999,22 @@ pub unsafe fn grid_unwrap_position(
         // yy is now 0 on unwrapped line containing wx
         // Walk forwards until we find end or line now containing wx
         if wx == u32::MAX {
-            while (*(*gd).linedata.add(yy as usize))
+            while (*grid_raw_line(gd, yy))
                 .flags
                 .intersects(grid_line_flag::WRAPPED)
             {
                 yy += 1;
             }
-            wx = (*(*gd).linedata.add(yy as usize)).cellused;
+            wx = (*grid_raw_line(gd, yy)).cellused;
         } else {
-            while (*(*gd).linedata.add(yy as usize))
+            while (*grid_raw_line(gd, yy))
                 .flags
                 .intersects(grid_line_flag::WRAPPED)
             {
-                if wx < (*(*gd).linedata.add(yy as usize)).cellused {
+                if wx < (*grid_raw_line(gd, yy)).cellused {
                     break;
                 }
-                wx -= (*(*gd).linedata.add(yy as usize)).cellused;
+                wx -= (*grid_raw_line(gd, yy)).cellused;
                 yy += 1;
             }
         }
@@ -1768,3 +3044,675 @@ pub unsafe fn grid_line_length(gd: *mut grid, py: u32) -> u32 {
         px
     }
 }
+
+/// Saved history file magic.
+const GRID_HISTORY_MAGIC: &[u8; 8] = b"tmuxhist";
+
+/// Line flags kept in saved history.
+const GRID_HISTORY_LINE_FLAGS: grid_line_flag = grid_line_flag::WRAPPED
+    .union(grid_line_flag::START_PROMPT)
+    .union(grid_line_flag::START_INPUT)
+    .union(grid_line_flag::START_OUTPUT)
+    .union(grid_line_flag::END_OUTPUT);
+
+/// Saved history format version. Bump this whenever the line or cell layout
+/// changes and keep loading the older versions in grid_load_history.
+pub const GRID_HISTORY_VERSION: u32 = 2;
+
+/// Save lines from the grid (history and visible) into a buffer, dropping
+/// trailing empty lines and keeping only the last limit lines if limit is not
+/// zero. All integers are little-endian. The file is:
+///
+/// - header: magic (8 bytes), version (u32), width (u32), line count (u32)
+/// - for each line: flags (u32), time (i64), cell count (u32), extended cell
+///   count (u32), the cells, then the extended cells
+/// - for each cell: flags (u8), then the offset of the extended cell (u32) if
+///   the cell is extended, otherwise attributes, fg, bg and character (u8 each)
+/// - for each extended cell: UTF-8 size (u8), width (u8), the UTF-8 data,
+///   attributes (u16), flags (u8), fg, bg and underscore colour (i32 each)
+/// - since version 2, after the extended cells: the command whose output
+///   starts on the line, as written by grid_command_encode
+///
+/// Hyperlinks are not saved, they belong to the screen rather than the grid.
+pub unsafe fn grid_save_history(gd: *mut grid, limit: u32, evb: *mut evbuffer) {
+    unsafe {
+        grid_reflow_finish(gd);
+
+        let mut last = (*gd).hsize + (*gd).sy;
+        while last > (*gd).hspill && (*grid_raw_line(gd, last - 1)).cellused == 0 {
+            last -= 1;
+        }
+        let first = if limit != 0 && last > limit {
+            last - limit
+        } else {
+            0
+        };
+
+        let mut out: Vec<u8> = Vec::new();
+        out.extend_from_slice(GRID_HISTORY_MAGIC);
+        out.extend_from_slice(&GRID_HISTORY_VERSION.to_le_bytes());
+        out.extend_from_slice(&(*gd).sx.to_le_bytes());
+        let count_offset = out.len();
+        out.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut count = 0u32;
+        let mut ud: utf8_data = zeroed();
+        for yy in first..last {
+            let mut gl = if yy < (*gd).hspill {
+                grid_get_line(gd, yy)
+            } else {
+                grid_raw_line(gd, yy)
+            };
+            if (*gl).flags.intersects(grid_line_flag::DEAD) {
+                continue;
+            }
+            count += 1;
+
+            // Read cold lines into a copy rather than uncompressing them.
+            let mut copy: grid_line = zeroed();
+            if !(*gl).cold.is_null() {
+                copy = std::ptr::read(gl);
+                grid_cold_read(gl, &raw mut copy);
+                gl = &raw mut copy;
+            }
+
+            let flags = (*gl).flags & GRID_HISTORY_LINE_FLAGS;
+            out.extend_from_slice(&flags.bits().to_le_bytes());
+            out.extend_from_slice(&(*gl).time.to_le_bytes());
+            out.extend_from_slice(&(*gl).cellused.to_le_bytes());
+            out.extend_from_slice(&(*gl).extdsize.to_le_bytes());
+
+            for px in 0..(*gl).cellused {
+                let gce = (*gl).celldata.add(px as usize);
+                out.push((*gce).flags.bits());
+                if (*gce).flags.intersects(grid_flag::EXTENDED) {
+                    out.extend_from_slice(&(*gce).union_.offset.to_le_bytes());
+                } else {
+                    let data = (*gce).union_.data;
+                    out.extend_from_slice(&[data.attr, data.fg, data.bg, data.data]);
+                }
+            }
+
+            for i in 0..(*gl).extdsize {
+                let gee = (*gl).extddata.add(i as usize);
+                utf8_to_data((*gee).data, &raw mut ud);
+                out.push(ud.size);
+                out.push(ud.width);
+                out.extend_from_slice(&ud.data[..ud.size as usize]);
+                out.extend_from_slice(&(*gee).attr.to_le_bytes());
+                out.push((*gee).flags);
+                out.extend_from_slice(&(*gee).fg.to_le_bytes());
+                out.extend_from_slice(&(*gee).bg.to_le_bytes());
+                out.extend_from_slice(&(*gee).us.to_le_bytes());
+            }
+            grid_command_encode(gl, &mut out);
+            free_(copy.celldata);
+            free_(copy.extddata);
+        }
+        out[count_offset..count_offset + 4].copy_from_slice(&count.to_le_bytes());
+
+        evbuffer_add(evb, out.as_ptr().cast(), out.len());
+    }
+}
+
+/// Cursor over saved history data.
+struct grid_history_reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl grid_history_reader<'_> {
+    fn bytes(&mut self, n: usize) -> Option<&[u8]> {
+        let end = self.offset.checked_add(n)?;
+        let bytes = self.data.get(self.offset..end)?;
+        self.offset = end;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        Some(i32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn i64(&mut self) -> Option<i64> {
+        Some(i64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+}
+
+/// Read one saved line.
+unsafe fn grid_load_history_line(
+    r: &mut grid_history_reader,
+    version: u32,
+    gl: *mut grid_line,
+) -> Option<()> {
+    unsafe {
+        let flags = r.u32()? as i32;
+        (*gl).flags = grid_line_flag::from_bits_truncate(flags) & GRID_HISTORY_LINE_FLAGS;
+        (*gl).time = r.i64()? as time_t;
+
+        let cellused = r.u32()?;
+        let extdsize = r.u32()?;
+        // Lines which have not been compacted can have more extended cells
+        // than cells, so only check there is enough data left for them.
+        if cellused as usize > r.data.len() || extdsize as usize > r.data.len() {
+            return None;
+        }
+
+        if cellused != 0 {
+            (*gl).celldata = xreallocarray_(null_mut(), cellused as usize).as_ptr();
+            (*gl).cellsize = cellused;
+        }
+        for px in 0..cellused {
+            let gce = (*gl).celldata.add(px as usize);
+            (*gce).flags = grid_flag::from_bits_truncate(r.u8()?) & !grid_flag::SELECTED;
+            if (*gce).flags.intersects(grid_flag::EXTENDED) {
+                let offset = r.u32()?;
+                if offset >= extdsize {
+                    return None;
+                }
+                (*gce).union_.offset = offset;
+            } else {
+                let data = r.bytes(4)?;
+                (*gce).union_.data = grid_cell_entry_data {
+                    attr: data[0],
+                    fg: data[1],
+                    bg: data[2],
+                    data: data[3],
+                };
+            }
+            (*gl).cellused = px + 1;
+        }
+
+        if extdsize != 0 {
+            (*gl).extddata = xreallocarray_(null_mut(), extdsize as usize).as_ptr();
+            (*gl).flags |= grid_line_flag::EXTENDED;
+        }
+        let mut ud: utf8_data = zeroed();
+        for i in 0..extdsize {
+            let size = r.u8()?;
+            let width = r.u8()?;
+            if size as usize > UTF8_SIZE || width > 2 {
+                return None;
+            }
+            ud = zeroed();
+            ud.data[..size as usize].copy_from_slice(r.bytes(size as usize)?);
+            ud.size = size;
+            ud.have = size;
+            ud.width = width;
+
+            let gee = (*gl).extddata.add(i as usize);
+            utf8_from_data(&raw const ud, &raw mut (*gee).data);
+            (*gee).attr = r.u16()?;
+            (*gee).flags = r.u8()? & !grid_flag::SELECTED.bits();
+            (*gee).fg = r.i32()?;
+            (*gee).bg = r.i32()?;
+            (*gee).us = r.i32()?;
+            (*gee).link = 0;
+            (*gl).extdsize = i + 1;
+        }
+
+        if version >= 2 {
+            grid_command_decode(r, gl)?;
+        }
+        Some(())
+    }
+}
+
+/// Load lines saved by grid_save_history and insert them into the history
+/// above the existing lines, reflowing if they were saved at a different width
+/// and trimming the oldest if there are now more than the history limit.
+pub unsafe fn grid_load_history(
+    gd: *mut grid,
+    data: *const u8,
+    size: usize,
+    cause: *mut *mut c_char,
+) -> i32 {
+    unsafe {
+        let mut r = grid_history_reader {
+            data: std::slice::from_raw_parts(data, size),
+            offset: 0,
+        };
+
+        if r.bytes(GRID_HISTORY_MAGIC.len()) != Some(GRID_HISTORY_MAGIC) {
+            *cause = xstrdup_(c"not a history file").as_ptr();
+            return -1;
+        }
+        let (Some(version), Some(sx), Some(count)) = (r.u32(), r.u32(), r.u32()) else {
+            *cause = xstrdup_(c"history file too short").as_ptr();
+            return -1;
+        };
+        if version == 0 || version > GRID_HISTORY_VERSION {
+            *cause = format_nul!("unsupported history version {}", version);
+            return -1;
+        }
+        if count as usize > size || sx == 0 {
+            *cause = xstrdup_(c"invalid history file").as_ptr();
+            return -1;
+        }
+
+        let lines: *mut grid_line = xcalloc_::<grid_line>(count.max(1) as usize).as_ptr();
+        for yy in 0..count {
+            if grid_load_history_line(&mut r, version, lines.add(yy as usize)).is_none() {
+                for i in 0..=yy {
+                    free_((*lines.add(i as usize)).celldata);
+                    free_((*lines.add(i as usize)).extddata);
+                    grid_command_free(lines.add(i as usize));
+                }
+                free_(lines);
+                *cause = format_nul!("invalid history file at line {}", yy);
+                return -1;
+            }
+        }
+        if count == 0 {
+            free_(lines);
+            return 0;
+        }
+
+        // A saved line cannot wrap onto whatever is below it now.
+        (*lines.add(count as usize - 1)).flags &= !grid_line_flag::WRAPPED;
+
+        // Any spilled lines stay above the loaded lines.
+        let total = (*gd).hsize - (*gd).hspill + (*gd).sy;
+        (*gd).linedata = xreallocarray_((*gd).linedata, (total + count) as usize).as_ptr();
+        std::ptr::copy(
+            (*gd).linedata,
+            (*gd).linedata.add(count as usize),
+            total as usize,
+        );
+        std::ptr::copy_nonoverlapping(lines, (*gd).linedata, count as usize);
+        free_(lines);
+        (*gd).hsize += count;
+        (*gd).hcold = (*gd).hspill;
+        if (*gd).hreflow > (*gd).hspill {
+            (*gd).hreflow += count;
+        }
+
+        if sx != (*gd).sx {
+            grid_reflow(gd, (*gd).sx);
+            grid_reflow_finish(gd);
+        }
+        if (*gd).hsize - (*gd).hspill > (*gd).hlimit {
+            let ny = (*gd).hsize - (*gd).hspill - (*gd).hlimit;
+            grid_trim_history(gd, ny);
+            (*gd).hsize -= ny;
+        }
+        if (*gd).hscrolled > (*gd).hsize - (*gd).hspill {
+            (*gd).hscrolled = (*gd).hsize - (*gd).hspill;
+        }
+
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_lz() {
+        let mut src = b"abcdabcdabcdabcd0123456789".repeat(50);
+        src.extend((0..1000u32).map(|i| (i * 7919 % 251) as u8));
+
+        for data in [&src[..], b"", b"a", b"abcabcabc"] {
+            let compressed = grid_lz_compress(data);
+            assert_eq!(grid_lz_decompress(&compressed, data.len()).unwrap(), data);
+        }
+        assert!(grid_lz_compress(&src).len() < src.len());
+        assert!(grid_lz_decompress(&grid_lz_compress(&src), src.len() + 1).is_none());
+    }
+
+    #[test]
+    fn test_grid_cold() {
+        unsafe {
+            let gd = grid_create(80, 24, 10000);
+            (*gd).flags |= GRID_HISTORY;
+
+            let mut extended = grid_default_cell;
+            extended.data = utf8_data::new([0xc3, 0xa9], 2, 2, 1);
+            extended.fg = colour_join_rgb(1, 2, 3);
+
+            for k in 0..3000u32 {
+                let mut gc = grid_default_cell;
+                for (x, ch) in format!("line {k}").bytes().enumerate() {
+                    utf8_set(&raw mut gc.data, ch);
+                    grid_set_cell(gd, x as u32, (*gd).hsize, &gc);
+                }
+                if k % 10 == 0 {
+                    grid_set_cell(gd, 20, (*gd).hsize, &extended);
+                }
+                grid_scroll_history(gd, 8);
+            }
+            assert_eq!((*gd).hsize, 3000);
+            assert!((*gd).hcold > 0);
+            assert!(!(*(*gd).linedata).cold.is_null());
+            let before = grid_history_bytes(gd);
+
+            let mut gc = grid_default_cell;
+            for k in 0..3000u32 {
+                let text = format!("line {k}");
+                for (x, ch) in text.bytes().enumerate() {
+                    grid_get_cell(gd, x as u32, k, &raw mut gc);
+                    assert_eq!(gc.data.data[0], ch);
+                }
+                grid_get_cell(gd, text.len() as u32, k, &raw mut gc);
+                assert_eq!(gc.data.data[0], b' ');
+                grid_get_cell(gd, 20, k, &raw mut gc);
+                if k % 10 == 0 {
+                    assert_eq!(gc.data.data[..2], [0xc3, 0xa9]);
+                    assert_eq!(gc.fg, colour_join_rgb(1, 2, 3));
+                } else {
+                    assert_eq!(gc.data.data[0], b' ');
+                }
+            }
+            assert_eq!((*gd).hcold, 0);
+            assert!(grid_history_bytes(gd) > before);
+
+            grid_destroy(gd);
+        }
+    }
+
+    #[test]
+    fn test_grid_spill() {
+        unsafe {
+            socket_path = format_nul!(
+                "{}/tmux-rs-test-{}",
+                std::env::temp_dir().display(),
+                std::process::id()
+            );
+
+            let gd = grid_create(80, 24, 100);
+            (*gd).slimit = 1000;
+
+            let mut extended = grid_default_cell;
+            extended.data = utf8_data::new([0xc3, 0xa9], 2, 2, 1);
+            extended.fg = colour_join_rgb(1, 2, 3);
+
+            for k in 0..3000u32 {
+                let mut gc = grid_default_cell;
+                for (x, ch) in format!("line {k}").bytes().enumerate() {
+                    utf8_set(&raw mut gc.data, ch);
+                    grid_set_cell(gd, x as u32, (*gd).hsize, &gc);
+                }
+                if k % 10 == 0 {
+                    grid_set_cell(gd, 20, (*gd).hsize, &extended);
+                }
+                grid_collect_history(gd);
+                grid_scroll_history(gd, 8);
+            }
+            assert!((*gd).hspill >= 900 && (*gd).hspill <= 1000);
+            assert!((*gd).hsize - (*gd).hspill <= 100);
+
+            let check = |gd: *mut grid| {
+                let first = 3000 - (*gd).hsize;
+                let mut gc = grid_default_cell;
+                for y in 0..(*gd).hsize {
+                    let k = first + y;
+                    for (x, ch) in format!("line {k}").bytes().enumerate() {
+                        grid_get_cell(gd, x as u32, y, &raw mut gc);
+                        assert_eq!(gc.data.data[0], ch);
+                    }
+                    grid_get_cell(gd, 20, y, &raw mut gc);
+                    if k % 10 == 0 {
+                        assert_eq!(gc.data.data[..2], [0xc3, 0xa9]);
+                        assert_eq!(gc.fg, colour_join_rgb(1, 2, 3));
+                    } else {
+                        assert_eq!(gc.data.data[0], b' ');
+                    }
+                }
+            };
+            check(gd);
+
+            // A copy shares the spilled lines.
+            let total = (*gd).hsize + (*gd).sy;
+            let copy = grid_create(80, total, 100);
+            let spilled = grid_duplicate_spill(copy, gd);
+            assert_eq!(spilled, (*gd).hspill);
+            grid_duplicate_lines(copy, spilled, gd, spilled, total - spilled);
+            (*copy).sy = (*gd).sy;
+            (*copy).hsize = (*gd).hsize;
+            check(copy);
+            grid_destroy(copy);
+
+            // Lines stay valid until the event loop however many blocks are
+            // read, and marks are kept without reading any.
+            let text = |gl: *mut grid_line| {
+                (0..(*gl).cellused)
+                    .map(|x| {
+                        let mut gc = grid_default_cell;
+                        grid_get_cell1(gl, x, &raw mut gc);
+                        gc.data.data[0] as char
+                    })
+                    .collect::<String>()
+            };
+            let first = 3000 - (*gd).hsize;
+            let gl = grid_get_line(gd, 0);
+            let mut y = GRID_COLD_LINES;
+            while y < (*gd).hspill {
+                grid_get_line(gd, y);
+                y += GRID_COLD_LINES;
+            }
+            assert!(text(gl).starts_with(&format!("line {first}")));
+            assert!(!(*(*gd).spill).retired.is_empty());
+            grid_spill_expire();
+            let spill = &*(*gd).spill;
+            grid_get_line(gd, (spill.cache[0].0 - spill.base) as u32);
+            assert!((*(*gd).spill).retired.is_empty());
+
+            let cached = |gd: *mut grid| {
+                (*(*gd).spill)
+                    .cache
+                    .iter()
+                    .map(|(first, _)| *first)
+                    .collect::<Vec<_>>()
+            };
+            let before = cached(gd);
+            (*grid_get_line_marks(gd, 1)).flags |= grid_line_flag::START_OUTPUT;
+            assert_eq!(
+                grid_find_last_command(gd, 1).map(|(start, _)| start),
+                Some(1)
+            );
+            assert_eq!(cached(gd), before);
+
+            // Spilled lines cannot be changed.
+            let mut gc = grid_default_cell;
+            utf8_set(&raw mut gc.data, b'X');
+            grid_set_cell(gd, 0, 0, &gc);
+            grid_clear_lines(gd, 0, 1, 8);
+            grid_get_cell(gd, 0, 0, &raw mut gc);
+            assert_eq!(gc.data.data[0], b'l');
+
+            // Lines which cannot be read are marked.
+            let spill = &mut *(*gd).spill;
+            for (_, lines) in spill.cache.drain(..) {
+                grid_spill_free_lines(lines);
+            }
+            libc::ftruncate((*spill.file).fd, 0);
+            assert_eq!(text(grid_get_line(gd, 0)), "[history could not be read]");
+
+            grid_clear_history(gd);
+            assert_eq!(((*gd).hsize, (*gd).hspill), (0, 0));
+            grid_destroy(gd);
+        }
+    }
+
+    #[test]
+    fn test_grid_reflow() {
+        unsafe {
+            let make = || {
+                let gd = grid_create(40, 10, 100000);
+                let mut gc = grid_default_cell;
+                for k in 0..5000u32 {
+                    let text = format!("{k:>5} {}", "x".repeat((k % 70) as usize));
+                    for (i, ch) in text.bytes().enumerate() {
+                        utf8_set(&raw mut gc.data, ch);
+                        grid_set_cell(gd, i as u32 % 40, (*gd).hsize, &gc);
+                        if i % 40 == 39 && i + 1 != text.len() {
+                            (*grid_get_line(gd, (*gd).hsize)).flags |= grid_line_flag::WRAPPED;
+                            grid_scroll_history(gd, 8);
+                        }
+                    }
+                    grid_scroll_history(gd, 8);
+                }
+                gd
+            };
+            let lazy = make();
+            let eager = make();
+
+            let (mut wx, mut wy) = (0, 0);
+            let mut before = grid_default_cell;
+            grid_get_cell(lazy, 45, 100, &raw mut before);
+            grid_wrap_position(lazy, 45, 100, &raw mut wx, &raw mut wy);
+
+            (*lazy).sx = 25;
+            grid_reflow(lazy, 25);
+            assert!((*lazy).hreflow > 0);
+            let last = (*lazy).hsize + (*lazy).sy - 1;
+            assert!((*grid_get_line(lazy, last)).cellused <= 25);
+
+            let (mut px, mut py) = (0, 0);
+            let mut after = grid_default_cell;
+            grid_unwrap_position(lazy, &raw mut px, &raw mut py, wx, wy);
+            grid_get_cell(lazy, px, py, &raw mut after);
+            assert_eq!(after.data.data[0], before.data.data[0]);
+
+            while grid_reflow_chunk(lazy, 700) {}
+            assert_eq!((*lazy).hreflow, 0);
+            grid_unwrap_position(lazy, &raw mut px, &raw mut py, wx, wy);
+            grid_get_cell(lazy, px, py, &raw mut after);
+            assert_eq!(after.data.data[0], before.data.data[0]);
+
+            (*eager).sx = 25;
+            grid_reflow(eager, 25);
+            grid_reflow_finish(eager);
+            assert_eq!((*lazy).hsize, (*eager).hsize);
+            for y in 0..(*lazy).hsize + (*lazy).sy {
+                let (gla, glb) = (grid_get_line(lazy, y), grid_get_line(eager, y));
+                assert_eq!((*gla).cellused, (*glb).cellused);
+                assert_eq!((*gla).flags.bits(), (*glb).flags.bits());
+                assert!((*gla).cellused <= 25);
+                let (mut gca, mut gcb) = (grid_default_cell, grid_default_cell);
+                for x in 0..(*gla).cellused {
+                    grid_get_cell(lazy, x, y, &raw mut gca);
+                    grid_get_cell(eager, x, y, &raw mut gcb);
+                    assert_eq!(grid_cells_equal(&gca, &gcb), 1);
+                }
+            }
+
+            grid_destroy(lazy);
+            grid_destroy(eager);
+        }
+    }
+
+    #[test]
+    fn test_grid_save_history() {
+        unsafe {
+            let gd = grid_create(4, 2, 100);
+            let mut rgb = grid_default_cell;
+            utf8_set(&raw mut rgb.data, b'r');
+
+            // Clearing and rewriting cells in place, as a full screen
+            // application redrawing does, leaves more extended cells than
+            // cells on a visible line until it is compacted.
+            for i in 0..5 {
+                rgb.fg = colour_join_rgb(i, 2, 3);
+                grid_clear(gd, 0, 0, 2, 1, 8);
+                grid_set_cell(gd, 0, 0, &rgb);
+                grid_set_cell(gd, 1, 0, &rgb);
+            }
+            let gl = grid_get_line(gd, 0);
+            assert!((*gl).extdsize > (*gl).cellused);
+
+            let evb = evbuffer_new();
+            grid_save_history(gd, 0, evb);
+            let loaded = grid_create(4, 2, 100);
+            let mut cause = null_mut();
+            let size = evbuffer_get_length(evb);
+            let data = evbuffer_pullup(evb, -1);
+            assert_eq!(grid_load_history(loaded, data, size, &raw mut cause), 0);
+            assert_eq!((*loaded).hsize, 1);
+
+            let mut gc = grid_default_cell;
+            for x in 0..2 {
+                grid_get_cell(loaded, x, 0, &raw mut gc);
+                assert_eq!(gc.data.data[0], b'r');
+                assert_eq!(gc.fg, colour_join_rgb(4, 2, 3));
+            }
+            evbuffer_free(evb);
+            grid_destroy(loaded);
+            grid_destroy(gd);
+        }
+    }
+
+    #[test]
+    fn test_grid_command() {
+        unsafe {
+            let gd = grid_create(20, 5, 100);
+            let mut gc = grid_default_cell;
+            let mut put = |gd: *mut grid, text: &str| {
+                for (x, ch) in text.bytes().enumerate() {
+                    utf8_set(&raw mut gc.data, ch);
+                    grid_set_cell(gd, x as u32, (*gd).hsize, &gc);
+                }
+                grid_scroll_history(gd, 8);
+            };
+
+            // A prompt, a command with two lines of output, then an empty one.
+            (*grid_get_line(gd, 0)).flags |= grid_line_flag::START_PROMPT;
+            put(gd, "$ false");
+            grid_start_command(gd, 1, String::from("false"));
+            put(gd, "one");
+            put(gd, "two");
+            assert!(!grid_end_command(gd, 3, true, Some(1)).is_null());
+            assert!(grid_end_command(gd, 3, true, Some(1)).is_null());
+            grid_start_command(gd, 3, String::from("true"));
+            assert!(!grid_end_command(gd, 3, true, Some(0)).is_null());
+            (*grid_get_line(gd, 3)).flags |= grid_line_flag::START_PROMPT;
+
+            assert_eq!(grid_find_command(gd, 0), Some((1, 3)));
+            assert_eq!(grid_find_command(gd, 2), Some((1, 3)));
+            assert_eq!(grid_find_command(gd, 3), None);
+            assert!(
+                (*grid_get_line(gd, 2))
+                    .flags
+                    .intersects(grid_line_flag::END_OUTPUT)
+            );
+
+            // Commands are kept when lines are copied, saved and loaded.
+            let check = |gd: *mut grid| {
+                let command = &*(*grid_get_line(gd, 1)).command;
+                assert_eq!(command.text, "false");
+                assert!(command.finished);
+                assert_eq!(command.status, Some(1));
+                assert_eq!(grid_find_command(gd, 0), Some((1, 3)));
+            };
+            let copy = grid_create(20, (*gd).hsize + (*gd).sy, 100);
+            grid_duplicate_lines(copy, 0, gd, 0, (*gd).hsize + (*gd).sy);
+            check(copy);
+            grid_destroy(copy);
+
+            let evb = evbuffer_new();
+            grid_save_history(gd, 0, evb);
+            let loaded = grid_create(20, 5, 100);
+            let mut cause = null_mut();
+            let size = evbuffer_get_length(evb);
+            let data = evbuffer_pullup(evb, -1);
+            assert_eq!(grid_load_history(loaded, data, size, &raw mut cause), 0);
+            check(loaded);
+            evbuffer_free(evb);
+            grid_destroy(loaded);
+
+            grid_destroy(gd);
+        }
+    }
+}
@@ -21,37 +21,42 @@ static mut all_images: images = TAILQ_HEAD_INITIALIZER!(all_images);
 
 static mut all_images_count: u32 = 0;
 
-unsafe fn image_free(im: NonNull<image>) {
+pub unsafe fn image_free(im: *mut image) {
     unsafe {
-        let im = im.as_ptr();
         let mut s = (*im).s;
 
         tailq_remove::<_, discr_all_entry>(&raw mut all_images, im);
         all_images_count -= 1;
 
         tailq_remove::<_, discr_entry>(&raw mut (*s).images, im);
-        crate::image_sixel::sixel_free((*im).data);
+        #[cfg(feature = "sixel")]
+        if !(*im).data.is_null() {
+            sixel_free((*im).data);
+        }
+        if !(*im).kitty.is_null() {
+            tty_kitty_delete_image(im);
+            kitty_image_release((*im).kitty);
+        }
         free_((*im).fallback);
         free_(im);
     }
 }
 
-pub unsafe fn image_free_all(s: *mut screen) -> i32 {
+pub unsafe fn image_free_all(s: *mut screen) -> bool {
     unsafe {
         let mut redraw = !tailq_empty(&raw mut (*s).images);
 
-        for im in tailq_foreach::<image, discr_entry>(&raw mut (*s).images) {
+        for im in tailq_foreach::<image, discr_entry>(&raw mut (*s).images).map(NonNull::as_ptr) {
             image_free(im);
         }
-        redraw as i32
+        redraw
     }
 }
 
 /// Create text placeholder for an image.
-
-pub unsafe fn image_fallback(ret: *mut *mut c_char, sx: u32, sy: u32) {
+pub unsafe fn image_fallback(ret: *mut *mut c_char, name: &str, sx: u32, sy: u32) {
     unsafe {
-        let mut label: *mut c_char = format_nul!("SIXEL IMAGE ({}x{})\r\n", sx, sy);
+        let mut label: *mut c_char = format_nul!("{} IMAGE ({}x{})\r\n", name, sx, sy);
 
         // Allocate first line.
         let lsize: u32 = libc::strlen(label) as u32 + 1;
@@ -87,17 +92,11 @@ pub unsafe fn image_fallback(ret: *mut *mut c_char, sx: u32, sy: u32) {
     }
 }
 
-pub unsafe fn image_store(s: *mut screen, si: *mut sixel_image) -> *mut image {
+unsafe fn image_insert(s: *mut screen, im: *mut image) -> *mut image {
     unsafe {
-        let mut im = xcalloc1::<image>() as *mut image;
         (*im).s = s;
-        (*im).data = si;
-
         (*im).px = (*s).cx;
         (*im).py = (*s).cy;
-        crate::image_sixel::sixel_size_in_cells(si, &raw mut (*im).sx, &raw mut (*im).sy);
-
-        image_fallback(&raw mut (*im).fallback, (*im).sx, (*im).sy);
 
         tailq_insert_tail::<image, discr_entry>(&raw mut (*s).images, im);
         tailq_insert_tail::<image, discr_all_entry>(&raw mut all_images, im);
@@ -110,6 +109,47 @@ pub unsafe fn image_store(s: *mut screen, si: *mut sixel_image) -> *mut image {
     }
 }
 
+#[cfg(feature = "sixel")]
+pub unsafe fn image_store(s: *mut screen, si: *mut sixel_image) -> *mut image {
+    unsafe {
+        let mut im = xcalloc1::<image>() as *mut image;
+        (*im).data = si;
+
+        sixel_size_in_cells(si, &raw mut (*im).sx, &raw mut (*im).sy);
+        image_fallback(&raw mut (*im).fallback, "SIXEL", (*im).sx, (*im).sy);
+
+        image_insert(s, im)
+    }
+}
+
+/// Store a placement of a kitty image covering sx by sy cells at the cursor,
+/// with crop rows already off the top. Takes a reference to the image.
+pub unsafe fn image_store_kitty(
+    s: *mut screen,
+    ki: *mut kitty_image,
+    sx: u32,
+    sy: u32,
+    crop: u32,
+) -> *mut image {
+    static mut next_placement: u32 = 0;
+
+    unsafe {
+        let mut im = xcalloc1::<image>() as *mut image;
+        (*im).kitty = ki;
+        (*ki).references += 1;
+
+        next_placement = next_placement.wrapping_add(1).max(1);
+        (*im).placement = next_placement;
+
+        (*im).sx = sx;
+        (*im).sy = sy;
+        (*im).crop = crop;
+        image_fallback(&raw mut (*im).fallback, "KITTY", sx, sy);
+
+        image_insert(s, im)
+    }
+}
+
 pub unsafe fn image_check_line(s: *mut screen, py: u32, ny: u32) -> bool {
     unsafe {
         let mut redraw = false;
@@ -142,7 +182,7 @@ pub unsafe fn image_check_area(s: *mut screen, px: u32, py: u32, nx: u32, ny: u3
     }
 }
 
-pub unsafe fn image_scroll_up(s: *mut screen, lines: u32) -> i32 {
+pub unsafe fn image_scroll_up(s: *mut screen, lines: u32) -> bool {
     unsafe {
         let mut redraw = false;
 
@@ -157,21 +197,91 @@ pub unsafe fn image_scroll_up(s: *mut screen, lines: u32) -> i32 {
                 redraw = true;
                 continue;
             }
-            let sx = (*im).sx;
             let sy = ((*im).py + (*im).sy) - lines;
 
-            let new =
-                crate::image_sixel::sixel_scale((*im).data, 0, 0, 0, (*im).sy - sy, sx, sy, 1);
-            crate::image_sixel::sixel_free((*im).data);
-            (*im).data = new;
+            if !(*im).kitty.is_null() {
+                // The outer terminal crops kitty images, so only remember how
+                // many rows are gone.
+                (*im).crop += (*im).sy - sy;
+                (*im).sy = sy;
+                (*im).py = 0;
 
-            (*im).py = 0;
-            crate::image_sixel::sixel_size_in_cells((*im).data, &(*im).sx, &(*im).sy);
+                free_((*im).fallback);
+                image_fallback(&raw mut (*im).fallback, "KITTY", (*im).sx, (*im).sy);
+                redraw = true;
+                continue;
+            }
+
+            #[cfg(feature = "sixel")]
+            {
+                let sx = (*im).sx;
+                let new = sixel_scale((*im).data, 0, 0, 0, (*im).sy - sy, sx, sy, 1);
+                if new.is_null() {
+                    image_free(im);
+                    redraw = true;
+                    continue;
+                }
+                sixel_free((*im).data);
+                (*im).data = new;
+
+                (*im).py = 0;
+                sixel_size_in_cells((*im).data, &raw mut (*im).sx, &raw mut (*im).sy);
+
+                free_((*im).fallback);
+                image_fallback(&raw mut (*im).fallback, "SIXEL", (*im).sx, (*im).sy);
+                redraw = true;
+            }
+        }
+        redraw
+    }
+}
+
+/// Fit images into a resized screen. Content has moved up by dy lines (or down
+/// if negative) so images follow it, then any that no longer fit are cropped.
+pub unsafe fn image_resize(s: *mut screen, dy: i32) -> bool {
+    unsafe {
+        let mut redraw = false;
+
+        if dy > 0 {
+            redraw = image_scroll_up(s, dy as u32);
+        } else if dy < 0 {
+            for im in tailq_foreach::<_, discr_entry>(&raw mut (*s).images).map(NonNull::as_ptr) {
+                (*im).py += dy.unsigned_abs();
+                redraw = true;
+            }
+        }
 
-            free_((*im).fallback);
-            image_fallback(&raw mut (*im).fallback, (*im).sx, (*im).sy);
+        let sx = screen_size_x(s);
+        let sy = screen_size_y(s);
+        for im in tailq_foreach::<_, discr_entry>(&raw mut (*s).images).map(NonNull::as_ptr) {
+            if (*im).px + (*im).sx <= sx && (*im).py + (*im).sy <= sy {
+                continue;
+            }
             redraw = true;
+
+            // Kitty images can only lose rows at the top.
+            if !(*im).kitty.is_null() || (*im).px >= sx || (*im).py >= sy {
+                image_free(im);
+                continue;
+            }
+
+            #[cfg(feature = "sixel")]
+            {
+                let nx = (*im).sx.min(sx - (*im).px);
+                let ny = (*im).sy.min(sy - (*im).py);
+                let new = sixel_scale((*im).data, 0, 0, 0, 0, nx, ny, 1);
+                if new.is_null() {
+                    image_free(im);
+                    continue;
+                }
+                sixel_free((*im).data);
+                (*im).data = new;
+                sixel_size_in_cells((*im).data, &raw mut (*im).sx, &raw mut (*im).sy);
+
+                free_((*im).fallback);
+                image_fallback(&raw mut (*im).fallback, "SIXEL", (*im).sx, (*im).sy);
+            }
         }
-        redraw.into()
+        redraw
     }
 }
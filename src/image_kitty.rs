@@ -0,0 +1,544 @@
+use crate::*;
+
+use crate::compat::{
+    b64::b64_pton,
+    queue::{tailq_first, tailq_foreach, tailq_insert_tail, tailq_remove},
+};
+
+// Kitty graphics protocol, received as APC "G" sequences:
+//
+//   ESC _ G key=value,key=value;payload ESC \
+//
+// Only direct transmission (t=d) is supported. The base64 payload is kept as
+// received and forwarded to outer terminals that have the kittygfx feature,
+// so images are never decoded here except for the PNG header when no size is
+// given. Application image ids are local to the screen and are mapped to ids
+// unique across the server before they are sent to outer terminals.
+
+/// Maximum number of images kept for each screen.
+const KITTY_IMAGES_MAX: u32 = 32;
+
+/// Maximum size of the base64 payload of one image.
+const KITTY_IMAGE_SIZE_MAX: usize = 32 * 1024 * 1024;
+
+static mut kitty_next_outer: u32 = 0;
+
+/// Parse control data into a command. Only keys present are changed.
+fn kitty_parse(control: &[u8], kc: &mut kitty_command) -> Result<(), ()> {
+    for pair in control.split(|&ch| ch == b',') {
+        if pair.is_empty() {
+            continue;
+        }
+        let [key, b'=', value @ ..] = pair else {
+            return Err(());
+        };
+        if value.is_empty() {
+            return Err(());
+        }
+
+        let character = || {
+            if value.len() == 1 {
+                Ok(value[0])
+            } else {
+                Err(())
+            }
+        };
+        let number = || -> Result<u32, ()> {
+            std::str::from_utf8(value)
+                .map_err(|_| ())?
+                .parse::<u32>()
+                .map_err(|_| ())
+        };
+        match key {
+            b'a' => kc.action = character()?,
+            b'd' => kc.delete = character()?,
+            b't' => kc.medium = character()?,
+            b'o' => kc.compression = character()?,
+            b'f' => kc.format = number()?,
+            b's' => kc.pixel_x = number()?,
+            b'v' => kc.pixel_y = number()?,
+            b'i' => kc.id = number()?,
+            b'I' => kc.number = number()?,
+            b'p' => kc.placement = number()?,
+            b'm' => kc.more = number()?,
+            b'q' => kc.quiet = number()?,
+            b'c' => kc.columns = number()?,
+            b'r' => kc.rows = number()?,
+            b'C' => kc.cursor = number()?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Build the reply to a command, if the application wants one.
+fn kitty_reply(kc: &kitty_command, error: Option<&str>) -> Option<String> {
+    if kc.id == 0 && kc.number == 0 {
+        return None;
+    }
+    match error {
+        None if kc.quiet >= 1 => return None,
+        Some(_) if kc.quiet >= 2 => return None,
+        _ => (),
+    }
+
+    let mut reply = format!("\x1b_Gi={}", kc.id);
+    if kc.number != 0 {
+        reply.push_str(&format!(",I={}", kc.number));
+    }
+    if kc.placement != 0 {
+        reply.push_str(&format!(",p={}", kc.placement));
+    }
+    reply.push(';');
+    reply.push_str(error.unwrap_or("OK"));
+    reply.push_str("\x1b\\");
+    Some(reply)
+}
+
+/// Drop a reference to an image, freeing it when it is no longer used.
+pub unsafe fn kitty_image_release(ki: *mut kitty_image) {
+    unsafe {
+        (*ki).references -= 1;
+        if (*ki).references != 0 {
+            return;
+        }
+        tty_kitty_forget((*ki).outer);
+        free_((*ki).data);
+        free_(ki);
+    }
+}
+
+/// Free the placements of an image, or of all images if ki is NULL. A non-zero
+/// placement limits it to that placement id.
+unsafe fn kitty_free_placements(s: *mut screen, ki: *mut kitty_image, placement: u32) -> bool {
+    unsafe {
+        let mut redraw = false;
+
+        for im in tailq_foreach::<_, discr_entry>(&raw mut (*s).images).map(NonNull::as_ptr) {
+            if (*im).kitty.is_null() || (!ki.is_null() && (*im).kitty != ki) {
+                continue;
+            }
+            if placement != 0 && (*im).placement != placement {
+                continue;
+            }
+            image_free(im);
+            redraw = true;
+        }
+        redraw
+    }
+}
+
+/// Remove an image from the screen list.
+unsafe fn kitty_remove(s: *mut screen, ki: *mut kitty_image) {
+    unsafe {
+        tailq_remove(&raw mut (*s).kitty_images, ki);
+        kitty_image_release(ki);
+    }
+}
+
+/// Free all kitty images of a screen.
+pub unsafe fn kitty_free_all(s: *mut screen) {
+    unsafe {
+        let pending = (*s).kitty_pending;
+        if !pending.is_null() {
+            kitty_image_release((*pending).image);
+            free_(pending);
+            (*s).kitty_pending = null_mut();
+        }
+
+        for ki in tailq_foreach(&raw mut (*s).kitty_images).map(NonNull::as_ptr) {
+            kitty_remove(s, ki);
+        }
+    }
+}
+
+/// Find an image by id or by number. The latest image with a number wins.
+unsafe fn kitty_find(s: *mut screen, id: u32, number: u32) -> *mut kitty_image {
+    unsafe {
+        let mut found = null_mut();
+
+        for ki in tailq_foreach(&raw mut (*s).kitty_images).map(NonNull::as_ptr) {
+            if id != 0 && (*ki).id == id {
+                return ki;
+            }
+            if id == 0 && number != 0 && (*ki).number == number {
+                found = ki;
+            }
+        }
+        found
+    }
+}
+
+/// Append a chunk of payload to an image being received.
+unsafe fn kitty_append(ki: *mut kitty_image, payload: &[u8]) -> Result<(), &'static str> {
+    unsafe {
+        if (*ki).size + payload.len() > KITTY_IMAGE_SIZE_MAX {
+            return Err("EFBIG:image too large");
+        }
+        (*ki).data = xrealloc_((*ki).data, (*ki).size + payload.len() + 1).as_ptr();
+        libc::memcpy(
+            (*ki).data.add((*ki).size).cast(),
+            payload.as_ptr().cast(),
+            payload.len(),
+        );
+        (*ki).size += payload.len();
+        *(*ki).data.add((*ki).size) = b'\0' as c_char;
+        Ok(())
+    }
+}
+
+/// Read the size of a PNG image from its header.
+unsafe fn kitty_png_size(ki: *mut kitty_image) -> Option<(u32, u32)> {
+    unsafe {
+        // The signature, the IHDR chunk length and type, then the width and
+        // height are 24 bytes, which is 32 base64 characters.
+        if (*ki).size < 32 {
+            return None;
+        }
+        let mut src = [0 as c_char; 33];
+        libc::memcpy(src.as_mut_ptr().cast(), (*ki).data.cast(), 32);
+
+        let mut header = [0u8; 24];
+        if b64_pton(src.as_ptr(), header.as_mut_ptr(), header.len()) != 24 {
+            return None;
+        }
+        if &header[..8] != b"\x89PNG\r\n\x1a\n" || &header[12..16] != b"IHDR" {
+            return None;
+        }
+        let width = u32::from_be_bytes(header[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(header[20..24].try_into().unwrap());
+        Some((width, height))
+    }
+}
+
+/// Finish receiving an image and add it to the screen.
+unsafe fn kitty_store(s: *mut screen, kc: &mut kitty_command) -> Result<(), &'static str> {
+    unsafe {
+        let ki = kc.image;
+        kc.image = null_mut();
+
+        if (*ki).size == 0 {
+            kitty_image_release(ki);
+            return Err("ENODATA:no image data");
+        }
+        if (*ki).pixel_x == 0 || (*ki).pixel_y == 0 {
+            let size = if (*ki).format == 100 && (*ki).compressed == 0 {
+                kitty_png_size(ki)
+            } else {
+                None
+            };
+            let Some((width, height)) = size else {
+                kitty_image_release(ki);
+                return Err("EINVAL:image size unknown");
+            };
+            (*ki).pixel_x = width;
+            (*ki).pixel_y = height;
+        }
+
+        // An image number asks for an id to be chosen.
+        if kc.id == 0 && kc.number != 0 {
+            let mut id = 0;
+            for old in tailq_foreach(&raw mut (*s).kitty_images).map(NonNull::as_ptr) {
+                id = id.max((*old).id);
+            }
+            kc.id = id + 1;
+        }
+        (*ki).id = kc.id;
+        (*ki).number = kc.number;
+
+        if kc.id != 0 {
+            let old = kitty_find(s, kc.id, 0);
+            if !old.is_null() {
+                kitty_free_placements(s, old, 0);
+                kitty_remove(s, old);
+            }
+        }
+
+        let mut count = 0;
+        for _ in tailq_foreach(&raw mut (*s).kitty_images) {
+            count += 1;
+        }
+        if count == KITTY_IMAGES_MAX {
+            let oldest = tailq_first(&raw mut (*s).kitty_images);
+            kitty_free_placements(s, oldest, 0);
+            kitty_remove(s, oldest);
+        }
+
+        tailq_insert_tail(&raw mut (*s).kitty_images, ki);
+        kc.image = ki;
+        Ok(())
+    }
+}
+
+/// Start receiving an image.
+unsafe fn kitty_create(kc: &kitty_command) -> *mut kitty_image {
+    unsafe {
+        let ki = xcalloc1::<kitty_image>() as *mut kitty_image;
+
+        kitty_next_outer = kitty_next_outer.wrapping_add(1).max(1);
+        (*ki).outer = kitty_next_outer;
+        (*ki).references = 1;
+
+        (*ki).format = kc.format;
+        (*ki).compressed = (kc.compression == b'z') as i32;
+        (*ki).pixel_x = kc.pixel_x;
+        (*ki).pixel_y = kc.pixel_y;
+
+        (*ki).data = xstrdup(c"".as_ptr()).as_ptr();
+        ki
+    }
+}
+
+/// Delete placements and images.
+unsafe fn kitty_delete(s: *mut screen, kc: &kitty_command) -> bool {
+    unsafe {
+        let mut redraw = false;
+
+        match kc.delete {
+            0 | b'a' | b'A' => {
+                redraw = kitty_free_placements(s, null_mut(), 0);
+                if kc.delete == b'A' {
+                    for ki in tailq_foreach(&raw mut (*s).kitty_images).map(NonNull::as_ptr) {
+                        kitty_remove(s, ki);
+                    }
+                }
+            }
+            b'i' | b'I' | b'n' | b'N' => {
+                let ki = if kc.delete.eq_ignore_ascii_case(&b'i') {
+                    kitty_find(s, kc.id, 0)
+                } else {
+                    kitty_find(s, 0, kc.number)
+                };
+                if ki.is_null() {
+                    return false;
+                }
+                redraw = kitty_free_placements(s, ki, kc.placement);
+                if kc.delete.is_ascii_uppercase() {
+                    kitty_remove(s, ki);
+                }
+            }
+            _ => (),
+        }
+        redraw
+    }
+}
+
+/// Handle a kitty graphics command. Returns the reply for the application.
+pub unsafe fn kitty_graphics(
+    ctx: *mut screen_write_ctx,
+    buf: *const u8,
+    len: usize,
+) -> Option<String> {
+    unsafe {
+        let s = (*ctx).s;
+        let wp = (*ctx).wp;
+
+        let buf = std::slice::from_raw_parts(buf, len);
+        let (control, payload) = match buf.iter().position(|&ch| ch == b';') {
+            Some(i) => (&buf[..i], &buf[i + 1..]),
+            None => (buf, &buf[len..]),
+        };
+
+        let mut kc: kitty_command;
+        let pending = (*s).kitty_pending;
+        if !pending.is_null() {
+            // Further chunks only carry m and q, the rest is from the first.
+            kc = *pending;
+            kc.more = 0;
+            if kitty_parse(control, &mut kc).is_err() {
+                kc.more = 0;
+            }
+            if let Err(error) = kitty_append(kc.image, payload) {
+                kitty_image_release(kc.image);
+                free_(pending);
+                (*s).kitty_pending = null_mut();
+                return kitty_reply(&kc, Some(error));
+            }
+            if kc.more != 0 {
+                *pending = kc;
+                return None;
+            }
+            free_(pending);
+            (*s).kitty_pending = null_mut();
+        } else {
+            kc = zeroed();
+            kc.action = b't';
+            kc.medium = b'd';
+            kc.format = 32;
+            if kitty_parse(control, &mut kc).is_err() {
+                return kitty_reply(&kc, Some("EINVAL:bad control data"));
+            }
+            log_debug!(
+                "kitty_graphics: a={} i={} I={} m={}",
+                kc.action as char,
+                kc.id,
+                kc.number,
+                kc.more
+            );
+
+            match kc.action {
+                b't' | b'T' | b'q' => {
+                    if kc.medium != b'd' {
+                        return kitty_reply(&kc, Some("EINVAL:unsupported transmission medium"));
+                    }
+                    if !matches!(kc.format, 24 | 32 | 100) {
+                        return kitty_reply(&kc, Some("EINVAL:unsupported format"));
+                    }
+                    kc.image = kitty_create(&kc);
+                    if let Err(error) = kitty_append(kc.image, payload) {
+                        kitty_image_release(kc.image);
+                        return kitty_reply(&kc, Some(error));
+                    }
+                    if kc.more != 0 {
+                        let copy = xmalloc_::<kitty_command>().as_ptr();
+                        *copy = kc;
+                        (*s).kitty_pending = copy;
+                        return None;
+                    }
+                }
+                b'p' => {
+                    let ki = kitty_find(s, kc.id, kc.number);
+                    if ki.is_null() {
+                        return kitty_reply(&kc, Some("ENOENT:image not found"));
+                    }
+                    screen_write_kittyimage(ctx, ki, &kc);
+                    return kitty_reply(&kc, None);
+                }
+                b'd' => {
+                    if kitty_delete(s, &kc) && !wp.is_null() {
+                        (*wp).flags |= window_pane_flags::PANE_REDRAW;
+                    }
+                    return None;
+                }
+                _ => return kitty_reply(&kc, Some("EINVAL:unsupported action")),
+            }
+        }
+
+        // The whole image has arrived. A query only checks it could be loaded.
+        if kc.action == b'q' {
+            let ki = kc.image;
+            let error = if (*ki).size == 0 {
+                Some("ENODATA:no image data")
+            } else {
+                None
+            };
+            kitty_image_release(ki);
+            return kitty_reply(&kc, error);
+        }
+        if let Err(error) = kitty_store(s, &mut kc) {
+            return kitty_reply(&kc, Some(error));
+        }
+        if kc.action == b'T' {
+            screen_write_kittyimage(ctx, kc.image, &kc);
+        }
+        kitty_reply(&kc, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compat::queue::tailq_init;
+
+    #[test]
+    fn test_kitty_parse() {
+        let mut kc: kitty_command = unsafe { zeroed() };
+        assert!(kitty_parse(b"a=T,f=100,i=7,,m=1,q=2,x=whatever", &mut kc).is_ok());
+        assert_eq!(kc.action, b'T');
+        assert_eq!((kc.format, kc.id, kc.more, kc.quiet), (100, 7, 1, 2));
+
+        for bad in [
+            &b"a"[..],
+            b"a=",
+            b"=t",
+            b"ab=t",
+            b"a=tt",
+            b"i=-1",
+            b"i=x",
+            b"i=4294967296",
+            b"s=1 ",
+        ] {
+            let mut kc: kitty_command = unsafe { zeroed() };
+            assert!(kitty_parse(bad, &mut kc).is_err(), "{:?}", bad);
+        }
+
+        // Keys before the bad one are kept.
+        let mut kc: kitty_command = unsafe { zeroed() };
+        assert!(kitty_parse(b"i=3,m=", &mut kc).is_err());
+        assert_eq!(kc.id, 3);
+    }
+
+    #[test]
+    fn test_kitty_graphics() {
+        unsafe {
+            let mut s: screen = zeroed();
+            tailq_init(&raw mut s.images);
+            tailq_init(&raw mut s.kitty_images);
+            let mut ctx: screen_write_ctx = zeroed();
+            ctx.s = &raw mut s;
+
+            let send = |ctx: &mut screen_write_ctx, buf: &[u8]| {
+                kitty_graphics(ctx, buf.as_ptr(), buf.len())
+            };
+            let reply = |text: &str| Some(format!("\x1b_G{text}\x1b\\"));
+
+            // Malformed control data is refused before anything is stored.
+            assert_eq!(
+                send(&mut ctx, b"i=1,s=x;AAAA"),
+                reply("i=1;EINVAL:bad control data")
+            );
+            assert_eq!(
+                send(&mut ctx, b"i=1,t=f;AAAA"),
+                reply("i=1;EINVAL:unsupported transmission medium")
+            );
+            assert!(tailq_first(&raw mut s.kitty_images).is_null());
+
+            // A chunked transfer is held until the chunk without m=1.
+            assert_eq!(send(&mut ctx, b"i=2,s=1,v=1,m=1;AAAA"), None);
+            assert!(!s.kitty_pending.is_null());
+            assert_eq!(send(&mut ctx, b"m=1;BBBB"), None);
+            assert_eq!(send(&mut ctx, b"m=0;CCCC"), reply("i=2;OK"));
+            assert!(s.kitty_pending.is_null());
+            let ki = kitty_find(&raw mut s, 2, 0);
+            assert!(!ki.is_null());
+            assert_eq!(cstr_to_str((*ki).data), "AAAABBBBCCCC");
+            assert_eq!(((*ki).pixel_x, (*ki).pixel_y), (1, 1));
+
+            // Bad control data in a later chunk ends the transfer.
+            assert_eq!(send(&mut ctx, b"i=3,s=1,v=1,m=1;AAAA"), None);
+            assert_eq!(send(&mut ctx, b"m=x;BBBB"), reply("i=3;OK"));
+            assert!(s.kitty_pending.is_null());
+            assert_eq!(
+                cstr_to_str((*kitty_find(&raw mut s, 3, 0)).data),
+                "AAAABBBB"
+            );
+
+            // The size is read from the PNG header when not given.
+            assert_eq!(
+                send(&mut ctx, b"i=4,f=100;iVBORw0KGgoAAAANSUhEUgAAAAMAAAAF"),
+                reply("i=4;OK")
+            );
+            let ki = kitty_find(&raw mut s, 4, 0);
+            assert_eq!(((*ki).pixel_x, (*ki).pixel_y), (3, 5));
+            assert_eq!(
+                send(&mut ctx, b"i=5,f=100;AAAA"),
+                reply("i=5;EINVAL:image size unknown")
+            );
+
+            // Oversize payloads are refused, in one piece or in chunks.
+            let mut big = b"i=6,s=1,v=1;".to_vec();
+            big.resize(big.len() + KITTY_IMAGE_SIZE_MAX + 1, b'A');
+            assert_eq!(send(&mut ctx, &big), reply("i=6;EFBIG:image too large"));
+            let mut big = b"i=7,s=1,v=1,m=1;".to_vec();
+            big.resize(big.len() + KITTY_IMAGE_SIZE_MAX, b'A');
+            assert_eq!(send(&mut ctx, &big), None);
+            assert_eq!(send(&mut ctx, b"m=1;A"), reply("i=7;EFBIG:image too large"));
+            assert!(s.kitty_pending.is_null());
+            assert!(kitty_find(&raw mut s, 6, 0).is_null());
+            assert!(kitty_find(&raw mut s, 7, 0).is_null());
+
+            kitty_free_all(&raw mut s);
+            assert!(tailq_first(&raw mut s.kitty_images).is_null());
+        }
+    }
+}
@@ -103,21 +103,23 @@ unsafe fn sixel_set_pixel(si: *mut sixel_image, x: u32, y: u32, c: u32) -> i32 {
 }
 
 unsafe fn sixel_parse_write(si: *mut sixel_image, ch: u32) -> i32 {
-    if sixel_parse_expand_lines(si, (*si).dy + 6) != 0 {
-        return 1;
-    }
-    let mut sl = (*si).lines.add((*si).dy as usize);
-
-    for i in 0..6 {
-        if sixel_parse_expand_line(si, sl, (*si).dx + 1) != 0 {
+    unsafe {
+        if sixel_parse_expand_lines(si, (*si).dy + 6) != 0 {
             return 1;
         }
-        if ch & (1 << i) != 0 {
-            *(*sl).data.add((*si).dx as usize) = (*si).dc as u16;
+        let mut sl = (*si).lines.add((*si).dy as usize);
+
+        for i in 0..6 {
+            if sixel_parse_expand_line(si, sl, (*si).dx + 1) != 0 {
+                return 1;
+            }
+            if ch & (1 << i) != 0 {
+                *(*sl).data.add((*si).dx as usize) = (*si).dc as u16;
+            }
+            sl = sl.add(1);
         }
-        sl = sl.add(1);
+        0
     }
-    return 0;
 }
 
 unsafe fn sixel_parse_attributes(
@@ -246,8 +248,6 @@ unsafe fn sixel_parse_repeat(
 
         let mut n: u32 = 0;
 
-        let mut errstr: *const c_char = null();
-
         let mut last = cp;
         while (last != end) {
             if (*last < b'0' as i8 || *last > b'9' as i8) {
@@ -267,16 +267,10 @@ unsafe fn sixel_parse_repeat(
         }
         tmp[n as usize] = b'\0' as i8;
 
-        n = strtonum(
-            (&raw const tmp) as *const i8,
-            1,
-            SIXEL_WIDTH_LIMIT as i64,
-            &raw mut errstr,
-        ) as u32;
-        if n == 0 || !errstr.is_null() {
+        let Ok(n) = strtonum(tmp.as_ptr(), 1, SIXEL_WIDTH_LIMIT) else {
             // log_debug("%s: repeat too wide", __func__);
             return null_mut();
-        }
+        };
 
         let ch = (*last) - 0x3f;
         last = last.add(1);
@@ -287,7 +281,7 @@ unsafe fn sixel_parse_repeat(
             }
             (*si).dx += 1;
         }
-        return last;
+        last
     }
 }
 
@@ -345,7 +339,7 @@ pub unsafe fn sixel_parse(
                     }
                     b'$' => (*si).dx = 0,
                     _ => {
-                        if !(ch < 0x20) {
+                        if ch >= 0x20 {
                             if (ch < 0x3f || ch > 0x7e) {
                                 break 'bad;
                             }
@@ -364,8 +358,8 @@ pub unsafe fn sixel_parse(
             }
             return si;
         } // 'bad:
-        free_(si);
-        return null_mut();
+        sixel_free(si);
+        null_mut()
     }
 }
 
@@ -509,11 +503,12 @@ unsafe fn sixel_print_add(
     slen: usize,
 ) {
     unsafe {
-        if (*used + slen >= *len + 1) {
+        // Always leave room for the terminating NUL.
+        while *used + slen >= *len {
             (*len) *= 2;
             *buf = xrealloc_(*buf, *len).as_ptr()
         }
-        libc::memcpy(buf.add(*used).cast(), s.cast(), slen);
+        libc::memcpy((*buf).add(*used).cast(), s.cast(), slen);
         (*used) += slen;
     }
 }
@@ -537,23 +532,18 @@ unsafe fn sixel_print_repeat(
             sixel_print_add(buf, len, used, &raw const ch, 1);
         } else if (count != 0) {
             let mut tmp: [c_char; 16] = [0; 16];
-            let tmplen = xsnprintf(
-                (&raw mut tmp) as *mut i8,
-                16usize,
-                c"!%u%c".as_ptr(),
-                count,
-                ch as i32,
-            ) as usize;
+            let tmplen =
+                xsnprintf_!(tmp.as_mut_ptr(), 16, "!{}{}", count, ch as u8 as char).unwrap();
             sixel_print_add(buf, len, used, (&raw mut tmp) as *mut i8, tmplen);
         }
     }
 }
 
-unsafe fn sixel_print(
+pub unsafe fn sixel_print(
     si: *mut sixel_image,
     map: *mut sixel_image,
     size: *mut usize,
-) -> *const c_char {
+) -> *mut c_char {
     unsafe {
         let mut buf: *mut c_char = null_mut();
         const size_of_tmp: usize = 64;
@@ -588,13 +578,14 @@ unsafe fn sixel_print(
             3,
         );
 
-        tmplen = xsnprintf(
-            (&raw mut tmp).cast(),
+        tmplen = xsnprintf_!(
+            tmp.as_mut_ptr(),
             size_of_tmp,
-            c"\"1;1;%u;%u".as_ptr(),
+            "\"1;1;{};{}",
             (*si).x,
-            (*si).y,
-        ) as usize;
+            (*si).y
+        )
+        .unwrap();
         sixel_print_add(
             &raw mut buf,
             &raw mut len,
@@ -605,16 +596,17 @@ unsafe fn sixel_print(
 
         for i in 0..ncolours {
             let c = *colours.add(i as usize);
-            tmplen = xsnprintf(
-                (&raw mut tmp).cast(),
+            tmplen = xsnprintf_!(
+                tmp.as_mut_ptr(),
                 size_of_tmp,
-                c"#%u;%u;%u;%u;%u".as_ptr(),
+                "#{};{};{};{};{}",
                 i,
                 c >> 24,
                 (c >> 16) & 0xff,
                 (c >> 8) & 0xff,
                 c & 0xff,
-            ) as usize;
+            )
+            .unwrap();
             sixel_print_add(
                 &raw mut buf,
                 &raw mut len,
@@ -643,7 +635,7 @@ unsafe fn sixel_print(
                 if *contains.add(c as usize) == 0 {
                     continue;
                 }
-                tmplen = xsnprintf((&raw mut tmp).cast(), size_of_tmp, c"#%u".as_ptr(), c) as usize;
+                tmplen = xsnprintf_!(tmp.as_mut_ptr(), size_of_tmp, "#{}", c).unwrap();
                 sixel_print_add(
                     &raw mut buf,
                     &raw mut len,
@@ -724,7 +716,7 @@ unsafe fn sixel_to_screen(si: *mut sixel_image) -> *mut screen {
         screen_init(s, sx, sy, 0);
 
         memcpy__(&raw mut gc, &raw const grid_default_cell);
-        gc.attr |= (GRID_ATTR_CHARSET | GRID_ATTR_DIM);
+        gc.attr |= grid_attr::GRID_ATTR_CHARSET | grid_attr::GRID_ATTR_DIM;
         utf8_set(&raw mut gc.data, b'~');
 
         screen_write_start(&raw mut ctx, s);
@@ -753,3 +745,154 @@ unsafe fn sixel_to_screen(si: *mut sixel_image) -> *mut screen {
         s
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Mutex, Once, PoisonError};
+
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    unsafe fn test_pane(sx: u32, sy: u32) -> *mut window_pane {
+        static INIT: Once = Once::new();
+
+        unsafe {
+            INIT.call_once(|| {
+                global_options = options_create(null_mut());
+                global_s_options = options_create(null_mut());
+                global_w_options = options_create(null_mut());
+
+                let mut oe: *const options_table_entry = &raw const options_table as _;
+                while !(*oe).name.is_null() {
+                    if (*oe).scope & OPTIONS_TABLE_SERVER != 0 {
+                        options_default(global_options, oe);
+                    }
+                    if (*oe).scope & OPTIONS_TABLE_SESSION != 0 {
+                        options_default(global_s_options, oe);
+                    }
+                    if (*oe).scope & OPTIONS_TABLE_WINDOW != 0 {
+                        options_default(global_w_options, oe);
+                    }
+                    oe = oe.add(1);
+                }
+            });
+
+            let w = window_create(sx, sy, 0, 0);
+            let wp = window_add_pane(w, null_mut(), 0, 0);
+            (*w).active = wp;
+            window_add_ref(w, c"test_pane".as_ptr());
+            (*wp).ictx = input_init(wp, null_mut(), &raw mut (*wp).palette);
+            wp
+        }
+    }
+
+    /// Build a solid sixel image of the given size in pixels.
+    fn test_sixel(x: u32, y: u32) -> Vec<u8> {
+        let mut buf = format!("\x1bPq\"1;1;{x};{y}#0;2;100;0;0").into_bytes();
+        for i in 0..y.div_ceil(6) {
+            if i != 0 {
+                buf.push(b'-');
+            }
+            buf.extend_from_slice(format!("#0!{x}~").as_bytes());
+        }
+        buf.extend_from_slice(b"\x1b\\");
+        buf
+    }
+
+    unsafe fn test_images(wp: *mut window_pane) -> Vec<(u32, u32, u32, u32)> {
+        unsafe {
+            tailq_foreach::<_, discr_entry>(&raw mut (*wp).base.images)
+                .map(|im| {
+                    let im = im.as_ptr();
+                    ((*im).px, (*im).py, (*im).sx, (*im).sy)
+                })
+                .collect()
+        }
+    }
+
+    unsafe fn test_parse(wp: *mut window_pane, buf: &[u8]) {
+        unsafe {
+            let mut buf = buf.to_vec();
+            input_parse_buffer(wp, buf.as_mut_ptr(), buf.len());
+        }
+    }
+
+    #[test]
+    fn test_sixel_store() {
+        let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        unsafe {
+            let wp = test_pane(80, 24);
+
+            // 48x96 pixels is 3x3 cells at the default 16x32 cell size.
+            test_parse(wp, &test_sixel(48, 96));
+            assert_eq!(test_images(wp), [(0, 0, 3, 3)]);
+            assert_eq!((*wp).base.cy, 3);
+
+            test_parse(wp, b"abc\r\n");
+            test_parse(wp, &test_sixel(20, 40));
+            assert_eq!(test_images(wp), [(0, 0, 3, 3), (0, 4, 2, 2)]);
+
+            window_remove_ref((*wp).window, c"test_sixel_store".as_ptr());
+        }
+    }
+
+    #[test]
+    fn test_sixel_invalid() {
+        let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        unsafe {
+            let wp = test_pane(80, 24);
+
+            test_parse(wp, b"\x1bPq\"1;1;x;y#0!4~\x1b\\");
+            test_parse(wp, b"\x1bPq\x1b\\");
+            assert!(test_images(wp).is_empty());
+
+            window_remove_ref((*wp).window, c"test_sixel_invalid".as_ptr());
+        }
+    }
+
+    #[test]
+    fn test_sixel_scroll() {
+        let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        unsafe {
+            let wp = test_pane(80, 10);
+
+            // An image taller than the pane is cut down to leave a line free.
+            test_parse(wp, &test_sixel(16, 32 * 12));
+            assert_eq!(test_images(wp), [(0, 0, 1, 9)]);
+
+            // Scrolling moves the image up and then trims it at the top.
+            test_parse(wp, b"\r\n\r\n");
+            assert_eq!(test_images(wp), [(0, 0, 1, 7)]);
+
+            window_remove_ref((*wp).window, c"test_sixel_scroll".as_ptr());
+        }
+    }
+
+    #[test]
+    fn test_sixel_resize() {
+        let _guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        unsafe {
+            let wp = test_pane(80, 24);
+            let s = &raw mut (*wp).base;
+
+            test_parse(wp, b"\r\n\r\n");
+            test_parse(wp, &test_sixel(64, 96));
+            assert_eq!(test_images(wp), [(0, 2, 4, 3)]);
+
+            // Narrower than the image: it is cropped to fit.
+            screen_resize(s, 3, 24, 0);
+            assert_eq!(test_images(wp), [(0, 2, 3, 3)]);
+
+            // Lines pushed into history take the image with them.
+            screen_resize(s, 3, 5, 0);
+            assert_eq!(test_images(wp), [(0, 1, 3, 3)]);
+
+            // Shrinking further pushes the top of the image into history.
+            screen_resize(s, 3, 2, 0);
+            assert_eq!(test_images(wp), [(0, 0, 3, 1)]);
+
+            window_remove_ref((*wp).window, c"test_sixel_resize".as_ptr());
+        }
+    }
+}
@@ -131,6 +131,10 @@ pub struct input_ctx {
 
     timer: event,
 
+    /// Column where the command line started, as marked by OSC 133 B, or
+    /// u32::MAX if there is none.
+    command_x: u32,
+
     /// All input received since we were last in the ground state. Sent to control clients on connection.
     since_ground: *mut evbuffer,
 }
@@ -225,6 +229,10 @@ enum input_csi_type {
     INPUT_CSI_HPA,
     INPUT_CSI_ICH,
     INPUT_CSI_IL,
+    INPUT_CSI_KITTY_POP,
+    INPUT_CSI_KITTY_PUSH,
+    INPUT_CSI_KITTY_QUERY,
+    INPUT_CSI_KITTY_SET,
     INPUT_CSI_MODOFF,
     INPUT_CSI_MODSET,
     INPUT_CSI_RCP,
@@ -245,7 +253,7 @@ enum input_csi_type {
 }
 
 /// control (csi) command table.
-static input_csi_table: [input_table_entry; 40] = [
+static input_csi_table: [input_table_entry; 44] = [
     input_table_entry::new_csi('@', c"", input_csi_type::INPUT_CSI_ICH),
     input_table_entry::new_csi('A', c"", input_csi_type::INPUT_CSI_CUU),
     input_table_entry::new_csi('B', c"", input_csi_type::INPUT_CSI_CUD),
@@ -286,6 +294,10 @@ static input_csi_table: [input_table_entry; 40] = [
     input_table_entry::new_csi('s', c"", input_csi_type::INPUT_CSI_SCP),
     input_table_entry::new_csi('t', c"", input_csi_type::INPUT_CSI_WINOPS),
     input_table_entry::new_csi('u', c"", input_csi_type::INPUT_CSI_RCP),
+    input_table_entry::new_csi('u', c"<", input_csi_type::INPUT_CSI_KITTY_POP),
+    input_table_entry::new_csi('u', c"=", input_csi_type::INPUT_CSI_KITTY_SET),
+    input_table_entry::new_csi('u', c">", input_csi_type::INPUT_CSI_KITTY_PUSH),
+    input_table_entry::new_csi('u', c"?", input_csi_type::INPUT_CSI_KITTY_QUERY),
 ];
 
 /// Input transition.
@@ -953,6 +965,8 @@ pub unsafe fn input_init(
         (*ictx).event = bev;
         (*ictx).palette = palette;
 
+        (*ictx).command_x = u32::MAX;
+
         (*ictx).input_space = INPUT_BUF_START;
         (*ictx).input_buf = xmalloc(INPUT_BUF_START).as_ptr().cast();
 
@@ -1409,6 +1423,9 @@ unsafe fn input_c0_dispatch(ictx: *mut input_ctx) -> i32 {
             NUL | BEL => {
                 if !wp.is_null() {
                     alerts_queue(NonNull::new((*wp).window).unwrap(), window_flag::BELL);
+                    if (*ictx).ch as u8 == BEL {
+                        window_pane_add_event(wp, window_pane_event_type::Bell, 0);
+                    }
                 }
             }
             BS => screen_write_backspace(sctx),
@@ -1630,6 +1647,38 @@ unsafe fn input_csi_dispatch(ictx: *mut input_ctx) -> i32 {
                     }
                 }
             }
+            Ok(input_csi_type::INPUT_CSI_KITTY_PUSH) => {
+                if input_kitty_keys_enabled() {
+                    let n = input_get(ictx, 0, 0, 0);
+                    if n != -1 {
+                        screen_kitty_keys_push(s, n as u32);
+                    }
+                }
+            }
+            Ok(input_csi_type::INPUT_CSI_KITTY_POP) => {
+                if input_kitty_keys_enabled() {
+                    let n = input_get(ictx, 0, 1, 1);
+                    if n != -1 {
+                        screen_kitty_keys_pop(s, n as u32);
+                    }
+                }
+            }
+            Ok(input_csi_type::INPUT_CSI_KITTY_SET) => {
+                if input_kitty_keys_enabled() {
+                    let n = input_get(ictx, 0, 0, 0);
+                    let m = input_get(ictx, 1, 1, 1);
+                    if n != -1 && m != -1 {
+                        screen_kitty_keys_set(s, n as u32, m as u32);
+                    }
+                }
+            }
+            Ok(input_csi_type::INPUT_CSI_KITTY_QUERY) => {
+                // Stay silent if the protocol is off so applications fall
+                // back to another keyboard mode.
+                if input_kitty_keys_enabled() {
+                    input_reply!(ictx, "\x1b[?{}u", screen_kitty_keys_flags(s));
+                }
+            }
             Ok(input_csi_type::INPUT_CSI_WINOPS) => input_csi_dispatch_winops(ictx),
             Ok(input_csi_type::INPUT_CSI_CUU) => {
                 let n = input_get(ictx, 0, 1, 1);
@@ -1656,7 +1705,7 @@ unsafe fn input_csi_dispatch(ictx: *mut input_ctx) -> i32 {
                 0 => {
                     #[cfg(feature = "sixel")]
                     {
-                        input_reply(ictx, c"\x1b[?1;2;4c".as_ptr());
+                        input_reply!(ictx, "\x1b[?1;2;4c");
                     }
                     #[cfg(not(feature = "sixel"))]
                     {
@@ -1815,6 +1864,14 @@ unsafe fn input_csi_dispatch(ictx: *mut input_ctx) -> i32 {
     }
 }
 
+/// Is the kitty keyboard protocol available to applications?
+unsafe fn input_kitty_keys_enabled() -> bool {
+    unsafe {
+        options_get_number_(global_options, c"extended-keys") != 0
+            && options_get_number_(global_options, c"extended-keys-format") == 2
+    }
+}
+
 /// Handle CSI RM.
 unsafe fn input_csi_dispatch_rm(ictx: *mut input_ctx) {
     unsafe {
@@ -1965,9 +2022,9 @@ unsafe fn input_csi_dispatch_sm_graphics(ictx: *mut input_ctx) {
             let o = input_get(ictx, 2, 0, 0);
 
             if n == 1 && (m == 1 || m == 2 || m == 4) {
-                input_reply(ictx, c"\x1b[?%d;0;%uS".as_ptr(), n, SIXEL_COLOUR_REGISTERS);
+                input_reply!(ictx, "\x1b[?{};0;{}S", n, SIXEL_COLOUR_REGISTERS);
             } else {
-                input_reply(ictx, c"\x1b[?%d;3;%dS".as_ptr(), n, o);
+                input_reply!(ictx, "\x1b[?{};3;{}S", n, o);
             }
         }
     }
@@ -2377,8 +2434,9 @@ unsafe fn input_dcs_dispatch(ictx: *mut input_ctx) -> i32 {
         {
             let w = (*wp).window;
             if *buf == b'q' {
-                if let Some(si) = sixel_parse(buf, len, (*w).xpixel, (*w).ypixel) {
-                    screen_write_sixelimage(sctx, si, (*ictx).cell.cell.bg);
+                let si = sixel_parse(buf.cast(), len, (*w).xpixel, (*w).ypixel);
+                if !si.is_null() {
+                    screen_write_sixelimage(sctx, si, (*ictx).cell.cell.bg as u32);
                 }
             }
         }
@@ -2510,6 +2568,14 @@ unsafe fn input_exit_apc(ictx: *mut input_ctx) {
         }
         log_debug!("input_exit_apc: \"{}\"", _s((*ictx).input_buf.cast()));
 
+        if *(*ictx).input_buf == b'G' {
+            let buf = (*ictx).input_buf.add(1);
+            if let Some(reply) = kitty_graphics(sctx, buf, (*ictx).input_len - 1) {
+                input_reply!(ictx, "{}", reply);
+            }
+            return;
+        }
+
         if screen_set_title((*sctx).s, (*ictx).input_buf.cast()) != 0 && !wp.is_null() {
             notify_pane(c"pane-title-changed", wp);
             server_redraw_window_borders((*wp).window);
@@ -2990,20 +3056,141 @@ unsafe fn input_osc_112(ictx: *mut input_ctx, p: *const c_char) {
     }
 }
 
-/// Handle the OSC 133 sequence.
+/// Find the line where the command line being run started. Line numbers change
+/// as history is collected or reflowed, so rather than keeping the line from
+/// OSC 133 B, look up for the mark it left, stopping at an earlier command.
+unsafe fn input_osc_133_input(gd: *mut grid, line: u32) -> Option<u32> {
+    unsafe {
+        let mut yy = line;
+        while yy >= (*gd).hspill {
+            let flags = (*grid_get_line_marks(gd, yy)).flags;
+            if flags.intersects(grid_line_flag::START_INPUT) {
+                return Some(yy);
+            }
+            if flags.intersects(grid_line_flag::START_PROMPT | grid_line_flag::START_OUTPUT)
+                || yy == 0
+            {
+                break;
+            }
+            yy -= 1;
+        }
+        None
+    }
+}
+
+/// Read the command line typed between OSC 133 B and C from the screen.
+unsafe fn input_osc_133_read(gd: *mut grid, line: u32, x: u32, end: u32, end_x: u32) -> String {
+    unsafe {
+        let mut gc: *mut grid_cell = null_mut();
+        let mut text = String::new();
+
+        for py in line..=end {
+            let wrapped = (*grid_get_line(gd, py))
+                .flags
+                .intersects(grid_line_flag::WRAPPED);
+            let px = if py == line { x } else { 0 };
+            let nx = if py == end { end_x } else { (*gd).sx };
+            if nx <= px {
+                continue;
+            }
+            let flags = if wrapped && py != end {
+                grid_string_flags::empty()
+            } else {
+                grid_string_flags::GRID_STRING_TRIM_SPACES
+            };
+            let cp = grid_string_cells(gd, px, py, nx - px, &raw mut gc, flags, null_mut());
+            text.push_str(&String::from_utf8_lossy(CStr::from_ptr(cp).to_bytes()));
+            free_(cp);
+            if !wrapped && py != end {
+                text.push('\n');
+            }
+        }
+        text.trim().to_string()
+    }
+}
+
+/// Decode a command line given as a percent-encoded URL, dropping any control
+/// characters other than newlines.
+fn input_osc_133_unquote(value: &[u8]) -> String {
+    let mut out = Vec::with_capacity(value.len());
+    let mut i = 0;
+    while i < value.len() {
+        if value[i] == b'%'
+            && let Some(hex) = value.get(i + 1..i + 3)
+            && let Ok(hex) = std::str::from_utf8(hex)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            out.push(byte);
+            i += 3;
+            continue;
+        }
+        out.push(value[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out)
+        .chars()
+        .filter(|c| *c == '\n' || !c.is_control())
+        .collect()
+}
+
+/// Handle the OSC 133 sequence for semantic prompts. A marks the start of the
+/// prompt, B the start of the command line, C the start of the output and D
+/// its end, followed by the exit status. The command line is read from the
+/// screen unless C has a cmdline_url option.
 unsafe fn input_osc_133(ictx: *mut input_ctx, p: *const c_char) {
     unsafe {
-        let gd = (*(*ictx).ctx.s).grid;
-        let line = (*(*ictx).ctx.s).cy + (*gd).hsize;
+        let wp = (*ictx).wp;
+        let s = (*ictx).ctx.s;
+        let gd = (*s).grid;
+        let line = (*s).cy + (*gd).hsize;
 
         if line > (*gd).hsize + (*gd).sy - 1 {
             return;
         }
         let gl = grid_get_line(gd, line);
 
-        match (*p) as u8 {
-            b'A' => (*gl).flags |= grid_line_flag::START_PROMPT,
-            b'C' => (*gl).flags |= grid_line_flag::START_OUTPUT,
+        let mut fields = CStr::from_ptr(p).to_bytes().split(|c| *c == b';');
+        let mark = fields.next().unwrap_or_default();
+        match mark {
+            b"A" => (*gl).flags |= grid_line_flag::START_PROMPT,
+            b"B" => {
+                (*gl).flags |= grid_line_flag::START_INPUT;
+                (*ictx).command_x = (*s).cx;
+            }
+            b"C" => {
+                let x = (*ictx).command_x;
+                (*ictx).command_x = u32::MAX;
+
+                let text = if let Some(url) = fields.find_map(|f| f.strip_prefix(b"cmdline_url=")) {
+                    input_osc_133_unquote(url)
+                } else if x != u32::MAX
+                    && let Some(input) = input_osc_133_input(gd, line)
+                {
+                    input_osc_133_read(gd, input, x, line, (*s).cx)
+                } else {
+                    String::new()
+                };
+                log_debug!("{}: command {}", "input_osc_133", text);
+
+                if !wp.is_null() {
+                    free_((*wp).last_command);
+                    (*wp).last_command = format_nul!("{}", text);
+                    server_status_window((*wp).window);
+                }
+                grid_start_command(gd, line, text);
+            }
+            b"D" => {
+                let status = fields
+                    .next()
+                    .and_then(|f| std::str::from_utf8(f).ok())
+                    .and_then(|f| f.parse::<i32>().ok());
+
+                let command = grid_end_command(gd, line, (*s).cx == 0, status);
+                if !command.is_null() && !wp.is_null() {
+                    (*wp).last_status = status.unwrap_or(-1);
+                    server_status_window((*wp).window);
+                }
+            }
             _ => (),
         }
     }
@@ -3169,3 +3356,48 @@ pub unsafe fn input_reply_clipboard(
         free_(out);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_osc_133_input() {
+        unsafe {
+            let gd = grid_create(10, 3, 20);
+            let mut gc = grid_default_cell;
+            for k in 0..40u32 {
+                let y = (*gd).hsize;
+                utf8_set(&raw mut gc.data, b'a' + (k % 26) as u8);
+                grid_set_cell(gd, 0, y, &gc);
+                if k == 25 {
+                    (*grid_get_line(gd, y)).flags |= grid_line_flag::START_INPUT;
+                }
+                if k == 35 {
+                    break;
+                }
+                grid_collect_history(gd);
+                grid_scroll_history(gd, 8);
+            }
+
+            // History has been collected since the mark, so the line it was
+            // on has moved up.
+            let line = (*gd).hsize;
+            let input = input_osc_133_input(gd, line).unwrap();
+            assert!(input < 25);
+            grid_get_cell(gd, 0, input, &raw mut gc);
+            assert_eq!(gc.data.data[0], b'z');
+
+            // An earlier command stops the search.
+            (*grid_get_line(gd, input + 2)).flags |= grid_line_flag::START_OUTPUT;
+            assert_eq!(input_osc_133_input(gd, line), None);
+            grid_destroy(gd);
+        }
+    }
+
+    #[test]
+    fn test_input_osc_133_unquote() {
+        assert_eq!(input_osc_133_unquote(b"ls%20-l%0Apwd"), "ls -l\npwd");
+        assert_eq!(input_osc_133_unquote(b"a%1bb%zz%2"), "ab%zz%2");
+    }
+}
@@ -61,54 +61,54 @@ const input_key_defaults_len: usize = 83;
 
 static mut input_key_defaults: [input_key_entry; 83] = [
     /* Paste keys. */
-    input_key_entry::new(keyc::KEYC_PASTE_START as u64, c"\xb11[200~"),
-    input_key_entry::new(keyc::KEYC_PASTE_END as u64, c"\xb11[201~"),
+    input_key_entry::new(keyc::KEYC_PASTE_START as u64, c"\x1b[200~"),
+    input_key_entry::new(keyc::KEYC_PASTE_END as u64, c"\x1b[201~"),
     /* Function keys. */
-    input_key_entry::new(keyc::KEYC_F1 as u64, c"\xb11OP"),
-    input_key_entry::new(keyc::KEYC_F2 as u64, c"\xb11OQ"),
-    input_key_entry::new(keyc::KEYC_F3 as u64, c"\xb11OR"),
-    input_key_entry::new(keyc::KEYC_F4 as u64, c"\xb11OS"),
-    input_key_entry::new(keyc::KEYC_F5 as u64, c"\xb11[15~"),
-    input_key_entry::new(keyc::KEYC_F6 as u64, c"\xb11[17~"),
-    input_key_entry::new(keyc::KEYC_F7 as u64, c"\xb11[18~"),
-    input_key_entry::new(keyc::KEYC_F8 as u64, c"\xb11[19~"),
-    input_key_entry::new(keyc::KEYC_F9 as u64, c"\xb11[20~"),
-    input_key_entry::new(keyc::KEYC_F10 as u64, c"\xb11[21~"),
-    input_key_entry::new(keyc::KEYC_F11 as u64, c"\xb11[23~"),
-    input_key_entry::new(keyc::KEYC_F12 as u64, c"\xb11[24~"),
-    input_key_entry::new(keyc::KEYC_IC as u64, c"\xb11[2~"),
-    input_key_entry::new(keyc::KEYC_DC as u64, c"\xb11[3~"),
-    input_key_entry::new(keyc::KEYC_HOME as u64, c"\xb11[1~"),
-    input_key_entry::new(keyc::KEYC_END as u64, c"\xb11[4~"),
-    input_key_entry::new(keyc::KEYC_NPAGE as u64, c"\xb11[6~"),
-    input_key_entry::new(keyc::KEYC_PPAGE as u64, c"\xb11[5~"),
-    input_key_entry::new(keyc::KEYC_BTAB as u64, c"\xb11[Z"),
+    input_key_entry::new(keyc::KEYC_F1 as u64, c"\x1bOP"),
+    input_key_entry::new(keyc::KEYC_F2 as u64, c"\x1bOQ"),
+    input_key_entry::new(keyc::KEYC_F3 as u64, c"\x1bOR"),
+    input_key_entry::new(keyc::KEYC_F4 as u64, c"\x1bOS"),
+    input_key_entry::new(keyc::KEYC_F5 as u64, c"\x1b[15~"),
+    input_key_entry::new(keyc::KEYC_F6 as u64, c"\x1b[17~"),
+    input_key_entry::new(keyc::KEYC_F7 as u64, c"\x1b[18~"),
+    input_key_entry::new(keyc::KEYC_F8 as u64, c"\x1b[19~"),
+    input_key_entry::new(keyc::KEYC_F9 as u64, c"\x1b[20~"),
+    input_key_entry::new(keyc::KEYC_F10 as u64, c"\x1b[21~"),
+    input_key_entry::new(keyc::KEYC_F11 as u64, c"\x1b[23~"),
+    input_key_entry::new(keyc::KEYC_F12 as u64, c"\x1b[24~"),
+    input_key_entry::new(keyc::KEYC_IC as u64, c"\x1b[2~"),
+    input_key_entry::new(keyc::KEYC_DC as u64, c"\x1b[3~"),
+    input_key_entry::new(keyc::KEYC_HOME as u64, c"\x1b[1~"),
+    input_key_entry::new(keyc::KEYC_END as u64, c"\x1b[4~"),
+    input_key_entry::new(keyc::KEYC_NPAGE as u64, c"\x1b[6~"),
+    input_key_entry::new(keyc::KEYC_PPAGE as u64, c"\x1b[5~"),
+    input_key_entry::new(keyc::KEYC_BTAB as u64, c"\x1b[Z"),
     /* Arrow keys. */
-    input_key_entry::new(keyc::KEYC_UP as u64 | KEYC_CURSOR, c"\xb11OA"),
-    input_key_entry::new(keyc::KEYC_DOWN as u64 | KEYC_CURSOR, c"\xb11OB"),
-    input_key_entry::new(keyc::KEYC_RIGHT as u64 | KEYC_CURSOR, c"\xb11OC"),
-    input_key_entry::new(keyc::KEYC_LEFT as u64 | KEYC_CURSOR, c"\xb11OD"),
-    input_key_entry::new(keyc::KEYC_UP as u64, c"\xb11[A"),
-    input_key_entry::new(keyc::KEYC_DOWN as u64, c"\xb11[B"),
-    input_key_entry::new(keyc::KEYC_RIGHT as u64, c"\xb11[C"),
-    input_key_entry::new(keyc::KEYC_LEFT as u64, c"\xb11[D"),
+    input_key_entry::new(keyc::KEYC_UP as u64 | KEYC_CURSOR, c"\x1bOA"),
+    input_key_entry::new(keyc::KEYC_DOWN as u64 | KEYC_CURSOR, c"\x1bOB"),
+    input_key_entry::new(keyc::KEYC_RIGHT as u64 | KEYC_CURSOR, c"\x1bOC"),
+    input_key_entry::new(keyc::KEYC_LEFT as u64 | KEYC_CURSOR, c"\x1bOD"),
+    input_key_entry::new(keyc::KEYC_UP as u64, c"\x1b[A"),
+    input_key_entry::new(keyc::KEYC_DOWN as u64, c"\x1b[B"),
+    input_key_entry::new(keyc::KEYC_RIGHT as u64, c"\x1b[C"),
+    input_key_entry::new(keyc::KEYC_LEFT as u64, c"\x1b[D"),
     /* Keypad keys. */
-    input_key_entry::new(keyc::KEYC_KP_SLASH as u64 | KEYC_KEYPAD, c"\xb11Oo"),
-    input_key_entry::new(keyc::KEYC_KP_STAR as u64 | KEYC_KEYPAD, c"\xb11Oj"),
-    input_key_entry::new(keyc::KEYC_KP_MINUS as u64 | KEYC_KEYPAD, c"\xb11Om"),
-    input_key_entry::new(keyc::KEYC_KP_SEVEN as u64 | KEYC_KEYPAD, c"\xb11Ow"),
-    input_key_entry::new(keyc::KEYC_KP_EIGHT as u64 | KEYC_KEYPAD, c"\xb11Ox"),
-    input_key_entry::new(keyc::KEYC_KP_NINE as u64 | KEYC_KEYPAD, c"\xb11Oy"),
-    input_key_entry::new(keyc::KEYC_KP_PLUS as u64 | KEYC_KEYPAD, c"\xb11Ok"),
-    input_key_entry::new(keyc::KEYC_KP_FOUR as u64 | KEYC_KEYPAD, c"\xb11Ot"),
-    input_key_entry::new(keyc::KEYC_KP_FIVE as u64 | KEYC_KEYPAD, c"\xb11Ou"),
-    input_key_entry::new(keyc::KEYC_KP_SIX as u64 | KEYC_KEYPAD, c"\xb11Ov"),
-    input_key_entry::new(keyc::KEYC_KP_ONE as u64 | KEYC_KEYPAD, c"\xb11Oq"),
-    input_key_entry::new(keyc::KEYC_KP_TWO as u64 | KEYC_KEYPAD, c"\xb11Or"),
-    input_key_entry::new(keyc::KEYC_KP_THREE as u64 | KEYC_KEYPAD, c"\xb11Os"),
-    input_key_entry::new(keyc::KEYC_KP_ENTER as u64 | KEYC_KEYPAD, c"\xb11OM"),
-    input_key_entry::new(keyc::KEYC_KP_ZERO as u64 | KEYC_KEYPAD, c"\xb11Op"),
-    input_key_entry::new(keyc::KEYC_KP_PERIOD as u64 | KEYC_KEYPAD, c"\xb11On"),
+    input_key_entry::new(keyc::KEYC_KP_SLASH as u64 | KEYC_KEYPAD, c"\x1bOo"),
+    input_key_entry::new(keyc::KEYC_KP_STAR as u64 | KEYC_KEYPAD, c"\x1bOj"),
+    input_key_entry::new(keyc::KEYC_KP_MINUS as u64 | KEYC_KEYPAD, c"\x1bOm"),
+    input_key_entry::new(keyc::KEYC_KP_SEVEN as u64 | KEYC_KEYPAD, c"\x1bOw"),
+    input_key_entry::new(keyc::KEYC_KP_EIGHT as u64 | KEYC_KEYPAD, c"\x1bOx"),
+    input_key_entry::new(keyc::KEYC_KP_NINE as u64 | KEYC_KEYPAD, c"\x1bOy"),
+    input_key_entry::new(keyc::KEYC_KP_PLUS as u64 | KEYC_KEYPAD, c"\x1bOk"),
+    input_key_entry::new(keyc::KEYC_KP_FOUR as u64 | KEYC_KEYPAD, c"\x1bOt"),
+    input_key_entry::new(keyc::KEYC_KP_FIVE as u64 | KEYC_KEYPAD, c"\x1bOu"),
+    input_key_entry::new(keyc::KEYC_KP_SIX as u64 | KEYC_KEYPAD, c"\x1bOv"),
+    input_key_entry::new(keyc::KEYC_KP_ONE as u64 | KEYC_KEYPAD, c"\x1bOq"),
+    input_key_entry::new(keyc::KEYC_KP_TWO as u64 | KEYC_KEYPAD, c"\x1bOr"),
+    input_key_entry::new(keyc::KEYC_KP_THREE as u64 | KEYC_KEYPAD, c"\x1bOs"),
+    input_key_entry::new(keyc::KEYC_KP_ENTER as u64 | KEYC_KEYPAD, c"\x1bOM"),
+    input_key_entry::new(keyc::KEYC_KP_ZERO as u64 | KEYC_KEYPAD, c"\x1bOp"),
+    input_key_entry::new(keyc::KEYC_KP_PERIOD as u64 | KEYC_KEYPAD, c"\x1bOn"),
     input_key_entry::new(keyc::KEYC_KP_SLASH as u64, c"/"),
     input_key_entry::new(keyc::KEYC_KP_STAR as u64, c"*"),
     input_key_entry::new(keyc::KEYC_KP_MINUS as u64, c"-"),
@@ -126,37 +126,28 @@ static mut input_key_defaults: [input_key_entry; 83] = [
     input_key_entry::new(keyc::KEYC_KP_ZERO as u64, c"0"),
     input_key_entry::new(keyc::KEYC_KP_PERIOD as u64, c"."),
     /* Keys with an embedded modifier. */
-    input_key_entry::new(keyc::KEYC_F1 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[1;_P"),
-    input_key_entry::new(keyc::KEYC_F2 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[1;_Q"),
-    input_key_entry::new(keyc::KEYC_F3 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[1;_R"),
-    input_key_entry::new(keyc::KEYC_F4 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[1;_S"),
-    input_key_entry::new(keyc::KEYC_F5 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[15;_~"),
-    input_key_entry::new(keyc::KEYC_F6 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[17;_~"),
-    input_key_entry::new(keyc::KEYC_F7 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[18;_~"),
-    input_key_entry::new(keyc::KEYC_F8 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[19;_~"),
-    input_key_entry::new(keyc::KEYC_F9 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[20;_~"),
-    input_key_entry::new(keyc::KEYC_F10 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[21;_~"),
-    input_key_entry::new(keyc::KEYC_F11 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[23;_~"),
-    input_key_entry::new(keyc::KEYC_F12 as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[24;_~"),
-    input_key_entry::new(keyc::KEYC_UP as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[1;_A"),
-    input_key_entry::new(keyc::KEYC_DOWN as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[1;_B"),
-    input_key_entry::new(
-        keyc::KEYC_RIGHT as u64 | KEYC_BUILD_MODIFIERS,
-        c"\xb11[1;_C",
-    ),
-    input_key_entry::new(keyc::KEYC_LEFT as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[1;_D"),
-    input_key_entry::new(keyc::KEYC_HOME as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[1;_H"),
-    input_key_entry::new(keyc::KEYC_END as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[1;_F"),
-    input_key_entry::new(
-        keyc::KEYC_PPAGE as u64 | KEYC_BUILD_MODIFIERS,
-        c"\xb11[5;_~",
-    ),
-    input_key_entry::new(
-        keyc::KEYC_NPAGE as u64 | KEYC_BUILD_MODIFIERS,
-        c"\xb11[6;_~",
-    ),
-    input_key_entry::new(keyc::KEYC_IC as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[2;_~"),
-    input_key_entry::new(keyc::KEYC_DC as u64 | KEYC_BUILD_MODIFIERS, c"\xb11[3;_~"),
+    input_key_entry::new(keyc::KEYC_F1 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_P"),
+    input_key_entry::new(keyc::KEYC_F2 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_Q"),
+    input_key_entry::new(keyc::KEYC_F3 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_R"),
+    input_key_entry::new(keyc::KEYC_F4 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_S"),
+    input_key_entry::new(keyc::KEYC_F5 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[15;_~"),
+    input_key_entry::new(keyc::KEYC_F6 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[17;_~"),
+    input_key_entry::new(keyc::KEYC_F7 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[18;_~"),
+    input_key_entry::new(keyc::KEYC_F8 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[19;_~"),
+    input_key_entry::new(keyc::KEYC_F9 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[20;_~"),
+    input_key_entry::new(keyc::KEYC_F10 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[21;_~"),
+    input_key_entry::new(keyc::KEYC_F11 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[23;_~"),
+    input_key_entry::new(keyc::KEYC_F12 as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[24;_~"),
+    input_key_entry::new(keyc::KEYC_UP as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_A"),
+    input_key_entry::new(keyc::KEYC_DOWN as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_B"),
+    input_key_entry::new(keyc::KEYC_RIGHT as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_C"),
+    input_key_entry::new(keyc::KEYC_LEFT as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_D"),
+    input_key_entry::new(keyc::KEYC_HOME as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_H"),
+    input_key_entry::new(keyc::KEYC_END as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[1;_F"),
+    input_key_entry::new(keyc::KEYC_PPAGE as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[5;_~"),
+    input_key_entry::new(keyc::KEYC_NPAGE as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[6;_~"),
+    input_key_entry::new(keyc::KEYC_IC as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[2;_~"),
+    input_key_entry::new(keyc::KEYC_DC as u64 | KEYC_BUILD_MODIFIERS, c"\x1b[3;_~"),
 ];
 
 static input_key_modifiers: [key_code; 9] = [
@@ -302,7 +293,7 @@ pub unsafe fn input_key_extended(bev: *mut bufferevent, mut key: key_code) -> i3
             xsnprintf_!(
                 tmp.as_mut_ptr().cast(),
                 sizeof_tmp,
-                "\x1b[{};{}",
+                "\x1b[{};{}u",
                 key,
                 modifier as char,
             );
@@ -417,6 +408,184 @@ pub unsafe fn input_key_mode1(bev: *mut bufferevent, key: key_code) -> i32 {
     -1
 }
 
+/// Kitty keyboard protocol numbers and final bytes for special keys.
+static input_key_kitty_table: [(key_code, u32, u8); 42] = [
+    (c0::C0_ESC as key_code, 27, b'u'),
+    (c0::C0_CR as key_code, 13, b'u'),
+    (c0::C0_HT as key_code, 9, b'u'),
+    (keyc::KEYC_BSPACE as key_code, 127, b'u'),
+    (keyc::KEYC_F1 as key_code, 1, b'P'),
+    (keyc::KEYC_F2 as key_code, 1, b'Q'),
+    (keyc::KEYC_F3 as key_code, 13, b'~'),
+    (keyc::KEYC_F4 as key_code, 1, b'S'),
+    (keyc::KEYC_F5 as key_code, 15, b'~'),
+    (keyc::KEYC_F6 as key_code, 17, b'~'),
+    (keyc::KEYC_F7 as key_code, 18, b'~'),
+    (keyc::KEYC_F8 as key_code, 19, b'~'),
+    (keyc::KEYC_F9 as key_code, 20, b'~'),
+    (keyc::KEYC_F10 as key_code, 21, b'~'),
+    (keyc::KEYC_F11 as key_code, 23, b'~'),
+    (keyc::KEYC_F12 as key_code, 24, b'~'),
+    (keyc::KEYC_IC as key_code, 2, b'~'),
+    (keyc::KEYC_DC as key_code, 3, b'~'),
+    (keyc::KEYC_HOME as key_code, 1, b'H'),
+    (keyc::KEYC_END as key_code, 1, b'F'),
+    (keyc::KEYC_NPAGE as key_code, 6, b'~'),
+    (keyc::KEYC_PPAGE as key_code, 5, b'~'),
+    (keyc::KEYC_UP as key_code, 1, b'A'),
+    (keyc::KEYC_DOWN as key_code, 1, b'B'),
+    (keyc::KEYC_RIGHT as key_code, 1, b'C'),
+    (keyc::KEYC_LEFT as key_code, 1, b'D'),
+    (keyc::KEYC_KP_ZERO as key_code, 57399, b'u'),
+    (keyc::KEYC_KP_ONE as key_code, 57400, b'u'),
+    (keyc::KEYC_KP_TWO as key_code, 57401, b'u'),
+    (keyc::KEYC_KP_THREE as key_code, 57402, b'u'),
+    (keyc::KEYC_KP_FOUR as key_code, 57403, b'u'),
+    (keyc::KEYC_KP_FIVE as key_code, 57404, b'u'),
+    (keyc::KEYC_KP_SIX as key_code, 57405, b'u'),
+    (keyc::KEYC_KP_SEVEN as key_code, 57406, b'u'),
+    (keyc::KEYC_KP_EIGHT as key_code, 57407, b'u'),
+    (keyc::KEYC_KP_NINE as key_code, 57408, b'u'),
+    (keyc::KEYC_KP_PERIOD as key_code, 57409, b'u'),
+    (keyc::KEYC_KP_SLASH as key_code, 57410, b'u'),
+    (keyc::KEYC_KP_STAR as key_code, 57411, b'u'),
+    (keyc::KEYC_KP_MINUS as key_code, 57412, b'u'),
+    (keyc::KEYC_KP_PLUS as key_code, 57413, b'u'),
+    (keyc::KEYC_KP_ENTER as key_code, 57414, b'u'),
+];
+
+/// Outputs the key using the kitty keyboard protocol with the given
+/// progressive enhancement flags. Returns -1 if the key should be sent in the
+/// legacy encoding instead.
+pub unsafe fn input_key_kitty(bev: *mut bufferevent, flags: u32, key: key_code) -> i32 {
+    let __func__ = c"input_key_kitty".as_ptr();
+    unsafe {
+        let Some(out) = input_key_kitty_encode(flags, key) else {
+            return -1;
+        };
+        if !out.is_empty() {
+            input_key_write(__func__, bev, out.as_ptr().cast(), out.len());
+        }
+        0
+    }
+}
+
+/// Encode a key for the kitty keyboard protocol. Returns None if the legacy
+/// encoding should be used or an empty string if nothing should be sent.
+unsafe fn input_key_kitty_encode(flags: u32, mut key: key_code) -> Option<String> {
+    unsafe {
+        let all = flags & KITTY_KEYS_REPORT_ALL != 0;
+        let disambiguate = all || flags & KITTY_KEYS_DISAMBIGUATE != 0;
+
+        let mut event = 1;
+        if key & KEYC_RELEASE != 0 {
+            event = 3;
+        } else if key & KEYC_REPEAT != 0 {
+            event = 2;
+        }
+        if flags & KITTY_KEYS_REPORT_EVENTS == 0 {
+            if event == 3 {
+                return Some(String::new());
+            }
+            event = 1;
+        }
+
+        if (key & KEYC_MASK_KEY) == keyc::KEYC_BTAB as key_code {
+            key = c0::C0_HT as key_code | (key & !KEYC_MASK_KEY) | KEYC_SHIFT;
+        }
+        let onlykey = key & KEYC_MASK_KEY;
+
+        let mut mods: u32 = 0;
+        if key & KEYC_SHIFT != 0 {
+            mods |= 1;
+        }
+        if key & KEYC_META != 0 {
+            mods |= 2;
+        }
+        if key & KEYC_CTRL != 0 {
+            mods |= 4;
+        }
+
+        /*
+         * Work out the key number and whether the legacy encoding is still
+         * used for it. Text and the Enter, Tab and Backspace keys are only
+         * encoded when modified (or if all keys are requested); functional
+         * keys use the legacy sequences unless an event type is needed.
+         */
+        let mut shifted: u32 = 0;
+        let mut text: u32 = 0;
+        let number: u32;
+        let final_: u8;
+        let legacy: bool;
+        if let Some(&(_, n, f)) = input_key_kitty_table.iter().find(|e| e.0 == onlykey) {
+            number = n;
+            final_ = f;
+            legacy = if n == 27 {
+                !disambiguate
+            } else if n == 13 || n == 9 || n == 127 {
+                !all && (mods == 0 || !disambiguate)
+            } else if n >= 57399 {
+                !disambiguate
+            } else {
+                !all && event == 1
+            };
+        } else if (0x20..0x7f).contains(&onlykey) || KEYC_IS_UNICODE(key) {
+            let mut cp = onlykey as u32;
+            if KEYC_IS_UNICODE(key) {
+                let mut ud: utf8_data = zeroed();
+                let mut wc: wchar_t = 0;
+                utf8_to_data(onlykey as u32, &raw mut ud);
+                if utf8_towc(&raw const ud, &raw mut wc) != utf8_state::UTF8_DONE {
+                    return None;
+                }
+                cp = wc as u32;
+            }
+            if (mods & 1) == 0 && (b'A' as u32..=b'Z' as u32).contains(&cp) {
+                mods |= 1;
+                cp |= 0x20;
+            }
+            if (mods & 1) != 0 && (b'a' as u32..=b'z' as u32).contains(&cp) {
+                shifted = cp & !0x20;
+            }
+            if all && flags & KITTY_KEYS_REPORT_TEXT != 0 && (mods & !1) == 0 && event != 3 {
+                text = if shifted != 0 { shifted } else { cp };
+            }
+            number = cp;
+            final_ = b'u';
+            legacy = !all && ((mods & !1) == 0 || !disambiguate);
+        } else {
+            return None;
+        }
+        if legacy {
+            if event == 3 {
+                return Some(String::new());
+            }
+            return None;
+        }
+
+        let mut out = String::from("\x1b[");
+        let fields = mods != 0 || event != 1 || text != 0;
+        if number != 1 || final_ == b'u' || fields {
+            out.push_str(&number.to_string());
+            if flags & KITTY_KEYS_REPORT_ALTERNATES != 0 && shifted != 0 {
+                out.push_str(&format!(":{shifted}"));
+            }
+        }
+        if fields {
+            out.push_str(&format!(";{}", mods + 1));
+            if event != 1 {
+                out.push_str(&format!(":{event}"));
+            }
+        }
+        if text != 0 {
+            out.push_str(&format!(";{text}"));
+        }
+        out.push(final_ as char);
+
+        Some(out)
+    }
+}
+
 /// Translate a key code into an output key sequence.
 pub unsafe fn input_key(s: *mut screen, bev: *mut bufferevent, mut key: key_code) -> i32 {
     let __func__ = c"input_key".as_ptr();
@@ -436,6 +605,19 @@ pub unsafe fn input_key(s: *mut screen, bev: *mut bufferevent, mut key: key_code
             return 0;
         }
 
+        /*
+         * Applications using the kitty keyboard protocol get their own
+         * encoding; key release events are only ever reported there.
+         */
+        let kflags = screen_kitty_keys_flags(s);
+        if kflags != 0 && input_key_kitty(bev, kflags, key) == 0 {
+            return 0;
+        }
+        if key & KEYC_RELEASE != 0 {
+            return 0;
+        }
+        key &= !KEYC_REPEAT;
+
         /* Is this backspace? */
         if (key & KEYC_MASK_KEY) == keyc::KEYC_BSPACE as u64 {
             let mut newkey = options_get_number_(global_options, c"backspace") as key_code;
@@ -705,3 +887,86 @@ pub unsafe fn input_key_mouse(wp: *mut window_pane, m: *mut mouse_event) {
         input_key_write(__func__, (*wp).event, buf, len);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_key_defaults() {
+        // Apart from the keypad keys in numeric mode, every key sends an
+        // escape sequence.
+        for ike in unsafe { &*&raw const input_key_defaults } {
+            let data = unsafe { CStr::from_ptr(ike.data) }.to_bytes();
+            assert!(data.len() == 1 || data[0] == b'\x1b', "{data:?}");
+        }
+    }
+
+    #[test]
+    fn test_input_key_kitty_encode() {
+        let a = b'a' as key_code;
+        let up = keyc::KEYC_UP as key_code;
+        let cases: [(u32, key_code, Option<&str>); 17] = [
+            // Disambiguate: only keys which would be ambiguous change.
+            (KITTY_KEYS_DISAMBIGUATE, a, None),
+            (KITTY_KEYS_DISAMBIGUATE, a | KEYC_CTRL, Some("\x1b[97;5u")),
+            (
+                KITTY_KEYS_DISAMBIGUATE,
+                a | KEYC_META | KEYC_CTRL,
+                Some("\x1b[97;7u"),
+            ),
+            (
+                KITTY_KEYS_DISAMBIGUATE,
+                c0::C0_ESC as key_code,
+                Some("\x1b[27u"),
+            ),
+            (KITTY_KEYS_DISAMBIGUATE, c0::C0_CR as key_code, None),
+            (
+                KITTY_KEYS_DISAMBIGUATE,
+                c0::C0_CR as key_code | KEYC_SHIFT,
+                Some("\x1b[13;2u"),
+            ),
+            (
+                KITTY_KEYS_DISAMBIGUATE,
+                keyc::KEYC_BTAB as key_code,
+                Some("\x1b[9;2u"),
+            ),
+            (
+                KITTY_KEYS_DISAMBIGUATE,
+                keyc::KEYC_KP_ONE as key_code,
+                Some("\x1b[57400u"),
+            ),
+            (KITTY_KEYS_DISAMBIGUATE, up, None),
+            // Releases are dropped unless events are reported.
+            (KITTY_KEYS_DISAMBIGUATE, a | KEYC_RELEASE, Some("")),
+            (
+                KITTY_KEYS_DISAMBIGUATE | KITTY_KEYS_REPORT_EVENTS,
+                a | KEYC_REPEAT,
+                None,
+            ),
+            (
+                KITTY_KEYS_DISAMBIGUATE | KITTY_KEYS_REPORT_EVENTS,
+                up | KEYC_RELEASE,
+                Some("\x1b[1;1:3A"),
+            ),
+            (
+                KITTY_KEYS_DISAMBIGUATE | KITTY_KEYS_REPORT_EVENTS,
+                keyc::KEYC_F1 as key_code | KEYC_REPEAT,
+                Some("\x1b[1;1:2P"),
+            ),
+            // Reporting all keys encodes plain text as well.
+            (KITTY_KEYS_REPORT_ALL, a, Some("\x1b[97u")),
+            (KITTY_KEYS_REPORT_ALL, up, Some("\x1b[A")),
+            (
+                KITTY_KEYS_REPORT_ALL | KITTY_KEYS_REPORT_TEXT,
+                b'A' as key_code,
+                Some("\x1b[97;2;65u"),
+            ),
+            (KITTY_KEYS_ALL, b'A' as key_code, Some("\x1b[97:65;2;65u")),
+        ];
+        for (flags, key, expected) in cases {
+            let out = unsafe { input_key_kitty_encode(flags, key) };
+            assert_eq!(out.as_deref(), expected, "flags {flags:#x} key {key:#x}");
+        }
+    }
+}
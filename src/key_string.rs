@@ -577,6 +577,12 @@ pub unsafe fn key_string_lookup_key(mut key: key_code, with_flags: i32) -> *cons
             if saved & KEYC_SENT != 0 {
                 strlcat(&raw mut out as *mut c_char, c"S".as_ptr(), sizeof_out);
             }
+            if saved & KEYC_RELEASE != 0 {
+                strlcat(&raw mut out as *mut c_char, c"U".as_ptr(), sizeof_out);
+            }
+            if saved & KEYC_REPEAT != 0 {
+                strlcat(&raw mut out as *mut c_char, c"R".as_ptr(), sizeof_out);
+            }
             strlcat(&raw mut out as *mut c_char, c"]".as_ptr(), sizeof_out);
         }
         &raw const out as *const i8
@@ -40,12 +40,22 @@ use ncurses_::*;
 mod libc_;
 use libc_::*;
 
-#[cfg(feature = "sixel")]
 mod image_;
+use crate::image_::{
+    image_check_area, image_check_line, image_free, image_free_all, image_resize, image_scroll_up,
+    image_store_kitty,
+};
+mod image_kitty;
+#[cfg(feature = "sixel")]
+use crate::image_::image_store;
+use crate::image_kitty::{kitty_free_all, kitty_graphics, kitty_image_release};
 #[cfg(feature = "sixel")]
 mod image_sixel;
 #[cfg(feature = "sixel")]
-use image_sixel::sixel_image;
+use crate::image_sixel::{
+    SIXEL_COLOUR_REGISTERS, sixel_free, sixel_image, sixel_parse, sixel_print, sixel_scale,
+    sixel_size_in_cells,
+};
 
 #[cfg(feature = "utempter")]
 mod utempter;
@@ -62,9 +72,9 @@ use core::{
 use std::sync::atomic::AtomicU32;
 
 use libc::{
-    FILE, REG_EXTENDED, REG_ICASE, SEEK_END, SEEK_SET, SIGHUP, WEXITSTATUS, WIFEXITED, WIFSIGNALED,
-    WTERMSIG, fclose, fdopen, fopen, fread, free, fseeko, ftello, fwrite, malloc, memcmp, mkstemp,
-    pid_t, strcpy, strerror, strlen, termios, time_t, timeval, uid_t, unlink,
+    FILE, SEEK_END, SEEK_SET, SIGHUP, WEXITSTATUS, WIFEXITED, WIFSIGNALED, WTERMSIG, fclose,
+    fdopen, fopen, fread, free, fseeko, ftello, fwrite, malloc, memcmp, mkstemp, pid_t, strcpy,
+    strerror, strlen, termios, time_t, timeval, uid_t, unlink,
 };
 
 // libevent2
@@ -234,10 +244,12 @@ const KEYC_IMPLIED_META: c_ulonglong = 0x08000000000000;
 const KEYC_BUILD_MODIFIERS: c_ulonglong = 0x10000000000000;
 const KEYC_VI: c_ulonglong = 0x20000000000000;
 const KEYC_SENT: c_ulonglong = 0x40000000000000;
+const KEYC_RELEASE: c_ulonglong = 0x80000000000000;
+const KEYC_REPEAT: c_ulonglong = 0x100000000000000;
 
 // Masks for key bits.
 const KEYC_MASK_MODIFIERS: c_ulonglong = 0x00f00000000000;
-const KEYC_MASK_FLAGS: c_ulonglong = 0xff000000000000;
+const KEYC_MASK_FLAGS: c_ulonglong = 0x1ff000000000000;
 const KEYC_MASK_KEY: c_ulonglong = 0x000fffffffffff;
 
 const KEYC_NUSER: c_ulonglong = 1000;
@@ -744,6 +756,8 @@ bitflags::bitflags! {
         const DEAD         = 1 << 2; // 0x4
         const START_PROMPT = 1 << 3; // 0x8
         const START_OUTPUT = 1 << 4; // 0x10
+        const START_INPUT  = 1 << 5; // 0x20
+        const END_OUTPUT   = 1 << 6; // 0x40
     }
 }
 
@@ -869,6 +883,13 @@ struct grid_line {
 
     flags: grid_line_flag,
     time: time_t,
+
+    /// Compressed block holding the cells if the line is in cold history.
+    cold: *mut grid_cold_block,
+    coldindex: u32,
+
+    /// Command whose output starts on this line.
+    command: *mut grid_command,
 }
 
 const GRID_HISTORY: i32 = 0x1; // scroll lines into history
@@ -885,6 +906,18 @@ struct grid {
     hsize: u32,
     hlimit: u32,
 
+    /// History lines above this have already been compressed where possible.
+    hcold: u32,
+
+    /// Number of the oldest history lines which have been spilled to disk.
+    /// These are not in linedata, which starts with the line after them.
+    hspill: u32,
+    /// History lines above this have not yet been reflowed to the grid width.
+    hreflow: u32,
+    /// Maximum number of lines to keep spilled, zero if lines are not spilled.
+    slimit: u32,
+    spill: *mut grid_spill,
+
     linedata: *mut grid_line,
 }
 
@@ -974,29 +1007,87 @@ struct style {
     default_type: style_default_type,
 }
 
-#[cfg(feature = "sixel")]
-crate::compat::impl_tailq_entry!(image, all_entry, tailq_entry<image>);
-#[cfg(feature = "sixel")]
-crate::compat::impl_tailq_entry!(image, entry, tailq_entry<image>);
-#[cfg(feature = "sixel")]
 #[repr(C)]
 #[derive(Copy, Clone)]
 struct image {
     s: *mut screen,
+    #[cfg(feature = "sixel")]
     data: *mut sixel_image,
+    kitty: *mut kitty_image,
     fallback: *mut c_char,
     px: u32,
     py: u32,
     sx: u32,
     sy: u32,
 
+    /// kitty placement id and the rows scrolled off the top of the placement
+    placement: u32,
+    crop: u32,
+
     all_entry: tailq_entry<image>,
     entry: tailq_entry<image>,
 }
-
-#[cfg(feature = "sixel")]
+impl Entry<image, discr_all_entry> for image {
+    unsafe fn entry(this: *mut Self) -> *mut tailq_entry<image> {
+        unsafe { &raw mut (*this).all_entry }
+    }
+}
+impl Entry<image, discr_entry> for image {
+    unsafe fn entry(this: *mut Self) -> *mut tailq_entry<image> {
+        unsafe { &raw mut (*this).entry }
+    }
+}
 type images = tailq_head<image>;
 
+/// Kitty graphics image data, shared by all placements of the image.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct kitty_image {
+    /// id and number chosen by the application
+    id: u32,
+    number: u32,
+    /// id used towards outer terminals, unique across all panes
+    outer: u32,
+    references: u32,
+
+    format: u32,
+    compressed: i32,
+    pixel_x: u32,
+    pixel_y: u32,
+
+    /// base64 payload as received
+    data: *mut c_char,
+    size: usize,
+
+    entry: tailq_entry<kitty_image>,
+}
+crate::compat::impl_tailq_entry!(kitty_image, entry, tailq_entry<kitty_image>);
+type kitty_images = tailq_head<kitty_image>;
+
+/// Kitty graphics command control data.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct kitty_command {
+    action: u8,
+    delete: u8,
+    medium: u8,
+    compression: u8,
+    format: u32,
+    pixel_x: u32,
+    pixel_y: u32,
+    id: u32,
+    number: u32,
+    placement: u32,
+    more: u32,
+    quiet: u32,
+    columns: u32,
+    rows: u32,
+    cursor: u32,
+
+    /// image being received when the command is split into chunks
+    image: *mut kitty_image,
+}
+
 /// Cursor style.
 #[repr(i32)]
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -1007,6 +1098,23 @@ enum screen_cursor_style {
     SCREEN_CURSOR_BAR,
 }
 
+// Kitty keyboard protocol progressive enhancement flags.
+const KITTY_KEYS_DISAMBIGUATE: u32 = 0x1;
+const KITTY_KEYS_REPORT_EVENTS: u32 = 0x2;
+const KITTY_KEYS_REPORT_ALTERNATES: u32 = 0x4;
+const KITTY_KEYS_REPORT_ALL: u32 = 0x8;
+const KITTY_KEYS_REPORT_TEXT: u32 = 0x10;
+const KITTY_KEYS_ALL: u32 = 0x1f;
+
+/// Kitty keyboard protocol flag stack.
+const SCREEN_KITTY_KEYS_MAX: usize = 8;
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct screen_kitty_keys {
+    flags: [u32; SCREEN_KITTY_KEYS_MAX],
+    depth: u32,
+}
+
 /// Virtual screen.
 #[repr(C)]
 #[derive(Clone)]
@@ -1045,11 +1153,17 @@ struct screen {
     saved_cell: grid_cell,
     saved_flags: i32,
 
+    /// kitty keyboard flags, one stack each for the main and alternate screen
+    kitty_keys: screen_kitty_keys,
+    saved_kitty_keys: screen_kitty_keys,
+
     tabs: *mut bitstr_t,
     sel: *mut screen_sel,
 
-    #[cfg(feature = "sixel")]
     images: images,
+    kitty_images: kitty_images,
+    /// command whose image is being received in chunks
+    kitty_pending: *mut kitty_command,
 
     write_list: *mut screen_write_cline,
 
@@ -1307,6 +1421,36 @@ bitflags::bitflags! {
     }
 }
 
+/// Type of pane timeline event.
+#[repr(i32)]
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum window_pane_event_type {
+    Output,
+    Bell,
+    Exit,
+}
+
+/// Pane timeline event. Output read in the same second is counted in one
+/// event.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct window_pane_event {
+    type_: window_pane_event_type,
+    time: time_t,
+    bytes: usize,
+}
+
+/// Number of events kept for each pane.
+const WINDOW_PANE_EVENTS: usize = 1024;
+
+/// Ring of recent pane events.
+#[repr(C)]
+struct window_pane_timeline {
+    events: [window_pane_event; WINDOW_PANE_EVENTS],
+    next: usize,
+    count: usize,
+}
+
 /// Child window structure.
 #[repr(C)]
 struct window_pane {
@@ -1337,6 +1481,11 @@ struct window_pane {
     status: i32,
     dead_time: timeval,
 
+    /// Last command run from a shell prompt, as marked by OSC 133.
+    last_command: *mut c_char,
+    /// Exit status of the last finished command, or -1 if not known.
+    last_status: i32,
+
     fd: i32,
     event: *mut bufferevent,
 
@@ -1345,6 +1494,8 @@ struct window_pane {
 
     resize_queue: window_pane_resizes,
     resize_timer: event,
+    /// Reflows the rest of the history after a resize.
+    reflow_timer: event,
 
     ictx: *mut input_ctx,
 
@@ -1373,6 +1524,8 @@ struct window_pane {
     control_bg: i32,
     control_fg: i32,
 
+    timeline: window_pane_timeline,
+
     /// link in list of all panes
     entry: tailq_entry<window_pane>,
     /// link in list of last visited
@@ -1719,6 +1872,7 @@ bitflags::bitflags! {
         const TERM_RGBCOLOURS = 0x10;
         const TERM_VT100LIKE = 0x20;
         const TERM_SIXEL = 0x40;
+        const TERM_KITTYGFX = 0x80;
     }
 }
 
@@ -1760,6 +1914,7 @@ bitflags::bitflags! {
         const TTY_HAVEXDA = 0x200;
         const TTY_SYNCING = 0x400;
         const TTY_HAVEDA2 = 0x800; // Secondary DA.
+        const TTY_VIRTUAL = 0x1000;
     }
 }
 const TTY_ALL_REQUEST_FLAGS: tty_flags = tty_flags::TTY_HAVEDA
@@ -1826,6 +1981,25 @@ struct tty {
 
     key_timer: event,
     key_tree: *mut tty_key,
+
+    /// kitty images transmitted to this terminal by outer id, and placements shown
+    kitty_sent: *mut u32,
+    kitty_nsent: u32,
+    kitty_placed: *mut tty_kitty_placement,
+    kitty_nplaced: u32,
+
+    /// in-memory terminal that output for a virtual client is parsed into
+    virtual_screen: *mut screen,
+    virtual_ictx: *mut input_ctx,
+}
+
+/// Kitty image placement shown on a client terminal.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct tty_kitty_placement {
+    outer: u32,
+    placement: u32,
+    pane: u32,
 }
 
 type tty_ctx_redraw_cb = Option<unsafe fn(*const tty_ctx)>;
@@ -2073,6 +2247,7 @@ bitflags::bitflags! {
         const CMDQ_STATE_REPEAT = 0x1;
         const CMDQ_STATE_CONTROL = 0x2;
         const CMDQ_STATE_NOHOOKS = 0x4;
+        const CMDQ_STATE_CONFIG = 0x8;
     }
 }
 
@@ -2267,6 +2442,8 @@ bitflags::bitflags! {
         const WINDOWSIZECHANGED  = 0x0400000000u64;
         const CLIPBOARDBUFFER    = 0x0800000000u64;
         const BRACKETPASTING     = 0x1000000000u64;
+        const CONTROL_JSON       = 0x2000000000u64;
+        const VIRTUAL            = 0x4000000000u64;
     }
 }
 
@@ -2289,6 +2466,7 @@ const PROMPT_NUMERIC: i32 = 0x2;
 const PROMPT_INCREMENTAL: i32 = 0x4;
 const PROMPT_NOFORMAT: i32 = 0x8;
 const PROMPT_KEY: i32 = 0x8;
+const PROMPT_HIGHLIGHT: i32 = 0x10;
 
 //#[derive(Copy, Clone)]
 crate::compat::impl_tailq_entry!(client, entry, tailq_entry<client>);
@@ -2595,8 +2773,10 @@ use crate::proc::{
 
 mod cfg_;
 use crate::cfg_::{
-    cfg_client, cfg_files, cfg_finished, cfg_nfiles, cfg_print_causes, cfg_quiet, cfg_show_causes,
-    load_cfg, load_cfg_from_buffer, start_cfg,
+    cfg_check, cfg_client, cfg_files, cfg_finished, cfg_nfiles, cfg_print_causes, cfg_quiet,
+    cfg_show_causes, cfg_watch_add, cfg_watch_add_pattern, cfg_watch_collecting, cfg_watch_parent,
+    cfg_watch_set_key, cfg_watch_set_option, cfg_watch_update, load_cfg, load_cfg_from_buffer,
+    start_cfg,
 };
 
 mod paste;
@@ -2613,9 +2793,10 @@ use crate::format::{
     format_create, format_create_defaults, format_create_from_state, format_create_from_target,
     format_defaults, format_defaults_pane, format_defaults_paste_buffer, format_defaults_window,
     format_each, format_expand, format_expand_time, format_flags, format_free, format_get_pane,
-    format_grid_hyperlink, format_grid_line, format_grid_word, format_job_tree, format_log_debug,
-    format_lost_client, format_merge, format_pretty_time, format_single, format_single_from_state,
-    format_single_from_target, format_skip, format_tidy_jobs, format_tree, format_true,
+    format_grid_hyperlink, format_grid_line, format_grid_word, format_job_tree, format_json,
+    format_json_string, format_log_debug, format_lost_client, format_merge, format_pretty_time,
+    format_single, format_single_from_state, format_single_from_target, format_skip,
+    format_tidy_jobs, format_tree, format_true,
 };
 
 mod format_draw_;
@@ -2671,27 +2852,32 @@ use crate::environ_::{
 use crate::environ_::{environ_log, environ_set};
 
 mod tty_;
+#[cfg(feature = "sixel")]
+use crate::tty_::tty_cmd_sixelimage;
 use crate::tty_::{
     tty_attributes, tty_cell, tty_clipboard_query, tty_close, tty_cmd_alignmenttest, tty_cmd_cell,
     tty_cmd_cells, tty_cmd_clearcharacter, tty_cmd_clearendofline, tty_cmd_clearendofscreen,
     tty_cmd_clearline, tty_cmd_clearscreen, tty_cmd_clearstartofline, tty_cmd_clearstartofscreen,
     tty_cmd_deletecharacter, tty_cmd_deleteline, tty_cmd_insertcharacter, tty_cmd_insertline,
-    tty_cmd_linefeed, tty_cmd_rawstring, tty_cmd_reverseindex, tty_cmd_scrolldown,
-    tty_cmd_scrollup, tty_cmd_setselection, tty_cmd_syncstart, tty_create_log, tty_cursor,
-    tty_default_colours, tty_draw_line, tty_free, tty_init, tty_margin_off, tty_open, tty_putc,
-    tty_putcode, tty_putcode_i, tty_putcode_ii, tty_putcode_iii, tty_putcode_s, tty_putcode_ss,
-    tty_putn, tty_puts, tty_raw, tty_region_off, tty_repeat_requests, tty_reset, tty_resize,
-    tty_send_requests, tty_set_path, tty_set_selection, tty_set_size, tty_set_title, tty_start_tty,
-    tty_stop_tty, tty_sync_end, tty_sync_start, tty_update_client_offset, tty_update_features,
-    tty_update_mode, tty_update_window_offset, tty_window_bigger, tty_window_offset, tty_write,
+    tty_cmd_kittyimage, tty_cmd_linefeed, tty_cmd_rawstring, tty_cmd_reverseindex,
+    tty_cmd_scrolldown, tty_cmd_scrollup, tty_cmd_setselection, tty_cmd_syncstart, tty_create_log,
+    tty_cursor, tty_default_colours, tty_draw_images, tty_draw_line, tty_free, tty_init,
+    tty_init_virtual, tty_kitty_delete_image, tty_kitty_delete_pane, tty_kitty_forget,
+    tty_margin_off, tty_open, tty_putc, tty_putcode, tty_putcode_i, tty_putcode_ii,
+    tty_putcode_iii, tty_putcode_s, tty_putcode_ss, tty_putn, tty_puts, tty_raw, tty_region_off,
+    tty_repeat_requests, tty_reset, tty_resize, tty_send_requests, tty_set_path, tty_set_selection,
+    tty_set_size, tty_set_title, tty_start_tty, tty_stop_tty, tty_sync_end, tty_sync_start,
+    tty_update_client_offset, tty_update_features, tty_update_mode, tty_update_window_offset,
+    tty_window_bigger, tty_window_offset, tty_write,
 };
 
 mod tty_term_;
 use crate::tty_term_::{
-    tty_code, tty_term_apply, tty_term_apply_overrides, tty_term_create, tty_term_describe,
-    tty_term_flag, tty_term_free, tty_term_free_list, tty_term_has, tty_term_ncodes,
-    tty_term_number, tty_term_read_list, tty_term_string, tty_term_string_i, tty_term_string_ii,
-    tty_term_string_iii, tty_term_string_s, tty_term_string_ss, tty_terms,
+    TTY_TERM_VIRTUAL_FEATURES, tty_code, tty_term_apply, tty_term_apply_overrides, tty_term_create,
+    tty_term_describe, tty_term_flag, tty_term_free, tty_term_free_list, tty_term_has,
+    tty_term_ncodes, tty_term_number, tty_term_read_list, tty_term_string, tty_term_string_i,
+    tty_term_string_ii, tty_term_string_iii, tty_term_string_s, tty_term_string_ss,
+    tty_term_virtual_list, tty_terms,
 };
 
 mod tty_features;
@@ -2735,15 +2921,16 @@ use crate::arguments::{
 mod cmd_;
 use crate::cmd_::cmd_log_argv;
 use crate::cmd_::{
-    cmd, cmd_append_argv, cmd_copy, cmd_copy_argv, cmd_free, cmd_free_argv, cmd_get_alias,
-    cmd_get_args, cmd_get_entry, cmd_get_group, cmd_get_source, cmd_list_all_have,
+    cmd, cmd_append_argv, cmd_copy, cmd_copy_argv, cmd_entries, cmd_free, cmd_free_argv,
+    cmd_get_alias, cmd_get_args, cmd_get_entry, cmd_get_group, cmd_get_source, cmd_list_all_have,
     cmd_list_any_have, cmd_list_append, cmd_list_append_all, cmd_list_copy, cmd_list_first,
     cmd_list_free, cmd_list_move, cmd_list_new, cmd_list_next, cmd_list_print, cmd_mouse_at,
     cmd_mouse_pane, cmd_mouse_window, cmd_pack_argv, cmd_parse, cmd_print, cmd_stringify_argv,
     cmd_table, cmd_template_replace, cmd_unpack_argv, cmds,
 };
 
-use crate::cmd_::cmd_attach_session::cmd_attach_session;
+use crate::cmd_::cmd_attach_session::{cmd_attach_session, cmd_attach_session_create_virtual};
+use crate::cmd_::cmd_load_history::cmd_load_history_file;
 
 use crate::cmd_::cmd_find::{
     cmd_find_best_client, cmd_find_clear_state, cmd_find_client, cmd_find_copy_state,
@@ -2759,13 +2946,13 @@ use crate::cmd_parse::{
 };
 
 use crate::cmd_::cmd_queue::{
-    cmdq_add_format, cmdq_add_formats, cmdq_append, cmdq_continue, cmdq_copy_state, cmdq_error,
-    cmdq_free, cmdq_free_state, cmdq_get_callback, cmdq_get_callback1, cmdq_get_client,
+    cmdq_add_format, cmdq_add_formats, cmdq_append, cmdq_cancel, cmdq_continue, cmdq_copy_state,
+    cmdq_error, cmdq_free, cmdq_free_state, cmdq_get_callback, cmdq_get_callback1, cmdq_get_client,
     cmdq_get_command, cmdq_get_current, cmdq_get_error, cmdq_get_event, cmdq_get_flags,
     cmdq_get_name, cmdq_get_source, cmdq_get_state, cmdq_get_target, cmdq_get_target_client,
     cmdq_guard, cmdq_insert_after, cmdq_insert_hook, cmdq_item, cmdq_link_state, cmdq_list,
     cmdq_merge_formats, cmdq_new, cmdq_new_state, cmdq_next, cmdq_print, cmdq_print_data,
-    cmdq_running, cmdq_state,
+    cmdq_print_escaped, cmdq_running, cmdq_state,
 };
 
 use crate::cmd_::cmd_wait_for::cmd_wait_for_flush;
@@ -2806,7 +2993,7 @@ use crate::server::{
 mod server_client;
 use crate::server_client::{
     server_client_add_client_window, server_client_check_nested, server_client_clear_overlay,
-    server_client_create, server_client_detach, server_client_exec,
+    server_client_create, server_client_create_virtual, server_client_detach, server_client_exec,
     server_client_get_client_window, server_client_get_cwd, server_client_get_flags,
     server_client_get_key_table, server_client_get_pane, server_client_handle_key,
     server_client_how_many, server_client_loop, server_client_lost, server_client_open,
@@ -2864,11 +3051,15 @@ use crate::attributes::{attributes_fromstring, attributes_tostring};
 mod grid_;
 use crate::grid_::{
     grid_adjust_lines, grid_cells_equal, grid_cells_look_equal, grid_clear, grid_clear_history,
-    grid_clear_lines, grid_collect_history, grid_compare, grid_create, grid_default_cell,
-    grid_destroy, grid_duplicate_lines, grid_empty_line, grid_get_cell, grid_get_line,
-    grid_line_length, grid_move_cells, grid_move_lines, grid_peek_line, grid_reflow,
-    grid_remove_history, grid_scroll_history, grid_scroll_history_region, grid_set_cell,
-    grid_set_cells, grid_set_padding, grid_string_cells, grid_unwrap_position, grid_wrap_position,
+    grid_clear_lines, grid_cold_block, grid_collect_history, grid_command, grid_compare,
+    grid_create, grid_default_cell, grid_destroy, grid_duplicate_lines, grid_duplicate_spill,
+    grid_empty_line, grid_end_command, grid_find_command, grid_find_last_command, grid_get_cell,
+    grid_get_line, grid_get_line_marks, grid_history_bytes, grid_line_length, grid_line_sizes,
+    grid_load_history, grid_move_cells, grid_move_lines, grid_peek_line, grid_reflow,
+    grid_reflow_chunk, grid_reflow_finish, grid_remove_history, grid_save_history,
+    grid_scroll_history, grid_scroll_history_region, grid_set_cell, grid_set_cells,
+    grid_set_padding, grid_spill, grid_spill_expire, grid_start_command, grid_string_cells,
+    grid_unwrap_position, grid_wrap_position,
 };
 
 mod grid_reader_;
@@ -2891,6 +3082,8 @@ use crate::grid_view::{
 };
 
 mod screen_write;
+#[cfg(feature = "sixel")]
+use crate::screen_write::screen_write_sixelimage;
 use crate::screen_write::{
     screen_write_alignmenttest, screen_write_alternateoff, screen_write_alternateon,
     screen_write_backspace, screen_write_box, screen_write_carriagereturn, screen_write_cell,
@@ -2902,12 +3095,12 @@ use crate::screen_write::{
     screen_write_cursorright, screen_write_cursorup, screen_write_deletecharacter,
     screen_write_deleteline, screen_write_fast_copy, screen_write_free_list,
     screen_write_fullredraw, screen_write_hline, screen_write_insertcharacter,
-    screen_write_insertline, screen_write_linefeed, screen_write_make_list, screen_write_menu,
-    screen_write_mode_clear, screen_write_mode_set, screen_write_preview, screen_write_putc,
-    screen_write_rawstring, screen_write_reset, screen_write_reverseindex, screen_write_scrolldown,
-    screen_write_scrollregion, screen_write_scrollup, screen_write_setselection,
-    screen_write_start, screen_write_start_callback, screen_write_start_pane, screen_write_stop,
-    screen_write_vline,
+    screen_write_insertline, screen_write_kittyimage, screen_write_linefeed,
+    screen_write_make_list, screen_write_menu, screen_write_mode_clear, screen_write_mode_set,
+    screen_write_preview, screen_write_putc, screen_write_rawstring, screen_write_reset,
+    screen_write_reverseindex, screen_write_scrolldown, screen_write_scrollregion,
+    screen_write_scrollup, screen_write_setselection, screen_write_start,
+    screen_write_start_callback, screen_write_start_pane, screen_write_stop, screen_write_vline,
 };
 use crate::screen_write::{
     screen_write_nputs, screen_write_puts, screen_write_strlen, screen_write_text,
@@ -2920,9 +3113,10 @@ use crate::screen_redraw::{screen_redraw_pane, screen_redraw_screen};
 mod screen_;
 use crate::screen_::{
     screen_alternate_off, screen_alternate_on, screen_check_selection, screen_clear_selection,
-    screen_free, screen_hide_selection, screen_init, screen_mode_to_string, screen_pop_title,
-    screen_push_title, screen_reinit, screen_reset_hyperlinks, screen_reset_tabs, screen_resize,
-    screen_resize_cursor, screen_sel, screen_select_cell, screen_set_cursor_colour,
+    screen_free, screen_hide_selection, screen_init, screen_kitty_keys_flags,
+    screen_kitty_keys_pop, screen_kitty_keys_push, screen_kitty_keys_set, screen_mode_to_string,
+    screen_pop_title, screen_push_title, screen_reinit, screen_reset_hyperlinks, screen_reset_tabs,
+    screen_resize, screen_resize_cursor, screen_sel, screen_select_cell, screen_set_cursor_colour,
     screen_set_cursor_style, screen_set_path, screen_set_selection, screen_set_title,
     screen_titles,
 };
@@ -2932,13 +3126,14 @@ use crate::window_::{
     all_window_panes, window_add_pane, window_add_ref, window_cmp, window_count_panes,
     window_create, window_destroy_panes, window_find_by_id, window_find_by_id_str,
     window_find_string, window_get_active_at, window_has_pane, window_lost_pane,
-    window_pane_at_index, window_pane_cmp, window_pane_default_cursor, window_pane_destroy_ready,
-    window_pane_exited, window_pane_find_by_id, window_pane_find_by_id_str, window_pane_find_down,
+    window_pane_activity_rate, window_pane_add_event, window_pane_at_index, window_pane_cmp,
+    window_pane_default_cursor, window_pane_destroy_ready, window_pane_events, window_pane_exited,
+    window_pane_find_by_id, window_pane_find_by_id_str, window_pane_find_down,
     window_pane_find_left, window_pane_find_right, window_pane_find_up, window_pane_get_new_data,
     window_pane_index, window_pane_key, window_pane_mode, window_pane_next_by_number,
-    window_pane_previous_by_number, window_pane_reset_mode, window_pane_reset_mode_all,
-    window_pane_resize, window_pane_search, window_pane_send_resize, window_pane_set_event,
-    window_pane_set_mode, window_pane_stack_push, window_pane_stack_remove,
+    window_pane_previous_by_number, window_pane_reflow_start, window_pane_reset_mode,
+    window_pane_reset_mode_all, window_pane_resize, window_pane_search, window_pane_send_resize,
+    window_pane_set_event, window_pane_set_mode, window_pane_stack_push, window_pane_stack_remove,
     window_pane_start_input, window_pane_update_focus, window_pane_update_used_data,
     window_pane_visible, window_pop_zoom, window_printable_flags, window_push_zoom,
     window_redraw_active_switch, window_remove_pane, window_remove_ref, window_resize,
@@ -2975,9 +3170,9 @@ use crate::mode_tree::{
     mode_tree_draw_as_parent, mode_tree_draw_cb, mode_tree_each_cb, mode_tree_each_tagged,
     mode_tree_expand, mode_tree_expand_current, mode_tree_free, mode_tree_get_current,
     mode_tree_get_current_name, mode_tree_height_cb, mode_tree_item, mode_tree_key,
-    mode_tree_key_cb, mode_tree_menu_cb, mode_tree_no_tag, mode_tree_remove, mode_tree_resize,
-    mode_tree_run_command, mode_tree_search_cb, mode_tree_set_current, mode_tree_start,
-    mode_tree_up, mode_tree_zoom,
+    mode_tree_key_cb, mode_tree_menu_cb, mode_tree_no_matches, mode_tree_no_tag, mode_tree_remove,
+    mode_tree_resize, mode_tree_run_command, mode_tree_search_cb, mode_tree_set_current,
+    mode_tree_set_filter, mode_tree_set_prompt, mode_tree_start, mode_tree_up, mode_tree_zoom,
 };
 
 mod window_buffer;
@@ -2996,21 +3191,32 @@ mod window_copy;
 use crate::window_copy::window_copy_add;
 use crate::window_copy::{
     window_copy_get_line, window_copy_get_word, window_copy_mode, window_copy_pagedown,
-    window_copy_pageup, window_copy_start_drag, window_copy_vadd, window_view_mode,
+    window_copy_pageup, window_copy_show_match, window_copy_start_drag, window_copy_vadd,
+    window_view_mode,
 };
 
 mod window_customize;
 use crate::window_customize::window_customize_mode;
 
+mod window_fuzzy;
+use crate::window_fuzzy::{window_fuzzy_mode, window_fuzzy_score};
+
+mod window_search;
+use crate::window_search::{window_search_has_matches, window_search_mode};
+
+mod window_timeline;
+use crate::window_timeline::window_timeline_mode;
+
 mod names;
 use crate::names::{check_window_name, default_window_name, parse_window_name};
 
 mod control;
 use crate::control::control_write;
 use crate::control::{
-    control_add_sub, control_all_done, control_continue_pane, control_discard, control_pane_offset,
-    control_pause_pane, control_ready, control_remove_sub, control_reset_offsets,
-    control_set_pane_off, control_set_pane_on, control_start, control_state, control_stop,
+    control_add_sub, control_all_done, control_continue_pane, control_discard, control_json,
+    control_json_line, control_pane_offset, control_pause_pane, control_print, control_ready,
+    control_remove_sub, control_reply, control_reset_offsets, control_set_pane_off,
+    control_set_pane_on, control_start, control_state, control_stop, control_write_json,
     control_write_output,
 };
 
@@ -3083,6 +3289,12 @@ use crate::style_::{style_add, style_apply, style_copy, style_parse, style_set,
 mod spawn;
 use crate::spawn::{spawn_pane, spawn_window};
 
+mod regex_;
+use crate::regex_::{
+    regex_compile, regex_exec, regex_flags, regex_get_syntax, regex_group_index, regex_groups,
+    regex_match, regex_pattern, regex_syntax, regex_syntax_from_flags,
+};
+
 mod regsub;
 use crate::regsub::regsub;
 
@@ -95,6 +95,7 @@ pub struct mode_tree_data {
     preview: i32,
     search: *mut c_char,
     filter: *mut c_char,
+    prompt: *mut c_char,
     no_matches: i32,
     search_dir: mode_tree_search_dir,
 }
@@ -338,11 +339,11 @@ pub unsafe fn mode_tree_collapse_current(mtd: *mut mode_tree_data) {
 pub unsafe fn mode_tree_get_tag(mtd: *mut mode_tree_data, tag: u64, found: *mut u32) -> i32 {
     unsafe {
         let mut i = 0;
-        for j in 0..(*mtd).line_size {
-            i = j;
+        while i < (*mtd).line_size {
             if (*(*(*mtd).line_list.add(i as usize)).item).tag == tag {
                 break;
             }
+            i += 1;
         }
 
         if i != (*mtd).line_size {
@@ -372,7 +373,7 @@ pub unsafe fn mode_tree_set_current(mtd: *mut mode_tree_data, tag: u64) -> i32 {
 
         if mode_tree_get_tag(mtd, tag, &raw mut found) != 0 {
             (*mtd).current = found;
-            if (*mtd).current > (*mtd).height - 1 {
+            if (*mtd).current > (*mtd).height.wrapping_sub(1) {
                 (*mtd).offset = (*mtd).current - (*mtd).height + 1;
             } else {
                 (*mtd).offset = 0;
@@ -585,10 +586,44 @@ pub unsafe fn mode_tree_build(mtd: *mut mode_tree_data) {
         } else {
             (*mtd).height = screen_size_y(s);
         }
+        if !(*mtd).prompt.is_null() && (*mtd).height > 1 {
+            (*mtd).height -= 1;
+        }
         mode_tree_check_selected(mtd);
     }
 }
 
+/// Replace the filter passed to the build callback. The caller rebuilds.
+pub unsafe fn mode_tree_set_filter(mtd: *mut mode_tree_data, filter: *const c_char) {
+    unsafe {
+        free_((*mtd).filter);
+        if filter.is_null() || *filter == b'\0' as c_char {
+            (*mtd).filter = null_mut();
+        } else {
+            (*mtd).filter = xstrdup(filter).as_ptr();
+        }
+    }
+}
+
+/// Show the filter on the first line after a prompt, with the cursor at its
+/// end, or remove the line if prompt is NULL.
+pub unsafe fn mode_tree_set_prompt(mtd: *mut mode_tree_data, prompt: *const c_char) {
+    unsafe {
+        free_((*mtd).prompt);
+        if prompt.is_null() {
+            (*mtd).prompt = null_mut();
+            (*mtd).screen.mode &= !mode_flag::MODE_CURSOR;
+        } else {
+            (*mtd).prompt = xstrdup(prompt).as_ptr();
+            (*mtd).screen.mode |= mode_flag::MODE_CURSOR;
+        }
+    }
+}
+
+pub unsafe fn mode_tree_no_matches(mtd: *mut mode_tree_data) -> bool {
+    unsafe { (*mtd).no_matches != 0 }
+}
+
 pub unsafe fn mode_tree_remove_ref(mtd: *mut mode_tree_data) {
     unsafe {
         (*mtd).references -= 1;
@@ -612,6 +647,7 @@ pub unsafe fn mode_tree_free(mtd: *mut mode_tree_data) {
 
         free_((*mtd).search);
         free_((*mtd).filter);
+        free_((*mtd).prompt);
 
         (*mtd).dead = 1;
         mode_tree_remove_ref(mtd);
@@ -723,10 +759,32 @@ pub unsafe fn mode_tree_draw(mtd: *mut mode_tree_data) {
 
             let w = (*mtd).width;
             let h = (*mtd).height;
+            let top = (!(*mtd).prompt.is_null()) as u32;
+            let filter = if (*mtd).filter.is_null() {
+                c"".as_ptr()
+            } else {
+                (*mtd).filter
+            };
 
             screen_write_start(&raw mut ctx, s);
             screen_write_clearscreen(&raw mut ctx, 8);
 
+            if top != 0 {
+                screen_write_cursormove(&raw mut ctx, 0, 0, 0);
+                screen_write_nputs!(
+                    &raw mut ctx,
+                    w as isize,
+                    &raw mut gc0,
+                    "{}{}",
+                    _s((*mtd).prompt),
+                    _s(filter),
+                );
+                if (*mtd).no_matches != 0 {
+                    screen_write_puts!(&raw mut ctx, &raw mut gc0, " ");
+                    screen_write_puts!(&raw mut ctx, &raw mut gc, "(no matches)");
+                }
+            }
+
             let mut keylen: i32 = 0;
             for i in 0..(*mtd).line_size {
                 let mti = (*(*mtd).line_list.add(i as usize)).item;
@@ -748,7 +806,12 @@ pub unsafe fn mode_tree_draw(mtd: *mut mode_tree_data) {
                 let line = (*mtd).line_list.add(i as usize);
                 let mti = (*line).item;
 
-                screen_write_cursormove(&raw mut ctx, 0, i as i32 - (*mtd).offset as i32, 0);
+                screen_write_cursormove(
+                    &raw mut ctx,
+                    0,
+                    (i + top) as i32 - (*mtd).offset as i32,
+                    0,
+                );
 
                 let pad = keylen - 2 - (*mti).keylen as i32;
                 let key = if (*mti).key != KEYC_NONE {
@@ -853,7 +916,7 @@ pub unsafe fn mode_tree_draw(mtd: *mut mode_tree_data) {
             }
 
             let sy = screen_size_y(s);
-            if (*mtd).preview == 0 || sy <= 4 || h <= 4 || sy - h <= 4 || w <= 4 {
+            if (*mtd).preview == 0 || sy <= 4 || h <= 4 || sy - h - top <= 4 || w <= 4 {
                 break 'done;
             }
 
@@ -863,11 +926,11 @@ pub unsafe fn mode_tree_draw(mtd: *mut mode_tree_data) {
                 mti = (*mti).parent;
             }
 
-            screen_write_cursormove(&raw mut ctx, 0, h as i32, 0);
+            screen_write_cursormove(&raw mut ctx, 0, (h + top) as i32, 0);
             screen_write_box(
                 &raw mut ctx,
                 w,
-                sy - h,
+                sy - h - top,
                 box_lines::BOX_LINES_DEFAULT,
                 null(),
                 null(),
@@ -888,7 +951,7 @@ pub unsafe fn mode_tree_draw(mtd: *mut mode_tree_data) {
                 format_nul!(" {}", _s((*mti).name))
             };
             if w - 2 >= strlen(text) as u32 {
-                screen_write_cursormove(&raw mut ctx, 1, h as i32, 0);
+                screen_write_cursormove(&raw mut ctx, 1, (h + top) as i32, 0);
                 screen_write_puts!(&raw mut ctx, &raw mut gc0, "{}", _s(text));
 
                 let n = if (*mtd).no_matches != 0 {
@@ -912,10 +975,10 @@ pub unsafe fn mode_tree_draw(mtd: *mut mode_tree_data) {
             free_(text);
 
             let box_x = w - 4;
-            let box_y = sy - h - 2;
+            let box_y = sy - h - top - 2;
 
             if box_x != 0 && box_y != 0 {
-                screen_write_cursormove(&raw mut ctx, 2, h as i32 + 1, 0);
+                screen_write_cursormove(&raw mut ctx, 2, (h + top) as i32 + 1, 0);
                 (*mtd).drawcb.unwrap()(
                     (*mtd).modedata,
                     NonNull::new((*mti).itemdata),
@@ -926,12 +989,20 @@ pub unsafe fn mode_tree_draw(mtd: *mut mode_tree_data) {
             }
         }
         // done:
-        screen_write_cursormove(
-            &raw mut ctx,
-            0,
-            (*mtd).current as i32 - (*mtd).offset as i32,
-            0,
-        );
+        if !(*mtd).prompt.is_null() {
+            let mut cx = utf8_cstrwidth((*mtd).prompt);
+            if !(*mtd).filter.is_null() {
+                cx += utf8_cstrwidth((*mtd).filter);
+            }
+            screen_write_cursormove(&raw mut ctx, cx.min((*mtd).width - 1) as i32, 0, 0);
+        } else {
+            screen_write_cursormove(
+                &raw mut ctx,
+                0,
+                (*mtd).current as i32 - (*mtd).offset as i32,
+                0,
+            );
+        }
         screen_write_stop(&raw mut ctx);
     }
 }
@@ -1238,6 +1309,13 @@ pub unsafe fn mode_tree_key(
                 *key = KEYC_NONE;
                 return 0;
             }
+            if !(*mtd).prompt.is_null() {
+                if y == 0 {
+                    *key = KEYC_NONE;
+                    return 0;
+                }
+                y -= 1;
+            }
             if !xp.is_null() {
                 *xp = x;
             }
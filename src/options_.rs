@@ -1357,6 +1357,10 @@ pub unsafe fn options_push_changes(name: *const c_char) {
             }
         }
 
+        if streq_(name, "config-watch") {
+            cfg_watch_update();
+        }
+
         if streq_(name, "cursor-colour") {
             for wp in rb_foreach(&raw mut all_window_panes) {
                 window_pane_default_cursor(wp.as_ptr());
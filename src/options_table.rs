@@ -132,8 +132,14 @@ static mut options_table_detach_on_destroy_list: [*const c_char; 6] = [
 ];
 static mut options_table_extended_keys_list: [*const c_char; 4] =
     [c"off".as_ptr(), c"on".as_ptr(), c"always".as_ptr(), null()];
-static mut options_table_extended_keys_format_list: [*const c_char; 3] =
-    [c"csi-u".as_ptr(), c"xterm".as_ptr(), null()];
+static mut options_table_extended_keys_format_list: [*const c_char; 4] = [
+    c"csi-u".as_ptr(),
+    c"xterm".as_ptr(),
+    c"kitty".as_ptr(),
+    null(),
+];
+static mut options_table_regex_syntax_list: [*const c_char; 3] =
+    [c"posix".as_ptr(), c"pcre".as_ptr(), null()];
 static mut options_table_allow_passthrough_list: [*const c_char; 4] =
     [c"off".as_ptr(), c"on".as_ptr(), c"all".as_ptr(), null()];
 
@@ -279,7 +285,7 @@ macro_rules! options_table_window_hook {
     };
 }
 
-pub static mut options_table: [options_table_entry; 191] = [
+pub static mut options_table: [options_table_entry; 202] = [
     options_table_entry {
         name: c"backspace".as_ptr(),
         type_: options_table_type::OPTIONS_TABLE_KEY,
@@ -308,6 +314,14 @@ pub static mut options_table: [options_table_entry; 191] = [
         text: c"Array of command aliases. Each entry is an alias and a command separated by '='.".as_ptr(),
         ..unsafe { zeroed() }
     },
+    options_table_entry {
+        name: c"config-watch".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_FLAG,
+        scope: OPTIONS_TABLE_SERVER,
+        default_num: 0,
+        text: c"Whether configuration files are reloaded when they change.".as_ptr(),
+        ..unsafe { zeroed() }
+    },
     options_table_entry {
         name: c"copy-command".as_ptr(),
         type_: options_table_type::OPTIONS_TABLE_STRING,
@@ -480,6 +494,15 @@ pub static mut options_table: [options_table_entry; 191] = [
         text: c"Maximum number of commands to keep in history.".as_ptr(),
         ..unsafe { zeroed() }
     },
+    options_table_entry {
+        name: c"regex-syntax".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_CHOICE,
+        scope: OPTIONS_TABLE_SERVER,
+        choices: &raw const options_table_regex_syntax_list as *const *const c_char,
+        default_num: 0,
+        text: c"Syntax of regular expressions used for searching and matching: POSIX extended ('posix') or Perl-like and UTF-8 aware without lookaround or backreferences ('pcre').".as_ptr(),
+        ..unsafe { zeroed() }
+    },
     options_table_entry {
         name: c"set-clipboard".as_ptr(),
         type_: options_table_type::OPTIONS_TABLE_CHOICE,
@@ -651,6 +674,17 @@ pub static mut options_table: [options_table_entry; 191] = [
         text: c"Maximum number of lines to keep in the history for each pane. If changed, the new value applies only to new panes.".as_ptr(),
         ..unsafe { zeroed() }
     },
+    options_table_entry {
+        name: c"history-spill-limit".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_NUMBER,
+        scope: OPTIONS_TABLE_SESSION,
+        minimum: 0,
+        maximum: i32::MAX as u32,
+        default_num: 0,
+        unit: c"lines".as_ptr(),
+        text: c"Maximum number of lines to keep on disk for each pane once they are removed from the history. Zero means lines are discarded. If changed, the new value applies only to new panes.".as_ptr(),
+        ..unsafe { zeroed() }
+    },
     options_table_entry {
         name: c"key-table".as_ptr(),
         type_: options_table_type::OPTIONS_TABLE_STRING,
@@ -731,6 +765,67 @@ pub static mut options_table: [options_table_entry; 191] = [
         text: c"A second prefix key.".as_ptr(),
         ..unsafe { zeroed() }
     },
+    options_table_entry {
+        name: c"prompt-command-style".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_STRING,
+        scope: OPTIONS_TABLE_SESSION,
+        default_str: c"bold".as_ptr(),
+        flags: OPTIONS_TABLE_IS_STYLE,
+        separator: c",".as_ptr(),
+        text: c"Style of command names in the command prompt.".as_ptr(),
+        ..unsafe { zeroed() }
+    },
+    options_table_entry {
+        name: c"prompt-error-style".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_STRING,
+        scope: OPTIONS_TABLE_SESSION,
+        default_str: c"fg=red,underscore".as_ptr(),
+        flags: OPTIONS_TABLE_IS_STYLE,
+        separator: c",".as_ptr(),
+        text: c"Style of unknown commands and flags and of parse errors in the command prompt.".as_ptr(),
+        ..unsafe { zeroed() }
+    },
+    options_table_entry {
+        name: c"prompt-flag-style".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_STRING,
+        scope: OPTIONS_TABLE_SESSION,
+        default_str: c"fg=blue".as_ptr(),
+        flags: OPTIONS_TABLE_IS_STYLE,
+        separator: c",".as_ptr(),
+        text: c"Style of flags in the command prompt.".as_ptr(),
+        ..unsafe { zeroed() }
+    },
+    options_table_entry {
+        name: c"prompt-format-style".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_STRING,
+        scope: OPTIONS_TABLE_SESSION,
+        default_str: c"fg=blue,bold".as_ptr(),
+        flags: OPTIONS_TABLE_IS_STYLE,
+        separator: c",".as_ptr(),
+        text: c"Style of formats in the command prompt.".as_ptr(),
+        ..unsafe { zeroed() }
+    },
+    options_table_entry {
+        name: c"prompt-string-style".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_STRING,
+        scope: OPTIONS_TABLE_SESSION,
+        default_str: c"fg=magenta".as_ptr(),
+        flags: OPTIONS_TABLE_IS_STYLE,
+        separator: c",".as_ptr(),
+        text: c"Style of quoted strings in the command prompt.".as_ptr(),
+        ..unsafe { zeroed() }
+    },
+    options_table_entry {
+        name: c"prompt-suggestion-style".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_STRING,
+        scope: OPTIONS_TABLE_SESSION,
+        default_str: c"dim".as_ptr(),
+        flags: OPTIONS_TABLE_IS_STYLE,
+        separator: c",".as_ptr(),
+        text: c"Style of the history suggestion shown after the cursor in the command prompt."
+            .as_ptr(),
+        ..unsafe { zeroed() }
+    },
     options_table_entry {
         name: c"renumber-windows".as_ptr(),
         type_: options_table_type::OPTIONS_TABLE_FLAG,
@@ -1070,6 +1165,27 @@ pub static mut options_table: [options_table_entry; 191] = [
         text: c"Character used to fill unused parts of window.".as_ptr(),
         ..unsafe { zeroed() }
     },
+    options_table_entry {
+        name: c"fuzzy-match-style".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_STRING,
+        scope: OPTIONS_TABLE_WINDOW,
+        default_str: c"bold,underscore".as_ptr(),
+        flags: OPTIONS_TABLE_IS_STYLE,
+        separator: c",".as_ptr(),
+        text: c"Style of matched characters in 'choose-fuzzy'.".as_ptr(),
+        ..unsafe { zeroed() }
+    },
+    options_table_entry {
+        name: c"history-save-limit".as_ptr(),
+        type_: options_table_type::OPTIONS_TABLE_NUMBER,
+        scope: OPTIONS_TABLE_WINDOW | OPTIONS_TABLE_PANE,
+        minimum: 0,
+        maximum: i32::MAX as u32,
+        default_num: 0,
+        unit: c"lines".as_ptr(),
+        text: c"Maximum number of lines saved by 'save-history' for each pane, or 0 for no limit.".as_ptr(),
+        ..unsafe { zeroed() }
+    },
     options_table_entry {
         name: c"main-pane-height".as_ptr(),
         type_: options_table_type::OPTIONS_TABLE_STRING,
@@ -0,0 +1,272 @@
+//! Regular expressions for searching and matching. Patterns are either POSIX
+//! extended, compiled by the C library, or Perl-like (lazy quantifiers, `\d`,
+//! Unicode classes, named groups; no lookaround or backreferences) and matched
+//! as UTF-8 by the regex crate.
+use crate::*;
+
+use libc::{
+    REG_EXTENDED, REG_ICASE, REG_NOSUB, REG_NOTBOL, regcomp, regex_t, regexec, regfree, regmatch_t,
+};
+
+/// The syntax of a regular expression.
+#[repr(i32)]
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum regex_syntax {
+    REGEX_POSIX,
+    REGEX_PCRE,
+}
+
+bitflags::bitflags! {
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Eq, PartialEq)]
+    pub struct regex_flags: i32 {
+        const REGEX_ICASE = 0x1;
+        const REGEX_NOSUB = 0x2;
+    }
+}
+
+/// Groups available to a POSIX pattern, which cannot be asked how many it has.
+const REGEX_POSIX_GROUPS: usize = 10;
+
+/// A match or capture group as byte offsets, -1 if it did not take part.
+#[derive(Copy, Clone)]
+pub struct regex_match {
+    pub so: isize,
+    pub eo: isize,
+}
+
+impl regex_match {
+    pub const fn empty() -> Self {
+        Self { so: -1, eo: -1 }
+    }
+}
+
+/// A compiled regular expression.
+pub enum regex_pattern {
+    Posix(Box<regex_t>),
+    Pcre(::regex::bytes::Regex),
+}
+
+impl Drop for regex_pattern {
+    fn drop(&mut self) {
+        if let regex_pattern::Posix(r) = self {
+            unsafe { regfree(&mut **r) };
+        }
+    }
+}
+
+/// Get the syntax selected by the regex-syntax option.
+pub unsafe fn regex_get_syntax() -> regex_syntax {
+    unsafe {
+        if options_get_number_(global_options, c"regex-syntax") == 1 {
+            regex_syntax::REGEX_PCRE
+        } else {
+            regex_syntax::REGEX_POSIX
+        }
+    }
+}
+
+/// Get the syntax to use from search flags: 'p' picks Perl-like, otherwise
+/// the regex-syntax option decides.
+pub unsafe fn regex_syntax_from_flags(flags: *const c_char) -> regex_syntax {
+    unsafe {
+        if !flags.is_null() && !libc::strchr(flags, b'p' as i32).is_null() {
+            regex_syntax::REGEX_PCRE
+        } else {
+            regex_get_syntax()
+        }
+    }
+}
+
+pub unsafe fn regex_compile(
+    pattern: *const c_char,
+    syntax: regex_syntax,
+    flags: regex_flags,
+) -> Option<regex_pattern> {
+    unsafe {
+        match syntax {
+            regex_syntax::REGEX_POSIX => {
+                let mut cflags = REG_EXTENDED;
+                if flags.intersects(regex_flags::REGEX_ICASE) {
+                    cflags |= REG_ICASE;
+                }
+                if flags.intersects(regex_flags::REGEX_NOSUB) {
+                    cflags |= REG_NOSUB;
+                }
+                let mut r: Box<regex_t> = Box::new(zeroed());
+                if regcomp(&mut *r, pattern, cflags) != 0 {
+                    return None;
+                }
+                Some(regex_pattern::Posix(r))
+            }
+            regex_syntax::REGEX_PCRE => {
+                let pattern = CStr::from_ptr(pattern).to_str().ok()?;
+                match ::regex::bytes::RegexBuilder::new(pattern)
+                    .case_insensitive(flags.intersects(regex_flags::REGEX_ICASE))
+                    .build()
+                {
+                    Ok(r) => Some(regex_pattern::Pcre(r)),
+                    Err(e) => {
+                        log_debug!("regex_compile: {}", e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Number of groups, including the whole match, that regex_exec can fill.
+pub fn regex_groups(r: &regex_pattern) -> usize {
+    match r {
+        regex_pattern::Posix(_) => REGEX_POSIX_GROUPS,
+        regex_pattern::Pcre(r) => r.captures_len(),
+    }
+}
+
+/// Find the index of a named group.
+pub fn regex_group_index(r: &regex_pattern, name: &[u8]) -> Option<usize> {
+    match r {
+        regex_pattern::Posix(_) => None,
+        regex_pattern::Pcre(r) => r
+            .capture_names()
+            .position(|n| n.map(str::as_bytes) == Some(name)),
+    }
+}
+
+/// Match against a string from byte start, filling in as many groups as there
+/// is space for in m. The text before start is not searched but is seen by
+/// Perl-like patterns for \b and multi-line ^; POSIX patterns are only told it
+/// is not the start of a line. Offsets in m are from start.
+pub unsafe fn regex_exec(
+    r: &regex_pattern,
+    text: *const c_char,
+    start: usize,
+    m: &mut [regex_match],
+) -> bool {
+    unsafe {
+        match r {
+            regex_pattern::Posix(r) => {
+                let mut pm: [regmatch_t; REGEX_POSIX_GROUPS] = zeroed();
+                let n = m.len().min(REGEX_POSIX_GROUPS);
+                let eflags = if start != 0 { REG_NOTBOL } else { 0 };
+                if regexec(&**r, text.add(start), n, pm.as_mut_ptr(), eflags) != 0 {
+                    return false;
+                }
+                for (i, m) in m.iter_mut().enumerate() {
+                    *m = if i < n && pm[i].rm_so != -1 {
+                        regex_match {
+                            so: pm[i].rm_so as isize,
+                            eo: pm[i].rm_eo as isize,
+                        }
+                    } else {
+                        regex_match::empty()
+                    };
+                }
+                true
+            }
+            regex_pattern::Pcre(r) => {
+                let text = CStr::from_ptr(text).to_bytes();
+
+                if m.is_empty() {
+                    return r.is_match_at(text, start);
+                }
+                let Some(caps) = r.captures_at(text, start) else {
+                    return false;
+                };
+                for (i, m) in m.iter_mut().enumerate() {
+                    *m = match caps.get(i) {
+                        Some(c) => regex_match {
+                            so: (c.start() - start) as isize,
+                            eo: (c.end() - start) as isize,
+                        },
+                        None => regex_match::empty(),
+                    };
+                }
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regex_exec() {
+        unsafe {
+            let mut m = [regex_match::empty(); 3];
+
+            let r = regex_compile(
+                c"a(\\d+?)(x)?".as_ptr(),
+                regex_syntax::REGEX_PCRE,
+                regex_flags::empty(),
+            )
+            .unwrap();
+            assert!(regex_exec(&r, c"xa123".as_ptr(), 0, &mut m));
+            assert_eq!((m[0].so, m[0].eo, m[1].so, m[1].eo), (1, 3, 2, 3));
+            assert_eq!(m[2].so, -1);
+
+            let r = regex_compile(
+                c"^\\p{Greek}+".as_ptr(),
+                regex_syntax::REGEX_PCRE,
+                regex_flags::empty(),
+            )
+            .unwrap();
+            let text = c"\u{3b1}\u{3b2}c".as_ptr();
+            assert!(regex_exec(&r, text, 0, &mut m[..1]));
+            assert_eq!((m[0].so, m[0].eo), (0, 4));
+            assert!(!regex_exec(&r, text, 2, &mut m[..1]));
+
+            // Text before start is context: it is not the start of a line
+            // even in multi-line mode, and \b sees the character before.
+            let r = regex_compile(
+                c"(?m)^a|\\bb".as_ptr(),
+                regex_syntax::REGEX_PCRE,
+                regex_flags::empty(),
+            )
+            .unwrap();
+            assert!(!regex_exec(&r, c"xa".as_ptr(), 1, &mut m[..1]));
+            assert!(!regex_exec(&r, c"xb".as_ptr(), 1, &mut m[..1]));
+            assert!(regex_exec(&r, c"x\na".as_ptr(), 2, &mut m[..1]));
+            assert!(regex_exec(&r, c"x b".as_ptr(), 1, &mut m[..1]));
+            assert_eq!((m[0].so, m[0].eo), (1, 2));
+
+            let r = regex_compile(
+                c"(?<word>[A-Z]+)".as_ptr(),
+                regex_syntax::REGEX_PCRE,
+                regex_flags::REGEX_ICASE,
+            )
+            .unwrap();
+            assert_eq!(regex_group_index(&r, b"word"), Some(1));
+            assert!(regex_exec(&r, c"12 ab".as_ptr(), 0, &mut []));
+
+            let r = regex_compile(
+                c"b+".as_ptr(),
+                regex_syntax::REGEX_POSIX,
+                regex_flags::empty(),
+            )
+            .unwrap();
+            assert!(regex_exec(&r, c"abbc".as_ptr(), 0, &mut m[..1]));
+            assert_eq!((m[0].so, m[0].eo), (1, 3));
+
+            let r = regex_compile(
+                c"^b".as_ptr(),
+                regex_syntax::REGEX_POSIX,
+                regex_flags::empty(),
+            )
+            .unwrap();
+            assert!(!regex_exec(&r, c"ab".as_ptr(), 1, &mut []));
+            assert!(regex_exec(&r, c"b".as_ptr(), 0, &mut m[..1]));
+            assert!(
+                regex_compile(
+                    c"a(".as_ptr(),
+                    regex_syntax::REGEX_PCRE,
+                    regex_flags::empty()
+                )
+                .is_none()
+            );
+        }
+    }
+}
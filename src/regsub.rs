@@ -13,7 +13,7 @@
 // OUT OF OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
 use core::ffi::{c_char, c_int};
-use libc::{memcpy, regcomp, regex_t, regexec, regfree, regmatch_t, strlen};
+use libc::{memcpy, strlen};
 use xmalloc::xrealloc_;
 
 use super::*;
@@ -33,32 +33,52 @@ unsafe fn regsub_copy(
     }
 }
 
+/// Find the group named by a \\g<name> reference, returning it and the length
+/// of the reference after the backslash.
+unsafe fn regsub_named(r: &regex_pattern, cp: *const c_char) -> Option<(usize, usize)> {
+    unsafe {
+        if *cp != b'g' as c_char || *cp.add(1) != b'<' as c_char {
+            return None;
+        }
+        let name = cp.add(2);
+        let end = libc::strchr(name, b'>' as i32);
+        if end.is_null() {
+            return None;
+        }
+        let namelen = end.offset_from(name) as usize;
+        let i = regex_group_index(r, std::slice::from_raw_parts(name.cast(), namelen))?;
+        Some((i, namelen + 3))
+    }
+}
+
 pub unsafe fn regsub_expand(
     buf: *mut *mut c_char,
     len: *mut isize,
     with: *const c_char,
     text: *const c_char,
-    m: *mut regmatch_t,
-    n: c_uint,
+    r: &regex_pattern,
+    m: &[regex_match],
 ) {
     unsafe {
         let mut cp: *const c_char = null();
-        let mut i: u32 = 0;
+        let mut i: usize = 0;
 
         cp = with;
         while *cp != b'\0' as c_char {
             if *cp == b'\\' as c_char {
                 cp = cp.add(1);
+                if let Some((i, n)) = regsub_named(r, cp) {
+                    if i < m.len() && m[i].so != m[i].eo {
+                        regsub_copy(buf, len, text, m[i].so as usize, m[i].eo as usize);
+                    }
+                    cp = cp.add(n);
+                    continue;
+                }
                 if *cp >= b'0' as _ && *cp <= b'9' as _ {
-                    i = (*cp - b'0' as c_char) as u32;
-                    if i < n && (*m.add(i as _)).rm_so != (*m.add(i as _)).rm_eo {
-                        regsub_copy(
-                            buf,
-                            len,
-                            text,
-                            (*m.add(i as _)).rm_so as usize,
-                            (*m.add(i as _)).rm_eo as usize,
-                        );
+                    i = (*cp - b'0' as c_char) as usize;
+                    if i < m.len() && m[i].so != m[i].eo {
+                        regsub_copy(buf, len, text, m[i].so as usize, m[i].eo as usize);
+                        cp = cp.add(1);
                         continue;
                     }
                 }
@@ -76,11 +96,10 @@ pub unsafe fn regsub(
     pattern: *const c_char,
     with: *const c_char,
     text: *const c_char,
-    flags: c_int,
+    syntax: regex_syntax,
+    flags: regex_flags,
 ) -> *mut c_char {
     unsafe {
-        let mut r: regex_t = zeroed();
-        let mut m: [regmatch_t; 10] = zeroed(); // TODO can use uninit
         let mut len: isize = 0;
         let mut empty = 0;
         let mut buf = null_mut();
@@ -88,23 +107,17 @@ pub unsafe fn regsub(
         if *text == b'\0' as c_char {
             return xstrdup(c"".as_ptr()).cast().as_ptr();
         }
-        if regcomp(&raw mut r, pattern, flags) != 0 {
+        let Some(r) = regex_compile(pattern, syntax, flags) else {
             return null_mut();
-        }
+        };
+        let mut m = vec![regex_match::empty(); regex_groups(&r)];
 
         let mut start: isize = 0;
         let mut last: isize = 0;
         let end: isize = strlen(text) as _;
 
         while start <= end {
-            if regexec(
-                &raw mut r,
-                text.add(start as _) as _,
-                m.len(),
-                m.as_mut_ptr(),
-                0,
-            ) != 0
-            {
+            if !regex_exec(&r, text.add(start as _), 0, &mut m) {
                 regsub_copy(
                     &raw mut buf,
                     &raw mut len,
@@ -124,7 +137,7 @@ pub unsafe fn regsub(
                 &raw mut len,
                 text,
                 last as usize,
-                (m[0].rm_so as isize + start) as usize,
+                (m[0].so + start) as usize,
             );
 
             /*
@@ -132,22 +145,15 @@ pub unsafe fn regsub(
              * later or has matched text), expand this match. If it is
              * empty, move on one character and try again from there.
              */
-            if empty != 0 || start + m[0].rm_so as isize != last || m[0].rm_so != m[0].rm_eo {
-                regsub_expand(
-                    &raw mut buf,
-                    &raw mut len,
-                    with,
-                    text.offset(start),
-                    m.as_mut_ptr(),
-                    m.len() as u32,
-                );
+            if empty != 0 || start + m[0].so != last || m[0].so != m[0].eo {
+                regsub_expand(&raw mut buf, &raw mut len, with, text.offset(start), &r, &m);
 
-                last = start + m[0].rm_eo as isize;
-                start += m[0].rm_eo as isize;
+                last = start + m[0].eo;
+                start += m[0].eo;
                 empty = 0;
             } else {
-                last = start + m[0].rm_eo as isize;
-                start += (m[0].rm_eo + 1) as isize;
+                last = start + m[0].eo;
+                start += m[0].eo + 1;
                 empty = 1;
             }
 
@@ -165,7 +171,6 @@ pub unsafe fn regsub(
         }
         *buf.offset(len) = b'\0' as _;
 
-        regfree(&raw mut r);
         buf
     }
 }
@@ -85,8 +85,9 @@ pub unsafe fn screen_init(s: *mut screen, sx: u32, sy: u32, hlimit: u32) {
         (*s).tabs = null_mut();
         (*s).sel = null_mut();
 
-        #[cfg(feature = "sixel")]
         tailq_init(&raw mut (*s).images);
+        tailq_init(&raw mut (*s).kitty_images);
+        (*s).kitty_pending = null_mut();
 
         (*s).write_list = null_mut();
         (*s).hyperlinks = null_mut();
@@ -110,6 +111,8 @@ pub unsafe fn screen_reinit(s: *mut screen) {
         if options_get_number_(global_options, c"extended-keys") == 2 {
             (*s).mode = ((*s).mode & !EXTENDED_KEY_MODES) | mode_flag::MODE_KEYS_EXTENDED;
         }
+        (*s).kitty_keys = zeroed();
+        (*s).saved_kitty_keys = zeroed();
 
         if !(*s).saved_grid.is_null() {
             screen_alternate_off(s, null_mut(), 0);
@@ -124,8 +127,8 @@ pub unsafe fn screen_reinit(s: *mut screen) {
         screen_clear_selection(s);
         screen_free_titles(s);
 
-        #[cfg(feature = "sixel")]
         image_free_all(s);
+        kitty_free_all(s);
 
         screen_reset_hyperlinks(s);
     }
@@ -164,8 +167,8 @@ pub unsafe fn screen_free(s: *mut screen) {
         }
         screen_free_titles(s);
 
-        #[cfg(feature = "sixel")]
         image_free_all(s);
+        kitty_free_all(s);
     }
 }
 
@@ -243,6 +246,58 @@ pub unsafe fn screen_set_cursor_colour(s: *mut screen, colour: c_int) {
     }
 }
 
+/// Get the current kitty keyboard flags.
+pub unsafe fn screen_kitty_keys_flags(s: *const screen) -> u32 {
+    unsafe {
+        let kk = &raw const (*s).kitty_keys;
+        if (*kk).depth == 0 {
+            return 0;
+        }
+        (*kk).flags[(*kk).depth as usize - 1]
+    }
+}
+
+/// Push kitty keyboard flags. If the stack is full, the oldest entry is lost.
+pub unsafe fn screen_kitty_keys_push(s: *mut screen, flags: u32) {
+    unsafe {
+        let kk = &raw mut (*s).kitty_keys;
+        if (*kk).depth as usize == SCREEN_KITTY_KEYS_MAX {
+            (*kk).flags.copy_within(1.., 0);
+            (*kk).depth -= 1;
+        }
+        (*kk).flags[(*kk).depth as usize] = flags & KITTY_KEYS_ALL;
+        (*kk).depth += 1;
+    }
+}
+
+/// Pop kitty keyboard flags. Popping more entries than present empties the
+/// stack.
+pub unsafe fn screen_kitty_keys_pop(s: *mut screen, n: u32) {
+    unsafe {
+        let kk = &raw mut (*s).kitty_keys;
+        (*kk).depth = (*kk).depth.saturating_sub(n);
+    }
+}
+
+/// Change the current kitty keyboard flags: mode 1 replaces them, 2 sets the
+/// given bits and 3 clears them.
+pub unsafe fn screen_kitty_keys_set(s: *mut screen, flags: u32, mode: u32) {
+    unsafe {
+        let kk = &raw mut (*s).kitty_keys;
+        if (*kk).depth == 0 {
+            (*kk).flags[0] = 0;
+            (*kk).depth = 1;
+        }
+        let top = &mut (*kk).flags[(*kk).depth as usize - 1];
+        match mode {
+            1 => *top = flags & KITTY_KEYS_ALL,
+            2 => *top |= flags & KITTY_KEYS_ALL,
+            3 => *top &= !flags,
+            _ => (),
+        }
+    }
+}
+
 /// Set screen title.
 pub unsafe fn screen_set_title(s: *mut screen, title: *const c_char) -> c_int {
     unsafe {
@@ -343,13 +398,12 @@ pub unsafe fn screen_resize_cursor(
             screen_resize_y(s, sy, eat_empty, &mut cy);
         }
 
-        #[cfg(feature = "sixel")]
-        image_free_all(s);
-
         if reflow != 0 {
             screen_reflow(s, sx, &mut cx, &mut cy, cursor);
         }
 
+        // Images stay with the line the cursor is on.
+        let ocy = (*s).cy;
         if cy >= (*(*s).grid).hsize {
             (*s).cx = cx;
             (*s).cy = cy - (*(*s).grid).hsize;
@@ -357,6 +411,7 @@ pub unsafe fn screen_resize_cursor(
             (*s).cx = 0;
             (*s).cy = 0;
         }
+        image_resize(s, ocy as i32 - (*s).cy as i32);
 
         log_debug!(
             "{}: cursor finished at {},{} = {},{}",
@@ -596,7 +651,7 @@ pub unsafe fn screen_check_selection(s: *mut screen, px: u32, py: u32) -> c_int
                 }
 
                 if (*sel).modekeys == modekey::MODEKEY_EMACS {
-                    xx = (*sel).sx - 1;
+                    xx = (*sel).sx.wrapping_sub(1);
                 } else {
                     xx = (*sel).sx;
                 }
@@ -705,6 +760,9 @@ pub unsafe fn screen_alternate_on(s: *mut screen, gc: *mut grid_cell, cursor: i3
 
         (*s).saved_flags = (*(*s).grid).flags;
         (*(*s).grid).flags &= !GRID_HISTORY;
+
+        (*s).saved_kitty_keys = (*s).kitty_keys;
+        (*s).kitty_keys = zeroed();
     }
 }
 
@@ -760,6 +818,9 @@ pub unsafe fn screen_alternate_off(s: *mut screen, gc: *mut grid_cell, cursor: i
         grid_destroy((*s).saved_grid);
         (*s).saved_grid = null_mut();
 
+        (*s).kitty_keys = (*s).saved_kitty_keys;
+        (*s).saved_kitty_keys = zeroed();
+
         if (*s).cx > screen_size_x(s) - 1 {
             (*s).cx = screen_size_x(s) - 1;
         }
@@ -866,3 +927,74 @@ pub unsafe fn screen_mode_to_string(mode: mode_flag) -> *const c_char {
         &raw mut TMP as *mut c_char
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screen_check_selection() {
+        unsafe {
+            let mut s: screen = zeroed();
+            let mut gc = grid_default_cell;
+
+            // An upward emacs selection started at the beginning of a line
+            // does not include that line.
+            screen_set_selection(
+                &raw mut s,
+                0,
+                2,
+                3,
+                0,
+                0,
+                modekey::MODEKEY_EMACS,
+                &raw mut gc,
+            );
+            assert_eq!(screen_check_selection(&raw mut s, 0, 2), 0);
+            assert_eq!(screen_check_selection(&raw mut s, 5, 1), 1);
+            assert_eq!(screen_check_selection(&raw mut s, 2, 0), 0);
+            assert_eq!(screen_check_selection(&raw mut s, 3, 0), 1);
+
+            free_(s.sel);
+        }
+    }
+
+    #[test]
+    fn test_screen_kitty_keys() {
+        unsafe {
+            let mut s: screen = zeroed();
+            let s = &raw mut s;
+            assert_eq!(screen_kitty_keys_flags(s), 0);
+
+            screen_kitty_keys_push(s, KITTY_KEYS_DISAMBIGUATE);
+            screen_kitty_keys_push(s, 0xff);
+            assert_eq!(screen_kitty_keys_flags(s), KITTY_KEYS_ALL);
+            screen_kitty_keys_set(s, KITTY_KEYS_REPORT_TEXT, 3);
+            assert_eq!(
+                screen_kitty_keys_flags(s),
+                KITTY_KEYS_ALL & !KITTY_KEYS_REPORT_TEXT
+            );
+            screen_kitty_keys_pop(s, 1);
+            assert_eq!(screen_kitty_keys_flags(s), KITTY_KEYS_DISAMBIGUATE);
+            screen_kitty_keys_set(s, KITTY_KEYS_REPORT_EVENTS, 2);
+            assert_eq!(
+                screen_kitty_keys_flags(s),
+                KITTY_KEYS_DISAMBIGUATE | KITTY_KEYS_REPORT_EVENTS
+            );
+            screen_kitty_keys_pop(s, 5);
+            assert_eq!(screen_kitty_keys_flags(s), 0);
+
+            // A full stack loses its oldest entry.
+            for flags in 1..=SCREEN_KITTY_KEYS_MAX as u32 + 1 {
+                screen_kitty_keys_push(s, flags);
+            }
+            screen_kitty_keys_pop(s, SCREEN_KITTY_KEYS_MAX as u32 - 1);
+            assert_eq!(screen_kitty_keys_flags(s), 2);
+
+            // Setting with an empty stack starts a new entry.
+            screen_kitty_keys_pop(s, 1);
+            screen_kitty_keys_set(s, KITTY_KEYS_REPORT_ALL, 1);
+            assert_eq!(screen_kitty_keys_flags(s), KITTY_KEYS_REPORT_ALL);
+        }
+    }
+}
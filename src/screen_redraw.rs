@@ -710,6 +710,7 @@ pub unsafe fn screen_redraw_screen(c: *mut client) {
         }
         if flags.intersects(client_flag::REDRAWWINDOW) {
             log_debug!("{}: redrawing panes", _s((*c).name));
+            tty_kitty_delete_pane(&raw mut (*c).tty, null_mut());
             screen_redraw_draw_panes(ctx);
         }
         if (*ctx).statuslines != 0
@@ -1047,7 +1048,6 @@ pub unsafe fn screen_redraw_draw_pane(ctx: *mut screen_redraw_ctx, wp: *mut wind
             tty_draw_line(tty, s, i, j, width, x, y, &raw mut defaults, palette);
         }
 
-        #[cfg(feature = "sixel")]
         tty_draw_images(c, wp, s);
     }
 }
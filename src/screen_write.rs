@@ -1139,11 +1139,8 @@ pub unsafe fn screen_write_alignmenttest(ctx: *mut screen_write_ctx) {
         memcpy__(&raw mut gc, &raw const grid_default_cell);
         utf8_set(&raw mut gc.data, b'E');
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_free_all(s) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_free_all(s) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         for yy in 0..screen_size_y(s) {
@@ -1185,11 +1182,8 @@ pub unsafe fn screen_write_insertcharacter(ctx: *mut screen_write_ctx, mut nx: u
             return;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         screen_write_initctx(ctx, &raw mut ttyctx, 0);
@@ -1224,11 +1218,8 @@ pub unsafe fn screen_write_deletecharacter(ctx: *mut screen_write_ctx, mut nx: u
             return;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         screen_write_initctx(ctx, &raw mut ttyctx, 0);
@@ -1263,11 +1254,8 @@ pub unsafe fn screen_write_clearcharacter(ctx: *mut screen_write_ctx, mut nx: u3
             return;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         screen_write_initctx(ctx, &raw mut ttyctx, 0);
@@ -1288,22 +1276,14 @@ pub unsafe fn screen_write_insertline(ctx: *mut screen_write_ctx, mut ny: u32, b
         let gd = (*s).grid;
         let mut ttyctx: tty_ctx = zeroed();
 
-        let mut sy: u32;
-
-        #[cfg(feature = "sixel")]
-        {
-            sy = screen_size_y(s);
-        }
+        let sy = screen_size_y(s);
 
         if ny == 0 {
             ny = 1;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, (*s).cy, sy - (*s).cy) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_check_line(s, (*s).cy, sy - (*s).cy) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         if (*s).cy < (*s).rupper || (*s).cy > (*s).rlower {
@@ -1360,11 +1340,8 @@ pub unsafe fn screen_write_deleteline(ctx: *mut screen_write_ctx, mut ny: u32, b
             ny = 1;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, (*s).cy, sy - (*s).cy) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_check_line(s, (*s).cy, sy - (*s).cy) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         if (*s).cy < (*s).rupper || (*s).cy > (*s).rlower {
@@ -1420,11 +1397,8 @@ pub unsafe fn screen_write_clearline(ctx: *mut screen_write_ctx, bg: u32) {
             return;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         grid_view_clear((*s).grid, 0, (*s).cy, sx, 1, bg);
@@ -1459,11 +1433,8 @@ pub unsafe fn screen_write_clearendofline(ctx: *mut screen_write_ctx, bg: u32) {
             return;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         grid_view_clear((*s).grid, (*s).cx, (*s).cy, sx - (*s).cx, 1, bg);
@@ -1497,11 +1468,8 @@ pub unsafe fn screen_write_clearstartofline(ctx: *mut screen_write_ctx, bg: u32)
             return;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_check_line(s, (*s).cy, 1) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         if (*s).cx > sx - 1 {
@@ -1564,11 +1532,8 @@ pub unsafe fn screen_write_reverseindex(ctx: *mut screen_write_ctx, bg: u32) {
         let mut ttyctx: tty_ctx = zeroed();
 
         if (*s).cy == (*s).rupper {
-            #[cfg(feature = "sixel")]
-            {
-                if image_free_all(s) && !(*ctx).wp.is_null() {
-                    (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-                }
+            if image_free_all(s) && !(*ctx).wp.is_null() {
+                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
             }
 
             grid_view_scroll_region_down((*s).grid, (*s).rupper, (*s).rlower, bg);
@@ -1618,12 +1583,6 @@ pub unsafe fn screen_write_linefeed(ctx: *mut screen_write_ctx, wrapped: i32, bg
     unsafe {
         let s = (*ctx).s;
         let gd = (*s).grid;
-        let mut redraw: i32;
-        #[cfg(feature = "sixel")]
-        {
-            redraw = 0;
-        }
-
         let rupper = (*s).rupper;
         let rlower = (*s).rlower;
 
@@ -1640,16 +1599,13 @@ pub unsafe fn screen_write_linefeed(ctx: *mut screen_write_ctx, wrapped: i32, bg
         }
 
         if (*s).cy == (*s).rlower {
-            #[cfg(feature = "sixel")]
-            {
-                if (rlower == screen_size_y(s) - 1) {
-                    redraw = image_scroll_up(s, 1);
-                } else {
-                    redraw = image_check_line(s, rupper, rlower - rupper);
-                }
-                if redraw != 0 && !(*ctx).wp.is_null() {
-                    (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-                }
+            let redraw = if rlower == screen_size_y(s) - 1 {
+                image_scroll_up(s, 1)
+            } else {
+                image_check_line(s, rupper, rlower - rupper)
+            };
+            if redraw && !(*ctx).wp.is_null() {
+                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
             }
             grid_view_scroll_region_up(gd, (*s).rupper, (*s).rlower, bg);
             screen_write_collect_scroll(ctx, bg);
@@ -1677,11 +1633,8 @@ pub unsafe fn screen_write_scrollup(ctx: *mut screen_write_ctx, mut lines: u32,
             (*ctx).bg = bg;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_scroll_up(s, lines) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flag::PANE_REDRAW;
-            }
+        if image_scroll_up(s, lines) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         for i in 0..lines {
@@ -1708,11 +1661,8 @@ pub unsafe fn screen_write_scrolldown(ctx: *mut screen_write_ctx, mut lines: u32
             lines = (*s).rlower - (*s).rupper + 1;
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_free_all(s) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_free_all(s) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         for i in 0..lines {
@@ -1741,11 +1691,8 @@ pub unsafe fn screen_write_clearendofscreen(ctx: *mut screen_write_ctx, bg: u32)
         let sx = screen_size_x(s);
         let sy = screen_size_y(s);
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, (*s).cy, sy - (*s).cy) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
-            }
+        if image_check_line(s, (*s).cy, sy - (*s).cy) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         screen_write_initctx(ctx, &raw mut ttyctx, 1);
@@ -1779,11 +1726,8 @@ pub unsafe fn screen_write_clearstartofscreen(ctx: *mut screen_write_ctx, bg: u3
         let mut ttyctx: tty_ctx = zeroed();
         let sx = screen_size_x(s);
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_line(s, 0, (*s).cy - 1) && (*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flag::PANE_REDRAW;
-            }
+        if image_check_line(s, 0, (*s).cy + 1) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         screen_write_initctx(ctx, &raw mut ttyctx, 1);
@@ -1812,11 +1756,8 @@ pub unsafe fn screen_write_clearscreen(ctx: *mut screen_write_ctx, bg: u32) {
         let sx = screen_size_x(s);
         let sy = screen_size_y(s);
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_free_all(s) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flag::PANE_REDRAW;
-            }
+        if image_free_all(s) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         screen_write_initctx(ctx, &raw mut ttyctx, 1);
@@ -2089,11 +2030,8 @@ pub unsafe fn screen_write_collect_end(ctx: *mut screen_write_ctx) {
             }
         }
 
-        #[cfg(feature = "sixel")]
-        {
-            if image_check_area(s, (*s).cx, (*s).cy, (*ci).used, 1) && !(*ctx).wp.is_null() {
-                (*(*ctx).wp).flags |= window_pane_flag::PANE_REDRAW;
-            }
+        if image_check_area(s, (*s).cx, (*s).cy, (*ci).used, 1) && !(*ctx).wp.is_null() {
+            (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
         }
 
         grid_view_set_cells(
@@ -2542,72 +2480,144 @@ pub unsafe fn screen_write_rawstring(
     }
 }
 
-// TODO
-#[cfg(feature = "sixel")]
 /// Write a SIXEL image.
-unsafe fn screen_write_sixelimage(ctx: *mut screen_write_ctx, si: *mut sixel_image, bg: u32) {
+#[cfg(feature = "sixel")]
+pub unsafe fn screen_write_sixelimage(
+    ctx: *mut screen_write_ctx,
+    mut si: *mut sixel_image,
+    bg: u32,
+) {
     unsafe {
-        let mut s = (*ctx).s;
-        let mut gd = (*s).grid;
+        let s = (*ctx).s;
+        let gd = (*s).grid;
         let mut ttyctx: tty_ctx = zeroed();
-
-        // u_int x, y, sx, sy, cx = (*s).cx, cy = (*s).cy, i, lines;
         let mut x: u32 = 0;
         let mut y: u32 = 0;
-        let mut sx: u32 = 0;
-        let mut sy: u32 = 0;
-        let mut cx: u32 = (*s).cx;
-        let mut cy: u32 = (*s).cy;
-        let mut new: *mut sixel_image = null_mut();
+        let cx: u32 = (*s).cx;
+        let cy: u32 = (*s).cy;
 
         sixel_size_in_cells(si, &raw mut x, &raw mut y);
-        if (x > screen_size_x(s) || y > screen_size_y(s)) {
-            if (x > screen_size_x(s) - cx) {
-                sx = screen_size_x(s) - cx;
+        if x > screen_size_x(s) || y > screen_size_y(s) {
+            let sx = if x > screen_size_x(s) - cx {
+                screen_size_x(s) - cx
             } else {
-                sx = x;
-            }
-            if (y > screen_size_y(s) - 1) {
-                sy = screen_size_y(s) - 1;
+                x
+            };
+            let sy = if y > screen_size_y(s) - 1 {
+                screen_size_y(s) - 1
             } else {
-                sy = y;
-            }
-            new = sixel_scale(si, 0, 0, 0, y - sy, sx, sy, 1);
+                y
+            };
+            let new = sixel_scale(si, 0, 0, 0, y - sy, sx, sy, 1);
             sixel_free(si);
             si = new;
 
-            /* Bail out if the image cannot be scaled. */
+            // Bail out if the image cannot be scaled.
             if si.is_null() {
                 return;
             }
-            sixel_size_in_cells(si, &x, &y);
+            sixel_size_in_cells(si, &raw mut x, &raw mut y);
         }
 
-        sy = screen_size_y(s) - cy;
-        if (sy < y) {
-            lines = y - sy + 1;
-            if image_scroll_up(s, lines) && (*ctx).wp != NULL {
-                (*(*ctx).wp).flags |= PANE_REDRAW;
+        let sy = screen_size_y(s) - cy;
+        if sy < y {
+            let lines = y - sy + 1;
+            if image_scroll_up(s, lines) && !(*ctx).wp.is_null() {
+                (*(*ctx).wp).flags |= window_pane_flags::PANE_REDRAW;
             }
-            for i in 0..lines {
+            for _ in 0..lines {
                 grid_view_scroll_region_up(gd, 0, screen_size_y(s) - 1, bg);
                 screen_write_collect_scroll(ctx, bg);
             }
             (*ctx).scrolled += lines;
-            if (lines > cy) {
+            if lines > cy {
                 screen_write_cursormove(ctx, -1, 0, 0);
             } else {
-                screen_write_cursormove(ctx, -1, cy - lines, 0);
+                screen_write_cursormove(ctx, -1, (cy - lines) as i32, 0);
             }
         }
-        screen_write_collect_flush(ctx, 0, __func__);
+        screen_write_collect_flush(ctx, 0, c"screen_write_sixelimage".as_ptr());
 
-        screen_write_initctx(ctx, &ttyctx, 0);
-        ttyctx.ptr = image_store(s, si);
+        screen_write_initctx(ctx, &raw mut ttyctx, 0);
+        ttyctx.ptr = image_store(s, si).cast();
 
-        tty_write(tty_cmd_sixelimage, &ttyctx);
+        tty_write(Some(tty_cmd_sixelimage), &raw mut ttyctx);
 
-        screen_write_cursormove(ctx, 0, cy + y, 0);
+        screen_write_cursormove(ctx, 0, (cy + y) as i32, 0);
+    }
+}
+
+/// Place a kitty image at the cursor.
+pub unsafe fn screen_write_kittyimage(
+    ctx: *mut screen_write_ctx,
+    ki: *mut kitty_image,
+    kc: *const kitty_command,
+) {
+    unsafe {
+        let s = (*ctx).s;
+        let gd = (*s).grid;
+        let wp = (*ctx).wp;
+        let mut ttyctx: tty_ctx = zeroed();
+
+        // If the cursor is waiting to wrap, the image starts on the next line.
+        if (*s).cx > screen_size_x(s) - 1 {
+            screen_write_linefeed(ctx, 1, 8);
+            screen_write_set_cursor(ctx, 0, -1);
+        }
+        let cx = (*s).cx;
+        let cy = (*s).cy;
+
+        let (mut xpixel, mut ypixel) = (DEFAULT_XPIXEL, DEFAULT_YPIXEL);
+        if !wp.is_null() && (*(*wp).window).xpixel != 0 && (*(*wp).window).ypixel != 0 {
+            xpixel = (*(*wp).window).xpixel;
+            ypixel = (*(*wp).window).ypixel;
+        }
+
+        let mut x = (*kc).columns;
+        if x == 0 {
+            x = (*ki).pixel_x.div_ceil(xpixel).max(1);
+        }
+        let mut y = (*kc).rows;
+        if y == 0 {
+            y = (*ki).pixel_y.div_ceil(ypixel).max(1);
+        }
+
+        // Squeeze into the rest of the line and lose the top of images taller
+        // than the screen, as if they had been scrolled off.
+        let sx = x.min(screen_size_x(s) - cx);
+        let crop = y.saturating_sub(screen_size_y(s));
+        y -= crop;
+
+        let sy = screen_size_y(s) - cy;
+        if sy < y {
+            let lines = y - sy;
+            if image_scroll_up(s, lines) && !wp.is_null() {
+                (*wp).flags |= window_pane_flags::PANE_REDRAW;
+            }
+            for _ in 0..lines {
+                grid_view_scroll_region_up(gd, 0, screen_size_y(s) - 1, 8);
+                screen_write_collect_scroll(ctx, 8);
+            }
+            (*ctx).scrolled += lines;
+            screen_write_cursormove(ctx, -1, (cy - lines) as i32, 0);
+        }
+        screen_write_collect_flush(ctx, 0, c"screen_write_kittyimage".as_ptr());
+
+        screen_write_initctx(ctx, &raw mut ttyctx, 0);
+        ttyctx.ptr = image_store_kitty(s, ki, sx, y, crop).cast();
+
+        tty_write(Some(tty_cmd_kittyimage), &raw mut ttyctx);
+
+        if (*kc).cursor != 1 {
+            let cx = (*s).cx + sx;
+            let cy = (*s).cy + y - 1;
+            if cx >= screen_size_x(s) {
+                screen_write_cursormove(ctx, 0, cy as i32, 0);
+                screen_write_linefeed(ctx, 0, 8);
+            } else {
+                screen_write_cursormove(ctx, cx as i32, cy as i32, 0);
+            }
+        }
     }
 }
 
@@ -2645,12 +2655,15 @@ pub unsafe fn screen_write_alternateoff(
         let mut ttyctx: tty_ctx = zeroed();
         let wp = (*ctx).wp;
 
-        if !wp.is_null() && !options_get_number_((*wp).options, c"alternate-screen") != 0 {
+        if !wp.is_null() && options_get_number_((*wp).options, c"alternate-screen") == 0 {
             return;
         }
 
         screen_write_collect_flush(ctx, 0, c"screen_write_alternateoff".as_ptr());
         screen_alternate_off((*ctx).s, gc, cursor);
+        if !wp.is_null() && (*ctx).s == &raw mut (*wp).base {
+            window_pane_reflow_start(wp);
+        }
 
         screen_write_initctx(ctx, &raw mut ttyctx, 1);
         if let Some(redraw_cb) = ttyctx.redraw_cb {
@@ -235,11 +235,12 @@ pub unsafe fn server_start(
         tailq_init(&raw mut message_log);
         gettimeofday(&raw mut start_time, null_mut());
 
-        if cfg!(feature = "systemd") {
-            // TODO we could be truncating important bits
-            server_fd =
-                crate::compat::systemd::systemd_create_socket(flags.bits() as i32, &raw mut cause);
-        } else {
+        #[cfg(feature = "systemd")]
+        {
+            server_fd = crate::compat::systemd::systemd_create_socket(flags, &raw mut cause);
+        }
+        #[cfg(not(feature = "systemd"))]
+        {
             server_fd = server_create_socket(flags, &raw mut cause);
         }
         if server_fd != -1 {
@@ -285,6 +286,7 @@ pub unsafe fn server_start(
 pub unsafe fn server_loop() -> i32 {
     unsafe {
         current_time = libc::time(null_mut());
+        grid_spill_expire();
 
         loop {
             let mut items = cmdq_next(null_mut());
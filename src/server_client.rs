@@ -297,6 +297,83 @@ pub unsafe fn server_client_create(fd: i32) -> *mut client {
     }
 }
 
+/// Create a virtual client. It has no terminal: the server side of a
+/// socketpair is a normal client and the other end only waits to be told to
+/// exit, while everything drawn is kept in an in-memory screen.
+pub unsafe fn server_client_create_virtual(sx: u32, sy: u32) -> *mut client {
+    static mut next_virtual: u32 = 0;
+
+    unsafe {
+        let mut pair: [c_int; 2] = [0; 2];
+
+        if libc::socketpair(
+            libc::AF_UNIX,
+            libc::SOCK_STREAM,
+            libc::PF_UNSPEC,
+            &raw mut pair as *mut c_int,
+        ) != 0
+        {
+            return null_mut();
+        }
+        setblocking(pair[1], 0);
+        let peer = proc_add_peer(
+            server_proc,
+            pair[1],
+            Some(server_client_virtual_dispatch),
+            null_mut(),
+        );
+        (*peer).arg = peer.cast();
+
+        let c = server_client_create(pair[0]);
+        let n = next_virtual;
+        next_virtual += 1;
+        (*c).name = format_nul!("virtual-{n}");
+        (*c).ttyname = xstrdup(c"".as_ptr()).as_ptr();
+        (*c).pid = libc::getpid();
+
+        (*c).term_name = xstrdup(c"virtual".as_ptr()).as_ptr();
+        tty_term_virtual_list(&raw mut (*c).term_caps, &raw mut (*c).term_ncaps);
+        tty_add_features(
+            &raw mut (*c).term_features,
+            TTY_TERM_VIRTUAL_FEATURES.as_ptr(),
+            c",".as_ptr(),
+        );
+
+        tty_init_virtual(&raw mut (*c).tty, c, sx, sy);
+        (*c).flags |= client_flag::IDENTIFIED
+            | client_flag::TERMINAL
+            | client_flag::UTF8
+            | client_flag::VIRTUAL;
+
+        log_debug!("new virtual client {} ({}x{})", _s((*c).name), sx, sy);
+        c
+    }
+}
+
+/// Dispatch a message sent to a virtual client. Any request to exit closes
+/// the socket once the message has been read, so the server sees the client
+/// go away like any other.
+unsafe fn server_client_virtual_dispatch(imsg: *mut imsg, arg: *mut c_void) {
+    unsafe {
+        let peer: *mut tmuxpeer = arg.cast();
+
+        if imsg.is_null() {
+            proc_remove_peer(peer);
+            return;
+        }
+        if let Ok(
+            msgtype::MSG_EXIT
+            | msgtype::MSG_EXITED
+            | msgtype::MSG_DETACH
+            | msgtype::MSG_DETACHKILL
+            | msgtype::MSG_SHUTDOWN,
+        ) = msgtype::try_from((*imsg).hdr.type_)
+        {
+            proc_kill_peer(peer);
+        }
+    }
+}
+
 /// Open client terminal if needed.
 pub unsafe fn server_client_open(c: *mut client, cause: *mut *mut c_char) -> i32 {
     unsafe {
@@ -305,6 +382,9 @@ pub unsafe fn server_client_open(c: *mut client, cause: *mut *mut c_char) -> i32
         if (*c).flags.intersects(client_flag::CONTROL) {
             return 0;
         }
+        if (*c).flags.intersects(client_flag::VIRTUAL) {
+            return tty_open(&raw mut (*c).tty, cause);
+        }
 
         if libc::strcmp((*c).ttyname, ttynam) == 0
             || ((libc::isatty(libc::STDIN_FILENO) != 0
@@ -1875,6 +1955,11 @@ pub unsafe fn server_client_key_callback(item: *mut cmdq_item, data: *mut c_void
                     break 'forward_key;
                 }
 
+                /* Key release events are never bound to anything. */
+                if key & KEYC_RELEASE != 0 {
+                    break 'forward_key;
+                }
+
                 /* Forward if bracket pasting. */
                 if server_client_is_bracket_pasting(c, key) != 0 {
                     break 'forward_key;
@@ -2100,6 +2185,16 @@ pub unsafe fn server_client_handle_key(c: *mut client, event: *mut key_event) ->
             return 0;
         }
 
+        /*
+         * Key release events only mean something to panes, so drop them while
+         * an overlay or the command prompt is taking keys.
+         */
+        if (*event).key & KEYC_RELEASE != 0
+            && ((*c).overlay_key.is_some() || !(*c).prompt_string.is_null())
+        {
+            return 0;
+        }
+
         /*
          * Key presses in overlay mode and the command prompt are a special
          * case. The queue might be blocked so they need to be processed
@@ -3316,7 +3411,7 @@ pub unsafe fn server_client_set_flags(c: *mut client, flags: *const c_char) {
         let mut s = copy;
         while {
             next = strsep(&raw mut s, c",".as_ptr());
-            next.is_null()
+            !next.is_null()
         } {
             not = *next == b'!' as i8;
             if not {
@@ -3381,6 +3476,9 @@ pub unsafe fn server_client_get_flags(c: *mut client) -> *const c_char {
         if (*c).flags.intersects(client_flag::CONTROL) {
             strlcat((&raw mut s).cast(), c"control-mode,".as_ptr(), sizeof_s);
         }
+        if (*c).flags.intersects(client_flag::VIRTUAL) {
+            strlcat((&raw mut s).cast(), c"virtual,".as_ptr(), sizeof_s);
+        }
         if (*c).flags.intersects(client_flag::IGNORESIZE) {
             strlcat((&raw mut s).cast(), c"ignore-size,".as_ptr(), sizeof_s);
         }
@@ -3526,13 +3624,13 @@ pub unsafe fn server_client_print(c: *mut client, parse: i32, evb: *mut evbuffer
                 if !(*c).flags.intersects(client_flag::UTF8) {
                     let sanitized = utf8_sanitize(msg);
                     if (*c).flags.intersects(client_flag::CONTROL) {
-                        control_write!(c, "{}", _s(sanitized));
+                        control_print(c, sanitized, strlen(sanitized));
                     } else {
                         file_print!(c, "{}\n", _s(sanitized));
                     }
                     free_(sanitized);
                 } else if (*c).flags.intersects(client_flag::CONTROL) {
-                    control_write!(c, "{}", _s(msg));
+                    control_print(c, msg, strlen(msg));
                 } else {
                     file_print!(c, "{}\n", _s(msg));
                 }
@@ -3576,3 +3674,43 @@ pub unsafe fn server_client_print(c: *mut client, parse: i32, evb: *mut evbuffer
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proc::PEER_BAD;
+
+    #[test]
+    fn test_server_client_set_flags() {
+        unsafe {
+            // A bad peer drops the MSG_FLAGS message sent to the client.
+            let mut peer: tmuxpeer = zeroed();
+            peer.flags = PEER_BAD;
+            let mut c: client = zeroed();
+            c.peer = &raw mut peer;
+
+            server_client_set_flags(&raw mut c, c"ignore-size,active-pane,bogus".as_ptr());
+            assert!(c.flags == client_flag::IGNORESIZE | client_flag::ACTIVEPANE);
+            server_client_set_flags(&raw mut c, c"!ignore-size,read-only".as_ptr());
+            assert!(c.flags == client_flag::ACTIVEPANE | client_flag::READONLY);
+
+            // A read-only client cannot make itself writable.
+            server_client_set_flags(&raw mut c, c"!read-only,!active-pane".as_ptr());
+            assert!(c.flags == client_flag::READONLY);
+
+            // Control mode flags are only accepted from control clients.
+            c.flags = client_flag::empty();
+            server_client_set_flags(&raw mut c, c"wait-exit,pause-after=2".as_ptr());
+            assert!(c.flags == client_flag::empty());
+            c.flags = client_flag::CONTROL;
+            server_client_set_flags(&raw mut c, c"wait-exit,pause-after=2".as_ptr());
+            assert!(
+                c.flags
+                    == client_flag::CONTROL
+                        | client_flag::CONTROL_WAITEXIT
+                        | client_flag::CONTROL_PAUSEAFTER
+            );
+            assert_eq!(c.pause_age, 2000);
+        }
+    }
+}
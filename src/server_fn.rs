@@ -356,6 +356,7 @@ pub unsafe fn server_destroy_pane(wp: *mut window_pane, notify: i32) {
                     (*wp).flags |= window_pane_flags::PANE_STATUSDRAWN;
 
                     gettimeofday(&raw mut (*wp).dead_time, null_mut());
+                    window_pane_add_event(wp, window_pane_event_type::Exit, 0);
                     if notify != 0 {
                         notify_pane(c"pane-died", wp);
                     }
@@ -308,6 +308,7 @@ pub unsafe fn spawn_pane(sc: *mut spawn_context, cause: *mut *mut c_char) -> *mu
                 }
                 window_pane_reset_mode_all((*sc).wp0);
                 screen_reinit(&raw mut (*(*sc).wp0).base);
+                window_pane_reflow_start((*sc).wp0);
                 input_free((*(*sc).wp0).ictx);
                 (*(*sc).wp0).ictx = null_mut();
                 new_wp = (*sc).wp0;
@@ -324,6 +325,8 @@ pub unsafe fn spawn_pane(sc: *mut spawn_context, cause: *mut *mut c_char) -> *mu
                     layout_assign_pane((*sc).lc, new_wp, 0);
                 }
             }
+            (*(*new_wp).base.grid).slimit =
+                options_get_number_((*s).options, c"history-spill-limit") as u32;
 
             /*
              * Now we have a pane with nothing running in it ready for the new
@@ -457,7 +460,9 @@ pub unsafe fn spawn_pane(sc: *mut spawn_context, cause: *mut *mut c_char) -> *mu
                      * Move the child process into a new cgroup for systemd-oomd
                      * isolation.
                      */
-                    if (systemd_move_pid_to_new_cgroup((*new_wp).pid, cause) < 0) {
+                    if crate::compat::systemd::systemd_move_pid_to_new_cgroup((*new_wp).pid, cause)
+                        < 0
+                    {
                         log_debug!(
                             "{}: moving pane to new cgroup failed: {}",
                             _s(__func__),
@@ -14,6 +14,7 @@
 
 use super::*;
 
+use crate::cmd_::cmd_find;
 use crate::compat::{
     fgetln,
     queue::{tailq_init, tailq_remove},
@@ -21,6 +22,8 @@ use crate::compat::{
 };
 use libc::strncmp;
 
+use std::ffi::CString;
+
 #[repr(C)]
 struct status_prompt_menu {
     c: *mut client,
@@ -919,6 +922,13 @@ pub unsafe fn status_prompt_redraw(c: *mut client) -> i32 {
             (*c).prompt_cursor =
                 (start as isize + (*c).prompt_index as isize - offset as isize) as i32;
 
+            let (classes, error) = if (*c).prompt_flags & PROMPT_HIGHLIGHT != 0 {
+                status_prompt_highlight(c)
+            } else {
+                (Vec::new(), null_mut())
+            };
+            let mut hgc: grid_cell = zeroed();
+
             let mut width = 0;
             let mut i = 0;
             while (*(*c).prompt_buffer.add(i)).size != 0 {
@@ -936,8 +946,22 @@ pub unsafe fn status_prompt_redraw(c: *mut client) -> i32 {
                 }
 
                 if i != (*c).prompt_index {
-                    utf8_copy(&raw mut gc.data, (*c).prompt_buffer.add(i));
-                    screen_write_cell(&raw mut ctx, &raw const gc);
+                    memcpy__(&raw mut hgc, &raw const gc);
+                    if let Some(class) = classes.get(i) {
+                        if !class.style.is_null() {
+                            style_add(&raw mut hgc, (*s).options, class.style, null_mut());
+                        }
+                        if class.bad {
+                            style_add(
+                                &raw mut hgc,
+                                (*s).options,
+                                c"prompt-error-style".as_ptr(),
+                                null_mut(),
+                            );
+                        }
+                    }
+                    utf8_copy(&raw mut hgc.data, (*c).prompt_buffer.add(i));
+                    screen_write_cell(&raw mut ctx, &raw const hgc);
                 } else {
                     utf8_copy(&raw mut cursorgc.data, (*c).prompt_buffer.add(i));
                     screen_write_cell(&raw mut ctx, &raw const cursorgc);
@@ -945,7 +969,65 @@ pub unsafe fn status_prompt_redraw(c: *mut client) -> i32 {
                 i += 1;
             }
             if (*(*sl).active).cx < screen_size_x((*sl).active) && (*c).prompt_index >= i {
-                screen_write_putc(&raw mut ctx, &raw const cursorgc, b' ');
+                let suggestion = status_prompt_suggestion(c);
+                if suggestion.is_null() {
+                    screen_write_putc(&raw mut ctx, &raw const cursorgc, b' ');
+                } else {
+                    let mut sgc: grid_cell = zeroed();
+                    memcpy__(&raw mut sgc, &raw const gc);
+                    style_add(
+                        &raw mut sgc,
+                        (*s).options,
+                        c"prompt-suggestion-style".as_ptr(),
+                        null_mut(),
+                    );
+
+                    let ud = utf8_fromcstr(suggestion);
+                    let mut j = 0;
+                    while (*ud.add(j)).size != 0 {
+                        let cx = (*(*sl).active).cx;
+                        if cx + (*ud.add(j)).width as u32 > screen_size_x((*sl).active) {
+                            break;
+                        }
+                        if j == 0 {
+                            utf8_copy(&raw mut cursorgc.data, ud);
+                            screen_write_cell(&raw mut ctx, &raw const cursorgc);
+                        } else {
+                            utf8_copy(&raw mut sgc.data, ud.add(j));
+                            screen_write_cell(&raw mut ctx, &raw const sgc);
+                        }
+                        j += 1;
+                    }
+                    free_(ud);
+                }
+            }
+            if !error.is_null() {
+                // Show the error on the right, cut short if it does not fit.
+                let cx = (*(*sl).active).cx;
+                let width = utf8_cstrwidth(error).min((*c).tty.sx.saturating_sub(cx + 2));
+                if width != 0 {
+                    memcpy__(&raw mut hgc, &raw const gc);
+                    style_add(
+                        &raw mut hgc,
+                        (*s).options,
+                        c"prompt-error-style".as_ptr(),
+                        null_mut(),
+                    );
+                    screen_write_cursormove(
+                        &raw mut ctx,
+                        ((*c).tty.sx - width - 1) as i32,
+                        promptline as i32,
+                        0,
+                    );
+                    screen_write_nputs!(
+                        &raw mut ctx,
+                        width as isize,
+                        &raw mut hgc,
+                        "{}",
+                        _s(error)
+                    );
+                }
+                free_(error);
             }
         }
         // finished:
@@ -960,6 +1042,266 @@ pub unsafe fn status_prompt_redraw(c: *mut client) -> i32 {
     }
 }
 
+/// Find the most recent history line that starts with the prompt and return
+/// the rest of it as a suggestion, if the cursor is at the end.
+unsafe fn status_prompt_suggestion(c: *mut client) -> *const c_char {
+    unsafe {
+        if (*c).prompt_flags & (PROMPT_SINGLE | PROMPT_NUMERIC) != 0 {
+            return null();
+        }
+        let size = utf8_strlen((*c).prompt_buffer);
+        if size == 0 || (*c).prompt_index != size {
+            return null();
+        }
+
+        let type_ = (*c).prompt_type as usize;
+        if type_ >= PROMPT_NTYPES as usize {
+            return null();
+        }
+        let s = utf8_tocstr((*c).prompt_buffer);
+        let len = strlen(s);
+
+        let mut found = null();
+        for i in (0..status_prompt_hsize[type_] as usize).rev() {
+            let line = *status_prompt_hlist[type_].add(i);
+            if strlen(line) > len && strncmp(line, s, len) == 0 {
+                found = line.add(len).cast_const();
+                break;
+            }
+        }
+        free_(s);
+        found
+    }
+}
+
+/// Style of one character of a highlighted command prompt.
+#[derive(Copy, Clone)]
+struct status_prompt_class {
+    style: *const c_char,
+    bad: bool,
+}
+
+/// Mark the quoted strings and formats inside a word.
+fn status_prompt_highlight_word(buf: &[u8], classes: &mut [status_prompt_class]) {
+    let mut quote = 0;
+    let mut i = 0;
+    while i < buf.len() {
+        let ch = buf[i];
+        if ch == b'\\' && quote != b'\'' {
+            if quote != 0 {
+                classes[i].style = c"prompt-string-style".as_ptr();
+                if i + 1 < buf.len() {
+                    classes[i + 1].style = c"prompt-string-style".as_ptr();
+                }
+            }
+            i += 2;
+            continue;
+        }
+        if ch == b'#' && i + 1 < buf.len() && buf[i + 1] == b'{' && quote != b'\'' {
+            let mut depth = 0;
+            let start = i;
+            while i < buf.len() {
+                if buf[i] == b'{' {
+                    depth += 1;
+                } else if buf[i] == b'}' {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                i += 1;
+            }
+            let end = (i + 1).min(buf.len());
+            for class in &mut classes[start..end] {
+                class.style = c"prompt-format-style".as_ptr();
+            }
+            i = end;
+            continue;
+        }
+        if quote == 0 && (ch == b'\'' || ch == b'"') {
+            quote = ch;
+        } else if quote != 0 && ch == quote {
+            classes[i].style = c"prompt-string-style".as_ptr();
+            quote = 0;
+            i += 1;
+            continue;
+        }
+        if quote != 0 {
+            classes[i].style = c"prompt-string-style".as_ptr();
+        }
+        i += 1;
+    }
+}
+
+/// Check the flags in a word against a command's template. Returns false if
+/// any are unknown and sets argument if the last one takes an argument.
+unsafe fn status_prompt_highlight_flags(
+    entry: *mut cmd_entry,
+    word: &[u8],
+    argument: &mut bool,
+) -> bool {
+    unsafe {
+        let template = (*entry).args.template;
+        if template.is_null() {
+            return false;
+        }
+        for (i, &ch) in word.iter().enumerate().skip(1) {
+            if ch == b':' {
+                return false;
+            }
+            let found = libc::strchr(template, ch as i32);
+            if found.is_null() {
+                return false;
+            }
+            if *found.add(1) == b':' as c_char {
+                *argument = i == word.len() - 1;
+                break;
+            }
+        }
+        true
+    }
+}
+
+/// Work out the style of each character in a command prompt and parse it,
+/// returning the error if it does not parse.
+unsafe fn status_prompt_highlight(c: *mut client) -> (Vec<status_prompt_class>, *mut c_char) {
+    unsafe {
+        let s = utf8_tocstr((*c).prompt_buffer);
+        let buf = CStr::from_ptr(s).to_bytes();
+
+        let mut classes = vec![
+            status_prompt_class {
+                style: null(),
+                bad: false,
+            };
+            buf.len()
+        ];
+        let mut command = true;
+        let mut entry: *mut cmd_entry = null_mut();
+        let mut flags = false;
+        let mut argument = false;
+        for token in cmd_parse_tokens(buf) {
+            let range = token.start..token.end;
+            match token.type_ {
+                cmd_parse_token_type::Newline => command = true,
+                cmd_parse_token_type::Separator => {
+                    command = buf[token.start] != b'}';
+                    entry = null_mut();
+                    flags = false;
+                }
+                cmd_parse_token_type::Condition => {
+                    for class in &mut classes[range] {
+                        class.style = c"prompt-command-style".as_ptr();
+                    }
+                }
+                cmd_parse_token_type::Format => {
+                    for class in &mut classes[range] {
+                        class.style = c"prompt-format-style".as_ptr();
+                    }
+                }
+                cmd_parse_token_type::Error => {
+                    for class in &mut classes[range] {
+                        class.bad = true;
+                    }
+                }
+                cmd_parse_token_type::Assignment => {
+                    status_prompt_highlight_word(&buf[range.clone()], &mut classes[range]);
+                }
+                cmd_parse_token_type::Word => {
+                    let value = CString::new(token.value.unwrap_or_default()).unwrap_or_default();
+                    let mut style = null();
+                    let mut bad = false;
+                    if command {
+                        command = false;
+                        flags = true;
+                        argument = false;
+                        style = c"prompt-command-style".as_ptr();
+                        match cmd_find(value.as_ptr()) {
+                            Ok(found) => entry = found,
+                            Err(cause) => {
+                                free_(cause);
+                                entry = null_mut();
+                                let alias = cmd_get_alias(value.as_ptr());
+                                bad = alias.is_null();
+                                free_(alias);
+                            }
+                        }
+                    } else if argument {
+                        argument = false;
+                    } else if flags
+                        && buf[token.start] == b'-'
+                        && value.as_bytes().len() > 1
+                        && value.as_bytes()[0] == b'-'
+                    {
+                        style = c"prompt-flag-style".as_ptr();
+                        if value.as_bytes() == b"--" {
+                            flags = false;
+                        } else if !entry.is_null() {
+                            bad = !status_prompt_highlight_flags(
+                                entry,
+                                value.as_bytes(),
+                                &mut argument,
+                            );
+                        }
+                    } else {
+                        flags = false;
+                    }
+                    for class in &mut classes[range.clone()] {
+                        class.style = style;
+                        class.bad = bad;
+                    }
+                    status_prompt_highlight_word(&buf[range.clone()], &mut classes[range]);
+                }
+            }
+        }
+
+        // Parse only, so assignments and definitions are not run as they are
+        // typed.
+        let mut error = null_mut();
+        if !buf.is_empty() {
+            let mut pi: cmd_parse_input = zeroed();
+            pi.flags = cmd_parse_input_flags::CMD_PARSE_PARSEONLY.into();
+            match cmd_parse_from_string(&String::from_utf8_lossy(buf), Some(&pi)) {
+                Ok(cmdlist) => cmd_list_free(cmdlist),
+                Err(cause) if cause.is_null() => error = xstrdup_(c"syntax error").as_ptr(),
+                Err(cause) => error = cause,
+            }
+        }
+
+        // Convert from bytes to characters.
+        let mut chars = Vec::new();
+        let mut off = 0;
+        let mut ud = (*c).prompt_buffer;
+        while (*ud).size != 0 && off < classes.len() {
+            chars.push(classes[off]);
+            off += (*ud).size as usize;
+            ud = ud.add(1);
+        }
+        free_(s);
+        (chars, error)
+    }
+}
+
+/// Accept the suggestion, if there is one.
+unsafe fn status_prompt_accept_suggestion(c: *mut client) -> bool {
+    unsafe {
+        let suggestion = status_prompt_suggestion(c);
+        if suggestion.is_null() {
+            return false;
+        }
+
+        let s = utf8_tocstr((*c).prompt_buffer);
+        let line = format_nul!("{}{}", _s(s), _s(suggestion));
+        free_(s);
+
+        free_((*c).prompt_buffer);
+        (*c).prompt_buffer = utf8_fromcstr(line);
+        (*c).prompt_index = utf8_strlen((*c).prompt_buffer);
+        free_(line);
+        true
+    }
+}
+
 /// Is this a separator?
 unsafe fn status_prompt_in_list(ws: *const c_char, ud: *const utf8_data) -> i32 {
     unsafe {
@@ -1254,7 +1596,9 @@ unsafe fn status_prompt_replace_complete(c: *mut client, mut s: *const c_char) -
             last = last.add(1);
         }
         if last < first {
-            return 0;
+            /* Between words, so complete an empty word at the cursor. */
+            first = (*c).prompt_buffer.add((*c).prompt_index);
+            last = first;
         }
         if s.is_null() {
             used = 0;
@@ -1512,6 +1856,8 @@ pub unsafe fn status_prompt_key(c: *mut client, mut key: key_code) -> i32 {
                     code::KEYC_RIGHT | code::F_CTRL => {
                         if (*c).prompt_index < size {
                             (*c).prompt_index += 1;
+                        } else if status_prompt_accept_suggestion(c) {
+                            break 'changed;
                         }
                     }
                     code::KEYC_HOME | code::A_CTRL => {
@@ -1522,6 +1868,8 @@ pub unsafe fn status_prompt_key(c: *mut client, mut key: key_code) -> i32 {
                     code::KEYC_END | code::E_CTRL => {
                         if (*c).prompt_index != size {
                             (*c).prompt_index = size;
+                        } else if status_prompt_accept_suggestion(c) {
+                            break 'changed;
                         }
                     }
                     code::TAB => {
@@ -1924,6 +2272,39 @@ unsafe fn status_prompt_add_list(list: *mut *mut *mut c_char, size: *mut u32, s:
     }
 }
 
+/// Add the candidates that fuzzily match a word to the completion list, best
+/// match first.
+unsafe fn status_prompt_add_ranked(
+    list: *mut *mut *mut c_char,
+    size: *mut u32,
+    s: *const c_char,
+    candidates: &[String],
+) {
+    unsafe {
+        let pattern: Vec<char> = String::from_utf8_lossy(CStr::from_ptr(s).to_bytes())
+            .chars()
+            .collect();
+
+        let mut ranked: Vec<(i32, &String)> = candidates
+            .iter()
+            .filter_map(|candidate| {
+                let text: Vec<char> = candidate.chars().collect();
+                window_fuzzy_score(&pattern, &text).map(|(score, _)| (score, candidate))
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then(a.1.len().cmp(&b.1.len()))
+                .then(a.1.cmp(b.1))
+        });
+
+        for (_, candidate) in ranked {
+            let candidate = CString::new(candidate.as_str()).unwrap_or_default();
+            status_prompt_add_list(list, size, candidate.as_ptr());
+        }
+    }
+}
+
 /// Build completion list.
 unsafe fn status_prompt_complete_list(
     size: *mut u32,
@@ -1931,73 +2312,192 @@ unsafe fn status_prompt_complete_list(
     at_start: i32,
 ) -> *mut *mut c_char {
     unsafe {
-        let mut tmp: *mut c_char = null_mut();
         let mut list: *mut *mut c_char = null_mut();
-        let slen = strlen(s);
-
-        let layouts: [*const c_char; 8] = [
-            c"even-horizontal".as_ptr(),
-            c"even-vertical".as_ptr(),
-            c"main-horizontal".as_ptr(),
-            c"main-horizontal-mirrored".as_ptr(),
-            c"main-vertical".as_ptr(),
-            c"main-vertical-mirrored".as_ptr(),
-            c"tiled".as_ptr(),
-            null_mut(),
+        let mut candidates: Vec<String> = Vec::new();
+
+        let layouts = [
+            "even-horizontal",
+            "even-vertical",
+            "main-horizontal",
+            "main-horizontal-mirrored",
+            "main-vertical",
+            "main-vertical-mirrored",
+            "tiled",
         ];
 
         *size = 0;
-        let mut cmdent: *const *const cmd_entry = (&raw const cmd_table) as *const *const cmd_entry;
-        while !(*cmdent).is_null() {
-            if strncmp((*(*cmdent)).name, s, slen) == 0 {
-                status_prompt_add_list(&raw mut list, size, (*(*cmdent)).name);
-            }
-            if !(*(*cmdent)).alias.is_null() && strncmp((*(*cmdent)).alias, s, slen) == 0 {
-                status_prompt_add_list(&raw mut list, size, (*(*cmdent)).alias);
+        for entry in cmd_entries() {
+            candidates.push(cstr_to_str((*entry).name).to_string());
+            if !(*entry).alias.is_null() {
+                candidates.push(cstr_to_str((*entry).alias).to_string());
             }
-            cmdent = cmdent.add(1);
         }
         let o = options_get_only(global_options, c"command-alias".as_ptr());
         if !o.is_null() {
             let mut a = options_array_first(o);
             while !a.is_null() {
-                'next: {
-                    let value = (*options_array_item_value(a)).string;
+                let value = CStr::from_ptr((*options_array_item_value(a)).string).to_bytes();
+                if let Some(end) = value.iter().position(|&ch| ch == b'=') {
+                    candidates.push(String::from_utf8_lossy(&value[..end]).into_owned());
+                }
+                a = options_array_next(a);
+            }
+        }
+        if at_start == 0 {
+            let mut oe = (&raw mut options_table) as *mut options_table_entry;
+            while !(*oe).name.is_null() {
+                candidates.push(cstr_to_str((*oe).name).to_string());
+                oe = oe.add(1);
+            }
+            candidates.extend(layouts.iter().map(|layout| layout.to_string()));
+        }
+        status_prompt_add_ranked(&raw mut list, size, s, &candidates);
+        list
+    }
+}
+
+/// Get the words of the command being typed before an offset in the prompt.
+unsafe fn status_prompt_complete_words(c: *mut client, offset: u32) -> Vec<String> {
+    unsafe {
+        let mut text: Vec<u8> = Vec::new();
+        for i in 0..offset as usize {
+            let ud = &*(*c).prompt_buffer.add(i);
+            text.extend_from_slice(&ud.data[..ud.size as usize]);
+        }
+        let text = String::from_utf8_lossy(&text);
+        let command = text.rsplit(';').next().unwrap_or_default();
+        command.split_whitespace().map(str::to_string).collect()
+    }
+}
+
+/// Build a completion list from the command being typed: its flags, or the
+/// values an option can take. Returns NULL if there is nothing to complete.
+unsafe fn status_prompt_complete_arguments(
+    size: *mut u32,
+    words: &[String],
+    s: *const c_char,
+) -> *mut *mut c_char {
+    unsafe {
+        let mut list: *mut *mut c_char = null_mut();
+        let mut candidates: Vec<String> = Vec::new();
+        let word = CStr::from_ptr(s).to_bytes();
 
-                    let cp = libc::strchr(value, b'=' as i32);
-                    if cp.is_null() {
-                        break 'next;
+        *size = 0;
+        let Some(name) = words.first() else {
+            return null_mut();
+        };
+        let name = CString::new(name.as_str()).unwrap_or_default();
+        let entry = match cmd_find(name.as_ptr()) {
+            Ok(entry) => entry,
+            Err(cause) => {
+                free_(cause);
+                return null_mut();
+            }
+        };
+
+        if word.first() == Some(&b'-') {
+            /* Targets are completed separately. */
+            if word.starts_with(b"-t") || word.starts_with(b"-s") {
+                return null_mut();
+            }
+            let template = CStr::from_ptr((*entry).args.template).to_bytes();
+            let takes_value = |flag: u8| {
+                template
+                    .iter()
+                    .position(|&ch| ch == flag)
+                    .is_some_and(|i| template.get(i + 1) == Some(&b':'))
+            };
+
+            /* Leave out flags that have already been given. */
+            let mut used = Vec::new();
+            for prev in words.iter().skip(1).map(String::as_bytes) {
+                if prev.len() < 2 || prev[0] != b'-' || prev == b"--" {
+                    continue;
+                }
+                for &flag in &prev[1..] {
+                    used.push(flag);
+                    if takes_value(flag) {
+                        break;
                     }
-                    let valuelen = cp.offset_from_unsigned(value);
-                    if slen > valuelen || strncmp(value, s, slen) != 0 {
-                        break 'next;
+                }
+            }
+            for &flag in template {
+                if flag != b':' && !used.contains(&flag) {
+                    candidates.push(format!("-{}", flag as char));
+                }
+            }
+        } else {
+            if cstr_to_str((*entry).name) != "set-option"
+                && cstr_to_str((*entry).name) != "set-window-option"
+            {
+                return null_mut();
+            }
+            let Some(option) = words.last() else {
+                return null_mut();
+            };
+
+            let mut oe = (&raw mut options_table) as *mut options_table_entry;
+            while !(*oe).name.is_null() && cstr_to_str((*oe).name) != option {
+                oe = oe.add(1);
+            }
+            if (*oe).name.is_null() {
+                return null_mut();
+            }
+            match (*oe).type_ {
+                options_table_type::OPTIONS_TABLE_FLAG => {
+                    candidates.push("on".to_string());
+                    candidates.push("off".to_string());
+                }
+                options_table_type::OPTIONS_TABLE_CHOICE => {
+                    let mut choice = (*oe).choices;
+                    while !(*choice).is_null() {
+                        candidates.push(cstr_to_str(*choice).to_string());
+                        choice = choice.add(1);
                     }
+                }
+                _ => return null_mut(),
+            }
+        }
+        status_prompt_add_ranked(&raw mut list, size, s, &candidates);
+        list
+    }
+}
 
-                    tmp = format_nul!("{:.*}", valuelen, _s(value));
-                    status_prompt_add_list(&raw mut list, size, tmp);
-                    free_(tmp);
-                } // next:
-                a = options_array_next(a);
+/// Choose what to replace a word with from a ranked list. Matches starting
+/// with the word come first: the only one, or the prefix they share if it is
+/// longer. Otherwise the only fuzzy match, if there is one.
+unsafe fn status_prompt_complete_ranked(
+    list: *mut *mut c_char,
+    size: u32,
+    word: *const c_char,
+) -> *mut c_char {
+    unsafe {
+        let len = strlen(word);
+
+        let mut prefixed: Vec<*mut c_char> = Vec::new();
+        for i in 0..size as usize {
+            if strncmp(*list.add(i), word, len) == 0 {
+                prefixed.push(*list.add(i));
             }
         }
-        if at_start != 0 {
-            return list;
+        let (list, size) = if prefixed.is_empty() {
+            (list, size)
+        } else {
+            (prefixed.as_mut_ptr(), prefixed.len() as u32)
+        };
+
+        if size == 0 {
+            return null_mut();
         }
-        let mut oe = (&raw mut options_table) as *mut options_table_entry;
-        while !(*oe).name.is_null() {
-            if strncmp((*oe).name, s, slen) == 0 {
-                status_prompt_add_list(&raw mut list, size, (*oe).name);
-            }
-            oe = oe.add(1);
+        if size == 1 {
+            return format_nul!("{} ", _s(*list));
         }
-        let mut layout = (&raw const layouts) as *const *const i8;
-        while !(*layout).is_null() {
-            if strncmp(*layout, s, slen) == 0 {
-                status_prompt_add_list(&raw mut list, size, *layout);
-            }
-            layout = layout.add(1);
+        let out = status_prompt_complete_prefix(list, size);
+        if strlen(out) <= len || strncmp(out, word, len) != 0 {
+            free_(out);
+            return null_mut();
         }
-        list
+        out
     }
 }
 
@@ -2103,14 +2603,21 @@ unsafe fn status_prompt_complete_list_menu(
         if height > size {
             height = size;
         }
-        (*spm).start = size - height;
+        (*spm).start = 0;
 
         let menu = menu_create(c"".as_ptr());
-        for i in (*spm).start..size {
-            item.name = SyncCharPtr::from_ptr(*list.add(i as usize));
+        for i in (*spm).start..height {
+            /* A leading - would show the item as disabled. */
+            let name = if **list.add(i as usize) == b'-' as c_char {
+                format_nul!("#[default]{}", _s(*list.add(i as usize)))
+            } else {
+                xstrdup(*list.add(i as usize)).as_ptr()
+            };
+            item.name = SyncCharPtr::from_ptr(name);
             item.key = b'0' as u64 + (i as i64 - (*spm).start as i64) as u64;
             item.command = SyncCharPtr::null();
             menu_add_item(menu, &raw mut item, null_mut(), c, null_mut());
+            free_(name);
         }
 
         let py = if options_get_number_((*(*c).session).options, c"status-position") == 0 {
@@ -2350,7 +2857,16 @@ unsafe fn status_prompt_complete(
         let mut out: *mut c_char = null_mut();
         let mut size: u32 = 0;
 
-        if *word == b'\0' as i8
+        let mut ranked = false;
+
+        if (*c).prompt_type == prompt_type::PROMPT_TYPE_COMMAND {
+            let words = status_prompt_complete_words(c, offset);
+            list = status_prompt_complete_arguments(&raw mut size, &words, word);
+            ranked = !list.is_null();
+        }
+
+        if !ranked
+            && *word == b'\0' as i8
             && (*c).prompt_type != prompt_type::PROMPT_TYPE_TARGET
             && (*c).prompt_type != prompt_type::PROMPT_TYPE_WINDOW_TARGET
         {
@@ -2358,19 +2874,18 @@ unsafe fn status_prompt_complete(
         }
 
         'found: {
+            if ranked {
+                out = status_prompt_complete_ranked(list, size, word);
+                break 'found;
+            }
             if (*c).prompt_type != prompt_type::PROMPT_TYPE_TARGET
                 && (*c).prompt_type != prompt_type::PROMPT_TYPE_WINDOW_TARGET
                 && strncmp(word, c"-t".as_ptr(), 2) != 0
                 && strncmp(word, c"-s".as_ptr(), 2) != 0
             {
                 list = status_prompt_complete_list(&raw mut size, word, (offset == 0) as i32);
-                out = if size == 0 {
-                    null_mut()
-                } else if size == 1 {
-                    format_nul!("{} ", _s(*list))
-                } else {
-                    status_prompt_complete_prefix(list, size)
-                };
+                out = status_prompt_complete_ranked(list, size, word);
+                ranked = true;
                 break 'found;
             }
 
@@ -2417,7 +2932,7 @@ unsafe fn status_prompt_complete(
                 }
             }
         } // found:
-        if size != 0 {
+        if size != 0 && !ranked {
             libc::qsort(
                 list.cast(),
                 size as usize,
@@ -2539,3 +3054,172 @@ mod code {
     pub const LF: u64 = '\n' as u64;
     pub const ESC: u64 = '\x1b' as u64;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn take_list(list: *mut *mut c_char, size: u32) -> Vec<String> {
+        unsafe {
+            let mut out = Vec::new();
+            for i in 0..size as usize {
+                out.push(cstr_to_str(*list.add(i)).to_string());
+                free_(*list.add(i));
+            }
+            free_(list);
+            out
+        }
+    }
+
+    #[test]
+    fn test_status_prompt_add_ranked() {
+        unsafe {
+            let mut list = null_mut();
+            let mut size = 0;
+            let candidates = [
+                "answer",
+                "split-window",
+                "kill-server",
+                "sw",
+                "swap-window",
+                "sw",
+            ]
+            .map(String::from);
+            status_prompt_add_ranked(&raw mut list, &raw mut size, c"sw".as_ptr(), &candidates);
+            assert_eq!(
+                take_list(list, size),
+                ["sw", "swap-window", "split-window", "answer"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_status_prompt_complete_ranked() {
+        unsafe {
+            let mut list = [
+                c"swap-window".as_ptr().cast_mut(),
+                c"select-window".as_ptr().cast_mut(),
+                c"select-pane".as_ptr().cast_mut(),
+            ];
+            let complete = |list: &mut [*mut c_char], word: &CStr| {
+                let out = status_prompt_complete_ranked(
+                    list.as_mut_ptr(),
+                    list.len() as u32,
+                    word.as_ptr(),
+                );
+                if out.is_null() {
+                    return None;
+                }
+                let s = cstr_to_str(out).to_string();
+                free_(out);
+                Some(s)
+            };
+
+            // Prefix matches win over fuzzy ones and share their prefix.
+            assert_eq!(complete(&mut list, c"sel").as_deref(), Some("select-"));
+            assert_eq!(complete(&mut list, c"swa").as_deref(), Some("swap-window "));
+            assert_eq!(complete(&mut list, c"select-").as_deref(), None);
+            assert_eq!(
+                complete(&mut list[..1], c"spw").as_deref(),
+                Some("swap-window ")
+            );
+            assert_eq!(complete(&mut list[..0], c"x").as_deref(), None);
+        }
+    }
+
+    #[test]
+    fn test_status_prompt_complete_arguments() {
+        unsafe {
+            let complete = |words: &[&str], word: &CStr| {
+                let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+                let mut size = 0;
+                let list = status_prompt_complete_arguments(&raw mut size, &words, word.as_ptr());
+                if list.is_null() {
+                    return None;
+                }
+                Some(take_list(list, size))
+            };
+
+            // Flags already given are left out, as is the value of -l.
+            assert_eq!(
+                complete(&["split-window", "-dl", "10", "-h"], c"-").unwrap(),
+                [
+                    "-F", "-H", "-I", "-P", "-Z", "-b", "-c", "-e", "-f", "-p", "-t", "-v"
+                ]
+            );
+            assert_eq!(complete(&["splitw"], c"-p").unwrap(), ["-P", "-p"]);
+            assert!(complete(&["split-window"], c"-t").is_none());
+
+            assert_eq!(
+                complete(&["set-option", "-g", "status-position"], c"").unwrap(),
+                ["top", "bottom"]
+            );
+            assert_eq!(complete(&["set", "mouse"], c"o").unwrap(), ["on", "off"]);
+            assert!(complete(&["set-option", "status-style"], c"").is_none());
+            assert!(complete(&["display-message"], c"x").is_none());
+            assert!(complete(&["no-such-command"], c"-").is_none());
+            assert!(complete(&[], c"").is_none());
+        }
+    }
+
+    #[test]
+    fn test_status_prompt_complete_words() {
+        unsafe {
+            let mut c: client = zeroed();
+            c.prompt_buffer = utf8_fromcstr(c"new -d ; splitw  -h -l".as_ptr());
+            assert_eq!(
+                status_prompt_complete_words(&raw mut c, 22),
+                ["splitw", "-h", "-l"]
+            );
+            assert_eq!(
+                status_prompt_complete_words(&raw mut c, 19),
+                ["splitw", "-h"]
+            );
+            assert_eq!(status_prompt_complete_words(&raw mut c, 6), ["new", "-d"]);
+            assert!(status_prompt_complete_words(&raw mut c, 8).is_empty());
+            free_(c.prompt_buffer);
+        }
+    }
+
+    #[test]
+    fn test_status_prompt_suggestion() {
+        unsafe {
+            let mut c: client = zeroed();
+            c.prompt_type = prompt_type::PROMPT_TYPE_COMMAND;
+            c.prompt_buffer = utf8_fromcstr(c"new-w".as_ptr());
+            c.prompt_index = 5;
+
+            let mut history = [
+                xstrdup(c"new-window -d".as_ptr()).as_ptr(),
+                xstrdup(c"new-window".as_ptr()).as_ptr(),
+                xstrdup(c"new-w".as_ptr()).as_ptr(),
+            ];
+            let type_ = prompt_type::PROMPT_TYPE_COMMAND as usize;
+            status_prompt_hlist[type_] = history.as_mut_ptr();
+            status_prompt_hsize[type_] = history.len() as u32;
+
+            // The latest longer line wins and is only offered at the end.
+            assert_eq!(cstr_to_str(status_prompt_suggestion(&raw mut c)), "indow");
+            c.prompt_index = 4;
+            assert!(status_prompt_suggestion(&raw mut c).is_null());
+            c.prompt_index = 5;
+            c.prompt_flags = PROMPT_NUMERIC;
+            assert!(status_prompt_suggestion(&raw mut c).is_null());
+            c.prompt_flags = 0;
+
+            assert!(status_prompt_accept_suggestion(&raw mut c));
+            let s = utf8_tocstr(c.prompt_buffer);
+            assert_eq!(cstr_to_str(s), "new-window");
+            assert_eq!(c.prompt_index, 10);
+            free_(s);
+            assert_eq!(cstr_to_str(status_prompt_suggestion(&raw mut c)), " -d");
+
+            status_prompt_hlist[type_] = null_mut();
+            status_prompt_hsize[type_] = 0;
+            for line in history {
+                free_(line);
+            }
+            free_(c.prompt_buffer);
+        }
+    }
+}
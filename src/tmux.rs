@@ -48,7 +48,7 @@ pub static mut shell_command: *mut c_char = null_mut();
 
 pub fn usage() -> ! {
     unsafe {
-        libc::fprintf(stderr, c"usage: %s [-2CDlNuVv] [-c shell-command] [-f file] [-L socket-name]\n            [-S socket-path] [-T features] [command [flags]]\n".as_ptr(), getprogname());
+        libc::fprintf(stderr, c"usage: %s [-2CDJlNuVv] [-c shell-command] [-f file] [-L socket-name]\n            [-S socket-path] [-T features] [command [flags]]\n".as_ptr(), getprogname());
         std::process::exit(1)
     }
 }
@@ -429,7 +429,7 @@ pub unsafe fn main(mut argc: i32, mut argv: *mut *mut c_char, env: *mut *mut c_c
 
         let mut opt;
         while {
-            opt = getopt(argc, argv, c"2c:CDdf:lL:NqS:T:uUvV".as_ptr());
+            opt = getopt(argc, argv, c"2c:CDdf:JlL:NqS:T:uUvV".as_ptr());
             opt != -1
         } {
             match opt as u8 {
@@ -461,6 +461,7 @@ pub unsafe fn main(mut argc: i32, mut argv: *mut *mut c_char, env: *mut *mut c_c
                     println!("tmux {}", getversion());
                     std::process::exit(0);
                 }
+                b'J' => flags |= client_flag::CONTROL_JSON,
                 b'l' => flags |= client_flag::LOGIN,
                 b'L' => {
                     free(label as _);
@@ -573,6 +574,11 @@ pub unsafe fn main(mut argc: i32, mut argv: *mut *mut c_char, env: *mut *mut c_c
             options_set_number(global_w_options, c"mode-keys".as_ptr(), keys as _);
         }
 
+        // Configuration files can be checked without a server.
+        if argc != 0 && streq_(*argv, "check-config") {
+            std::process::exit(cfg_check(argc, argv));
+        }
+
         // If socket is specified on the command-line with -S or -L, it is
         // used. Otherwise, $TMUX is checked and if that fails "default" is
         // used.
@@ -88,6 +88,23 @@ pub unsafe fn tty_init(tty: *mut tty, c: *mut client) -> i32 {
     }
 }
 
+/// Set up the tty of a virtual client. Nothing is read from or written to a
+/// file descriptor; output is parsed into a screen when the tty is opened.
+pub unsafe fn tty_init_virtual(tty: *mut tty, c: *mut client, sx: u32, sy: u32) {
+    unsafe {
+        memset0(tty);
+        (*tty).client = c;
+
+        (*tty).cstyle = screen_cursor_style::SCREEN_CURSOR_DEFAULT;
+        (*tty).ccolour = -1;
+        (*tty).fg = -1;
+        (*tty).bg = -1;
+
+        (*tty).flags |= tty_flags::TTY_VIRTUAL;
+        tty_set_size(tty, sx, sy, 0, 0);
+    }
+}
+
 pub unsafe fn tty_resize(tty: *mut tty) {
     unsafe {
         let c = (*tty).client;
@@ -130,6 +147,10 @@ pub unsafe fn tty_set_size(tty: *mut tty, sx: u32, sy: u32, xpixel: u32, ypixel:
         (*tty).sy = sy;
         (*tty).xpixel = xpixel;
         (*tty).ypixel = ypixel;
+
+        if !(*tty).virtual_screen.is_null() {
+            screen_resize((*tty).virtual_screen, sx, sy, 0);
+        }
     }
 }
 
@@ -294,6 +315,12 @@ pub unsafe fn tty_open(tty: *mut tty, cause: *mut *mut c_char) -> i32 {
 
         evtimer_set(&raw mut (*tty).timer, Some(tty_timer_callback), tty.cast());
 
+        if (*tty).flags.intersects(tty_flags::TTY_VIRTUAL) {
+            (*tty).virtual_screen = xcalloc1::<screen>();
+            screen_init((*tty).virtual_screen, (*tty).sx, (*tty).sy, 0);
+            (*tty).virtual_ictx = input_init(null_mut(), null_mut(), null_mut());
+        }
+
         tty_start_tty(tty);
         tty_keys_build(tty);
 
@@ -326,6 +353,10 @@ pub unsafe fn tty_start_tty(tty: *mut tty) {
             tv_usec: 0,
         };
 
+        if (*tty).flags.intersects(tty_flags::TTY_VIRTUAL) {
+            return tty_start_virtual(tty);
+        }
+
         setblocking((*c).fd, 0);
         event_add(&raw mut (*tty).event_in, null_mut());
 
@@ -395,6 +426,21 @@ pub unsafe fn tty_start_tty(tty: *mut tty) {
     }
 }
 
+/// Start a virtual tty: there is no terminal to set up, so just clear the
+/// screen and mark the tty started.
+unsafe fn tty_start_virtual(tty: *mut tty) {
+    unsafe {
+        tty_putcode(tty, tty_code_code::TTYC_CLEAR);
+
+        (*tty).flags |= tty_flags::TTY_STARTED | TTY_ALL_REQUEST_FLAGS;
+        tty_invalidate(tty);
+
+        (*tty).mouse_drag_flag = 0;
+        (*tty).mouse_drag_update = None;
+        (*tty).mouse_drag_release = None;
+    }
+}
+
 pub unsafe fn tty_send_requests(tty: *mut tty) {
     unsafe {
         if !(*tty).flags.intersects(tty_flags::TTY_STARTED) {
@@ -451,6 +497,10 @@ pub unsafe fn tty_stop_tty(tty: *mut tty) {
         }
         (*tty).flags &= !tty_flags::TTY_STARTED;
 
+        // Whatever runs in the terminal next has none of our kitty images.
+        (*tty).kitty_nsent = 0;
+        (*tty).kitty_nplaced = 0;
+
         evtimer_del(&raw mut (*tty).start_timer);
 
         event_del(&raw mut (*tty).timer);
@@ -542,6 +592,21 @@ pub unsafe fn tty_close(tty: *mut tty) {
 
             (*tty).flags &= !tty_flags::TTY_OPENED;
         }
+
+        free_((*tty).kitty_sent);
+        (*tty).kitty_sent = null_mut();
+        free_((*tty).kitty_placed);
+        (*tty).kitty_placed = null_mut();
+
+        if !(*tty).virtual_ictx.is_null() {
+            input_free((*tty).virtual_ictx);
+            (*tty).virtual_ictx = null_mut();
+        }
+        if !(*tty).virtual_screen.is_null() {
+            screen_free((*tty).virtual_screen);
+            free_((*tty).virtual_screen);
+            (*tty).virtual_screen = null_mut();
+        }
     }
 }
 
@@ -587,6 +652,10 @@ pub unsafe fn tty_raw(tty: *mut tty, mut s: *const c_char) {
         let c = (*tty).client;
 
         let mut slen = strlen(s);
+        if (*tty).flags.intersects(tty_flags::TTY_VIRTUAL) {
+            tty_add(tty, s, slen);
+            return;
+        }
         for i in 0..5 {
             let n = libc::write((*c).fd, s.cast(), slen);
             if n >= 0 {
@@ -666,6 +735,21 @@ pub unsafe fn tty_add(tty: *mut tty, buf: *const c_char, len: usize) {
             return;
         }
 
+        if (*tty).flags.intersects(tty_flags::TTY_VIRTUAL) {
+            if !(*tty).virtual_screen.is_null() {
+                input_parse_screen(
+                    (*tty).virtual_ictx,
+                    (*tty).virtual_screen,
+                    None,
+                    null_mut(),
+                    buf as *mut u8,
+                    len,
+                );
+            }
+            (*c).written += len;
+            return;
+        }
+
         evbuffer_add((*tty).out, buf.cast(), len);
         // log_debug("%s: %.*s", (*c).name, (int)len, buf);
         (*c).written += len;
@@ -1948,7 +2032,6 @@ pub unsafe fn tty_draw_line(
 }
 
 /// Update context for client.
-#[cfg(feature = "sixel")]
 pub unsafe fn tty_set_client_cb(ttyctx: *mut tty_ctx, c: *mut client) -> i32 {
     unsafe {
         let mut wp: *mut window_pane = (*ttyctx).arg.cast();
@@ -1979,12 +2062,12 @@ pub unsafe fn tty_set_client_cb(ttyctx: *mut tty_ctx, c: *mut client) -> i32 {
     }
 }
 
-#[cfg(feature = "sixel")]
 pub unsafe fn tty_draw_images(c: *mut client, wp: *mut window_pane, s: *mut screen) {
     unsafe {
-        for im in tailq_foreach(&raw mut (*s).images).map(NonNull::as_ptr) {
+        tty_kitty_delete_pane(&raw mut (*c).tty, wp);
+
+        for im in tailq_foreach::<_, discr_entry>(&raw mut (*s).images).map(NonNull::as_ptr) {
             let mut ttyctx: tty_ctx = zeroed();
-            memset0(&raw mut ttyctx);
 
             // Set the client independent properties.
             ttyctx.ocx = (*im).px;
@@ -2002,6 +2085,11 @@ pub unsafe fn tty_draw_images(c: *mut client, wp: *mut window_pane, s: *mut scre
             ttyctx.arg = wp.cast();
             ttyctx.set_client_cb = Some(tty_set_client_cb);
             ttyctx.allow_invisible_panes = 1;
+            if !(*im).kitty.is_null() {
+                tty_write_one(tty_cmd_kittyimage, c, &raw mut ttyctx);
+                continue;
+            }
+            #[cfg(feature = "sixel")]
             tty_write_one(tty_cmd_sixelimage, c, &raw mut ttyctx);
         }
     }
@@ -2090,17 +2178,18 @@ pub unsafe fn tty_write(cmdfn: Option<unsafe fn(*mut tty, *const tty_ctx)>, ctx:
 }
 
 /// Only write to the incoming tty instead of every client.
-#[cfg(feature = "sixel")]
 pub unsafe fn tty_write_one(
-    cmdfn: fn(*mut tty, *const tty_ctx),
+    cmdfn: unsafe fn(*mut tty, *const tty_ctx),
     c: *mut client,
     ctx: *mut tty_ctx,
 ) {
-    let Some(set_client_cb) = (*ctx).set_client_cb else {
-        return;
-    };
-    if set_client_cb(ctx, c) == 1 {
-        cmdfn(&raw mut (*c).tty, ctx);
+    unsafe {
+        let Some(set_client_cb) = (*ctx).set_client_cb else {
+            return;
+        };
+        if set_client_cb(ctx, c) == 1 {
+            cmdfn(&raw mut (*c).tty, ctx);
+        }
     }
 }
 
@@ -2764,15 +2853,13 @@ pub unsafe fn tty_cmd_rawstring(tty: *mut tty, ctx: *const tty_ctx) {
 #[cfg(feature = "sixel")]
 pub unsafe fn tty_cmd_sixelimage(tty: *mut tty, ctx: *const tty_ctx) {
     unsafe {
-        let mut im: *mut image = (*ctx).ptr.cast();
-        let mut si: *mut sixel_image = (*im).data;
+        let im: *mut image = (*ctx).ptr.cast();
+        let si: *mut sixel_image = (*im).data;
         let mut new: *mut sixel_image = null_mut();
-        let mut data: *mut c_char = null_mut();
+        let data: *mut c_char;
         let mut size = 0;
         let cx = (*ctx).ocx;
         let cy = (*ctx).ocy;
-        // sx, sy;
-        // u_int i, j, x, y, rx, ry;
         let mut i: u32 = 0;
         let mut j: u32 = 0;
         let mut x: u32 = 0;
@@ -2781,19 +2868,19 @@ pub unsafe fn tty_cmd_sixelimage(tty: *mut tty, ctx: *const tty_ctx) {
         let mut ry: u32 = 0;
         let mut sx: u32 = 0;
         let mut sy: u32 = 0;
-        let mut fallback = 0;
+        let mut fallback = false;
 
         if !(*(*tty).term).flags.intersects(term_flags::TERM_SIXEL)
             && !tty_term_has((*tty).term, tty_code_code::TTYC_SXL)
         {
-            fallback = 1;
+            fallback = true;
         }
         if (*tty).xpixel == 0 || (*tty).ypixel == 0 {
-            fallback = 1;
+            fallback = true;
         }
 
         sixel_size_in_cells(si, &raw mut sx, &raw mut sy);
-        // log_debug("%s: image is %ux%u", __func__, sx, sy);
+        log_debug!("tty_cmd_sixelimage: image is {}x{}", sx, sy);
         if !tty_clamp_area(
             tty,
             ctx,
@@ -2810,9 +2897,9 @@ pub unsafe fn tty_cmd_sixelimage(tty: *mut tty, ctx: *const tty_ctx) {
         ) {
             return;
         }
-        // log_debug("%s: clamping to %u,%u-%u,%u", __func__, i, j, rx, ry);
+        log_debug!("tty_cmd_sixelimage: clamping to {},{}-{},{}", i, j, rx, ry);
 
-        if (fallback == 1) {
+        if fallback {
             data = xstrdup((*im).fallback).as_ptr();
             size = strlen(data);
         } else {
@@ -2821,10 +2908,10 @@ pub unsafe fn tty_cmd_sixelimage(tty: *mut tty, ctx: *const tty_ctx) {
                 return;
             }
 
-            data = sixel_print(new, si, &size);
+            data = sixel_print(new, si, &raw mut size);
         }
         if !data.is_null() {
-            // log_debug("%s: %zu bytes: %s", __func__, size, data);
+            log_debug!("tty_cmd_sixelimage: {} bytes", size);
             tty_region_off(tty);
             tty_margin_off(tty);
             tty_cursor(tty, x, y);
@@ -2835,12 +2922,206 @@ pub unsafe fn tty_cmd_sixelimage(tty: *mut tty, ctx: *const tty_ctx) {
             free_(data);
         }
 
-        if fallback == 0 {
+        if !fallback {
             sixel_free(new);
         }
     }
 }
 
+/// Send a kitty graphics command.
+unsafe fn tty_kitty_command(tty: *mut tty, control: &str, payload: *const c_char, size: usize) {
+    unsafe {
+        let start = format!("\x1b_G{control}");
+        tty_add(tty, start.as_ptr().cast(), start.len());
+        if size != 0 {
+            tty_add(tty, c";".as_ptr(), 1);
+            tty_add(tty, payload, size);
+        }
+        tty_add(tty, c"\x1b\\".as_ptr(), 2);
+    }
+}
+
+/// Transmit a kitty image to the terminal unless it already has it.
+unsafe fn tty_kitty_transmit(tty: *mut tty, ki: *mut kitty_image) {
+    const CHUNK: usize = 4096;
+
+    unsafe {
+        for i in 0..(*tty).kitty_nsent {
+            if *(*tty).kitty_sent.add(i as usize) == (*ki).outer {
+                return;
+            }
+        }
+        (*tty).kitty_sent =
+            xreallocarray_((*tty).kitty_sent, (*tty).kitty_nsent as usize + 1).as_ptr();
+        *(*tty).kitty_sent.add((*tty).kitty_nsent as usize) = (*ki).outer;
+        (*tty).kitty_nsent += 1;
+
+        let mut control = format!("a=t,q=2,f={},i={}", (*ki).format, (*ki).outer);
+        if (*ki).format != 100 {
+            control.push_str(&format!(",s={},v={}", (*ki).pixel_x, (*ki).pixel_y));
+        }
+        if (*ki).compressed != 0 {
+            control.push_str(",o=z");
+        }
+
+        let mut off = 0;
+        while off < (*ki).size {
+            let n = CHUNK.min((*ki).size - off);
+            let more = (off + n < (*ki).size) as i32;
+            if off == 0 {
+                control.push_str(&format!(",m={more}"));
+            } else {
+                control = format!("q=2,m={more}");
+            }
+            tty_kitty_command(tty, &control, (*ki).data.add(off), n);
+            off += n;
+        }
+    }
+}
+
+/// Remove a placement from the list on the terminal and delete it.
+unsafe fn tty_kitty_unplace(tty: *mut tty, idx: u32) {
+    unsafe {
+        let kp = *(*tty).kitty_placed.add(idx as usize);
+        let control = format!("a=d,d=i,i={},p={},q=2", kp.outer, kp.placement);
+        tty_kitty_command(tty, &control, null(), 0);
+
+        (*tty).kitty_nplaced -= 1;
+        *(*tty).kitty_placed.add(idx as usize) =
+            *(*tty).kitty_placed.add((*tty).kitty_nplaced as usize);
+    }
+}
+
+/// Delete the kitty placements of a pane, or all of them if wp is NULL.
+pub unsafe fn tty_kitty_delete_pane(tty: *mut tty, wp: *mut window_pane) {
+    unsafe {
+        let mut i = 0;
+        while i < (*tty).kitty_nplaced {
+            if wp.is_null() || (*(*tty).kitty_placed.add(i as usize)).pane == (*wp).id {
+                tty_kitty_unplace(tty, i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Delete the placement of an image from every terminal showing it.
+pub unsafe fn tty_kitty_delete_image(im: *mut image) {
+    unsafe {
+        for c in tailq_foreach(&raw mut clients).map(NonNull::as_ptr) {
+            let tty = &raw mut (*c).tty;
+            for i in 0..(*tty).kitty_nplaced {
+                if (*(*tty).kitty_placed.add(i as usize)).placement == (*im).placement {
+                    tty_kitty_unplace(tty, i);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Free a kitty image in every terminal it was sent to.
+pub unsafe fn tty_kitty_forget(outer: u32) {
+    unsafe {
+        for c in tailq_foreach(&raw mut clients).map(NonNull::as_ptr) {
+            let tty = &raw mut (*c).tty;
+            for i in 0..(*tty).kitty_nsent {
+                if *(*tty).kitty_sent.add(i as usize) != outer {
+                    continue;
+                }
+                let control = format!("a=d,d=I,i={outer},q=2");
+                tty_kitty_command(tty, &control, null(), 0);
+
+                (*tty).kitty_nsent -= 1;
+                *(*tty).kitty_sent.add(i as usize) =
+                    *(*tty).kitty_sent.add((*tty).kitty_nsent as usize);
+                break;
+            }
+        }
+    }
+}
+
+pub unsafe fn tty_cmd_kittyimage(tty: *mut tty, ctx: *const tty_ctx) {
+    unsafe {
+        let im: *mut image = (*ctx).ptr.cast();
+        let ki = (*im).kitty;
+        let wp: *mut window_pane = (*ctx).arg.cast();
+        let mut i: u32 = 0;
+        let mut j: u32 = 0;
+        let mut x: u32 = 0;
+        let mut y: u32 = 0;
+        let mut rx: u32 = 0;
+        let mut ry: u32 = 0;
+
+        if !tty_clamp_area(
+            tty,
+            ctx,
+            (*ctx).ocx,
+            (*ctx).ocy,
+            (*im).sx,
+            (*im).sy,
+            &raw mut i,
+            &raw mut j,
+            &raw mut x,
+            &raw mut y,
+            &raw mut rx,
+            &raw mut ry,
+        ) {
+            return;
+        }
+
+        tty_region_off(tty);
+        tty_margin_off(tty);
+        tty_cursor(tty, x, y);
+        (*tty).flags |= tty_flags::TTY_NOBLOCK;
+
+        if !(*(*tty).term).flags.intersects(term_flags::TERM_KITTYGFX) {
+            tty_add(tty, (*im).fallback, strlen((*im).fallback));
+            tty_invalidate(tty);
+            return;
+        }
+        tty_kitty_transmit(tty, ki);
+
+        // Show the visible part of the image, in pixels of the image, and
+        // replace any earlier placement.
+        let (width, height) = ((*ki).pixel_x, (*ki).pixel_y);
+        let (columns, rows) = ((*im).sx, (*im).sy + (*im).crop);
+        let mut control = format!(
+            "a=p,i={},p={},c={rx},r={ry},C=1,q=2",
+            (*ki).outer,
+            (*im).placement
+        );
+        if rx != columns || ry != rows {
+            control.push_str(&format!(
+                ",x={},y={},w={},h={}",
+                i * width / columns,
+                ((*im).crop + j) * height / rows,
+                rx * width / columns,
+                ry * height / rows
+            ));
+        }
+        tty_kitty_command(tty, &control, null(), 0);
+        tty_invalidate(tty);
+
+        let pane = if wp.is_null() { u32::MAX } else { (*wp).id };
+        for n in 0..(*tty).kitty_nplaced {
+            if (*(*tty).kitty_placed.add(n as usize)).placement == (*im).placement {
+                (*(*tty).kitty_placed.add(n as usize)).pane = pane;
+                return;
+            }
+        }
+        (*tty).kitty_placed =
+            xreallocarray_((*tty).kitty_placed, (*tty).kitty_nplaced as usize + 1).as_ptr();
+        *(*tty).kitty_placed.add((*tty).kitty_nplaced as usize) = tty_kitty_placement {
+            outer: (*ki).outer,
+            placement: (*im).placement,
+            pane,
+        };
+        (*tty).kitty_nplaced += 1;
+    }
+}
+
 pub unsafe fn tty_cmd_syncstart(tty: *mut tty, ctx: *const tty_ctx) {
     unsafe {
         if (*ctx).num == 0x11 {
@@ -3205,7 +3486,7 @@ pub unsafe fn tty_cursor(tty: *mut tty, mut cx: u32, cy: u32) {
 
                     /* One above. */
                     if thisy != (*tty).rupper
-                        && cy == thisy - 1
+                        && cy == thisy.wrapping_sub(1)
                         && tty_term_has(term, tty_code_code::TTYC_CUU1)
                     {
                         tty_putcode(tty, tty_code_code::TTYC_CUU1);
@@ -3877,3 +4158,45 @@ pub unsafe fn tty_clipboard_query(tty: *mut tty) {
         evtimer_add(&raw mut (*tty).clipboard_timer, &tv);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tty_virtual() {
+        unsafe {
+            let mut c: client = zeroed();
+            let tty = &raw mut c.tty;
+            tty_init_virtual(tty, &raw mut c, 10, 3);
+            assert!((*tty).flags.intersects(tty_flags::TTY_VIRTUAL));
+            assert_eq!(((*tty).sx, (*tty).sy), (10, 3));
+            assert_eq!(((*tty).fg, (*tty).bg), (-1, -1));
+            assert!((*tty).virtual_screen.is_null());
+
+            // Output is parsed into the screen rather than buffered.
+            let mut s: screen = zeroed();
+            s.grid = grid_create(10, 3, 0);
+            (*tty).virtual_screen = &raw mut s;
+            (*tty).virtual_ictx = input_init(null_mut(), null_mut(), null_mut());
+            let data = b"ab\x1b[2;3Hc";
+            tty_add(tty, data.as_ptr().cast(), data.len());
+            assert_eq!(c.written, data.len());
+            assert_eq!((s.cx, s.cy), (3, 1));
+            let mut gc: grid_cell = zeroed();
+            grid_get_cell(s.grid, 2, 1, &raw mut gc);
+            assert_eq!(gc.data.data[0], b'c');
+
+            // Resizing the tty resizes the screen.
+            tty_set_size(tty, 20, 5, 0, 0);
+            assert_eq!(((*tty).sx, (*tty).sy), (20, 5));
+            assert_eq!(
+                (screen_size_x(&raw mut s), screen_size_y(&raw mut s)),
+                (20, 5)
+            );
+
+            input_free((*tty).virtual_ictx);
+            grid_destroy(s.grid);
+        }
+    }
+}
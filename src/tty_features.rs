@@ -229,6 +229,27 @@ static tty_feature_extkeys: tty_feature = tty_feature::new(
     term_flags::empty(),
 );
 
+/// Terminal supports the kitty graphics protocol.
+static tty_feature_kittygfx_capabilities: &[SyncCharPtr] = &[SyncCharPtr::null()];
+static tty_feature_kittygfx: tty_feature = tty_feature::new(
+    SyncCharPtr::new(c"kittygfx"),
+    tty_feature_kittygfx_capabilities,
+    term_flags::TERM_KITTYGFX,
+);
+
+/// Terminal supports the kitty keyboard protocol. This replaces the extkeys
+/// sequences, asking for disambiguated keys, event types and alternate keys.
+static tty_feature_kittykeys_capabilities: &[SyncCharPtr] = &[
+    SyncCharPtr::new(c"Eneks=\\E[=7;1u"),
+    SyncCharPtr::new(c"Dseks=\\E[=0;1u"),
+    SyncCharPtr::null(),
+];
+static tty_feature_kittykeys: tty_feature = tty_feature::new(
+    SyncCharPtr::new(c"kittykeys"),
+    tty_feature_kittykeys_capabilities,
+    term_flags::empty(),
+);
+
 /// Terminal supports DECSLRM margins.
 static tty_feature_margins_capabilities: &[SyncCharPtr] = &[
     SyncCharPtr::new(c"Enmg=\\E[?69h"),
@@ -337,7 +358,7 @@ static tty_feature_sixel: tty_feature = tty_feature::new(
 );
 
 /// Available terminal features.
-static tty_features: [&tty_feature; 20] = [
+static tty_features: [&tty_feature; 22] = [
     &tty_feature_256,
     &tty_feature_bpaste,
     &tty_feature_ccolour,
@@ -347,6 +368,8 @@ static tty_features: [&tty_feature; 20] = [
     &tty_feature_extkeys,
     &tty_feature_focus,
     &tty_feature_ignorefkeys,
+    &tty_feature_kittygfx,
+    &tty_feature_kittykeys,
     &tty_feature_margins,
     &tty_feature_mouse,
     &tty_feature_osc7,
@@ -365,9 +388,8 @@ pub unsafe fn tty_add_features(feat: *mut i32, s: *const c_char, separators: *co
         log_debug!("adding terminal features {}", _s(s));
 
         let copy = xstrdup(s).as_ptr();
-        let loop_ = copy;
+        let mut loop_ = copy;
         let mut next = null_mut();
-        let mut loop_ = null_mut();
 
         while {
             next = strsep(&raw mut loop_, separators);
@@ -423,7 +445,7 @@ pub unsafe fn tty_apply_features(term: *mut tty_term, feat: i32) -> bool {
         log_debug!("applying terminal features: {}", _s(tty_get_features(feat)));
 
         for (i, tf) in tty_features.iter().cloned().enumerate() {
-            if ((*term).features & (1 << i) != 9) || (!feat & (1 << i)) != 0 {
+            if ((*term).features & (1 << i)) != 0 || (!feat & (1 << i)) != 0 {
                 continue;
             }
 
@@ -465,6 +487,7 @@ pub unsafe fn tty_default_features(feat: *mut i32, name: *const c_char, version:
         entry { name: c"mintty", features: concat!( TTY_FEATURES_BASE_MODERN_XTERM!(), ",ccolour,cstyle,extkeys,margins,overline,usstyle\0"), version: 0, },
         entry { name: c"tmux", features: concat!( TTY_FEATURES_BASE_MODERN_XTERM!(), ",ccolour,cstyle,focus,overline,usstyle,hyperlinks\0"), version: 0, },
         entry { name: c"rxvt-unicode", features: "256,bpaste,ccolour,cstyle,mouse,title,ignorefkeys\0", version: 0, },
+        entry { name: c"kitty", features: concat!( TTY_FEATURES_BASE_MODERN_XTERM!(), ",ccolour,cstyle,extkeys,focus,kittygfx,kittykeys,overline,usstyle,sync,hyperlinks\0"), version: 0, },
         entry { name: c"iTerm2", features: concat!( TTY_FEATURES_BASE_MODERN_XTERM!(), ",cstyle,extkeys,margins,usstyle,sync,osc7,hyperlinks\0"), version: 0, },
         // xterm also supports DECSLRM and DECFRA, but they can be
         // disabled so not set it here - they will be added if
@@ -484,3 +507,32 @@ pub unsafe fn tty_default_features(feat: *mut i32, name: *const c_char, version:
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tty_features() {
+        unsafe {
+            let mut feat = 0;
+            tty_add_features(
+                &raw mut feat,
+                c"RGB,kittygfx:nonexistent,sync".as_ptr(),
+                c",:".as_ptr(),
+            );
+            assert_eq!(cstr_to_str(tty_get_features(feat)), "kittygfx,RGB");
+
+            let mut term: tty_term = zeroed();
+            let kittygfx = 1
+                << tty_features
+                    .iter()
+                    .position(|tf| cstr_to_str(tf.name.as_ptr()) == "kittygfx")
+                    .unwrap();
+            assert!(tty_apply_features(&raw mut term, kittygfx));
+            assert!(term.flags.intersects(term_flags::TERM_KITTYGFX));
+            assert_eq!(term.features, kittygfx);
+            assert!(!tty_apply_features(&raw mut term, kittygfx));
+        }
+    }
+}
@@ -1363,8 +1363,10 @@ unsafe extern "C" fn tty_keys_callback(_fd: i32, _events: i16, data: *mut c_void
 }
 
 /// Handle extended key input. This has two forms: \x1b[27;m;k~ and \x1b[k;mu,
-/// where k is key as a number and m is a modifier. Returns 0 for success, -1
-/// for failure, 1 for partial;
+/// where k is key as a number and m is a modifier. The kitty keyboard protocol
+/// extends the second form to \x1b[k:s:b;m:e;tu (with shifted and base layout
+/// keys, an event type and text) and adds the event type to functional keys
+/// like \x1b[1;m:eA. Returns 0 for success, -1 for failure, 1 for partial;
 unsafe fn tty_keys_extended_key(
     tty: *mut tty,
     buf: *const c_char,
@@ -1374,12 +1376,14 @@ unsafe fn tty_keys_extended_key(
 ) -> i32 {
     unsafe {
         let c = (*tty).client;
-        let end: usize = 0;
-        let mut number: u32 = 0;
-        let mut modifiers: u32 = 0;
         const size_of_tmp: usize = 64;
-        let mut tmp: [c_char; 64] = [0; 64];
-        let mut nkey: key_code = 0;
+        let mut fields: [[u32; 3]; 3] = [[0; 3]; 3];
+        let mut nfields: usize = 0;
+        let mut nsub: usize = 0;
+        let mut event: bool = false;
+        let mut number: u32;
+        let mut modifiers: u32;
+        let mut nkey: key_code;
 
         let mut ud: utf8_data = zeroed();
         let mut uc: utf8_char = zeroed();
@@ -1401,61 +1405,139 @@ unsafe fn tty_keys_extended_key(
         }
 
         /*
-         * Look for a terminator. Stop at either '~' or anything that isn't a
-         * number or ';'.
+         * Look for a terminator. Stop at anything that isn't a number, ';' or
+         * ':', splitting the parameters on the way.
          */
-        for end in 2..len.min(size_of_tmp) {
-            if *buf.add(end) == b'~' as i8 {
-                break;
-            }
-            if !(*buf.add(end) as u8).is_ascii_digit() && *buf.add(end) != b';' as i8 {
+        let mut end = 2;
+        while end < len && end < size_of_tmp {
+            let ch = *buf.add(end) as u8;
+            if ch.is_ascii_digit() {
+                if nfields < 3 && nsub < 3 {
+                    let value = &mut fields[nfields][nsub];
+                    *value = value.saturating_mul(10).saturating_add((ch - b'0') as u32);
+                }
+            } else if ch == b';' {
+                nfields += 1;
+                nsub = 0;
+            } else if ch == b':' {
+                if nfields == 1 {
+                    event = true;
+                }
+                nsub += 1;
+            } else {
                 break;
             }
+            end += 1;
         }
         if end == len {
             return 1;
         }
-        if end == size_of_tmp || (*buf.add(end) != b'~' as i8 && *buf.add(end) != b'u' as i8) {
+        if end == size_of_tmp {
             return -1;
         }
+        nfields += 1;
+        let final_ = *buf.add(end) as u8;
 
-        // Copy to the buffer.
-        libc::memcpy(tmp.as_mut_ptr().cast(), buf.add(2).cast(), end);
-        tmp[end] = 0;
+        /*
+         * Functional keys with no event type are left to the normal key
+         * lookup.
+         */
+        nkey = match final_ {
+            b'u' => KEYC_NONE,
+            b'~' if nfields == 3 && fields[0][0] == 27 => KEYC_NONE,
+            b'~' if event => match fields[0][0] {
+                1 | 7 => keyc::KEYC_HOME as key_code,
+                2 => keyc::KEYC_IC as key_code,
+                3 => keyc::KEYC_DC as key_code,
+                4 | 8 => keyc::KEYC_END as key_code,
+                5 => keyc::KEYC_PPAGE as key_code,
+                6 => keyc::KEYC_NPAGE as key_code,
+                11 => keyc::KEYC_F1 as key_code,
+                12 => keyc::KEYC_F2 as key_code,
+                13 => keyc::KEYC_F3 as key_code,
+                14 => keyc::KEYC_F4 as key_code,
+                15 => keyc::KEYC_F5 as key_code,
+                17..=21 => keyc::KEYC_F6 as key_code + (fields[0][0] - 17) as key_code,
+                23 => keyc::KEYC_F11 as key_code,
+                24 => keyc::KEYC_F12 as key_code,
+                _ => return -1,
+            },
+            b'A' if event => keyc::KEYC_UP as key_code,
+            b'B' if event => keyc::KEYC_DOWN as key_code,
+            b'C' if event => keyc::KEYC_RIGHT as key_code,
+            b'D' if event => keyc::KEYC_LEFT as key_code,
+            b'H' if event => keyc::KEYC_HOME as key_code,
+            b'F' if event => keyc::KEYC_END as key_code,
+            b'P' if event => keyc::KEYC_F1 as key_code,
+            b'Q' if event => keyc::KEYC_F2 as key_code,
+            b'R' if event => keyc::KEYC_F3 as key_code,
+            b'S' if event => keyc::KEYC_F4 as key_code,
+            _ => return -1,
+        };
+        *size = end + 1;
 
-        /* Try to parse either form of key. */
-        if *buf.add(end) == b'~' as i8 {
-            if libc::sscanf(
-                tmp.as_ptr(),
-                c"27;%u;%u".as_ptr(),
-                &raw mut modifiers,
-                &raw mut number,
-            ) != 2
-            {
-                return -1;
-            }
-        } else if libc::sscanf(
-            tmp.as_ptr(),
-            c"%u;%u".as_ptr(),
-            &raw mut number,
-            &raw mut modifiers,
-        ) != 2
-        {
+        /* Pick out the key and modifiers from either form. */
+        if final_ == b'~' && nkey == KEYC_NONE {
+            modifiers = fields[1][0];
+            number = fields[2][0];
+        } else {
+            modifiers = fields[1][0];
+            number = fields[0][0];
+        }
+        if final_ == b'u' && nfields == 1 && number == 0 {
+            *size = 0;
             return -1;
         }
-        *size = end + 1;
 
-        /* Store the key. */
+        /*
+         * Use the shifted key if the terminal sent one and Shift is the only
+         * modifier; the Shift is then dropped below.
+         */
+        if final_ == b'u' && fields[0][1] != 0 && modifiers > 0 && ((modifiers - 1) & !0xc0) == 1 {
+            number = fields[0][1];
+        }
 
-        let bspace: libc::cc_t = (*tty).tio.c_cc[libc::VERASE];
-        if bspace != libc::_POSIX_VDISABLE && number == bspace as u32 {
-            nkey = keyc::KEYC_BSPACE as key_code;
-        } else {
-            nkey = number as key_code;
+        if nkey == KEYC_NONE {
+            /* Store the key. */
+            let bspace: libc::cc_t = (*tty).tio.c_cc[libc::VERASE];
+            nkey = match number {
+                _ if bspace != libc::_POSIX_VDISABLE && number == bspace as u32 => {
+                    keyc::KEYC_BSPACE as key_code
+                }
+                127 => keyc::KEYC_BSPACE as key_code,
+                57399..=57408 => {
+                    const KP_DIGITS: [key_code; 10] = [
+                        keyc::KEYC_KP_ZERO as key_code,
+                        keyc::KEYC_KP_ONE as key_code,
+                        keyc::KEYC_KP_TWO as key_code,
+                        keyc::KEYC_KP_THREE as key_code,
+                        keyc::KEYC_KP_FOUR as key_code,
+                        keyc::KEYC_KP_FIVE as key_code,
+                        keyc::KEYC_KP_SIX as key_code,
+                        keyc::KEYC_KP_SEVEN as key_code,
+                        keyc::KEYC_KP_EIGHT as key_code,
+                        keyc::KEYC_KP_NINE as key_code,
+                    ];
+                    KP_DIGITS[(number - 57399) as usize]
+                }
+                57409 => keyc::KEYC_KP_PERIOD as key_code,
+                57410 => keyc::KEYC_KP_SLASH as key_code,
+                57411 => keyc::KEYC_KP_STAR as key_code,
+                57412 => keyc::KEYC_KP_MINUS as key_code,
+                57413 => keyc::KEYC_KP_PLUS as key_code,
+                57414 => keyc::KEYC_KP_ENTER as key_code,
+                // Other private use keys (lone modifiers, media keys and the
+                // like) are consumed but not passed on.
+                57344..=63743 => {
+                    *key = KEYC_UNKNOWN;
+                    return 0;
+                }
+                _ => number as key_code,
+            };
         }
 
         /* Convert UTF-32 codepoint into internal representation. */
-        if nkey != keyc::KEYC_BSPACE as key_code && (nkey & !0x7f) != 0 {
+        if nkey != keyc::KEYC_BSPACE as key_code && (nkey & !0x7f) != 0 && number == nkey as u32 {
             if utf8_fromwc(nkey as wchar_t, &raw mut ud) == utf8_state::UTF8_DONE
                 && utf8_from_data(&raw const ud, &raw mut uc) == utf8_state::UTF8_DONE
             {
@@ -1482,6 +1564,13 @@ unsafe fn tty_keys_extended_key(
             }
         }
 
+        /* Add the kitty event type. */
+        match fields[1][1] {
+            2 => nkey |= KEYC_REPEAT,
+            3 => nkey |= KEYC_RELEASE,
+            _ => (),
+        }
+
         /* Convert S-Tab into Backtab. */
         if (nkey & KEYC_MASK_KEY) == b'\x09' as key_code && (nkey & KEYC_SHIFT) != 0 {
             nkey = (keyc::KEYC_BTAB as u64) | (nkey & !KEYC_MASK_KEY & !KEYC_SHIFT);
@@ -1792,7 +1881,7 @@ unsafe fn tty_keys_clipboard(
         /* Convert from base64. */
         let needed: usize = (end / 4) * 3;
         let out: *mut c_char = xmalloc(needed).as_ptr().cast();
-        let outlen: i32 = b64_pton(copy, out.cast(), len);
+        let outlen: i32 = b64_pton(copy, out.cast(), needed);
         if outlen == -1 {
             free_(out);
             free_(copy);
@@ -2117,6 +2206,8 @@ unsafe fn tty_keys_extended_device_attributes(
             tty_default_features(features, c"XTerm".as_ptr(), 0);
         } else if libc::strncmp(tmp.as_ptr(), c"mintty ".as_ptr(), 7) == 0 {
             tty_default_features(features, c"mintty".as_ptr(), 0);
+        } else if libc::strncmp(tmp.as_ptr(), c"kitty(".as_ptr(), 6) == 0 {
+            tty_default_features(features, c"kitty".as_ptr(), 0);
         }
         // log_debug(c"%s: received extended DA %.*s\0".as_ptr(), (*c).name, *size as i32, buf);
 
@@ -2229,3 +2320,64 @@ pub unsafe fn tty_keys_colours(
         0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tty_keys_extended_key() {
+        let a = b'a' as key_code;
+        let cases: [(&[u8], i32, key_code); 11] = [
+            (b"\x1b[97;5u", 0, a | KEYC_CTRL),
+            (b"\x1b[27;5;97~", 0, a | KEYC_CTRL),
+            (b"\x1b[97;1:3u", 0, a | KEYC_RELEASE),
+            (b"\x1b[97:65;2u", 0, b'A' as key_code),
+            (b"\x1b[97;2;65u", 0, a),
+            (b"\x1b[1;1:2A", 0, keyc::KEYC_UP as key_code | KEYC_REPEAT),
+            (
+                b"\x1b[15;5:3~",
+                0,
+                keyc::KEYC_F5 as key_code | KEYC_CTRL | KEYC_RELEASE,
+            ),
+            (b"\x1b[9;2u", 0, keyc::KEYC_BTAB as key_code),
+            (b"\x1b[57400u", 0, keyc::KEYC_KP_ONE as key_code),
+            (b"\x1b[57441;2u", 0, KEYC_UNKNOWN),
+            (b"\x1b[97;5", 1, KEYC_NONE),
+        ];
+        unsafe {
+            let mut tty: tty = zeroed();
+            for (buf, ret, expected) in cases {
+                let mut size = 0;
+                let mut key = KEYC_NONE;
+                assert_eq!(
+                    tty_keys_extended_key(
+                        &raw mut tty,
+                        buf.as_ptr().cast(),
+                        buf.len(),
+                        &raw mut size,
+                        &raw mut key,
+                    ),
+                    ret,
+                    "{buf:?}"
+                );
+                if ret == 0 {
+                    assert_eq!((size, key), (buf.len(), expected), "{buf:?}");
+                }
+            }
+
+            // Legacy functional keys are left to the normal lookup.
+            let mut size = 0;
+            let mut key = KEYC_NONE;
+            let buf = b"\x1b[A";
+            let ret = tty_keys_extended_key(
+                &raw mut tty,
+                buf.as_ptr().cast(),
+                buf.len(),
+                &raw mut size,
+                &raw mut key,
+            );
+            assert_eq!(ret, -1);
+        }
+    }
+}
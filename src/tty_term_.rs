@@ -1010,6 +1010,75 @@ pub unsafe fn tty_term_read_list(
     }
 }
 
+/// Capabilities of the terminal used by virtual clients, as tigetstr would
+/// return them. Anything beyond these comes from the terminal features.
+static tty_term_virtual_caps: &[&CStr] = &[
+    c"AX=1",
+    c"XT=1",
+    c"colors=8",
+    c"bel=\x07",
+    c"blink=\x1b[5m",
+    c"bold=\x1b[1m",
+    c"civis=\x1b[?25l",
+    c"clear=\x1b[H\x1b[2J",
+    c"cnorm=\x1b[?12l\x1b[?25h",
+    c"csr=\x1b[%i%p1%d;%p2%dr",
+    c"cub=\x1b[%p1%dD",
+    c"cub1=\x08",
+    c"cud=\x1b[%p1%dB",
+    c"cud1=\n",
+    c"cuf=\x1b[%p1%dC",
+    c"cuf1=\x1b[C",
+    c"cup=\x1b[%i%p1%d;%p2%dH",
+    c"cuu=\x1b[%p1%dA",
+    c"cuu1=\x1b[A",
+    c"dch=\x1b[%p1%dP",
+    c"dch1=\x1b[P",
+    c"dim=\x1b[2m",
+    c"dl=\x1b[%p1%dM",
+    c"dl1=\x1b[M",
+    c"ech=\x1b[%p1%dX",
+    c"ed=\x1b[J",
+    c"el=\x1b[K",
+    c"el1=\x1b[1K",
+    c"home=\x1b[H",
+    c"hpa=\x1b[%i%p1%dG",
+    c"ich=\x1b[%p1%d@",
+    c"ich1=\x1b[@",
+    c"il=\x1b[%p1%dL",
+    c"il1=\x1b[L",
+    c"indn=\x1b[%p1%dS",
+    c"invis=\x1b[8m",
+    c"op=\x1b[39;49m",
+    c"rev=\x1b[7m",
+    c"ri=\x1bM",
+    c"rin=\x1b[%p1%dT",
+    c"ritm=\x1b[23m",
+    c"rmso=\x1b[27m",
+    c"setab=\x1b[4%p1%dm",
+    c"setaf=\x1b[3%p1%dm",
+    c"sgr0=\x1b[m",
+    c"sitm=\x1b[3m",
+    c"smso=\x1b[7m",
+    c"smul=\x1b[4m",
+    c"vpa=\x1b[%i%p1%dd",
+];
+
+/// Terminal features of virtual clients.
+pub const TTY_TERM_VIRTUAL_FEATURES: &CStr =
+    c"256,RGB,ccolour,cstyle,overline,strikethrough,title,usstyle,hyperlinks,bpaste,focus";
+
+/// Build the capability list for a virtual client, which has no terminfo.
+pub unsafe fn tty_term_virtual_list(caps: *mut *mut *mut c_char, ncaps: *mut u32) {
+    unsafe {
+        *ncaps = tty_term_virtual_caps.len() as u32;
+        *caps = xreallocarray_(null_mut(), tty_term_virtual_caps.len()).as_ptr();
+        for (i, cap) in tty_term_virtual_caps.iter().enumerate() {
+            *(*caps).add(i) = xstrdup(cap.as_ptr()).as_ptr();
+        }
+    }
+}
+
 pub unsafe fn tty_term_free_list(caps: *mut *mut c_char, ncaps: u32) {
     unsafe {
         for i in 0..ncaps {
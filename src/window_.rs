@@ -16,7 +16,7 @@ use crate::*;
 
 use libc::{
     FIONREAD, FNM_CASEFOLD, TIOCSWINSZ, close, fnmatch, free, gethostname, gettimeofday, ioctl,
-    isspace, memset, regcomp, regex_t, regexec, regfree, strcasecmp, strlen, winsize,
+    isspace, memset, strcasecmp, strlen, winsize,
 };
 
 use crate::compat::{
@@ -1039,6 +1039,8 @@ pub unsafe fn window_pane_create(
         (*wp).control_bg = -1;
         (*wp).control_fg = -1;
 
+        (*wp).last_status = -1;
+
         colour_palette_init(&raw mut (*wp).palette);
         colour_palette_from_option(&raw mut (*wp).palette, (*wp).options);
 
@@ -1085,6 +1087,9 @@ unsafe fn window_pane_destroy(wp: *mut window_pane) {
         if event_initialized(&raw mut (*wp).resize_timer) != 0 {
             event_del(&raw mut (*wp).resize_timer);
         }
+        if event_initialized(&raw mut (*wp).reflow_timer) != 0 {
+            event_del(&raw mut (*wp).reflow_timer);
+        }
         for r in tailq_foreach(&raw mut (*wp).resize_queue).map(NonNull::as_ptr) {
             tailq_remove::<_, ()>(&raw mut (*wp).resize_queue, r);
             free_(r);
@@ -1095,12 +1100,62 @@ unsafe fn window_pane_destroy(wp: *mut window_pane) {
         options_free((*wp).options);
         free((*wp).cwd as _);
         free((*wp).shell as _);
+        free((*wp).last_command as _);
         cmd_free_argv((*wp).argc, (*wp).argv);
         colour_palette_free(&raw mut (*wp).palette);
         free(wp as _);
     }
 }
 
+/// Add an event to the pane timeline.
+pub unsafe fn window_pane_add_event(
+    wp: *mut window_pane,
+    type_: window_pane_event_type,
+    bytes: usize,
+) {
+    unsafe {
+        let tl = &mut (*wp).timeline;
+        let time = libc::time(null_mut());
+
+        if type_ == window_pane_event_type::Output && tl.count != 0 {
+            let last = &mut tl.events[(tl.next + WINDOW_PANE_EVENTS - 1) % WINDOW_PANE_EVENTS];
+            if last.type_ == window_pane_event_type::Output && last.time == time {
+                last.bytes += bytes;
+                return;
+            }
+        }
+
+        tl.events[tl.next] = window_pane_event { type_, time, bytes };
+        tl.next = (tl.next + 1) % WINDOW_PANE_EVENTS;
+        if tl.count < WINDOW_PANE_EVENTS {
+            tl.count += 1;
+        }
+    }
+}
+
+/// Iterate over the pane timeline, newest event first.
+pub unsafe fn window_pane_events(wp: *mut window_pane) -> impl Iterator<Item = window_pane_event> {
+    unsafe {
+        let tl = &(*wp).timeline;
+        let next = tl.next;
+        let events = &tl.events;
+        (1..=tl.count).map(move |i| events[(next + WINDOW_PANE_EVENTS - i) % WINDOW_PANE_EVENTS])
+    }
+}
+
+/// Average bytes of output a second over the last minute.
+pub unsafe fn window_pane_activity_rate(wp: *mut window_pane) -> usize {
+    unsafe {
+        let since = libc::time(null_mut()) - 60;
+        let bytes: usize = window_pane_events(wp)
+            .take_while(|event| event.time > since)
+            .filter(|event| event.type_ == window_pane_event_type::Output)
+            .map(|event| event.bytes)
+            .sum();
+        bytes / 60
+    }
+}
+
 unsafe extern "C" fn window_pane_read_callback(_bufev: *mut bufferevent, data: *mut c_void) {
     unsafe {
         let wp: *mut window_pane = data as _;
@@ -1118,6 +1173,9 @@ unsafe extern "C" fn window_pane_read_callback(_bufev: *mut bufferevent, data: *
         }
 
         log_debug!("%%{} has {} bytes", (*wp).id, size);
+        if size != 0 {
+            window_pane_add_event(wp, window_pane_event_type::Output, size);
+        }
         for c in tailq_foreach(&raw mut clients).map(NonNull::as_ptr) {
             if !(*c).session.is_null() && (*c).flags.intersects(client_flag::CONTROL) {
                 control_write_output(c, wp);
@@ -1193,6 +1251,7 @@ pub unsafe fn window_pane_resize(wp: *mut window_pane, sx: u32, sy: u32) {
             sy,
             (*wp).base.saved_grid.is_null() as i32,
         );
+        window_pane_reflow_start(wp);
 
         if let Some(wme) = NonNull::new(tailq_first(&raw mut (*wp).modes))
             && let Some(resize) = (*(*wme.as_ptr()).mode).resize
@@ -1202,6 +1261,50 @@ pub unsafe fn window_pane_resize(wp: *mut window_pane, sx: u32, sy: u32) {
     }
 }
 
+/// Number of history lines reflowed each time the pane reflow timer fires.
+const WINDOW_PANE_REFLOW_LINES: u32 = 1000;
+
+/// Pane reflow timer. Reflow some more of the history, a chunk at a time so
+/// the server is not held up for long. This stops while the alternate screen
+/// is active and starts again when it is turned off.
+unsafe extern "C" fn window_pane_reflow_timer(_fd: i32, _events: i16, arg: *mut c_void) {
+    unsafe {
+        let wp = arg as *mut window_pane;
+
+        if !(*wp).base.saved_grid.is_null() {
+            return;
+        }
+        if grid_reflow_chunk((*wp).base.grid, WINDOW_PANE_REFLOW_LINES) {
+            window_pane_reflow_start(wp);
+        }
+    }
+}
+
+/// Start the reflow timer if a resize has left history to be reflowed.
+pub unsafe fn window_pane_reflow_start(wp: *mut window_pane) {
+    unsafe {
+        let gd = (*wp).base.grid;
+        let tv = timeval {
+            tv_sec: 0,
+            tv_usec: 0,
+        };
+
+        if (*gd).hreflow <= (*gd).hspill {
+            return;
+        }
+        if event_initialized(&raw mut (*wp).reflow_timer) == 0 {
+            evtimer_set(
+                &raw mut (*wp).reflow_timer,
+                Some(window_pane_reflow_timer),
+                wp.cast(),
+            );
+        }
+        if evtimer_pending(&raw mut (*wp).reflow_timer, null_mut()) == 0 {
+            evtimer_add(&raw mut (*wp).reflow_timer, &raw const tv);
+        }
+    }
+}
+
 pub unsafe fn window_pane_set_mode(
     wp: *mut window_pane,
     swp: *mut window_pane,
@@ -1323,6 +1426,9 @@ pub unsafe fn window_pane_key(
             && let Some(key_fn) = (*(*wme.as_ptr()).mode).key
             && !c.is_null()
         {
+            if key & KEYC_RELEASE != 0 {
+                return 0;
+            }
             key &= !KEYC_MASK_FLAGS;
             key_fn(wme, c, s, wl, key, m);
             return 0;
@@ -1369,27 +1475,29 @@ pub unsafe fn window_pane_exited(wp: *mut window_pane) -> i32 {
 pub unsafe fn window_pane_search(
     wp: *mut window_pane,
     term: *const c_char,
-    regex: i32,
+    regex: Option<regex_syntax>,
     ignore: i32,
 ) -> u32 {
     unsafe {
         let s: *mut screen = &raw mut (*wp).base;
-        let mut r: regex_t = zeroed();
+        let mut r = None;
         let mut new: *mut c_char = null_mut();
         let mut flags = 0;
 
-        if regex == 0 {
+        if let Some(syntax) = regex {
+            let mut rflags = regex_flags::REGEX_NOSUB;
             if ignore != 0 {
-                flags |= FNM_CASEFOLD;
+                rflags |= regex_flags::REGEX_ICASE;
+            }
+            r = regex_compile(term, syntax, rflags);
+            if r.is_none() {
+                return 0;
             }
-            new = format_nul!("*{}*", _s(term));
         } else {
             if ignore != 0 {
-                flags |= REG_ICASE;
-            }
-            if regcomp(&raw mut r, term, flags | REG_EXTENDED) != 0 {
-                return 0;
+                flags |= FNM_CASEFOLD;
             }
+            new = format_nul!("*{}*", _s(term));
         }
 
         let mut i = 0;
@@ -1405,10 +1513,9 @@ pub unsafe fn window_pane_search(
             }
 
             log_debug!("{}: {}", "window_pane_search", _s(line));
-            let found = if regex == 0 {
-                fnmatch(new, line, flags) == 0
-            } else {
-                regexec(&r, line, 0, null_mut(), 0) == 0
+            let found = match &r {
+                Some(r) => regex_exec(r, line, 0, &mut []),
+                None => fnmatch(new, line, flags) == 0,
             };
             free(line as _);
 
@@ -1417,11 +1524,7 @@ pub unsafe fn window_pane_search(
             }
         }
 
-        if regex == 0 {
-            free(new as _);
-        } else {
-            regfree(&raw mut r);
-        }
+        free(new as _);
 
         if i == screen_size_y(s) {
             return 0;
@@ -1916,3 +2019,59 @@ pub unsafe fn window_pane_mode(wp: *mut window_pane) -> i32 {
         WINDOW_PANE_NO_MODE
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_pane_events() {
+        unsafe {
+            let wp = xcalloc1::<window_pane>() as *mut window_pane;
+            let tl = &raw mut (*wp).timeline;
+
+            // Output in the same second is counted in one event.
+            loop {
+                let now = libc::time(null_mut());
+                window_pane_add_event(wp, window_pane_event_type::Bell, 0);
+                window_pane_add_event(wp, window_pane_event_type::Output, 10);
+                window_pane_add_event(wp, window_pane_event_type::Output, 5);
+                if libc::time(null_mut()) == now {
+                    break;
+                }
+                (*tl).next = 0;
+                (*tl).count = 0;
+            }
+            let events: Vec<_> = window_pane_events(wp).collect();
+            assert_eq!(events.len(), 2);
+            assert!(events[0].type_ == window_pane_event_type::Output);
+            assert_eq!(events[0].bytes, 15);
+            assert!(events[1].type_ == window_pane_event_type::Bell);
+            assert_eq!(window_pane_activity_rate(wp), 0);
+
+            // Only the newest events are kept, newest first.
+            for i in 0..WINDOW_PANE_EVENTS + 5 {
+                window_pane_add_event(wp, window_pane_event_type::Exit, i);
+            }
+            assert_eq!((*tl).count, WINDOW_PANE_EVENTS);
+            assert_eq!((*tl).next, 7);
+            let bytes: Vec<_> = window_pane_events(wp).map(|event| event.bytes).collect();
+            assert_eq!(bytes.len(), WINDOW_PANE_EVENTS);
+            assert_eq!(bytes[0], WINDOW_PANE_EVENTS + 4);
+            assert_eq!(bytes[WINDOW_PANE_EVENTS - 1], 5);
+
+            // The rate is output over the last minute.
+            let now = libc::time(null_mut());
+            (*tl).next = 0;
+            (*tl).count = 0;
+            for (time, bytes) in [(now - 120, 6000), (now - 30, 600), (now - 1, 1200)] {
+                window_pane_add_event(wp, window_pane_event_type::Output, bytes);
+                (*tl).events[((*tl).next + WINDOW_PANE_EVENTS - 1) % WINDOW_PANE_EVENTS].time =
+                    time;
+            }
+            assert_eq!(window_pane_activity_rate(wp), 30);
+
+            free_(wp);
+        }
+    }
+}
@@ -270,11 +270,18 @@ pub unsafe fn window_copy_clone_screen(
          * during resizing.
          */
         (*(*dst).grid).flags |= GRID_HISTORY;
-        grid_duplicate_lines((*dst).grid, 0, (*src).grid, 0, sy);
+
+        // Share rather than copy any lines spilled to disk.
+        let spilled = grid_duplicate_spill((*dst).grid, (*src).grid);
+        grid_duplicate_lines((*dst).grid, spilled, (*src).grid, spilled, sy - spilled);
 
         (*(*dst).grid).sy = sy - screen_hsize(src);
         (*(*dst).grid).hsize = screen_hsize(src);
         (*(*dst).grid).hscrolled = (*(*src).grid).hscrolled;
+
+        // Copy mode needs the whole history at the current width, but reflow
+        // the copy so the pane is not held up.
+        (*(*dst).grid).hreflow = (*(*src).grid).hreflow;
         if (*src).cy > (*(*dst).grid).sy - 1 {
             (*dst).cx = 0;
             (*dst).cy = (*(*dst).grid).sy - 1;
@@ -286,7 +293,8 @@ pub unsafe fn window_copy_clone_screen(
         if !cx.is_null() && !cy.is_null() {
             *cx = (*dst).cx;
             *cy = screen_hsize(dst) + (*dst).cy;
-            reflow = screen_size_x(hint) != screen_size_x(dst);
+            reflow = screen_size_x(hint) != screen_size_x(dst)
+                || (*(*dst).grid).hreflow > (*(*dst).grid).hspill;
         } else {
             reflow = false;
         }
@@ -294,6 +302,7 @@ pub unsafe fn window_copy_clone_screen(
             grid_wrap_position((*dst).grid, *cx, *cy, &raw mut wx, &raw mut wy);
         }
         screen_resize_cursor(dst, screen_size_x(hint), screen_size_y(hint), 1, 0, 0);
+        grid_reflow_finish((*dst).grid);
         if reflow {
             grid_unwrap_position((*dst).grid, cx, cy, wx, wy);
         }
@@ -889,6 +898,7 @@ pub unsafe fn window_copy_resize(wme: NonNull<window_mode_entry>, sx: u32, sy: u
             grid_wrap_position(gd, cx, cy, &raw mut wx, &raw mut wy);
         }
         screen_resize_cursor((*data).backing, sx, sy, 1, 0, 0);
+        grid_reflow_finish(gd);
         if reflow {
             grid_unwrap_position(gd, &raw mut cx, &raw mut cy, wx, wy);
         }
@@ -1241,6 +1251,43 @@ pub unsafe fn window_copy_cmd_copy_pipe_line_and_cancel(
     unsafe { window_copy_do_copy_line(cs, 1, 1) }
 }
 
+pub unsafe fn window_copy_cmd_copy_command_output(
+    cs: *mut window_copy_cmd_state,
+) -> window_copy_cmd_action {
+    unsafe {
+        let wme: *mut window_mode_entry = (*cs).wme;
+        let c: *mut client = (*cs).c;
+        let s: *mut session = (*cs).s;
+        let wl: *mut winlink = (*cs).wl;
+        let wp: *mut window_pane = (*wme).wp;
+        let data: *mut window_copy_mode_data = (*wme).data.cast();
+        let mut prefix = null_mut();
+        let arg1 = args_string((*cs).args, 1);
+
+        let ocx = (*data).cx;
+        let ocy = (*data).cy;
+        let ooy = (*data).oy;
+
+        if !window_copy_select_command(wme) {
+            return window_copy_cmd_action::WINDOW_COPY_CMD_NOTHING;
+        }
+        if !arg1.is_null() {
+            prefix = format_single(null_mut(), arg1, c, s, wl, wp);
+        }
+        if !s.is_null() {
+            window_copy_copy_selection(wme, prefix);
+        }
+        window_copy_clear_selection(wme);
+
+        (*data).cx = ocx;
+        (*data).cy = ocy;
+        (*data).oy = ooy;
+
+        free_(prefix);
+        window_copy_cmd_action::WINDOW_COPY_CMD_REDRAW
+    }
+}
+
 pub unsafe fn window_copy_cmd_copy_selection_no_clear(
     cs: *mut window_copy_cmd_state,
 ) -> window_copy_cmd_action {
@@ -2258,6 +2305,17 @@ pub unsafe fn window_copy_cmd_select_line(
     }
 }
 
+pub unsafe fn window_copy_cmd_select_command_output(
+    cs: *mut window_copy_cmd_state,
+) -> window_copy_cmd_action {
+    unsafe {
+        if !window_copy_select_command((*cs).wme) {
+            return window_copy_cmd_action::WINDOW_COPY_CMD_NOTHING;
+        }
+        window_copy_cmd_action::WINDOW_COPY_CMD_REDRAW
+    }
+}
+
 pub unsafe fn window_copy_cmd_select_word(
     cs: *mut window_copy_cmd_state,
 ) -> window_copy_cmd_action {
@@ -2578,6 +2636,15 @@ pub unsafe fn window_copy_cmd_previous_prompt(
     }
 }
 
+pub unsafe fn window_copy_cmd_next_failed_command(
+    cs: *mut window_copy_cmd_state,
+) -> window_copy_cmd_action {
+    unsafe {
+        window_copy_cursor_failed_command((*cs).wme);
+        window_copy_cmd_action::WINDOW_COPY_CMD_NOTHING
+    }
+}
+
 pub unsafe fn window_copy_cmd_search_backward(
     cs: *mut window_copy_cmd_state,
 ) -> window_copy_cmd_action {
@@ -2828,7 +2895,7 @@ struct window_copy_cmd_table_entry {
     f: unsafe fn(*mut window_copy_cmd_state) -> window_copy_cmd_action,
 }
 
-static window_copy_cmd_table: [window_copy_cmd_table_entry; 85] = [
+static window_copy_cmd_table: [window_copy_cmd_table_entry; 88] = [
     window_copy_cmd_table_entry {
         command: SyncCharPtr::new(c"append-selection"),
         minargs: 0,
@@ -2906,6 +2973,13 @@ static window_copy_cmd_table: [window_copy_cmd_table_entry; 85] = [
         clear: window_copy_cmd_clear::WINDOW_COPY_CMD_CLEAR_ALWAYS,
         f: window_copy_cmd_copy_pipe_end_of_line_and_cancel,
     },
+    window_copy_cmd_table_entry {
+        command: SyncCharPtr::new(c"copy-command-output"),
+        minargs: 0,
+        maxargs: 1,
+        clear: window_copy_cmd_clear::WINDOW_COPY_CMD_CLEAR_ALWAYS,
+        f: window_copy_cmd_copy_command_output,
+    },
     window_copy_cmd_table_entry {
         command: SyncCharPtr::new(c"copy-line"),
         minargs: 0,
@@ -3123,6 +3197,13 @@ static window_copy_cmd_table: [window_copy_cmd_table_entry; 85] = [
         clear: window_copy_cmd_clear::WINDOW_COPY_CMD_CLEAR_ALWAYS,
         f: window_copy_cmd_previous_prompt,
     },
+    window_copy_cmd_table_entry {
+        command: SyncCharPtr::new(c"next-failed-command"),
+        minargs: 0,
+        maxargs: 0,
+        clear: window_copy_cmd_clear::WINDOW_COPY_CMD_CLEAR_ALWAYS,
+        f: window_copy_cmd_next_failed_command,
+    },
     window_copy_cmd_table_entry {
         command: SyncCharPtr::new(c"middle-line"),
         minargs: 0,
@@ -3375,6 +3456,13 @@ static window_copy_cmd_table: [window_copy_cmd_table_entry; 85] = [
         clear: window_copy_cmd_clear::WINDOW_COPY_CMD_CLEAR_ALWAYS,
         f: window_copy_cmd_search_reverse,
     },
+    window_copy_cmd_table_entry {
+        command: SyncCharPtr::new(c"select-command-output"),
+        minargs: 0,
+        maxargs: 0,
+        clear: window_copy_cmd_clear::WINDOW_COPY_CMD_CLEAR_ALWAYS,
+        f: window_copy_cmd_select_command_output,
+    },
     window_copy_cmd_table_entry {
         command: SyncCharPtr::new(c"select-line"),
         minargs: 0,
@@ -3542,6 +3630,40 @@ pub unsafe fn window_copy_scroll_to(
     }
 }
 
+/// Move to a match found outside copy mode at px and py in the backing grid
+/// and mark it and the other matches.
+pub unsafe fn window_copy_show_match(
+    wme: *mut window_mode_entry,
+    px: u32,
+    py: u32,
+    str: *const c_char,
+    regex: i32,
+) {
+    unsafe {
+        let wp: *mut window_pane = (*wme).wp;
+        let data: *mut window_copy_mode_data = (*wme).data.cast();
+        let s: *mut screen = (*data).backing;
+
+        if py >= screen_hsize(s) + screen_size_y(s) {
+            return;
+        }
+
+        free_((*data).searchstr);
+        (*data).searchstr = xstrdup(str).as_ptr();
+        (*data).searchtype = window_copy::WINDOW_COPY_SEARCHDOWN;
+        (*data).searchregex = regex;
+        (*data).timeout = 0;
+
+        free_((*wp).searchstr);
+        (*wp).searchstr = xstrdup(str).as_ptr();
+        (*wp).searchregex = regex;
+
+        window_copy_scroll_to(wme, px, py, true);
+        window_copy_search_marks(wme, null_mut(), regex, 0);
+        window_copy_redraw_screen(wme);
+    }
+}
+
 pub unsafe fn window_copy_search_compare(
     gd: *mut grid,
     px: u32,
@@ -3591,8 +3713,7 @@ pub unsafe fn window_copy_search_lr(
         let endline = (*gd).hsize + (*gd).sy - 1;
         for ax in first..last {
             let mut bx = 0;
-            for bx_ in 0..(*sgd).sx {
-                bx = bx_;
+            while bx < (*sgd).sx {
                 let mut px = ax + bx;
                 let mut pywrap = py;
                 /* Wrap line. */
@@ -3612,6 +3733,7 @@ pub unsafe fn window_copy_search_lr(
                 if !matched {
                     break;
                 }
+                bx += 1;
             }
             if bx == (*sgd).sx {
                 *ppx = ax;
@@ -3641,8 +3763,7 @@ pub unsafe fn window_copy_search_rl(
         let mut ax = last;
         while ax > first {
             let mut bx = 0;
-            for bx_ in 0..(*sgd).sx {
-                bx = bx_;
+            while bx < (*sgd).sx {
                 let mut px = ax - 1 + bx;
                 let mut pywrap = py;
                 /* Wrap line. */
@@ -3662,6 +3783,7 @@ pub unsafe fn window_copy_search_rl(
                 if !matched {
                     break;
                 }
+                bx += 1;
             }
             if bx == (*sgd).sx {
                 *ppx = ax - 1;
@@ -3680,15 +3802,14 @@ pub unsafe fn window_copy_search_lr_regex(
     py: u32,
     first: u32,
     last: u32,
-    reg: *mut libc::regex_t,
+    reg: &regex_pattern,
 ) -> i32 {
     unsafe {
-        let mut eflags = 0;
         let mut size: u32 = 1;
         // u_int endline, foundx, foundy, len, pywrap, size = 1;
         // char *buf;
         // regmatch_t regmatch;
-        let mut regmatch: libc::regmatch_t = zeroed();
+        let mut regmatch = [regex_match::empty()];
         // struct grid_line *gl;
 
         /*
@@ -3699,14 +3820,14 @@ pub unsafe fn window_copy_search_lr_regex(
             return 0;
         }
 
-        /* Set flags for regex search. */
-        if first != 0 {
-            eflags |= libc::REG_NOTBOL;
-        }
-
-        /* Need to look at the entire string. */
+        /*
+         * Need to look at the entire string. The part of the line before
+         * first is included so the pattern can see what precedes the match.
+         */
         let mut buf = xmalloc(size as usize).cast::<i8>().as_ptr();
         *buf = b'\0' as i8;
+        buf = window_copy_stringify(gd, py, 0, first, buf, &raw mut size);
+        let start = size as usize - 1;
         buf = window_copy_stringify(gd, py, first, (*gd).sx, buf, &raw mut size);
         let mut len = (*gd).sx - first;
         let endline = (*gd).hsize + (*gd).sy - 1;
@@ -3721,9 +3842,7 @@ pub unsafe fn window_copy_search_lr_regex(
             len += (*gd).sx;
         }
 
-        if libc::regexec(reg, buf, 1, &raw mut regmatch, eflags) == 0
-            && regmatch.rm_so != regmatch.rm_eo
-        {
+        if regex_exec(reg, buf, start, &mut regmatch) && regmatch[0].so != regmatch[0].eo {
             let mut foundx = first;
             let mut foundy = py;
             window_copy_cstrtocellpos(
@@ -3731,7 +3850,7 @@ pub unsafe fn window_copy_search_lr_regex(
                 len,
                 &raw mut foundx,
                 &raw mut foundy,
-                buf.add(regmatch.rm_so as usize),
+                buf.add(start + regmatch[0].so as usize),
             );
             if foundy == py && foundx < last {
                 *ppx = foundx;
@@ -3741,7 +3860,7 @@ pub unsafe fn window_copy_search_lr_regex(
                     len,
                     &raw mut foundx,
                     &raw mut foundy,
-                    buf.add(regmatch.rm_eo as usize),
+                    buf.add(start + regmatch[0].eo as usize),
                 );
                 *psx = foundx;
                 while foundy > py {
@@ -3768,23 +3887,22 @@ pub unsafe fn window_copy_search_rl_regex(
     py: u32,
     first: u32,
     last: u32,
-    reg: *mut libc::regex_t,
+    reg: &regex_pattern,
 ) -> i32 {
     unsafe {
-        let mut eflags = 0;
         let mut size: u32 = 1;
         // u_int endline, len, pywrap, size = 1;
         // char *buf;
         // struct grid_line *gl;
 
-        /* Set flags for regex search. */
-        if first != 0 {
-            eflags |= libc::REG_NOTBOL;
-        }
-
-        /* Need to look at the entire string. */
+        /*
+         * Need to look at the entire string. The part of the line before
+         * first is included so the pattern can see what precedes the match.
+         */
         let mut buf = xmalloc(size as usize).cast::<i8>().as_ptr();
         *buf = b'\0' as i8;
+        buf = window_copy_stringify(gd, py, 0, first, buf, &raw mut size);
+        let start = size as usize - 1;
         buf = window_copy_stringify(gd, py, first, (*gd).sx, buf, &raw mut size);
         let mut len = (*gd).sx - first;
         let endline = (*gd).hsize + (*gd).sy - 1;
@@ -3799,7 +3917,7 @@ pub unsafe fn window_copy_search_rl_regex(
             len += (*gd).sx;
         }
 
-        if window_copy_last_regex(gd, py, first, last, len, ppx, psx, buf, reg, eflags) != 0 {
+        if window_copy_last_regex(gd, py, first, last, len, ppx, psx, buf, reg, start) != 0 {
             free_(buf);
             return 1;
         }
@@ -3869,21 +3987,22 @@ pub unsafe fn window_copy_last_regex(
     ppx: *mut u32,
     psx: *mut u32,
     buf: *const c_char,
-    preg: *const libc::regex_t,
-    eflags: i32,
+    preg: &regex_pattern,
+    start: usize,
 ) -> i32 {
     unsafe {
         let oldx = 0;
-        let mut px = 0;
+        let mut px = start;
         let mut savepx = 0;
         let mut savesx = 0;
-        let mut regmatch: libc::regmatch_t = zeroed();
+        let mut regmatch = [regex_match::empty()];
 
         let mut foundx = first;
         let mut foundy = py;
         let mut oldx = first;
-        while libc::regexec(preg, buf.add(px), 1, &raw mut regmatch, eflags) == 0 {
-            if regmatch.rm_so == regmatch.rm_eo {
+        while regex_exec(preg, buf, px, &mut regmatch) {
+            let regmatch = regmatch[0];
+            if regmatch.so == regmatch.eo {
                 break;
             }
             window_copy_cstrtocellpos(
@@ -3891,7 +4010,7 @@ pub unsafe fn window_copy_last_regex(
                 len,
                 &raw mut foundx,
                 &raw mut foundy,
-                buf.add(px + regmatch.rm_so as usize),
+                buf.add(px + regmatch.so as usize),
             );
             if foundy > py || foundx >= last {
                 break;
@@ -3903,7 +4022,7 @@ pub unsafe fn window_copy_last_regex(
                 len,
                 &raw mut foundx,
                 &raw mut foundy,
-                buf.add(px + regmatch.rm_eo as usize),
+                buf.add(px + regmatch.eo as usize),
             );
             if foundy > py || foundx >= last {
                 *ppx = savepx;
@@ -3919,7 +4038,7 @@ pub unsafe fn window_copy_last_regex(
                 len -= savesx;
                 oldx = foundx;
             }
-            px += regmatch.rm_eo as usize;
+            px += regmatch.eo as usize;
         }
 
         if savesx > 0 {
@@ -4154,7 +4273,7 @@ pub unsafe fn window_copy_is_lowercase(mut ptr: *const c_char) -> bool {
 
 pub unsafe fn window_copy_search_back_overlap(
     gd: *mut grid,
-    preg: *mut libc::regex_t,
+    preg: &regex_pattern,
     ppx: *mut u32,
     psx: *mut u32,
     ppy: *mut u32,
@@ -4242,9 +4361,9 @@ pub unsafe fn window_copy_search_jump(
         // u_int i, px, sx;
         let mut ssize: u32 = 1;
         let mut found = 0;
-        let mut cflags = libc::REG_EXTENDED;
+        let mut rflags = regex_flags::empty();
 
-        let mut reg: libc::regex_t = zeroed();
+        let mut reg = None;
         // char *sbuf;
         // regex_t reg;
 
@@ -4254,13 +4373,13 @@ pub unsafe fn window_copy_search_jump(
             *sbuf = b'\0' as i8;
             sbuf = window_copy_stringify(sgd, 0, 0, (*sgd).sx, sbuf, &raw mut ssize);
             if cis != 0 {
-                cflags |= REG_ICASE;
+                rflags |= regex_flags::REGEX_ICASE;
             }
-            if libc::regcomp(&raw mut reg, sbuf, cflags) != 0 {
-                free_(sbuf);
+            reg = regex_compile(sbuf, regex_get_syntax(), rflags);
+            free_(sbuf);
+            if reg.is_none() {
                 return 0;
             }
-            free_(sbuf);
         }
 
         let mut i = 0;
@@ -4268,7 +4387,7 @@ pub unsafe fn window_copy_search_jump(
             for i_ in fy..=endline {
                 i = i_;
 
-                if regex != 0 {
+                if let Some(reg) = &reg {
                     found = window_copy_search_lr_regex(
                         gd,
                         &raw mut px,
@@ -4276,7 +4395,7 @@ pub unsafe fn window_copy_search_jump(
                         i,
                         fx,
                         (*gd).sx,
-                        &raw mut reg,
+                        reg,
                     );
                 } else {
                     found = window_copy_search_lr(gd, sgd, &raw mut px, i, fx, (*gd).sx, cis);
@@ -4289,7 +4408,7 @@ pub unsafe fn window_copy_search_jump(
         } else {
             i = fy + 1;
             while endline < i {
-                if regex != 0 {
+                if let Some(reg) = &reg {
                     found = window_copy_search_rl_regex(
                         gd,
                         &raw mut px,
@@ -4297,12 +4416,12 @@ pub unsafe fn window_copy_search_jump(
                         i - 1,
                         0,
                         fx + 1,
-                        &raw mut reg,
+                        reg,
                     );
                     if found != 0 {
                         window_copy_search_back_overlap(
                             gd,
-                            &raw mut reg,
+                            reg,
                             &raw mut px,
                             &raw mut sx,
                             &raw mut i,
@@ -4320,9 +4439,7 @@ pub unsafe fn window_copy_search_jump(
                 i -= 1;
             }
         }
-        if regex != 0 {
-            libc::regfree(&raw mut reg);
-        }
+        drop(reg);
 
         if found != 0 {
             window_copy_scroll_to(wme, px, i, true);
@@ -4518,7 +4635,7 @@ pub unsafe fn window_copy_search(
                 if keys == Ok(modekey::MODEKEY_EMACS) {
                     window_copy_move_after_search_mark(data, &raw mut fx, &raw mut fy, wrapflag);
                     (*data).cx = fx;
-                    (*data).cy = fy - screen_hsize((*data).backing) + (*data).oy;
+                    (*data).cy = fy + (*data).oy - screen_hsize((*data).backing);
                 }
             } else {
                 /*
@@ -4532,7 +4649,7 @@ pub unsafe fn window_copy_search(
                             == *(*data).searchmark.add(start as usize)
                     {
                         (*data).cx = fx;
-                        (*data).cy = fy - screen_hsize((*data).backing) + (*data).oy;
+                        (*data).cy = fy + (*data).oy - screen_hsize((*data).backing);
                         if at == 0 {
                             break;
                         }
@@ -4607,7 +4724,7 @@ pub unsafe fn window_copy_search_marks(
         let mut cis: i32 = 0;
         let mut stopped: i32 = 0;
 
-        let mut cflags = libc::REG_EXTENDED;
+        let mut rflags = regex_flags::empty();
         let mut px: u32;
         let mut py: u32;
         let mut b: u32 = 0;
@@ -4619,7 +4736,7 @@ pub unsafe fn window_copy_search_marks(
         let mut end: u32 = 0;
 
         let mut sbuf = null_mut();
-        let mut reg: libc::regex_t = zeroed();
+        let mut reg = None;
         let mut stop: u64 = 0;
         let mut tstart: u64 = 0;
         let mut t: u64 = 0;
@@ -4655,13 +4772,13 @@ pub unsafe fn window_copy_search_marks(
                     &raw mut ssize,
                 );
                 if cis != 0 {
-                    cflags |= REG_ICASE;
+                    rflags |= regex_flags::REGEX_ICASE;
                 }
-                if libc::regcomp(&raw mut reg, sbuf, cflags) != 0 {
-                    free_(sbuf);
+                reg = regex_compile(sbuf, regex_get_syntax(), rflags);
+                free_(sbuf);
+                if reg.is_none() {
                     return 0;
                 }
-                free_(sbuf);
             }
             tstart = get_timer();
 
@@ -4683,7 +4800,7 @@ pub unsafe fn window_copy_search_marks(
                 for py in start..end {
                     px = 0;
                     loop {
-                        if regex != 0 {
+                        if let Some(reg) = &reg {
                             found = window_copy_search_lr_regex(
                                 gd,
                                 &raw mut px,
@@ -4691,7 +4808,7 @@ pub unsafe fn window_copy_search_marks(
                                 py,
                                 px,
                                 (*gd).sx,
-                                &raw mut reg,
+                                reg,
                             );
                             if found == 0 {
                                 break;
@@ -4777,9 +4894,6 @@ pub unsafe fn window_copy_search_marks(
         if ssp == &raw mut ss {
             screen_free(&raw mut ss);
         }
-        if regex != 0 {
-            libc::regfree(&raw mut reg);
-        }
         1
     }
 }
@@ -6464,7 +6578,7 @@ pub unsafe fn window_copy_cursor_prompt(
             }
             line += add as u32;
 
-            if (*grid_get_line(gd, line)).flags.intersects(line_flag) {
+            if (*grid_get_line_marks(gd, line)).flags.intersects(line_flag) {
                 break;
             }
         }
@@ -6483,6 +6597,79 @@ pub unsafe fn window_copy_cursor_prompt(
     }
 }
 
+/// Select the output of the command at the cursor, marked by OSC 133.
+pub unsafe fn window_copy_select_command(wme: *mut window_mode_entry) -> bool {
+    unsafe {
+        let data: *mut window_copy_mode_data = (*wme).data.cast();
+        let gd: *mut grid = (*(*data).backing).grid;
+        let py = (*gd).hsize + (*data).cy - (*data).oy;
+
+        let Some((start, end)) = grid_find_command(gd, py) else {
+            return false;
+        };
+        if start == end {
+            return false;
+        }
+
+        (*data).lineflag = line_sel::LINE_SEL_NONE;
+        (*data).rectflag = 0;
+        (*data).selflag = selflag::SEL_CHAR;
+        window_copy_scroll_to(wme, 0, start, true);
+        window_copy_start_selection(wme);
+        window_copy_scroll_to(wme, 0, end - 1, true);
+        window_copy_cursor_end_of_line(wme);
+        true
+    }
+}
+
+/// Move to the prompt of the next command which failed, wrapping around to
+/// the top if wrap-search is on.
+pub unsafe fn window_copy_cursor_failed_command(wme: *mut window_mode_entry) {
+    unsafe {
+        let wp: *mut window_pane = (*wme).wp;
+        let data: *mut window_copy_mode_data = (*wme).data.cast();
+        let gd: *mut grid = (*(*data).backing).grid;
+        let total = (*gd).hsize + (*gd).sy;
+        let py = (*gd).hsize + (*data).cy - (*data).oy;
+
+        let failed = |yy: u32| {
+            let gl = grid_get_line_marks(gd, yy);
+            (*gl).flags.intersects(grid_line_flag::START_OUTPUT)
+                && !(*gl).command.is_null()
+                && (*(*gl).command).status.is_some_and(|status| status != 0)
+        };
+
+        // Skip the command at the cursor.
+        let first = match grid_find_command(gd, py) {
+            Some((start, _)) => start.max(py) + 1,
+            None => py + 1,
+        };
+        let mut found = (first..total).find(|yy| failed(*yy));
+        if found.is_none() && options_get_number_((*(*wp).window).options, c"wrap-search") != 0 {
+            found = (0..first.min(total)).find(|yy| failed(*yy));
+        }
+        let Some(output) = found else {
+            return;
+        };
+
+        // Move to the prompt if there is one.
+        let mut line = output;
+        for yy in (0..output).rev() {
+            let flags = (*grid_get_line_marks(gd, yy)).flags;
+            if flags.intersects(grid_line_flag::START_PROMPT | grid_line_flag::START_INPUT) {
+                line = yy;
+                if flags.intersects(grid_line_flag::START_PROMPT) {
+                    break;
+                }
+            }
+            if flags.intersects(grid_line_flag::START_OUTPUT | grid_line_flag::END_OUTPUT) {
+                break;
+            }
+        }
+        window_copy_scroll_to(wme, 0, line, false);
+    }
+}
+
 pub unsafe fn window_copy_scroll_up(wme: *mut window_mode_entry, mut ny: u32) {
     unsafe {
         let wp: *mut window_pane = (*wme).wp;
@@ -6804,7 +6991,7 @@ pub unsafe fn window_copy_acquire_cursor_down(
     no_reset: i32,
 ) {
     unsafe {
-        let cy = py - hsize + oy;
+        let cy = py + oy - hsize;
         let yy = sy - 1;
         let mut ny;
         let nd;
@@ -0,0 +1,782 @@
+use crate::*;
+
+use std::ffi::CString;
+
+use crate::compat::tree::rb_foreach;
+use crate::xmalloc::xreallocarray;
+
+const WINDOW_FUZZY_TARGET_COMMAND: &CStr = c"switch-client -Zt '%%'";
+const WINDOW_FUZZY_BUFFER_COMMAND: &CStr = c"paste-buffer -p -b '%%'";
+const WINDOW_FUZZY_COMMAND_COMMAND: &CStr = c"command-prompt -I '%% '";
+
+const WINDOW_FUZZY_SESSION_FORMAT: &CStr = c"#{session_name}";
+const WINDOW_FUZZY_WINDOW_FORMAT: &CStr = c"#{session_name}:#{window_index} #{window_name}";
+const WINDOW_FUZZY_PANE_FORMAT: &CStr =
+    c"#{session_name}:#{window_index}.#{pane_index} #{pane_current_command}";
+const WINDOW_FUZZY_BUFFER_FORMAT: &CStr = c"#{buffer_name} #{buffer_sample}";
+
+// Scores used to rank matches: each matched character is worth a fixed
+// amount, with bonuses for runs and word starts and a penalty for gaps.
+const WINDOW_FUZZY_SCORE_MATCH: i32 = 16;
+const WINDOW_FUZZY_BONUS_CONSECUTIVE: i32 = 8;
+const WINDOW_FUZZY_BONUS_BOUNDARY: i32 = 8;
+const WINDOW_FUZZY_BONUS_CAMEL: i32 = 7;
+const WINDOW_FUZZY_PENALTY_GAP_START: i32 = 3;
+const WINDOW_FUZZY_PENALTY_GAP_EXTENSION: i32 = 1;
+
+pub static window_fuzzy_mode: window_mode = window_mode {
+    name: SyncCharPtr::new(c"fuzzy-mode"),
+
+    init: Some(window_fuzzy_init),
+    free: Some(window_fuzzy_free),
+    resize: Some(window_fuzzy_resize),
+    update: Some(window_fuzzy_update),
+    key: Some(window_fuzzy_key),
+    ..unsafe { zeroed() }
+};
+
+bitflags::bitflags! {
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Eq, PartialEq)]
+    pub struct window_fuzzy_types: u32 {
+        const WINDOW_FUZZY_SESSIONS = 0x1;
+        const WINDOW_FUZZY_WINDOWS = 0x2;
+        const WINDOW_FUZZY_PANES = 0x4;
+        const WINDOW_FUZZY_BUFFERS = 0x8;
+        const WINDOW_FUZZY_COMMANDS = 0x10;
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum window_fuzzy_type {
+    WINDOW_FUZZY_SESSION,
+    WINDOW_FUZZY_WINDOW,
+    WINDOW_FUZZY_PANE,
+    WINDOW_FUZZY_BUFFER,
+    WINDOW_FUZZY_COMMAND,
+}
+
+struct window_fuzzy_itemdata {
+    type_: window_fuzzy_type,
+
+    session: u32,
+    winlink: i32,
+    pane: u32,
+    entry: *const cmd_entry,
+
+    /// Replaces %% in the template.
+    target: CString,
+    /// Text matched against the query.
+    label: Vec<char>,
+    /// Shown after the label and not matched.
+    detail: String,
+    /// Pane contents, matched as well as the label.
+    lines: Vec<Vec<char>>,
+
+    score: i32,
+    line: Option<usize>,
+    positions: Vec<usize>,
+}
+
+pub struct window_fuzzy_modedata {
+    wp: *mut window_pane,
+
+    data: *mut mode_tree_data,
+    command: *mut c_char,
+    types: window_fuzzy_types,
+
+    query: Vec<u8>,
+    items: Vec<window_fuzzy_itemdata>,
+}
+
+fn window_fuzzy_is_boundary(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            '-' | '_' | '.' | ':' | '/' | '\\' | ',' | '=' | '%' | '$'
+        )
+}
+
+/// Score text against a pattern, returning the score and the position of each
+/// pattern character in the text, or None if they do not all appear in order.
+/// Lowercase patterns match either case. Every alignment is tried so that
+/// word starts and runs win over the first occurrence.
+pub fn window_fuzzy_score(pattern: &[char], text: &[char]) -> Option<(i32, Vec<usize>)> {
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let sensitive = pattern.iter().any(|c| c.is_uppercase());
+    let eq = |a: char, b: char| {
+        if sensitive {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+    let bonus = |j: usize| {
+        if j == 0 || window_fuzzy_is_boundary(text[j - 1]) {
+            WINDOW_FUZZY_BONUS_BOUNDARY
+        } else if text[j].is_uppercase() && text[j - 1].is_lowercase() {
+            WINDOW_FUZZY_BONUS_CAMEL
+        } else {
+            0
+        }
+    };
+
+    // best[i][j] is the score of matching the first i + 1 pattern characters
+    // with the last at j, and from[i][j] where the one before it was.
+    let (m, n) = (pattern.len(), text.len());
+    let mut best = vec![vec![None::<i32>; n]; m];
+    let mut from = vec![vec![0usize; n]; m];
+    for (j, &c) in text.iter().enumerate() {
+        if eq(pattern[0], c) {
+            best[0][j] = Some(WINDOW_FUZZY_SCORE_MATCH + 2 * bonus(j));
+        }
+    }
+    for i in 1..m {
+        // The best earlier position to jump from with a gap, adjusted so the
+        // gap penalty can be applied once the end is known.
+        let mut gap: Option<(i32, usize)> = None;
+        for j in i..n {
+            if j >= 2
+                && let Some(prev) = best[i - 1][j - 2]
+            {
+                let adjusted = prev + WINDOW_FUZZY_PENALTY_GAP_EXTENSION * (j - 2) as i32;
+                if gap.is_none_or(|(b, _)| adjusted > b) {
+                    gap = Some((adjusted, j - 2));
+                }
+            }
+            if !eq(pattern[i], text[j]) {
+                continue;
+            }
+            let mut link = None;
+            if let Some(prev) = best[i - 1][j - 1] {
+                link = Some((prev + WINDOW_FUZZY_BONUS_CONSECUTIVE, j - 1));
+            }
+            if let Some((adjusted, k)) = gap {
+                let score = adjusted
+                    - WINDOW_FUZZY_PENALTY_GAP_START
+                    - WINDOW_FUZZY_PENALTY_GAP_EXTENSION * (j - 2) as i32;
+                if link.is_none_or(|(b, _)| score > b) {
+                    link = Some((score, k));
+                }
+            }
+            if let Some((score, k)) = link {
+                best[i][j] = Some(score + WINDOW_FUZZY_SCORE_MATCH + bonus(j));
+                from[i][j] = k;
+            }
+        }
+    }
+
+    let mut end = None;
+    for (j, &score) in best[m - 1].iter().enumerate() {
+        if let Some(score) = score
+            && end.is_none_or(|(_, b)| score > b)
+        {
+            end = Some((j, score));
+        }
+    }
+    let (mut j, score) = end?;
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+    Some((score, positions))
+}
+
+/// Append text for format_draw, marking matched characters with style.
+fn window_fuzzy_markup(out: &mut String, text: &[char], positions: &[usize], style: &str) {
+    let mut on = false;
+    for (i, &c) in text.iter().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != on {
+            if matched {
+                out.push_str("#[");
+                out.push_str(style);
+                out.push(']');
+            } else {
+                out.push_str("#[default]");
+            }
+            on = matched;
+        }
+        if c == '#' {
+            out.push('#');
+        }
+        out.push(c);
+    }
+    if on {
+        out.push_str("#[default]");
+    }
+}
+
+unsafe fn window_fuzzy_format(
+    fmt: &CStr,
+    s: *mut session,
+    wl: *mut winlink,
+    wp: *mut window_pane,
+) -> Vec<char> {
+    unsafe {
+        let text = format_single(null_mut(), fmt.as_ptr(), null_mut(), s, wl, wp);
+        let label = String::from_utf8_lossy(CStr::from_ptr(text).to_bytes())
+            .chars()
+            .collect();
+        free_(text);
+        label
+    }
+}
+
+fn window_fuzzy_add_item(
+    data: &mut window_fuzzy_modedata,
+    type_: window_fuzzy_type,
+    target: String,
+    label: Vec<char>,
+) -> &mut window_fuzzy_itemdata {
+    data.items.push(window_fuzzy_itemdata {
+        type_,
+        session: 0,
+        winlink: -1,
+        pane: 0,
+        entry: null(),
+        target: CString::new(target).unwrap_or_default(),
+        label,
+        detail: String::new(),
+        lines: Vec::new(),
+        score: 0,
+        line: None,
+        positions: Vec::new(),
+    });
+    data.items.last_mut().unwrap()
+}
+
+/// Collect the visible lines of a pane.
+unsafe fn window_fuzzy_pane_lines(wp: *mut window_pane) -> Vec<Vec<char>> {
+    unsafe {
+        let s = &raw mut (*wp).base;
+        let gd = (*s).grid;
+        let mut gc: *mut grid_cell = null_mut();
+        let mut lines = Vec::new();
+
+        for y in 0..screen_size_y(s) {
+            let cp = grid_string_cells(
+                gd,
+                0,
+                (*gd).hsize + y,
+                screen_size_x(s),
+                &raw mut gc,
+                grid_string_flags::GRID_STRING_TRIM_SPACES,
+                null_mut(),
+            );
+            let line = String::from_utf8_lossy(CStr::from_ptr(cp).to_bytes())
+                .trim()
+                .chars()
+                .collect::<Vec<char>>();
+            free_(cp);
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+}
+
+unsafe fn window_fuzzy_collect(data: &mut window_fuzzy_modedata) {
+    unsafe {
+        let types = data.types;
+
+        for s in rb_foreach(&raw mut sessions).map(NonNull::as_ptr) {
+            if types.intersects(window_fuzzy_types::WINDOW_FUZZY_SESSIONS) {
+                let label = window_fuzzy_format(
+                    WINDOW_FUZZY_SESSION_FORMAT,
+                    s,
+                    (*s).curw,
+                    (*(*(*s).curw).window).active,
+                );
+                let target = format!("={}:", _s((*s).name));
+                let item = window_fuzzy_add_item(
+                    data,
+                    window_fuzzy_type::WINDOW_FUZZY_SESSION,
+                    target,
+                    label,
+                );
+                item.session = (*s).id;
+            }
+            for wl in rb_foreach(&raw mut (*s).windows).map(NonNull::as_ptr) {
+                let w = (*wl).window;
+                if types.intersects(window_fuzzy_types::WINDOW_FUZZY_WINDOWS) {
+                    let label = window_fuzzy_format(WINDOW_FUZZY_WINDOW_FORMAT, s, wl, (*w).active);
+                    let target = format!("={}:{}.", _s((*s).name), (*wl).idx);
+                    let item = window_fuzzy_add_item(
+                        data,
+                        window_fuzzy_type::WINDOW_FUZZY_WINDOW,
+                        target,
+                        label,
+                    );
+                    item.session = (*s).id;
+                    item.winlink = (*wl).idx;
+                }
+                if !types.intersects(window_fuzzy_types::WINDOW_FUZZY_PANES) {
+                    continue;
+                }
+                for wp in tailq_foreach::<_, discr_entry>(&raw mut (*w).panes).map(NonNull::as_ptr)
+                {
+                    let label = window_fuzzy_format(WINDOW_FUZZY_PANE_FORMAT, s, wl, wp);
+                    let target = format!("={}:{}.%{}", _s((*s).name), (*wl).idx, (*wp).id);
+                    let lines = window_fuzzy_pane_lines(wp);
+                    let item = window_fuzzy_add_item(
+                        data,
+                        window_fuzzy_type::WINDOW_FUZZY_PANE,
+                        target,
+                        label,
+                    );
+                    item.session = (*s).id;
+                    item.winlink = (*wl).idx;
+                    item.pane = (*wp).id;
+                    item.lines = lines;
+                }
+            }
+        }
+
+        if types.intersects(window_fuzzy_types::WINDOW_FUZZY_BUFFERS) {
+            let mut pb = paste_walk(null_mut());
+            while let Some(pb_non_null) = NonNull::new(pb) {
+                let ft = format_create(null_mut(), null_mut(), FORMAT_NONE, format_flags::empty());
+                format_defaults_paste_buffer(ft, pb);
+                let text = format_expand(ft, WINDOW_FUZZY_BUFFER_FORMAT.as_ptr());
+                let label = String::from_utf8_lossy(CStr::from_ptr(text).to_bytes())
+                    .chars()
+                    .collect();
+                free_(text);
+                format_free(ft);
+
+                let target = cstr_to_str(paste_buffer_name(pb_non_null)).to_string();
+                window_fuzzy_add_item(data, window_fuzzy_type::WINDOW_FUZZY_BUFFER, target, label);
+                pb = paste_walk(pb);
+            }
+        }
+
+        if types.intersects(window_fuzzy_types::WINDOW_FUZZY_COMMANDS) {
+            for entry in cmd_entries() {
+                let mut label = cstr_to_str((*entry).name).to_string();
+                if !(*entry).alias.is_null() {
+                    label.push_str(&format!(" ({})", _s((*entry).alias)));
+                }
+                let target = cstr_to_str((*entry).name).to_string();
+                let item = window_fuzzy_add_item(
+                    data,
+                    window_fuzzy_type::WINDOW_FUZZY_COMMAND,
+                    target,
+                    label.chars().collect(),
+                );
+                item.entry = entry;
+                item.detail = cstr_to_str((*entry).usage).to_string();
+            }
+        }
+    }
+}
+
+unsafe fn window_fuzzy_build(
+    modedata: NonNull<c_void>,
+    _sort_crit: *mut mode_tree_sort_criteria,
+    _tag: *mut u64,
+    filter: *const c_char,
+) {
+    unsafe {
+        let data = &mut *modedata.cast::<window_fuzzy_modedata>().as_ptr();
+        let oo = (*(*data.wp).window).options;
+
+        data.items.clear();
+        window_fuzzy_collect(data);
+
+        let pattern: Vec<char> = if filter.is_null() {
+            Vec::new()
+        } else {
+            String::from_utf8_lossy(CStr::from_ptr(filter).to_bytes())
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect()
+        };
+
+        // Score each item by its label or best matching line.
+        let mut order = Vec::with_capacity(data.items.len());
+        for (i, item) in data.items.iter_mut().enumerate() {
+            let mut best = window_fuzzy_score(&pattern, &item.label).map(|m| (m, None));
+            for (j, line) in item.lines.iter().enumerate() {
+                if let Some(m) = window_fuzzy_score(&pattern, line)
+                    && best.as_ref().is_none_or(|((score, _), _)| m.0 > *score)
+                {
+                    best = Some((m, Some(j)));
+                }
+            }
+            if let Some(((score, positions), line)) = best {
+                item.score = score;
+                item.positions = positions;
+                item.line = line;
+                order.push(i);
+            }
+        }
+        if !pattern.is_empty() {
+            order.sort_by(|&a, &b| {
+                let (a, b) = (&data.items[a], &data.items[b]);
+                b.score
+                    .cmp(&a.score)
+                    .then(a.label.len().cmp(&b.label.len()))
+            });
+        }
+
+        let style = cstr_to_str(options_get_string_(oo, c"fuzzy-match-style"));
+        for i in order {
+            let item = &mut data.items[i];
+
+            let mut text = String::new();
+            match item.line {
+                Some(line) => {
+                    window_fuzzy_markup(&mut text, &item.label, &[], style);
+                    text.push_str("  ");
+                    window_fuzzy_markup(&mut text, &item.lines[line], &item.positions, style);
+                }
+                None => window_fuzzy_markup(&mut text, &item.label, &item.positions, style),
+            }
+            if !item.detail.is_empty() {
+                text.push_str("  ");
+                text.push_str(&item.detail.replace('#', "##"));
+            }
+            let text = CString::new(text).unwrap_or_default();
+
+            let name = match item.type_ {
+                window_fuzzy_type::WINDOW_FUZZY_SESSION => c"session",
+                window_fuzzy_type::WINDOW_FUZZY_WINDOW => c"window",
+                window_fuzzy_type::WINDOW_FUZZY_PANE => c"pane",
+                window_fuzzy_type::WINDOW_FUZZY_BUFFER => c"buffer",
+                window_fuzzy_type::WINDOW_FUZZY_COMMAND => c"command",
+            };
+            mode_tree_add(
+                data.data,
+                null_mut(),
+                (&raw mut *item).cast(),
+                i as u64,
+                name.as_ptr(),
+                text.as_ptr(),
+                -1,
+            );
+        }
+    }
+}
+
+unsafe fn window_fuzzy_draw(
+    _modedata: *mut c_void,
+    itemdata: Option<NonNull<c_void>>,
+    ctx: *mut screen_write_ctx,
+    sx: u32,
+    sy: u32,
+) {
+    unsafe {
+        let Some(item) = itemdata.map(NonNull::cast::<window_fuzzy_itemdata>) else {
+            return;
+        };
+        let item = &*item.as_ptr();
+        let cx = (*(*ctx).s).cx;
+        let cy = (*(*ctx).s).cy;
+
+        let mut wp: *mut window_pane = null_mut();
+        match item.type_ {
+            window_fuzzy_type::WINDOW_FUZZY_SESSION | window_fuzzy_type::WINDOW_FUZZY_WINDOW => {
+                if let Some(s) = session_find_by_id(item.session) {
+                    let wl = if item.winlink == -1 {
+                        (*s.as_ptr()).curw
+                    } else {
+                        winlink_find_by_index(&raw mut (*s.as_ptr()).windows, item.winlink)
+                    };
+                    if !wl.is_null() {
+                        wp = (*(*wl).window).active;
+                    }
+                }
+            }
+            window_fuzzy_type::WINDOW_FUZZY_PANE => wp = window_pane_find_by_id(item.pane),
+            window_fuzzy_type::WINDOW_FUZZY_BUFFER => {
+                let Some(pb) = NonNull::new(paste_get_name(item.target.as_ptr())) else {
+                    return;
+                };
+                let mut size: usize = 0;
+                let pdata = paste_buffer_data_(pb, &mut size);
+                let data = std::slice::from_raw_parts(pdata.cast::<u8>(), size);
+                for (i, line) in data.split(|&c| c == b'\n').take(sy as usize).enumerate() {
+                    let buf: *mut c_char =
+                        xreallocarray(null_mut(), 4, line.len() + 1).as_ptr().cast();
+                    utf8_strvis(
+                        buf,
+                        line.as_ptr().cast(),
+                        line.len(),
+                        vis_flags::VIS_OCTAL | vis_flags::VIS_CSTYLE | vis_flags::VIS_TAB,
+                    );
+                    screen_write_cursormove(ctx, cx as i32, (cy + i as u32) as i32, 0);
+                    screen_write_nputs!(
+                        ctx,
+                        sx as isize,
+                        &raw const grid_default_cell,
+                        "{}",
+                        _s(buf),
+                    );
+                    free_(buf);
+                }
+                return;
+            }
+            window_fuzzy_type::WINDOW_FUZZY_COMMAND => {
+                let entry = item.entry;
+                screen_write_cursormove(ctx, cx as i32, cy as i32, 0);
+                screen_write_nputs!(
+                    ctx,
+                    sx as isize,
+                    &raw const grid_default_cell,
+                    "{} {}",
+                    _s((*entry).name),
+                    _s((*entry).usage),
+                );
+                return;
+            }
+        }
+        if !wp.is_null() {
+            screen_write_preview(ctx, &raw mut (*wp).base, sx, sy);
+        }
+    }
+}
+
+unsafe fn window_fuzzy_get_key(
+    _modedata: NonNull<c_void>,
+    _itemdata: NonNull<c_void>,
+    _line: u32,
+) -> key_code {
+    // Keys are typed into the query, so items have no shortcuts.
+    KEYC_NONE
+}
+
+unsafe fn window_fuzzy_init(
+    wme: NonNull<window_mode_entry>,
+    _fs: *mut cmd_find_state,
+    args: *mut args,
+) -> *mut screen {
+    unsafe {
+        let wp = (*wme.as_ptr()).wp;
+        let mut s: *mut screen = null_mut();
+
+        let mut types = window_fuzzy_types::empty();
+        if !args.is_null() {
+            for (flag, type_) in [
+                ('s', window_fuzzy_types::WINDOW_FUZZY_SESSIONS),
+                ('w', window_fuzzy_types::WINDOW_FUZZY_WINDOWS),
+                ('p', window_fuzzy_types::WINDOW_FUZZY_PANES),
+                ('b', window_fuzzy_types::WINDOW_FUZZY_BUFFERS),
+                ('c', window_fuzzy_types::WINDOW_FUZZY_COMMANDS),
+            ] {
+                if args_has_(args, flag) {
+                    types |= type_;
+                }
+            }
+        }
+        if types.is_empty() {
+            types = window_fuzzy_types::all();
+        }
+
+        let data = Box::leak(Box::new(window_fuzzy_modedata {
+            wp,
+            data: null_mut(),
+            command: null_mut(),
+            types,
+            query: Vec::new(),
+            items: Vec::new(),
+        }));
+        (*wme.as_ptr()).data = (&raw mut *data).cast();
+
+        if !args.is_null() && args_count(args) != 0 {
+            data.command = xstrdup(args_string(args, 0)).as_ptr();
+        }
+
+        data.data = mode_tree_start(
+            wp,
+            args,
+            Some(window_fuzzy_build),
+            Some(window_fuzzy_draw),
+            None,
+            None,
+            None,
+            Some(window_fuzzy_get_key),
+            (&raw mut *data).cast(),
+            null(),
+            null(),
+            0,
+            &raw mut s,
+        );
+        mode_tree_zoom(data.data, args);
+        mode_tree_set_prompt(data.data, c"> ".as_ptr());
+
+        mode_tree_build(data.data);
+        mode_tree_draw(data.data);
+
+        s
+    }
+}
+
+unsafe fn window_fuzzy_free(wme: NonNull<window_mode_entry>) {
+    unsafe {
+        let data = (*wme.as_ptr()).data as *mut window_fuzzy_modedata;
+        if data.is_null() {
+            return;
+        }
+
+        mode_tree_free((*data).data);
+        free_((*data).command);
+
+        drop(Box::from_raw(data));
+    }
+}
+
+unsafe fn window_fuzzy_resize(wme: NonNull<window_mode_entry>, sx: u32, sy: u32) {
+    unsafe {
+        let data = (*wme.as_ptr()).data as *mut window_fuzzy_modedata;
+
+        mode_tree_resize((*data).data, sx, sy);
+    }
+}
+
+unsafe fn window_fuzzy_update(wme: NonNull<window_mode_entry>) {
+    unsafe {
+        let data = (*wme.as_ptr()).data as *mut window_fuzzy_modedata;
+
+        mode_tree_build((*data).data);
+        mode_tree_draw((*data).data);
+        (*(*data).wp).flags |= window_pane_flags::PANE_REDRAW;
+    }
+}
+
+/// Run the template, or the default for the type of item, on the selection.
+unsafe fn window_fuzzy_run(data: *mut window_fuzzy_modedata, c: *mut client) {
+    unsafe {
+        let mtd = (*data).data;
+        let item: NonNull<window_fuzzy_itemdata> = mode_tree_get_current(mtd).cast();
+        let item = &*item.as_ptr();
+
+        let command = if !(*data).command.is_null() {
+            (*data).command.cast_const()
+        } else {
+            match item.type_ {
+                window_fuzzy_type::WINDOW_FUZZY_BUFFER => WINDOW_FUZZY_BUFFER_COMMAND.as_ptr(),
+                window_fuzzy_type::WINDOW_FUZZY_COMMAND => WINDOW_FUZZY_COMMAND_COMMAND.as_ptr(),
+                _ => WINDOW_FUZZY_TARGET_COMMAND.as_ptr(),
+            }
+        };
+        mode_tree_run_command(c, null_mut(), command, item.target.as_ptr());
+    }
+}
+
+unsafe fn window_fuzzy_key(
+    wme: NonNull<window_mode_entry>,
+    c: *mut client,
+    _: *mut session,
+    _wl: *mut winlink,
+    mut key: key_code,
+    m: *mut mouse_event,
+) {
+    unsafe {
+        let wp = (*wme.as_ptr()).wp;
+        let data = (*wme.as_ptr()).data as *mut window_fuzzy_modedata;
+        let mtd = (*data).data;
+        let query = &mut (*data).query;
+        let mut finished = false;
+        let mut changed = true;
+
+        const BSPACE: u64 = keyc::KEYC_BSPACE as u64;
+        const C_C: u64 = 'c' as u64 | KEYC_CTRL;
+        const C_G: u64 = 'g' as u64 | KEYC_CTRL;
+        const C_H: u64 = 'h' as u64 | KEYC_CTRL;
+        const C_U: u64 = 'u' as u64 | KEYC_CTRL;
+        const C_W: u64 = 'w' as u64 | KEYC_CTRL;
+
+        match key & (KEYC_MASK_KEY | KEYC_MASK_MODIFIERS) {
+            0x1b | C_C | C_G => {
+                finished = true;
+                changed = false;
+            }
+            0x0d => {
+                if !mode_tree_no_matches(mtd) {
+                    window_fuzzy_run(data, c);
+                    finished = true;
+                }
+                changed = false;
+            }
+            BSPACE | C_H => {
+                while let Some(ch) = query.pop() {
+                    if ch & 0xc0 != 0x80 {
+                        break;
+                    }
+                }
+            }
+            C_U => query.clear(),
+            C_W => {
+                while query.last() == Some(&b' ') {
+                    query.pop();
+                }
+                while query.last().is_some_and(|&ch| ch != b' ') {
+                    query.pop();
+                }
+            }
+            k @ 0x20..0x7f => query.push(k as u8),
+            k if KEYC_IS_UNICODE(k) => {
+                let mut ud: utf8_data = zeroed();
+                utf8_to_data(k as utf8_char, &raw mut ud);
+                query.extend_from_slice(&ud.data[..ud.size as usize]);
+            }
+            _ => {
+                changed = false;
+                finished = mode_tree_key(mtd, c, &raw mut key, m, null_mut(), null_mut()) != 0;
+                if key == b'\r' as u64 && !mode_tree_no_matches(mtd) {
+                    window_fuzzy_run(data, c);
+                    finished = true;
+                }
+            }
+        }
+
+        if changed {
+            let filter = CString::new(query.as_slice()).unwrap_or_default();
+            mode_tree_set_filter(mtd, filter.as_ptr());
+            mode_tree_build(mtd);
+            mode_tree_set_current(mtd, u64::MAX);
+        }
+
+        if finished {
+            window_pane_reset_mode(wp);
+        } else {
+            mode_tree_draw(mtd);
+            (*wp).flags |= window_pane_flags::PANE_REDRAW;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pattern: &str, text: &str) -> Option<(i32, Vec<usize>)> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        window_fuzzy_score(&pattern, &text)
+    }
+
+    #[test]
+    fn test_window_fuzzy_score() {
+        assert_eq!(score("nw", "new-window").unwrap().1, vec![0, 4]);
+        assert_eq!(score("win", "new-window").unwrap().1, vec![4, 5, 6]);
+        assert_eq!(score("abc", "xaxbxcabc").unwrap().1, vec![6, 7, 8]);
+        assert!(score("ba", "abc").is_none());
+        assert!(score("A", "abc").is_none());
+        assert!(score("a", "ABC").is_some());
+
+        assert!(score("ab", "ab-x").unwrap().0 > score("ab", "a-b").unwrap().0);
+        assert!(score("sw", "split-window").unwrap().0 > score("sw", "answer").unwrap().0);
+        assert!(score("nw", "new-window").unwrap().0 > score("nw", "next-window").unwrap().0);
+
+        let mut text = String::new();
+        let chars: Vec<char> = "a#bc".chars().collect();
+        window_fuzzy_markup(&mut text, &chars, &[2, 3], "bold");
+        assert_eq!(text, "a###[bold]bc#[default]");
+    }
+}
@@ -0,0 +1,516 @@
+use crate::*;
+
+use std::ffi::CString;
+
+use crate::compat::tree::rb_foreach;
+
+const WINDOW_SEARCH_TARGET_COMMAND: &CStr = c"switch-client -Zt '%%'";
+
+/// Characters of context kept before a match in its snippet.
+const WINDOW_SEARCH_CONTEXT: usize = 20;
+
+pub static window_search_mode: window_mode = window_mode {
+    name: SyncCharPtr::new(c"search-mode"),
+
+    init: Some(window_search_init),
+    free: Some(window_search_free),
+    resize: Some(window_search_resize),
+    update: Some(window_search_update),
+    key: Some(window_search_key),
+    ..unsafe { zeroed() }
+};
+
+/// A pane with matches, or with line set, one matching line in it.
+struct window_search_itemdata {
+    pane: u32,
+    line: Option<u32>,
+    px: u32,
+
+    /// Replaces %% when switching to the pane.
+    target: CString,
+    name: CString,
+    text: CString,
+}
+
+pub struct window_search_modedata {
+    wp: *mut window_pane,
+
+    data: *mut mode_tree_data,
+    pattern: *mut c_char,
+    regex: bool,
+    icase: bool,
+
+    /// Each pane is followed by its matches.
+    items: Vec<window_search_itemdata>,
+}
+
+/// Get the text of a grid line and the cell each byte of it came from.
+unsafe fn window_search_line(gd: *mut grid, y: u32) -> (String, Vec<u32>) {
+    unsafe {
+        let gl = grid_peek_line(gd, y);
+        let mut gc: grid_cell = zeroed();
+        let mut line = String::new();
+        let mut xs = Vec::new();
+
+        for x in 0..(*gl).cellused {
+            grid_get_cell(gd, x, y, &raw mut gc);
+            if gc.flags.intersects(grid_flag::PADDING) {
+                continue;
+            }
+            let ud = String::from_utf8_lossy(&gc.data.data[..gc.data.size as usize]);
+            line.push_str(&ud);
+            xs.resize(line.len(), x);
+        }
+        (line, xs)
+    }
+}
+
+/// Find the first match in a line as a byte range.
+fn window_search_match(
+    line: &str,
+    pattern: &[u8],
+    regex: Option<&regex_pattern>,
+    icase: bool,
+) -> Option<(usize, usize)> {
+    if let Some(r) = regex {
+        let text = CString::new(line).ok()?;
+        let mut m = [regex_match::empty()];
+        if unsafe { !regex_exec(r, text.as_ptr(), 0, &mut m) } || m[0].so < 0 {
+            return None;
+        }
+        return Some((m[0].so as usize, m[0].eo as usize));
+    }
+    if pattern.is_empty() {
+        return None;
+    }
+    line.as_bytes()
+        .windows(pattern.len())
+        .position(|w| {
+            if icase {
+                w.eq_ignore_ascii_case(pattern)
+            } else {
+                w == pattern
+            }
+        })
+        .map(|so| (so, so + pattern.len()))
+}
+
+/// Make the snippet shown for a match: the line from a little before the
+/// match, with the match in style.
+fn window_search_snippet(line: &str, so: usize, eo: usize, style: &str) -> String {
+    let lead = (line.len() - line.trim_start().len()).min(so);
+    let before = &line[lead..so];
+    let start = match before.char_indices().rev().nth(WINDOW_SEARCH_CONTEXT - 1) {
+        Some((i, _)) if i != 0 => lead + i,
+        _ => lead,
+    };
+
+    let mut out = String::new();
+    if start != lead {
+        out.push_str("...");
+    }
+    out.push_str(&line[start..so].replace('#', "##"));
+    out.push_str("#[");
+    out.push_str(style);
+    out.push(']');
+    out.push_str(&line[so..eo].replace('#', "##"));
+    out.push_str("#[default]");
+    out.push_str(&line[eo..].trim_end().replace('#', "##"));
+    out
+}
+
+/// Find the matching lines of a grid. Any reflow left from a resize is
+/// finished first, so the lines found stay where they are when the pane is
+/// later copied into copy mode.
+unsafe fn window_search_grid(
+    gd: *mut grid,
+    pane: u32,
+    pattern: &[u8],
+    regex: Option<&regex_pattern>,
+    icase: bool,
+    style: &str,
+) -> Vec<window_search_itemdata> {
+    unsafe {
+        grid_reflow_finish(gd);
+
+        let mut matches = Vec::new();
+        for y in 0..(*gd).hsize + (*gd).sy {
+            let (line, xs) = window_search_line(gd, y);
+            let Some((so, eo)) = window_search_match(&line, pattern, regex, icase) else {
+                continue;
+            };
+            matches.push(window_search_itemdata {
+                pane,
+                line: Some(y),
+                px: xs.get(so).copied().unwrap_or(0),
+                target: CString::default(),
+                name: CString::new((y + 1).to_string()).unwrap(),
+                text: CString::new(window_search_snippet(&line, so, eo, style)).unwrap_or_default(),
+            });
+        }
+        matches
+    }
+}
+
+/// Search the history and visible lines of every pane.
+unsafe fn window_search_collect(data: &mut window_search_modedata) {
+    unsafe {
+        let oo = (*(*data.wp).window).options;
+        let style = cstr_to_str(options_get_string_(oo, c"copy-mode-match-style")).to_string();
+        let pattern = CStr::from_ptr(data.pattern).to_bytes().to_vec();
+
+        let regex = if data.regex {
+            let mut flags = regex_flags::empty();
+            if data.icase {
+                flags |= regex_flags::REGEX_ICASE;
+            }
+            regex_compile(data.pattern, regex_get_syntax(), flags)
+        } else {
+            None
+        };
+        if data.regex && regex.is_none() {
+            return;
+        }
+
+        // A window linked into several sessions is only searched once.
+        let mut seen = Vec::new();
+        for s in rb_foreach(&raw mut sessions).map(NonNull::as_ptr) {
+            for wl in rb_foreach(&raw mut (*s).windows).map(NonNull::as_ptr) {
+                let w = (*wl).window;
+                for wp in tailq_foreach::<_, discr_entry>(&raw mut (*w).panes).map(NonNull::as_ptr)
+                {
+                    if seen.contains(&(*wp).id) {
+                        continue;
+                    }
+                    seen.push((*wp).id);
+
+                    let matches = window_search_grid(
+                        (*wp).base.grid,
+                        (*wp).id,
+                        &pattern,
+                        regex.as_ref(),
+                        data.icase,
+                        &style,
+                    );
+                    if matches.is_empty() {
+                        continue;
+                    }
+
+                    let mut idx: u32 = 0;
+                    window_pane_index(wp, &raw mut idx);
+                    let name = format!("{}:{}.{}", _s((*s).name), (*wl).idx, idx);
+                    let target = format!("={}:{}.%{}", _s((*s).name), (*wl).idx, (*wp).id);
+                    let text = format!(
+                        "{} match{}",
+                        matches.len(),
+                        if matches.len() == 1 { "" } else { "es" }
+                    );
+                    data.items.push(window_search_itemdata {
+                        pane: (*wp).id,
+                        line: None,
+                        px: 0,
+                        target: CString::new(target.clone()).unwrap_or_default(),
+                        name: CString::new(name).unwrap_or_default(),
+                        text: CString::new(text).unwrap(),
+                    });
+                    for mut item in matches {
+                        item.target = CString::new(target.clone()).unwrap_or_default();
+                        data.items.push(item);
+                    }
+                }
+            }
+        }
+    }
+}
+
+unsafe fn window_search_build(
+    modedata: NonNull<c_void>,
+    _sort_crit: *mut mode_tree_sort_criteria,
+    _tag: *mut u64,
+    _filter: *const c_char,
+) {
+    unsafe {
+        let data = &mut *modedata.cast::<window_search_modedata>().as_ptr();
+        let mut parent = null_mut();
+
+        for item in data.items.iter_mut() {
+            let tag = ((item.pane as u64) << 32) | item.line.unwrap_or(u32::MAX) as u64;
+            let mti = mode_tree_add(
+                data.data,
+                if item.line.is_some() {
+                    parent
+                } else {
+                    null_mut()
+                },
+                (&raw mut *item).cast(),
+                tag,
+                item.name.as_ptr(),
+                item.text.as_ptr(),
+                1,
+            );
+            if item.line.is_none() {
+                parent = mti;
+            }
+        }
+    }
+}
+
+unsafe fn window_search_draw(
+    _modedata: *mut c_void,
+    itemdata: Option<NonNull<c_void>>,
+    ctx: *mut screen_write_ctx,
+    sx: u32,
+    sy: u32,
+) {
+    unsafe {
+        let Some(item) = itemdata.map(NonNull::cast::<window_search_itemdata>) else {
+            return;
+        };
+        let item = &*item.as_ptr();
+        let wp = window_pane_find_by_id(item.pane);
+        if wp.is_null() {
+            return;
+        }
+        let s = &raw mut (*wp).base;
+
+        let Some(py) = item.line else {
+            screen_write_preview(ctx, s, sx, sy);
+            return;
+        };
+
+        // Show the lines around the match, with it in the middle.
+        let total = screen_hsize(s) + screen_size_y(s);
+        let ny = sy.min(total);
+        let top = py.saturating_sub(sy / 2).min(total - ny);
+        screen_write_cursormove(ctx, (*(*ctx).s).cx as i32, (*(*ctx).s).cy as i32, 0);
+        screen_write_fast_copy(ctx, s, 0, top, sx.min(screen_size_x(s)), ny);
+    }
+}
+
+unsafe fn window_search_init(
+    wme: NonNull<window_mode_entry>,
+    _fs: *mut cmd_find_state,
+    args: *mut args,
+) -> *mut screen {
+    unsafe {
+        let wp = (*wme.as_ptr()).wp;
+        let mut s: *mut screen = null_mut();
+
+        let data = Box::leak(Box::new(window_search_modedata {
+            wp,
+            data: null_mut(),
+            pattern: xstrdup(args_string(args, 0)).as_ptr(),
+            regex: args_has_(args, 'r'),
+            icase: args_has_(args, 'i'),
+            items: Vec::new(),
+        }));
+        (*wme.as_ptr()).data = (&raw mut *data).cast();
+
+        window_search_collect(data);
+
+        data.data = mode_tree_start(
+            wp,
+            args,
+            Some(window_search_build),
+            Some(window_search_draw),
+            None,
+            None,
+            None,
+            None,
+            (&raw mut *data).cast(),
+            null(),
+            null(),
+            0,
+            &raw mut s,
+        );
+        mode_tree_zoom(data.data, args);
+
+        mode_tree_build(data.data);
+        mode_tree_draw(data.data);
+
+        s
+    }
+}
+
+unsafe fn window_search_free(wme: NonNull<window_mode_entry>) {
+    unsafe {
+        let data = (*wme.as_ptr()).data as *mut window_search_modedata;
+        if data.is_null() {
+            return;
+        }
+
+        mode_tree_free((*data).data);
+        free_((*data).pattern);
+
+        drop(Box::from_raw(data));
+    }
+}
+
+unsafe fn window_search_resize(wme: NonNull<window_mode_entry>, sx: u32, sy: u32) {
+    unsafe {
+        let data = (*wme.as_ptr()).data as *mut window_search_modedata;
+
+        mode_tree_resize((*data).data, sx, sy);
+    }
+}
+
+unsafe fn window_search_update(wme: NonNull<window_mode_entry>) {
+    unsafe {
+        let data = (*wme.as_ptr()).data as *mut window_search_modedata;
+
+        mode_tree_build((*data).data);
+        mode_tree_draw((*data).data);
+        (*(*data).wp).flags |= window_pane_flags::PANE_REDRAW;
+    }
+}
+
+/// Check if the search mode on a pane found anything.
+pub unsafe fn window_search_has_matches(wp: *mut window_pane) -> bool {
+    unsafe {
+        let wme = tailq_first(&raw mut (*wp).modes);
+        if wme.is_null() || !std::ptr::eq((*wme).mode, &window_search_mode) {
+            return false;
+        }
+        let data = (*wme).data as *mut window_search_modedata;
+        !(*data).items.is_empty()
+    }
+}
+
+/// Switch to the pane with a match and show the line in copy mode.
+unsafe fn window_search_jump(
+    c: *mut client,
+    pane: u32,
+    line: Option<u32>,
+    px: u32,
+    target: &CStr,
+    pattern: &CStr,
+    regex: bool,
+) {
+    unsafe {
+        let wp = window_pane_find_by_id(pane);
+        if wp.is_null() {
+            return;
+        }
+        mode_tree_run_command(
+            c,
+            null_mut(),
+            WINDOW_SEARCH_TARGET_COMMAND.as_ptr(),
+            target.as_ptr(),
+        );
+
+        let Some(py) = line else {
+            return;
+        };
+        let args = args_create();
+        window_pane_set_mode(wp, wp, &raw const window_copy_mode, null_mut(), args);
+        args_free(args);
+
+        let wme = tailq_first(&raw mut (*wp).modes);
+        if !wme.is_null() && std::ptr::eq((*wme).mode, &raw const window_copy_mode) {
+            window_copy_show_match(wme, px, py, pattern.as_ptr(), regex as i32);
+        }
+    }
+}
+
+unsafe fn window_search_key(
+    wme: NonNull<window_mode_entry>,
+    c: *mut client,
+    _: *mut session,
+    _wl: *mut winlink,
+    mut key: key_code,
+    m: *mut mouse_event,
+) {
+    unsafe {
+        let wp = (*wme.as_ptr()).wp;
+        let data = (*wme.as_ptr()).data as *mut window_search_modedata;
+        let mtd = (*data).data;
+
+        let finished = mode_tree_key(mtd, c, &raw mut key, m, null_mut(), null_mut()) != 0;
+        if key == b'\r' as u64 {
+            let item = &*mode_tree_get_current(mtd)
+                .cast::<window_search_itemdata>()
+                .as_ptr();
+            let (pane, line, px) = (item.pane, item.line, item.px);
+            let target = item.target.clone();
+
+            // Copy mode matches lowercase text in either case.
+            let mut pattern = CStr::from_ptr((*data).pattern).to_owned();
+            if (*data).icase && !(*data).regex {
+                pattern = CString::new(pattern.to_bytes().to_ascii_lowercase()).unwrap();
+            }
+            let regex = (*data).regex;
+
+            window_pane_reset_mode(wp);
+            window_search_jump(c, pane, line, px, &target, &pattern, regex);
+            return;
+        }
+
+        if finished {
+            window_pane_reset_mode(wp);
+        } else {
+            mode_tree_draw(mtd);
+            (*wp).flags |= window_pane_flags::PANE_REDRAW;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_search_snippet() {
+        let line = "  error: #1 failed";
+        let (so, eo) = window_search_match(line, b"ERROR", None, true).unwrap();
+        assert_eq!((so, eo), (2, 7));
+        assert!(window_search_match(line, b"ERROR", None, false).is_none());
+        assert_eq!(
+            window_search_snippet(line, so, eo, "bold"),
+            "#[bold]error#[default]: ##1 failed"
+        );
+
+        let line = format!("{}match", "x".repeat(30));
+        assert_eq!(
+            window_search_snippet(&line, 30, 35, "bold"),
+            format!("...{}#[bold]match#[default]", "x".repeat(20))
+        );
+    }
+
+    #[test]
+    fn test_window_search_grid_reflow() {
+        unsafe {
+            let gd = grid_create(40, 10, 10000);
+            let mut gc = grid_default_cell;
+            for k in 0..2000u32 {
+                let text = format!("{k:>5} {}", "x".repeat((k % 70) as usize));
+                for (i, ch) in text.bytes().enumerate() {
+                    utf8_set(&raw mut gc.data, ch);
+                    grid_set_cell(gd, i as u32 % 40, (*gd).hsize, &gc);
+                    if i % 40 == 39 && i + 1 != text.len() {
+                        (*grid_get_line(gd, (*gd).hsize)).flags |= grid_line_flag::WRAPPED;
+                        grid_scroll_history(gd, 8);
+                    }
+                }
+                grid_scroll_history(gd, 8);
+            }
+
+            (*gd).sx = 25;
+            grid_reflow(gd, 25);
+            assert!((*gd).hreflow > (*gd).hspill);
+
+            let matches = window_search_grid(gd, 0, b"  20 x", None, false, "");
+            assert_eq!(matches.len(), 1);
+            assert_eq!((*gd).hreflow, (*gd).hspill);
+
+            // Copy mode finishes the reflow too, which must not move the match.
+            grid_reflow_finish(gd);
+            let (line, px) = (matches[0].line.unwrap(), matches[0].px);
+            for (i, ch) in b"20 x".iter().enumerate() {
+                grid_get_cell(gd, px + 2 + i as u32, line, &raw mut gc);
+                assert_eq!(gc.data.data[0], *ch);
+            }
+
+            grid_destroy(gd);
+        }
+    }
+}
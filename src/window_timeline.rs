@@ -0,0 +1,314 @@
+use crate::*;
+
+use crate::compat::queue::tailq_first;
+use crate::compat::tree::rb_foreach;
+
+pub static window_timeline_mode: window_mode = window_mode {
+    name: SyncCharPtr::new(c"pane-timeline"),
+
+    init: Some(window_timeline_init),
+    free: Some(window_timeline_free),
+    resize: Some(window_timeline_resize),
+    key: Some(window_timeline_key),
+    ..unsafe { zeroed() }
+};
+
+const WINDOW_TIMELINE_DEFAULT_MINUTES: u32 = 10;
+const WINDOW_TIMELINE_LABEL: &CStr = c"#{window_index}.#{pane_index} #{pane_current_command}";
+const WINDOW_TIMELINE_BARS: [&str; 8] = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+
+#[repr(C)]
+pub struct window_timeline_mode_data {
+    pub screen: screen,
+    pub timer: event,
+
+    pub session: u32,
+    pub minutes: u32,
+}
+
+/// Activity in one column of a sparkline.
+#[derive(Copy, Clone, Default)]
+struct window_timeline_bucket {
+    bytes: usize,
+    bell: bool,
+    exit: bool,
+}
+
+unsafe extern "C" fn window_timeline_timer_callback(_fd: i32, _events: i16, arg: *mut c_void) {
+    unsafe {
+        let wme = arg as *mut window_mode_entry;
+        let wp = (*wme).wp;
+        let data = (*wme).data as *mut window_timeline_mode_data;
+        let tv = timeval {
+            tv_sec: 1,
+            tv_usec: 0,
+        };
+
+        evtimer_del(&raw mut (*data).timer);
+        evtimer_add(&raw mut (*data).timer, &tv);
+
+        if tailq_first(&raw mut (*wp).modes) != wme {
+            return;
+        }
+        window_timeline_draw_screen(NonNull::new_unchecked(wme));
+        (*wp).flags |= window_pane_flags::PANE_REDRAW;
+    }
+}
+
+unsafe fn window_timeline_init(
+    wme: NonNull<window_mode_entry>,
+    fs: *mut cmd_find_state,
+    args: *mut args,
+) -> *mut screen {
+    unsafe {
+        let wp: *mut window_pane = (*wme.as_ptr()).wp;
+        let tv = timeval {
+            tv_sec: 1,
+            tv_usec: 0,
+        };
+        let mut cause = null_mut();
+
+        let data = xcalloc1::<window_timeline_mode_data>() as *mut window_timeline_mode_data;
+        (*wme.as_ptr()).data = data.cast();
+
+        (*data).session = u32::MAX;
+        if !fs.is_null() && !(*fs).s.is_null() {
+            (*data).session = (*(*fs).s).id;
+        }
+        (*data).minutes = WINDOW_TIMELINE_DEFAULT_MINUTES;
+        if !args.is_null() && args_has(args, b'm') != 0 {
+            (*data).minutes = args_strtonum(args, b'm', 1, 1440, &raw mut cause) as u32;
+            if !cause.is_null() {
+                free_(cause);
+                (*data).minutes = WINDOW_TIMELINE_DEFAULT_MINUTES;
+            }
+        }
+
+        evtimer_set(
+            &raw mut (*data).timer,
+            Some(window_timeline_timer_callback),
+            wme.cast().as_ptr(),
+        );
+        evtimer_add(&raw mut (*data).timer, &tv);
+
+        let s = &raw mut (*data).screen;
+        screen_init(
+            s,
+            screen_size_x(&raw mut (*wp).base),
+            screen_size_y(&raw mut (*wp).base),
+            0,
+        );
+        (*s).mode &= !mode_flag::MODE_CURSOR;
+
+        window_timeline_draw_screen(wme);
+
+        s
+    }
+}
+
+unsafe fn window_timeline_free(wme: NonNull<window_mode_entry>) {
+    unsafe {
+        let data = (*wme.as_ptr()).data as *mut window_timeline_mode_data;
+
+        evtimer_del(&raw mut (*data).timer);
+        screen_free(&raw mut (*data).screen);
+        free_(data);
+    }
+}
+
+unsafe fn window_timeline_resize(wme: NonNull<window_mode_entry>, sx: u32, sy: u32) {
+    unsafe {
+        let data = (*wme.as_ptr()).data as *mut window_timeline_mode_data;
+
+        screen_resize(&raw mut (*data).screen, sx, sy, 0);
+        window_timeline_draw_screen(wme);
+    }
+}
+
+unsafe fn window_timeline_key(
+    wme: NonNull<window_mode_entry>,
+    _c: *mut client,
+    _session: *mut session,
+    _wl: *mut winlink,
+    _key: key_code,
+    _m: *mut mouse_event,
+) {
+    unsafe {
+        window_pane_reset_mode((*wme.as_ptr()).wp);
+    }
+}
+
+/// Sort the events of a pane into columns, the newest on the right.
+unsafe fn window_timeline_buckets(
+    wp: *mut window_pane,
+    now: time_t,
+    seconds: time_t,
+    width: usize,
+) -> Vec<window_timeline_bucket> {
+    unsafe {
+        let mut buckets = vec![window_timeline_bucket::default(); width];
+
+        for event in window_pane_events(wp) {
+            let age = now - event.time;
+            if age >= seconds {
+                break;
+            }
+            let bucket = &mut buckets[width - 1 - (age.max(0) as usize * width / seconds as usize)];
+            match event.type_ {
+                window_pane_event_type::Output => bucket.bytes += event.bytes,
+                window_pane_event_type::Bell => bucket.bell = true,
+                window_pane_event_type::Exit => bucket.exit = true,
+            }
+        }
+        buckets
+    }
+}
+
+unsafe fn window_timeline_draw_pane(
+    ctx: *mut screen_write_ctx,
+    s: *mut session,
+    wl: *mut winlink,
+    wp: *mut window_pane,
+    now: time_t,
+    seconds: time_t,
+) {
+    unsafe {
+        let sx = screen_size_x((*ctx).s);
+        let label_width = (sx / 3).min(24);
+        let width = sx - label_width;
+
+        let mut gc: grid_cell = grid_default_cell;
+        if label_width > 1 {
+            let label = format_single(
+                null_mut(),
+                WINDOW_TIMELINE_LABEL.as_ptr(),
+                null_mut(),
+                s,
+                wl,
+                wp,
+            );
+            screen_write_nputs!(ctx, label_width as isize - 1, &gc, "{}", _s(label));
+            free_(label);
+        }
+
+        if width == 0 {
+            return;
+        }
+        let buckets = window_timeline_buckets(wp, now, seconds, width as usize);
+        let most = buckets.iter().map(|bucket| bucket.bytes).max().unwrap_or(0);
+
+        for (x, bucket) in buckets.iter().enumerate() {
+            screen_write_cursormove(
+                ctx,
+                (label_width as usize + x) as i32,
+                (*(*ctx).s).cy as i32,
+                0,
+            );
+            gc = grid_default_cell;
+            if bucket.exit {
+                gc.fg = 1;
+                screen_write_putc(ctx, &gc, b'x');
+                continue;
+            }
+            if bucket.bell {
+                gc.fg = 3;
+            }
+            if bucket.bytes != 0 {
+                let level = (bucket.bytes * WINDOW_TIMELINE_BARS.len()).div_ceil(most);
+                screen_write_puts!(ctx, &gc, "{}", WINDOW_TIMELINE_BARS[level - 1]);
+            } else if bucket.bell {
+                screen_write_putc(ctx, &gc, b'!');
+            }
+        }
+    }
+}
+
+unsafe fn window_timeline_draw_screen(wme: NonNull<window_mode_entry>) {
+    unsafe {
+        let wp = (*wme.as_ptr()).wp;
+        let data = (*wme.as_ptr()).data as *mut window_timeline_mode_data;
+        let s = &raw mut (*data).screen;
+        let mut ctx: screen_write_ctx = zeroed();
+        let now = libc::time(null_mut());
+        let seconds = (*data).minutes as time_t * 60;
+
+        screen_write_start(&raw mut ctx, s);
+        screen_write_clearscreen(&raw mut ctx, 8);
+
+        if screen_size_y(s) != 0 {
+            screen_write_cursormove(&raw mut ctx, 0, 0, 0);
+            screen_write_nputs!(
+                &raw mut ctx,
+                screen_size_x(s) as isize,
+                &grid_default_cell,
+                "Activity over the last {} minute{}",
+                (*data).minutes,
+                if (*data).minutes == 1 { "" } else { "s" },
+            );
+        }
+
+        let mut y = 1;
+        let session = session_find_by_id((*data).session);
+        if let Some(session) = session {
+            let session = session.as_ptr();
+            'out: for wl in rb_foreach(&raw mut (*session).windows).map(NonNull::as_ptr) {
+                let w = (*wl).window;
+                for loop_ in
+                    tailq_foreach::<_, discr_entry>(&raw mut (*w).panes).map(NonNull::as_ptr)
+                {
+                    if y >= screen_size_y(s) {
+                        break 'out;
+                    }
+                    screen_write_cursormove(&raw mut ctx, 0, y as i32, 0);
+                    window_timeline_draw_pane(&raw mut ctx, session, wl, loop_, now, seconds);
+                    y += 1;
+                }
+            }
+        } else if y < screen_size_y(s) {
+            screen_write_cursormove(&raw mut ctx, 0, y as i32, 0);
+            window_timeline_draw_pane(&raw mut ctx, null_mut(), null_mut(), wp, now, seconds);
+        }
+
+        screen_write_stop(&raw mut ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_timeline_buckets() {
+        unsafe {
+            let wp = xcalloc1::<window_pane>() as *mut window_pane;
+            let tl = &mut (*wp).timeline;
+            let now = 1000;
+            for (i, (type_, time, bytes)) in [
+                (window_pane_event_type::Output, now - 100, 1),
+                (window_pane_event_type::Output, now - 59, 2),
+                (window_pane_event_type::Bell, now - 31, 0),
+                (window_pane_event_type::Output, now - 30, 4),
+                (window_pane_event_type::Output, now - 29, 8),
+                (window_pane_event_type::Exit, now, 0),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                tl.events[i] = window_pane_event { type_, time, bytes };
+                tl.next = i + 1;
+                tl.count = i + 1;
+            }
+
+            // Six columns of ten seconds each, the newest on the right.
+            let buckets = window_timeline_buckets(wp, now, 60, 6);
+            let bytes: Vec<_> = buckets.iter().map(|b| b.bytes).collect();
+            assert_eq!(bytes, [2, 0, 4, 8, 0, 0]);
+            let bells: Vec<_> = buckets.iter().map(|b| b.bell).collect();
+            assert_eq!(bells, [false, false, true, false, false, false]);
+            assert!(buckets[5].exit);
+            assert!(buckets[..5].iter().all(|b| !b.exit));
+
+            free_(wp);
+        }
+    }
+}
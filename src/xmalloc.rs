@@ -249,7 +249,8 @@ pub(crate) unsafe fn xsnprintf__(
         return Err(std::io::ErrorKind::WriteZero.into());
     }
 
-    Ok(adapter.written)
+    // Like snprintf, the terminating NUL is not counted.
+    Ok(adapter.written - 1)
 }
 
 pub unsafe fn free_<T>(p: *mut T) {